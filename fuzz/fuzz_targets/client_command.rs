@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use memoni::ipc::ClientCommand;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(command) = ClientCommand::parse(data) {
+        assert_eq!(command.to_bytes(), data);
+    }
+});
@@ -0,0 +1,14 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use memoni::persistence::decode_selection_data;
+
+// Covers both versions, as data that fails to decode as version 2 is tried as version 1
+fuzz_target!(|data: &[u8]| {
+    if let Ok((items, metadata, _)) = decode_selection_data(data) {
+        assert!(metadata.pinned_count <= items.len());
+        for (id, item) in &items {
+            let _ = (id, item.text());
+        }
+    }
+});
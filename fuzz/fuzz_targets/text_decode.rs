@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use memoni::text::{percent_decode, utf16le_to_string};
+
+fuzz_target!(|data: &[u8]| {
+    let decoded = percent_decode(data);
+    assert!(decoded.len() <= data.len());
+
+    let string = utf16le_to_string(data);
+    assert!(string.chars().count() <= data.len().div_ceil(2));
+});
@@ -4,8 +4,8 @@ use proc_macro::TokenStream;
 use proc_macro2::{Literal, TokenTree};
 use quote::quote;
 use syn::{
-    Attribute, Data, DeriveInput, Fields, Ident, LitStr, Meta, Token, Type, Visibility,
-    parenthesized, parse::Parse, parse_macro_input, parse_str,
+    Attribute, Data, DeriveInput, Field, Fields, Ident, LitStr, Meta, Path, Token, Type,
+    Visibility, parenthesized, parse::Parse, parse_macro_input, parse_str,
 };
 
 #[proc_macro_derive(MakeOptional, attributes(optional))]
@@ -29,13 +29,17 @@ pub fn make_optional(input: TokenStream) -> TokenStream {
         .partition(|attr| attr.path().is_ident("optional"));
 
     let mut extra_derive_idents = vec![];
+    let mut schema_path = None;
     for attr in optional_attrs {
         match process_struct_optional_attr(attr) {
-            Ok((mut derives, vis)) => {
+            Ok((mut derives, vis, schema)) => {
                 extra_derive_idents.append(&mut derives);
                 if let Some(vis) = vis {
                     optional_vis = vis;
                 }
+                if let Some(schema) = schema {
+                    schema_path = Some(schema);
+                }
             }
             Err(err) => return err.to_compile_error().into(),
         }
@@ -49,8 +53,12 @@ pub fn make_optional(input: TokenStream) -> TokenStream {
 
     let mut optional_fields = vec![];
     let mut field_applies = vec![];
+    let mut schema_fields = vec![];
     for field in &named_fields.named {
         let ident = &field.ident;
+        if let Some(schema_path) = &schema_path {
+            schema_fields.push(schema_field(schema_path, field));
+        }
         let (attrs, use_optional_type) = match process_field_attrs(&field.attrs) {
             Ok(res) => res,
             Err(err) => return err.to_compile_error().into(),
@@ -91,6 +99,15 @@ pub fn make_optional(input: TokenStream) -> TokenStream {
     }
 
     let optional_name = Ident::new(&format!("Optional{name}"), name.span());
+    let schema_impl = schema_path.map(|schema_path| {
+        quote! {
+            impl #schema_path::Schema for #name {
+                fn schema() -> #schema_path::SchemaType {
+                    #schema_path::SchemaType::Section(vec![#(#schema_fields,)*])
+                }
+            }
+        }
+    });
     quote! {
         #[derive(#(#extra_derive_idents),*)]
         #(#attrs)*
@@ -108,13 +125,66 @@ pub fn make_optional(input: TokenStream) -> TokenStream {
                 self
             }
         }
+
+        #schema_impl
     }
     .into()
 }
 
-fn process_struct_optional_attr(attr: Attribute) -> syn::Result<(Vec<Ident>, Option<Visibility>)> {
+// Named as in the config file, and one_or_many() where serde_as takes a single value for a list
+fn schema_field(schema_path: &Path, field: &Field) -> proc_macro2::TokenStream {
+    let ty = &field.ty;
+    let mut name = field
+        .ident
+        .as_ref()
+        .map(Ident::to_string)
+        .unwrap_or_default();
+    let mut one_or_many = false;
+    for attr in &field.attrs {
+        let Meta::List(meta) = &attr.meta else {
+            continue;
+        };
+        if attr.path().is_ident("serde") {
+            let tokens = meta.tokens.clone().into_iter().collect::<Vec<_>>();
+            for window in tokens.windows(3) {
+                if let [
+                    TokenTree::Ident(ident),
+                    TokenTree::Punct(punct),
+                    TokenTree::Literal(lit),
+                ] = window
+                    && ident == "rename"
+                    && punct.as_char() == '='
+                    && let Ok(rename) = parse_str::<LitStr>(&lit.to_string())
+                {
+                    name = rename.value();
+                }
+            }
+        } else if attr.path().is_ident("serde_as") {
+            one_or_many |= meta.tokens.to_string().contains("OneOrMany");
+        }
+    }
+
+    let schema = if one_or_many {
+        quote! { <#ty as #schema_path::Schema>::schema().one_or_many() }
+    } else {
+        quote! { <#ty as #schema_path::Schema>::schema() }
+    };
+    quote! {
+        #schema_path::SchemaField {
+            name: #name,
+            ty: #schema,
+            // Structs of the config have serde(default)
+            required: false,
+        }
+    }
+}
+
+type StructOptions = (Vec<Ident>, Option<Visibility>, Option<Path>);
+
+fn process_struct_optional_attr(attr: Attribute) -> syn::Result<StructOptions> {
     let mut derive_idents = vec![];
     let mut vis = None;
+    let mut schema = None;
     attr.parse_nested_meta(|meta| {
         if meta.path.is_ident("derive") {
             let content;
@@ -131,10 +201,18 @@ fn process_struct_optional_attr(attr: Attribute) -> syn::Result<(Vec<Ident>, Opt
             return Ok(());
         }
 
+        // Path of the module with the Schema trait to implement, e.g., schema(crate::config_schema)
+        if meta.path.is_ident("schema") {
+            let content;
+            parenthesized!(content in meta.input);
+            schema = Some(content.parse()?);
+            return Ok(());
+        }
+
         Err(meta.error("unrecognized attribute `optional` option"))
     })?;
 
-    Ok((derive_idents, vis))
+    Ok((derive_idents, vis, schema))
 }
 
 fn process_field_attrs(attrs: &Vec<Attribute>) -> syn::Result<(Vec<Attribute>, bool)> {
@@ -0,0 +1,91 @@
+//! Screen reader support, built with the `accessibility` feature. egui describes what it draws
+//! as an AccessKit tree, in which each item button is named after its label and the active one
+//! is selected. The tree goes to screen readers over AT-SPI, and their actions come back as egui
+//! input
+
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use accesskit_unix::Adapter;
+use anyhow::Result;
+use egui::{
+    FullOutput,
+    accesskit::{ActionHandler, ActionRequest, ActivationHandler, DeactivationHandler, TreeUpdate},
+};
+use log::{debug, trace};
+
+/// Forwards the AccessKit tree to AT-SPI, which calls back from its own thread
+pub struct ScreenReader {
+    adapter: Adapter,
+    actions: Receiver<ActionRequest>,
+}
+
+impl ScreenReader {
+    pub fn new(registry: &mio::Registry, token: mio::Token) -> Result<Self> {
+        let waker = mio::Waker::new(registry, token)?;
+        let (sender, receiver) = mpsc::channel();
+        let adapter = Adapter::new(Activation, Actions { sender, waker }, Deactivation);
+        Ok(ScreenReader {
+            adapter,
+            actions: receiver,
+        })
+    }
+
+    /// Takes the AccessKit tree update out of a frame's output, before it's rendered
+    pub fn forward_update(&mut self, mut full_output: FullOutput) -> FullOutput {
+        let Some(update) = full_output.platform_output.accesskit_update.take() else {
+            return full_output;
+        };
+        trace!(
+            "accesskit update of {} nodes, focus on {:?}",
+            update.nodes.len(),
+            update.focus
+        );
+        self.adapter.update_if_active(|| update);
+        full_output
+    }
+
+    /// Actions screen readers requested since the last call, as egui events
+    pub fn take_events(&self) -> Vec<egui::Event> {
+        self.actions
+            .try_iter()
+            .map(egui::Event::AccessKitActionRequest)
+            .collect()
+    }
+
+    /// The window only has focus while it's shown, since it grabs the keyboard
+    pub fn set_focused(&mut self, focused: bool) {
+        self.adapter.update_window_focus_state(focused);
+    }
+}
+
+struct Activation;
+
+impl ActivationHandler for Activation {
+    // egui sends the whole tree with every frame, so the next one activates the adapter
+    fn request_initial_tree(&mut self) -> Option<TreeUpdate> {
+        debug!("screen reader connected");
+        None
+    }
+}
+
+struct Actions {
+    sender: Sender<ActionRequest>,
+    waker: mio::Waker,
+}
+
+impl ActionHandler for Actions {
+    fn do_action(&mut self, request: ActionRequest) {
+        trace!("screen reader requested {:?}", request.action);
+        if self.sender.send(request).is_ok() {
+            let _ = self.waker.wake();
+        }
+    }
+}
+
+struct Deactivation;
+
+impl DeactivationHandler for Deactivation {
+    fn deactivate_accessibility(&mut self) {
+        debug!("screen reader disconnected");
+    }
+}
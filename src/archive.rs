@@ -0,0 +1,182 @@
+//! Items pushed out of the history by item_limit, kept on disk with `archive.enabled` for
+//! `memoni list --archived` and `memoni restore`
+
+use std::{
+    collections::HashSet,
+    fs::{self, File, OpenOptions},
+    io::Write as _,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use bincode::{Decode, Encode};
+use log::{debug, info, warn};
+
+use crate::{
+    config::ArchiveConfig,
+    persistence::{BINCODE_CONFIG, DECODE_CONFIG},
+    selection::SelectionItem,
+};
+
+const ARCHIVE_VERSION: u32 = 1;
+// Items are appended as they're archived, and the file is only rewritten without the expired and
+// repeated ones once this many more than archive.item_limit have piled up
+const VACUUM_SLACK: usize = 100;
+
+#[derive(Debug, Encode, Decode)]
+pub struct ArchivedItem {
+    pub item: SelectionItem,
+    /// Seconds since the Unix epoch
+    pub archived_at: u64,
+}
+
+pub struct Archive {
+    file_path: PathBuf,
+    temp_file_path: PathBuf,
+    item_limit: usize,
+    max_age: Option<Duration>,
+    // Items in the file, including the expired and repeated ones until the next vacuum
+    record_count: usize,
+}
+
+impl Archive {
+    /// Vacuums the archive, so the caps of the config apply from the start
+    pub fn open(file_path: &Path, config: &ArchiveConfig) -> Result<Self> {
+        let mut archive = Archive {
+            file_path: file_path.to_path_buf(),
+            temp_file_path: file_path.with_extension("tmp"),
+            item_limit: config.item_limit,
+            max_age: (config.max_age_days > 0)
+                .then(|| Duration::from_secs(config.max_age_days * 24 * 60 * 60)),
+            record_count: 0,
+        };
+        if archive.file_path.exists() {
+            archive.vacuum()?;
+        }
+        Ok(archive)
+    }
+
+    pub fn add(&mut self, items: Vec<SelectionItem>) -> Result<()> {
+        if items.is_empty() {
+            return Ok(());
+        }
+
+        let archived_at = now();
+        let mut data = vec![];
+        if !self.file_path.exists() {
+            data.extend(ARCHIVE_VERSION.to_le_bytes());
+        }
+        let count = items.len();
+        for item in items {
+            bincode::encode_into_std_write(
+                ArchivedItem { item, archived_at },
+                &mut data,
+                BINCODE_CONFIG,
+            )?;
+        }
+        let mut f = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.file_path)?;
+        f.write_all(&data)?;
+        self.record_count += count;
+        info!("{count} items archived");
+
+        if self.record_count > self.item_limit + VACUUM_SLACK {
+            self.vacuum()?;
+        }
+        Ok(())
+    }
+
+    /// Archived items, the most recently archived first
+    pub fn items(&self) -> Result<Vec<ArchivedItem>> {
+        let records = self.read()?;
+        let oldest_kept = self
+            .max_age
+            .map(|max_age| now().saturating_sub(max_age.as_secs()));
+        let mut seen = HashSet::new();
+        Ok(records
+            .into_iter()
+            .rev()
+            .filter(|record| oldest_kept.is_none_or(|oldest| record.archived_at >= oldest))
+            .filter(|record| seen.insert(record.item.id))
+            .take(self.item_limit)
+            .collect())
+    }
+
+    /// Removes the item from the archive, returning it if it was there
+    pub fn take(&mut self, item_id: u64) -> Result<Option<SelectionItem>> {
+        let mut items = self.items()?;
+        let Some(idx) = items.iter().position(|record| record.item.id == item_id) else {
+            return Ok(None);
+        };
+        let record = items.remove(idx);
+        self.write(items)?;
+        Ok(Some(record.item))
+    }
+
+    /// Rewrites the file without the items past the caps of the config
+    pub fn vacuum(&mut self) -> Result<()> {
+        let items = self.items()?;
+        debug!(
+            "vacuuming archive from {} to {} items",
+            self.record_count,
+            items.len()
+        );
+        self.write(items)
+    }
+
+    fn read(&self) -> Result<Vec<ArchivedItem>> {
+        let data = match fs::read(&self.file_path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+        let Some((version_buf, mut rest)) = data.split_first_chunk::<4>() else {
+            warn!("archive file too short, ignoring it");
+            return Ok(vec![]);
+        };
+        if u32::from_le_bytes(*version_buf) != ARCHIVE_VERSION {
+            warn!("archive file has an invalid version, ignoring it");
+            return Ok(vec![]);
+        }
+
+        let mut records = vec![];
+        while !rest.is_empty() {
+            match bincode::decode_from_slice::<ArchivedItem, _>(rest, DECODE_CONFIG) {
+                Ok((record, len)) => {
+                    records.push(record);
+                    rest = &rest[len..];
+                }
+                // An append cut short, the next vacuum drops it
+                Err(e) => {
+                    warn!("archive file is truncated, ignoring its end: {e}");
+                    break;
+                }
+            }
+        }
+        Ok(records)
+    }
+
+    // Items in the order of items(), written oldest first like add appends them
+    fn write(&mut self, items: Vec<ArchivedItem>) -> Result<()> {
+        let mut data = ARCHIVE_VERSION.to_le_bytes().to_vec();
+        let count = items.len();
+        for record in items.into_iter().rev() {
+            bincode::encode_into_std_write(record, &mut data, BINCODE_CONFIG)?;
+        }
+        let mut f = File::create(&self.temp_file_path)?;
+        f.write_all(&data)?;
+        f.sync_all()?;
+        fs::rename(&self.temp_file_path, &self.file_path)?;
+        self.record_count = count;
+        Ok(())
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs())
+}
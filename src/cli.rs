@@ -0,0 +1,886 @@
+//! The command line of memoni as data, so help, shell completions and the man page are generated
+//! from the same table the arguments are parsed by
+
+use std::fmt::Write as _;
+
+use anyhow::{Result, bail};
+
+use crate::config::ThemeConfig;
+
+/// An option of a command
+pub struct Flag {
+    pub short: Option<char>,
+    pub long: &'static str,
+    /// Name of the value in help, for options that take one
+    pub value: Option<&'static str>,
+    pub help: &'static str,
+    pub possible_values: &'static [&'static str],
+    pub default: Option<&'static str>,
+}
+
+/// An argument of a command that isn't an option
+pub struct Positional {
+    pub name: &'static str,
+    pub possible_values: &'static [&'static str],
+    /// Whether the command runs without it, e.g., when a flag stands in for it
+    pub optional: bool,
+}
+
+pub struct Command {
+    /// None for showing the window, which has no subcommand
+    pub name: Option<&'static str>,
+    /// What running the command does, e.g., "follow new items", for lists of commands
+    pub summary: &'static str,
+    pub about: &'static str,
+    pub positional: Option<Positional>,
+    pub flags: &'static [Flag],
+    /// A section of help after the usage, e.g., the keys of the tui
+    pub extra_help: Option<&'static str>,
+}
+
+impl Command {
+    pub fn find_short(&self, short: char) -> Option<&Flag> {
+        self.flags.iter().find(|flag| flag.short == Some(short))
+    }
+
+    pub fn find_long(&self, long: &str) -> Option<&Flag> {
+        self.flags.iter().find(|flag| flag.long == long)
+    }
+
+    fn path(&self) -> String {
+        match self.name {
+            Some(name) => format!("memoni {name}"),
+            None => "memoni".to_string(),
+        }
+    }
+
+    fn usage(&self) -> String {
+        match &self.positional {
+            Some(positional) if positional.optional => {
+                format!("{} [OPTIONS] [{}]", self.path(), positional.name)
+            }
+            Some(positional) => format!("{} [OPTIONS] {}", self.path(), positional.name),
+            None => format!("{} [OPTIONS]", self.path()),
+        }
+    }
+
+    pub fn help(&self) -> String {
+        let mut help = format!("{}\n", self.about);
+        if self.name.is_none() {
+            for command in subcommands() {
+                let _ = writeln!(help, "To {}, use: {}", command.summary, command.usage());
+            }
+        }
+        let _ = write!(help, "\nUSAGE:\n  {}\n", self.usage());
+        if let Some(extra_help) = self.extra_help {
+            let _ = write!(help, "\n{extra_help}\n");
+        }
+        help.push_str("\nOPTIONS:\n");
+        for flag in self.flags {
+            let _ = writeln!(help, "{:<25} {}", flag_usage(flag), flag_help(flag));
+        }
+        help.pop();
+        help
+    }
+}
+
+const SELECTION: Flag = Flag {
+    short: Some('s'),
+    long: "selection",
+    value: Some("TYPE"),
+    help: "Sets selection type",
+    possible_values: &["CLIPBOARD", "PRIMARY"],
+    default: Some("CLIPBOARD"),
+};
+
+const LOG_LEVEL: Flag = Flag {
+    short: Some('l'),
+    long: "log-level",
+    value: Some("LEVEL"),
+    help: "Sets log level",
+    possible_values: &["off", "error", "warn", "info", "debug", "trace"],
+    default: Some("warn"),
+};
+
+const HELP: Flag = Flag {
+    short: Some('h'),
+    long: "help",
+    value: None,
+    help: "Prints help information",
+    possible_values: &[],
+    default: None,
+};
+
+const VERSION: Flag = Flag {
+    short: Some('v'),
+    long: "version",
+    value: None,
+    help: "Prints memoni version",
+    possible_values: &[],
+    default: None,
+};
+
+const fn profile(help: &'static str) -> Flag {
+    Flag {
+        short: Some('p'),
+        long: "profile",
+        value: Some("NAME"),
+        help,
+        possible_values: &[],
+        default: None,
+    }
+}
+
+/// The commands of memoni, the one without a subcommand first
+pub const COMMANDS: &[Command] = &[
+    Command {
+        name: None,
+        summary: "show the window",
+        about: "Show memoni window if memoni server is running.",
+        positional: None,
+        flags: &[
+            SELECTION,
+            profile("Shows the window of the server started with the same profile"),
+            LOG_LEVEL,
+            VERSION,
+            HELP,
+        ],
+        extra_help: None,
+    },
+    Command {
+        name: Some("server"),
+        summary: "run in server mode",
+        about: "Start memoni server.",
+        positional: None,
+        flags: &[
+            SELECTION,
+            profile("Keeps a separate history for the named profile (e.g., work, personal)"),
+            Flag {
+                short: None,
+                long: "profile-overlay",
+                value: None,
+                help: "Shows frame and event loop timings over the window",
+                possible_values: &[],
+                default: None,
+            },
+            Flag {
+                short: None,
+                long: "measure-show-latency",
+                value: None,
+                help: "Prints how long each step of showing the window takes",
+                possible_values: &[],
+                default: None,
+            },
+            LOG_LEVEL,
+            HELP,
+        ],
+        extra_help: None,
+    },
+    Command {
+        name: Some("watch"),
+        summary: "follow new items",
+        about: "\
+Print the newest item of memoni server, then each new one, as JSON lines for status bars.
+Previews of items that look like passwords or tokens are redacted.",
+        positional: None,
+        flags: &[
+            SELECTION,
+            profile("Watches the server started with the same profile"),
+            LOG_LEVEL,
+            HELP,
+        ],
+        extra_help: None,
+    },
+    Command {
+        name: Some("pipe"),
+        summary: "run a command with each new item",
+        about: "\
+Run a shell command with the text of each new item of memoni server on stdin, one at a time, e.g.,
+to append copied citations to a notes file. Items that look like passwords or tokens are skipped.",
+        positional: None,
+        flags: &[
+            Flag {
+                short: None,
+                long: "exec",
+                value: Some("CMD"),
+                help: "Runs CMD with sh -c, required",
+                possible_values: &[],
+                default: None,
+            },
+            SELECTION,
+            profile("Follows the server started with the same profile"),
+            LOG_LEVEL,
+            HELP,
+        ],
+        extra_help: None,
+    },
+    Command {
+        name: Some("tui"),
+        summary: "browse the history in a terminal",
+        about: "\
+Browse the history of memoni server in the terminal, e.g., over SSH where its window can't show.
+The chosen item is copied to the terminal's clipboard with an OSC 52 escape sequence.",
+        positional: None,
+        flags: &[
+            SELECTION,
+            profile("Browses the server started with the same profile"),
+            LOG_LEVEL,
+            HELP,
+        ],
+        extra_help: Some(
+            "\
+KEYS:
+  j, k, ↑, ↓              Moves between items
+  Enter                   Copies the item and exits
+  d                       Deletes the item
+  r                       Reloads the history
+  q, Esc                  Exits",
+        ),
+    },
+    Command {
+        name: Some("get"),
+        summary: "print an item",
+        about: "\
+Print the text of an item of memoni server, by the id shown by memoni watch or by its place in
+the history, or with --mime, the raw data of another target, e.g., to use the history like
+xclip -o.",
+        positional: Some(Positional {
+            name: "ID",
+            possible_values: &[],
+            optional: true,
+        }),
+        flags: &[
+            Flag {
+                short: None,
+                long: "index",
+                value: Some("N"),
+                help: "Prints the Nth item from 0 instead, in the order of memoni list, pinned \
+                       items first",
+                possible_values: &[],
+                default: None,
+            },
+            Flag {
+                short: None,
+                long: "mime",
+                value: Some("TYPE"),
+                help: "Prints the data of the TYPE target, e.g., image/png, instead of the text",
+                possible_values: &[],
+                default: None,
+            },
+            Flag {
+                short: None,
+                long: "osc52",
+                value: None,
+                help: "Prints the text in an OSC 52 escape sequence instead, which puts it in the \
+                       clipboard of the terminal, even over SSH",
+                possible_values: &[],
+                default: None,
+            },
+            Flag {
+                short: None,
+                long: "osc52-limit",
+                value: Some("BYTES"),
+                help: "Fails if the encoded text is longer than the terminal takes",
+                possible_values: &[],
+                // Same as cli_output::DEFAULT_OSC52_LIMIT
+                default: Some("100000"),
+            },
+            SELECTION,
+            profile("Reads from the server started with the same profile"),
+            LOG_LEVEL,
+            HELP,
+        ],
+        extra_help: None,
+    },
+    Command {
+        name: Some("list"),
+        summary: "list the items",
+        about: "\
+Print the items of memoni server as JSON lines, the pinned ones first, or with --archived, the
+items archived with archive.enabled, the most recently archived first.",
+        positional: None,
+        flags: &[
+            Flag {
+                short: None,
+                long: "archived",
+                value: None,
+                help: "Lists the archived items instead",
+                possible_values: &[],
+                default: None,
+            },
+            SELECTION,
+            profile("Lists the items of the server started with the same profile"),
+            LOG_LEVEL,
+            HELP,
+        ],
+        extra_help: None,
+    },
+    Command {
+        name: Some("restore"),
+        summary: "restore an archived item",
+        about: "Put an archived item back in the history of memoni server, by the id shown by \
+                memoni list --archived.",
+        positional: Some(Positional {
+            name: "ID",
+            possible_values: &[],
+            optional: false,
+        }),
+        flags: &[
+            SELECTION,
+            profile("Restores to the server started with the same profile"),
+            LOG_LEVEL,
+            HELP,
+        ],
+        extra_help: None,
+    },
+    Command {
+        name: Some("paste"),
+        summary: "paste the newest item",
+        about: "\
+Paste the newest unpinned item of memoni server into the focused window without showing the
+window, or with --prev, the one before it, e.g., after copying over the text meant to be pasted.",
+        positional: None,
+        flags: &[
+            Flag {
+                short: None,
+                long: "prev",
+                value: None,
+                help: "Pastes the item before the newest one instead",
+                possible_values: &[],
+                default: None,
+            },
+            Flag {
+                short: None,
+                long: "expire",
+                value: Some("SECS"),
+                help: "Removes the pasted item from the history SECS seconds later, e.g., a \
+                       one-time code",
+                possible_values: &[],
+                default: None,
+            },
+            SELECTION,
+            profile("Pastes from the server started with the same profile"),
+            LOG_LEVEL,
+            HELP,
+        ],
+        extra_help: None,
+    },
+    Command {
+        name: Some("add"),
+        summary: "add an item from stdin",
+        about: "\
+Add what's read from stdin to the history of memoni server as the newest item and print its id,
+e.g., a generated password or the output of a command.",
+        positional: None,
+        flags: &[
+            Flag {
+                short: None,
+                long: "mime",
+                value: Some("TYPE"),
+                help: "Adds the data as TYPE, e.g., image/png",
+                possible_values: &[],
+                // Same as DEFAULT_ADD_MIME of main
+                default: Some("text/plain"),
+            },
+            Flag {
+                short: None,
+                long: "own",
+                value: None,
+                help: "Makes the item the current selection too, so it can be pasted right away",
+                possible_values: &[],
+                default: None,
+            },
+            Flag {
+                short: None,
+                long: "expire",
+                value: Some("SECS"),
+                help: "Removes the item from the history SECS seconds later",
+                possible_values: &[],
+                default: None,
+            },
+            SELECTION,
+            profile("Adds to the server started with the same profile"),
+            LOG_LEVEL,
+            HELP,
+        ],
+        extra_help: None,
+    },
+    Command {
+        name: Some("preview-theme"),
+        summary: "try a theme",
+        about: "Show the window of memoni server with the theme of a file for a while, without saving \
+                it. The file is either a config file with a [theme] table or has theme options only.",
+        positional: Some(Positional {
+            name: "FILE",
+            possible_values: &[],
+            optional: false,
+        }),
+        flags: &[
+            Flag {
+                short: None,
+                long: "seconds",
+                value: Some("N"),
+                help: "Goes back to the configured theme after N seconds",
+                possible_values: &[],
+                // Same as DEFAULT_THEME_PREVIEW_SECONDS of main
+                default: Some("30"),
+            },
+            SELECTION,
+            profile("Previews in the server started with the same profile"),
+            LOG_LEVEL,
+            HELP,
+        ],
+        extra_help: None,
+    },
+    Command {
+        name: Some("completions"),
+        summary: "generate shell completions",
+        about: "Print the completion script of memoni for a shell.",
+        positional: Some(Positional {
+            name: "SHELL",
+            possible_values: Shell::NAMES,
+            optional: false,
+        }),
+        flags: &[HELP],
+        extra_help: None,
+    },
+    Command {
+        name: Some("manpage"),
+        summary: "generate the man page",
+        about: "Print the man page of memoni in roff format.",
+        positional: None,
+        flags: &[HELP],
+        extra_help: None,
+    },
+    Command {
+        name: Some("init-config"),
+        summary: "write a starting config file",
+        about: "\
+Write a config file with every option described and commented out at its default value, to
+$XDG_CONFIG_HOME/memoni/config.toml unless it exists.",
+        positional: None,
+        flags: &[
+            Flag {
+                short: Some('t'),
+                long: "theme",
+                value: Some("PRESET"),
+                help: "Fills in the colors of a built-in theme",
+                possible_values: ThemeConfig::PRESETS,
+                default: None,
+            },
+            Flag {
+                short: None,
+                long: "detect-font",
+                value: None,
+                help: "Fills in the font family fontconfig picks for sans-serif",
+                possible_values: &[],
+                default: None,
+            },
+            Flag {
+                short: None,
+                long: "print",
+                value: None,
+                help: "Prints the config instead of writing it",
+                possible_values: &[],
+                default: None,
+            },
+            Flag {
+                short: None,
+                long: "force",
+                value: None,
+                help: "Overwrites an existing config file",
+                possible_values: &[],
+                default: None,
+            },
+            LOG_LEVEL,
+            HELP,
+        ],
+        extra_help: None,
+    },
+    Command {
+        name: Some("config-schema"),
+        summary: "print the JSON Schema of the config",
+        about: "\
+Print the JSON Schema of the config file, for editors to validate and complete it. With Taplo
+(e.g., Even Better TOML), point to it from the first line of config.toml:
+  #:schema ./config.schema.json",
+        positional: None,
+        flags: &[HELP],
+        extra_help: None,
+    },
+    #[cfg(feature = "self-update")]
+    Command {
+        name: Some("self-update"),
+        summary: "update to the latest release",
+        about: "\
+Replace this binary with the one of the latest GitHub release, checked against its SHA-256 checksum.
+Requires curl and sha256sum.",
+        positional: None,
+        flags: &[
+            Flag {
+                short: None,
+                long: "check",
+                value: None,
+                help: "Only tells if a newer release is available",
+                possible_values: &[],
+                default: None,
+            },
+            LOG_LEVEL,
+            HELP,
+        ],
+        extra_help: None,
+    },
+];
+
+pub fn root() -> &'static Command {
+    &COMMANDS[0]
+}
+
+pub fn subcommand(name: &str) -> Option<&'static Command> {
+    subcommands().find(|command| command.name == Some(name))
+}
+
+fn subcommands() -> impl Iterator<Item = &'static Command> {
+    COMMANDS[1..].iter()
+}
+
+fn flag_usage(flag: &Flag) -> String {
+    let short = match flag.short {
+        Some(short) => format!("-{short}, "),
+        None => "    ".to_string(),
+    };
+    match flag.value {
+        Some(value) => format!("  {short}--{} {value}", flag.long),
+        None => format!("  {short}--{}", flag.long),
+    }
+}
+
+fn flag_help(flag: &Flag) -> String {
+    let mut help = flag.help.to_string();
+    if !flag.possible_values.is_empty() {
+        let _ = write!(
+            help,
+            " [possible values: {}]",
+            flag.possible_values.join(", ")
+        );
+    }
+    if let Some(default) = flag.default {
+        let _ = write!(help, " [default: {default}]");
+    }
+    help
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    const NAMES: &[&str] = &["bash", "zsh", "fish"];
+
+    pub fn parse(name: &str) -> Result<Self> {
+        Ok(match name {
+            "bash" => Shell::Bash,
+            "zsh" => Shell::Zsh,
+            "fish" => Shell::Fish,
+            _ => bail!(
+                "unsupported shell \"{name}\", use one of: {}",
+                Shell::NAMES.join(", ")
+            ),
+        })
+    }
+
+    pub fn completions(self) -> String {
+        match self {
+            Shell::Bash => bash_completions(),
+            Shell::Zsh => zsh_completions(),
+            Shell::Fish => fish_completions(),
+        }
+    }
+}
+
+fn bash_completions() -> String {
+    let mut script = String::from(
+        "\
+_memoni() {
+    local cur=${COMP_WORDS[COMP_CWORD]} prev=${COMP_WORDS[COMP_CWORD-1]}
+    local command=
+    [[ $COMP_CWORD -gt 1 ]] && command=${COMP_WORDS[1]}
+
+    case $prev in
+",
+    );
+    let mut value_flags: Vec<&Flag> = vec![];
+    for flag in COMMANDS.iter().flat_map(|command| command.flags) {
+        if flag.value.is_some() && !value_flags.iter().any(|f| f.long == flag.long) {
+            value_flags.push(flag);
+        }
+    }
+    for flag in value_flags {
+        let names = match flag.short {
+            Some(short) => format!("-{short}|--{}", flag.long),
+            None => format!("--{}", flag.long),
+        };
+        let _ = write!(
+            script,
+            "        {names})
+            COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))
+            return
+            ;;
+",
+            flag.possible_values.join(" ")
+        );
+    }
+    script.push_str("    esac\n\n    local words\n    case $command in\n");
+    for command in subcommands() {
+        let _ = write!(
+            script,
+            "        {})\n            words=\"{}\"\n            ;;\n",
+            command.name.unwrap_or_default(),
+            bash_words(command)
+        );
+    }
+    let _ = write!(
+        script,
+        "        *)
+            words=\"{}\"
+            [[ $COMP_CWORD -eq 1 ]] && words+=\" {}\"
+            ;;
+    esac
+    COMPREPLY=($(compgen -W \"$words\" -- \"$cur\"))
+}}
+
+complete -F _memoni memoni
+",
+        bash_words(root()),
+        subcommands()
+            .filter_map(|command| command.name)
+            .collect::<Vec<_>>()
+            .join(" ")
+    );
+    script
+}
+
+fn bash_words(command: &Command) -> String {
+    let mut words = vec![];
+    for flag in command.flags {
+        if let Some(short) = flag.short {
+            words.push(format!("-{short}"));
+        }
+        words.push(format!("--{}", flag.long));
+    }
+    if let Some(positional) = &command.positional {
+        words.extend(positional.possible_values.iter().map(|v| v.to_string()));
+    }
+    words.join(" ")
+}
+
+fn zsh_completions() -> String {
+    let mut script = String::from("#compdef memoni\n\n_memoni() {\n    local line state\n");
+    script.push_str("    _arguments -C \\\n");
+    for spec in zsh_specs(root()) {
+        let _ = writeln!(script, "        {spec} \\");
+    }
+    script.push_str(
+        "        '1: :->command' \\
+        '*:: :->args'
+
+    case $state in
+        command)
+            local -a commands=(
+",
+    );
+    for command in subcommands() {
+        let _ = writeln!(
+            script,
+            "                {}",
+            zsh_quote(&format!(
+                "{}:{}",
+                command.name.unwrap_or_default(),
+                capitalize(command.summary)
+            ))
+        );
+    }
+    script.push_str(
+        "            )
+            _describe command commands
+            ;;
+        args)
+            case $line[1] in
+",
+    );
+    for command in subcommands() {
+        let _ = writeln!(
+            script,
+            "                {})",
+            command.name.unwrap_or_default()
+        );
+        script.push_str("                    _arguments");
+        for spec in zsh_specs(command) {
+            let _ = write!(script, " \\\n                        {spec}");
+        }
+        script.push_str("\n                    ;;\n");
+    }
+    script.push_str(
+        "            esac
+            ;;
+    esac
+}
+
+_memoni \"$@\"
+",
+    );
+    script
+}
+
+fn zsh_specs(command: &Command) -> Vec<String> {
+    let mut specs = vec![];
+    for flag in command.flags {
+        let description = flag
+            .help
+            .replace('\\', "\\\\")
+            .replace('[', "\\[")
+            .replace(']', "\\]");
+        let argument = match flag.value {
+            Some(value) if flag.possible_values.is_empty() => format!(":{value}:"),
+            Some(value) => format!(":{value}:({})", flag.possible_values.join(" ")),
+            None => String::new(),
+        };
+        specs.push(match flag.short {
+            Some(short) => format!(
+                "'(-{short} --{long})'{{-{short},--{long}}}{}",
+                zsh_quote(&format!("[{description}]{argument}")),
+                long = flag.long
+            ),
+            None => zsh_quote(&format!("--{}[{description}]{argument}", flag.long)),
+        });
+    }
+    if let Some(positional) = &command.positional {
+        let action = if positional.possible_values.is_empty() {
+            String::new()
+        } else {
+            format!("({})", positional.possible_values.join(" "))
+        };
+        let colon = if positional.optional { "::" } else { ":" };
+        specs.push(zsh_quote(&format!("1{colon}{}:{action}", positional.name)));
+    }
+    specs
+}
+
+fn zsh_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+fn fish_completions() -> String {
+    let mut script = String::from("complete -c memoni -f\n");
+    for command in subcommands() {
+        let _ = writeln!(
+            script,
+            "complete -c memoni -n __fish_use_subcommand -a {} -d {}",
+            command.name.unwrap_or_default(),
+            fish_quote(&capitalize(command.summary))
+        );
+    }
+    for command in COMMANDS {
+        let condition = match command.name {
+            Some(name) => fish_quote(&format!("__fish_seen_subcommand_from {name}")),
+            None => "__fish_use_subcommand".to_string(),
+        };
+        for flag in command.flags {
+            let mut line = format!("complete -c memoni -n {condition}");
+            if let Some(short) = flag.short {
+                let _ = write!(line, " -s {short}");
+            }
+            let _ = write!(line, " -l {}", flag.long);
+            if flag.value.is_some() {
+                line.push_str(" -x");
+            }
+            if !flag.possible_values.is_empty() {
+                let _ = write!(line, " -a {}", fish_quote(&flag.possible_values.join(" ")));
+            }
+            let _ = writeln!(script, "{line} -d {}", fish_quote(flag.help));
+        }
+        if let Some(positional) = &command.positional
+            && !positional.possible_values.is_empty()
+        {
+            let _ = writeln!(
+                script,
+                "complete -c memoni -n {condition} -a {}",
+                fish_quote(&positional.possible_values.join(" "))
+            );
+        }
+    }
+    script
+}
+
+fn fish_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// The man page in roff, with a section per subcommand
+pub fn manpage() -> String {
+    let root = root();
+    let mut page = format!(
+        ".TH MEMONI 1 \"\" \"memoni v{}\" \"User Commands\"\n",
+        env!("CARGO_PKG_VERSION")
+    );
+    page.push_str(".SH NAME\nmemoni \\- clipboard manager for X11\n.SH SYNOPSIS\n");
+    for command in COMMANDS {
+        let _ = writeln!(page, "{}\n.br", roff_escape(&command.usage()));
+    }
+    let _ = writeln!(page, ".SH DESCRIPTION\n{}", roff_escape(root.about));
+    page.push_str(".SH OPTIONS\n");
+    roff_flags(&mut page, root.flags);
+    page.push_str(".SH COMMANDS\n");
+    for command in subcommands() {
+        let _ = writeln!(
+            page,
+            ".SS {}\n{}",
+            roff_escape(&command.usage()),
+            roff_escape(command.about)
+        );
+        if let Some(extra_help) = command.extra_help {
+            let _ = writeln!(page, ".PP\n.nf\n{}\n.fi", roff_escape(extra_help));
+        }
+        roff_flags(&mut page, command.flags);
+    }
+    page
+}
+
+fn roff_flags(page: &mut String, flags: &[Flag]) {
+    for flag in flags {
+        page.push_str(".TP\n");
+        if let Some(short) = flag.short {
+            let _ = write!(page, "\\fB\\-{short}\\fR, ");
+        }
+        let _ = write!(page, "\\fB{}\\fR", roff_escape(&format!("--{}", flag.long)));
+        if let Some(value) = flag.value {
+            let _ = write!(page, " \\fI{value}\\fR");
+        }
+        let _ = writeln!(page, "\n{}", roff_escape(&flag_help(flag)));
+    }
+}
+
+fn roff_escape(s: &str) -> String {
+    s.lines()
+        .map(|line| {
+            let line = line.replace('\\', "\\e").replace('-', "\\-");
+            if line.starts_with(['.', '\'']) {
+                format!("\\&{line}")
+            } else {
+                line
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,93 @@
+//! Output of the command line clients, for terminals that may be far from the X server
+
+use std::env;
+
+use anyhow::{Result, bail};
+
+/// Encoded bytes most terminals take in one OSC 52 sequence, e.g., xterm and hterm
+pub const DEFAULT_OSC52_LIMIT: usize = 100_000;
+// screen drops DCS strings longer than 768 bytes
+const SCREEN_CHUNK_LEN: usize = 76;
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Terminal multiplexers keep escape sequences to themselves unless they're wrapped to be passed
+/// through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Multiplexer {
+    None,
+    Tmux,
+    Screen,
+}
+
+impl Multiplexer {
+    pub fn from_env() -> Self {
+        if env::var_os("TMUX").is_some() {
+            Multiplexer::Tmux
+        } else if env::var("TERM").is_ok_and(|term| term.starts_with("screen")) {
+            Multiplexer::Screen
+        } else {
+            Multiplexer::None
+        }
+    }
+}
+
+/// An OSC 52 sequence that has the terminal put `data` in its clipboard, failing if the encoded
+/// data is longer than `limit`, as terminals drop those silently
+pub fn osc52(data: &[u8], multiplexer: Multiplexer, limit: usize) -> Result<Vec<u8>> {
+    let encoded = base64_encode(data);
+    if encoded.len() > limit {
+        bail!(
+            "item takes {} bytes encoded, over the OSC 52 limit of {limit}",
+            encoded.len()
+        );
+    }
+
+    let mut sequence = b"\x1b]52;c;".to_vec();
+    sequence.extend(encoded);
+    sequence.push(b'\x07');
+
+    Ok(match multiplexer {
+        Multiplexer::None => sequence,
+        Multiplexer::Tmux => {
+            let mut wrapped = b"\x1bPtmux;".to_vec();
+            for byte in sequence {
+                if byte == b'\x1b' {
+                    wrapped.push(byte);
+                }
+                wrapped.push(byte);
+            }
+            wrapped.extend(b"\x1b\\");
+            wrapped
+        }
+        Multiplexer::Screen => {
+            let mut wrapped = vec![];
+            for chunk in sequence.chunks(SCREEN_CHUNK_LEN) {
+                wrapped.extend(b"\x1bP");
+                wrapped.extend(chunk);
+                wrapped.extend(b"\x1b\\");
+            }
+            wrapped
+        }
+    })
+}
+
+pub fn base64_encode(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let triple = u32::from_be_bytes([
+            0,
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_ALPHABET[(triple >> (18 - i * 6)) as usize & 0x3f]);
+            } else {
+                out.push(b'=');
+            }
+        }
+    }
+    out
+}
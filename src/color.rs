@@ -116,3 +116,52 @@ fn hsl_to_rgb(h: f32, s: f32, l: f32) -> (u8, u8, u8) {
         ((b + m) * 255.0).round() as u8,
     )
 }
+
+/// WCAG contrast ratio between two colors, from 1 to 21. Alpha is ignored
+pub fn contrast_ratio(a: Color32, b: Color32) -> f32 {
+    let (lighter, darker) = {
+        let (la, lb) = (relative_luminance(a), relative_luminance(b));
+        if la > lb { (la, lb) } else { (lb, la) }
+    };
+    (lighter + 0.05) / (darker + 0.05)
+}
+
+/// Moves `foreground` towards white or black, whichever gets further, until it has at least
+/// `min_ratio` contrast against every one of `backgrounds`
+pub fn ensure_contrast(foreground: Color32, backgrounds: &[Color32], min_ratio: f32) -> Color32 {
+    let worst_ratio = |color: Color32| {
+        backgrounds
+            .iter()
+            .map(|&bg| contrast_ratio(color, bg))
+            .fold(f32::INFINITY, f32::min)
+    };
+    if worst_ratio(foreground) >= min_ratio {
+        return foreground;
+    }
+
+    let target = if worst_ratio(Color32::WHITE) >= worst_ratio(Color32::BLACK) {
+        Color32::WHITE
+    } else {
+        Color32::BLACK
+    };
+    let mut adjusted = foreground;
+    for step in 1..=20 {
+        adjusted = foreground.lerp_to_gamma(target, step as f32 / 20.0);
+        if worst_ratio(adjusted) >= min_ratio {
+            break;
+        }
+    }
+    adjusted
+}
+
+fn relative_luminance(color: Color32) -> f32 {
+    let channel = |c: u8| {
+        let c = c as f32 / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * channel(color.r()) + 0.7152 * channel(color.g()) + 0.0722 * channel(color.b())
+}
@@ -1,14 +1,15 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use egui::Color32;
 use egui::ecolor::ParseHexColorError;
 use log::{debug, info};
 use make_optional::MakeOptional;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_with::{DisplayFromStr, FromInto, OneOrMany, serde_as};
 use std::collections::HashMap;
 use std::fmt::{self, Display, Formatter};
 use std::fs;
 use std::ops::Deref;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use xkeysym::Keysym;
 
@@ -16,7 +17,7 @@ use crate::selection::SelectionType;
 
 #[derive(Deserialize, Debug, Default)]
 #[serde(default, deny_unknown_fields)]
-struct ConfigSet {
+pub(crate) struct ConfigSet {
     #[serde(flatten)]
     common: OptionalConfig,
 
@@ -28,19 +29,51 @@ struct ConfigSet {
 }
 
 #[derive(MakeOptional)]
-#[optional(derive(Default), vis())]
+#[optional(derive(Default), vis(), schema(crate::config_schema))]
 #[serde_as]
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Clone, Debug)]
 #[serde(default, deny_unknown_fields)]
 pub struct Config {
     pub item_limit: usize,
+    pub max_disk_mb: u64,
+    pub storage: StorageKind,
+    pub dedupe_keys: Vec<DedupeKey>,
+    pub expire_after_secs: u64,
     pub show_ribbon: bool,
     pub scroll_bar_auto_hide: bool,
+    pub smooth_scroll: bool,
+    pub smooth_scroll_duration_ms: u64,
     pub show_quick_paste_hint: bool,
+    pub count_prefix: bool,
+    pub show_source_icon: bool,
+    pub fetch_favicons: bool,
+    pub show_header: bool,
+    pub selection_label: String,
+    pub show_whitespace: bool,
+    pub locale: String,
+    pub icon_theme: String,
+    pub text_ellipsis: TextEllipsis,
     pub window_position_mode: WindowPositionMode,
+    pub capture_debounce_ms: u64,
+    pub paste_method: PasteMethod,
+    pub paste_hotkeys: Vec<KeyStroke>,
+    pub paste_previous_hotkeys: Vec<KeyStroke>,
+    pub grab_pointer: bool,
+    pub managed_window: bool,
+    pub keep_open: bool,
+    pub restore_scroll_position: bool,
+    pub editor_command: Vec<String>,
+    pub join_delimiter: String,
+    pub providers: Vec<ProviderKind>,
+    pub metrics_port: u16,
+    pub renderer: RendererKind,
+    pub preview_max_texture_side: u32,
+    pub preview_memory_limit_mb: usize,
 
     #[serde_as(as = "HashMap<_, OneOrMany<_>>")]
     pub app_paste_keymaps: HashMap<String, Vec<KeyStroke>>,
+    #[serde_as(as = "HashMap<_, OneOrMany<_>>")]
+    pub macros: HashMap<String, Vec<MacroAction>>,
 
     #[optional(optional_type)]
     pub layout: LayoutConfig,
@@ -48,27 +81,73 @@ pub struct Config {
     pub font: FontConfig,
     #[optional(optional_type)]
     pub theme: ThemeConfig,
+    #[optional(optional_type)]
+    pub accessibility: AccessibilityConfig,
+    #[optional(optional_type)]
+    pub archive: ArchiveConfig,
+    #[optional(optional_type)]
+    pub merge: MergeConfig,
+    #[optional(optional_type)]
+    pub yank_pop: YankPopConfig,
+    #[optional(optional_type)]
+    pub pointer: PointerConfig,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             item_limit: 100,
+            max_disk_mb: 0,
+            storage: StorageKind::File,
+            dedupe_keys: vec![],
+            expire_after_secs: 300,
             show_ribbon: false,
             scroll_bar_auto_hide: false,
+            smooth_scroll: false,
+            smooth_scroll_duration_ms: 150,
             show_quick_paste_hint: true,
+            count_prefix: false,
+            show_source_icon: true,
+            fetch_favicons: false,
+            show_header: false,
+            selection_label: String::new(),
+            show_whitespace: true,
+            locale: "auto".to_string(),
+            icon_theme: "auto".to_string(),
+            text_ellipsis: TextEllipsis::End,
             window_position_mode: WindowPositionMode::Monitor,
+            capture_debounce_ms: 0,
+            paste_method: PasteMethod::Auto,
+            paste_hotkeys: vec![],
+            paste_previous_hotkeys: vec![],
+            grab_pointer: true,
+            managed_window: false,
+            keep_open: false,
+            restore_scroll_position: false,
+            editor_command: vec!["xterm".to_string(), "-e".to_string(), "vi".to_string()],
+            join_delimiter: ", ".to_string(),
+            providers: vec![ProviderKind::Calculator, ProviderKind::Emoji],
+            metrics_port: 0,
+            renderer: RendererKind::Auto,
+            preview_max_texture_side: 0,
+            preview_memory_limit_mb: 32,
             app_paste_keymaps: Default::default(),
+            macros: Default::default(),
             layout: Default::default(),
             font: Default::default(),
             theme: Default::default(),
+            accessibility: Default::default(),
+            archive: Default::default(),
+            merge: Default::default(),
+            yank_pop: Default::default(),
+            pointer: Default::default(),
         }
     }
 }
 
 #[derive(MakeOptional)]
-#[optional(derive(Default), vis())]
-#[derive(Deserialize, Debug)]
+#[optional(derive(Default), vis(), schema(crate::config_schema))]
+#[derive(Deserialize, Serialize, Clone, Debug)]
 #[serde(default, deny_unknown_fields)]
 pub struct LayoutConfig {
     pub window_dimensions: Dimensions,
@@ -88,6 +167,14 @@ pub struct LayoutConfig {
     pub pin_size: f32,
     pub color_preview_size: f32,
     pub color_preview_corner_radius: u8,
+    pub source_icon_size: f32,
+    pub preview_lines: usize,
+    pub text_overflow: LabelOverflow,
+    pub path_overflow: LabelOverflow,
+    pub url_overflow: LabelOverflow,
+    pub mode: LayoutMode,
+    pub grid_columns: usize,
+    pub density: Density,
 }
 
 impl Default for LayoutConfig {
@@ -116,14 +203,22 @@ impl Default for LayoutConfig {
             pin_size: 4.0,
             color_preview_size: 18.0,
             color_preview_corner_radius: 4,
+            source_icon_size: 16.0,
+            preview_lines: 1,
+            text_overflow: LabelOverflow::Wrap,
+            path_overflow: LabelOverflow::Truncate,
+            url_overflow: LabelOverflow::Wrap,
+            mode: LayoutMode::List,
+            grid_columns: 3,
+            density: Density::Comfortable,
         }
     }
 }
 
 #[derive(MakeOptional)]
 #[serde_as]
-#[optional(derive(Default), vis())]
-#[derive(Deserialize, Debug)]
+#[optional(derive(Default), vis(), schema(crate::config_schema))]
+#[derive(Deserialize, Serialize, Clone, Debug)]
 #[serde(default, deny_unknown_fields)]
 pub struct FontConfig {
     #[serde(rename = "family")]
@@ -136,6 +231,13 @@ pub struct FontConfig {
     pub y_offset_factors: Vec<f32>,
     pub underline_offset: f32,
     pub pending_keys_text_size: f32,
+    pub cjk_fallback: String,
+    pub arabic_fallback: String,
+    pub devanagari_fallback: String,
+    pub monospace_family: String,
+    pub monospace_code: bool,
+    pub gamma: f32,
+    pub pixel_snap: bool,
 }
 
 impl Default for FontConfig {
@@ -147,14 +249,21 @@ impl Default for FontConfig {
             y_offset_factors: vec![],
             underline_offset: 0.0,
             pending_keys_text_size: 16.0,
+            cjk_fallback: String::new(),
+            arabic_fallback: String::new(),
+            devanagari_fallback: String::new(),
+            monospace_family: String::new(),
+            monospace_code: true,
+            gamma: 0.0,
+            pixel_snap: false,
         }
     }
 }
 
 #[derive(MakeOptional)]
-#[optional(derive(Default), vis())]
+#[optional(derive(Default), vis(), schema(crate::config_schema))]
 #[serde_as]
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Clone, Debug)]
 #[serde(default, deny_unknown_fields)]
 pub struct ThemeConfig {
     #[serde_as(as = "DisplayFromStr")]
@@ -164,6 +273,8 @@ pub struct ThemeConfig {
     #[serde_as(as = "DisplayFromStr")]
     pub muted_foreground: Color,
     #[serde_as(as = "DisplayFromStr")]
+    pub whitespace_foreground: Color,
+    #[serde_as(as = "DisplayFromStr")]
     pub button_background: Color,
     #[serde_as(as = "DisplayFromStr")]
     pub button_active_background: Color,
@@ -181,6 +292,16 @@ pub struct ThemeConfig {
     pub pending_keys_background: Color,
     #[serde_as(as = "DisplayFromStr")]
     pub pin_color: Color,
+    #[serde_as(as = "DisplayFromStr")]
+    pub toast_foreground: Color,
+    #[serde_as(as = "DisplayFromStr")]
+    pub toast_error_foreground: Color,
+    #[serde_as(as = "DisplayFromStr")]
+    pub toast_background: Color,
+    #[serde_as(as = "DisplayFromStr")]
+    pub header_foreground: Color,
+    #[serde_as(as = "DisplayFromStr")]
+    pub header_background: Color,
 }
 
 impl Default for ThemeConfig {
@@ -189,6 +310,7 @@ impl Default for ThemeConfig {
             background: Color(0xff191919),
             foreground: Color(0xffcccccc),
             muted_foreground: Color(0xff707070),
+            whitespace_foreground: Color(0xff707070),
             button_background: Color(0xff2f2f2f),
             button_active_background: Color(0xff454545),
             scroll_background: Color(0xff0a0a0a),
@@ -198,16 +320,202 @@ impl Default for ThemeConfig {
             pending_keys_foreground: Color(0xffcccccc),
             pending_keys_background: Color(0xa0000000),
             pin_color: Color(0xffcccccc),
+            toast_foreground: Color(0xffcccccc),
+            toast_error_foreground: Color(0xffff7070),
+            toast_background: Color(0xe0000000),
+            header_foreground: Color(0xff707070),
+            header_background: Color(0x00000000),
+        }
+    }
+}
+
+impl ThemeConfig {
+    pub const PRESETS: &[&str] = &["dark", "light", "high-contrast"];
+
+    /// Built-in themes to start from, "dark" being the default one
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::default()),
+            "light" => Some(Self {
+                background: Color(0xfff5f5f5),
+                foreground: Color(0xff1f1f1f),
+                muted_foreground: Color(0xff6b6b6b),
+                whitespace_foreground: Color(0xff9a9a9a),
+                button_background: Color(0xffe4e4e4),
+                button_active_background: Color(0xffc8c8c8),
+                scroll_background: Color(0xffe0e0e0),
+                scroll_handle: Color(0xff5f5f5f),
+                preview_background: Color(0x77ffffff),
+                ribbon: Color(0x55000000),
+                pending_keys_foreground: Color(0xff1f1f1f),
+                pending_keys_background: Color(0xa0ffffff),
+                pin_color: Color(0xff1f1f1f),
+                toast_foreground: Color(0xff1f1f1f),
+                toast_error_foreground: Color(0xffc62828),
+                toast_background: Color(0xe0ffffff),
+                header_foreground: Color(0xff6b6b6b),
+                header_background: Color(0x00000000),
+            }),
+            "high-contrast" => Some(Self {
+                background: Color(0xff000000),
+                foreground: Color(0xffffffff),
+                muted_foreground: Color(0xffc8c8c8),
+                whitespace_foreground: Color(0xffc8c8c8),
+                button_background: Color(0xff1a1a1a),
+                button_active_background: Color(0xff0050a0),
+                scroll_background: Color(0xff000000),
+                scroll_handle: Color(0xffffffff),
+                preview_background: Color(0xff000000),
+                ribbon: Color(0xaaffff00),
+                pending_keys_foreground: Color(0xffffffff),
+                pending_keys_background: Color(0xf0000000),
+                pin_color: Color(0xffffff00),
+                toast_foreground: Color(0xffffffff),
+                toast_error_foreground: Color(0xffff8080),
+                toast_background: Color(0xf0000000),
+                header_foreground: Color(0xffc8c8c8),
+                header_background: Color(0xff000000),
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(MakeOptional)]
+#[optional(derive(Default), vis(), schema(crate::config_schema))]
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct AccessibilityConfig {
+    pub min_contrast: f32,
+    pub reduced_motion: bool,
+    pub large_hit_targets: bool,
+}
+
+impl Default for AccessibilityConfig {
+    fn default() -> Self {
+        Self {
+            min_contrast: 0.0,
+            reduced_motion: false,
+            large_hit_targets: false,
+        }
+    }
+}
+
+#[derive(MakeOptional)]
+#[optional(derive(Default), vis(), schema(crate::config_schema))]
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ArchiveConfig {
+    pub enabled: bool,
+    pub item_limit: usize,
+    pub max_age_days: u64,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            item_limit: 1000,
+            max_age_days: 90,
+        }
+    }
+}
+
+#[derive(MakeOptional)]
+#[optional(derive(Default), vis(), schema(crate::config_schema))]
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct MergeConfig {
+    pub strategy: MergeStrategy,
+    pub window_ms: u64,
+}
+
+impl Default for MergeConfig {
+    fn default() -> Self {
+        Self {
+            strategy: MergeStrategy::Off,
+            window_ms: 1000,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MergeStrategy {
+    Off,
+    Substring,
+    Prefix,
+}
+
+#[derive(MakeOptional)]
+#[optional(derive(Default), vis(), schema(crate::config_schema))]
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct YankPopConfig {
+    pub enabled: bool,
+    pub window_ms: u64,
+    pub undo: YankPopUndo,
+}
+
+impl Default for YankPopConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window_ms: 1500,
+            undo: YankPopUndo::Undo,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum YankPopUndo {
+    Undo,
+    Backspace,
+}
+
+#[derive(MakeOptional)]
+#[optional(derive(Default), vis(), schema(crate::config_schema))]
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct PointerConfig {
+    pub click: PointerGestureAction,
+    pub double_click: PointerGestureAction,
+    pub hold: PointerGestureAction,
+    pub double_click_ms: u64,
+    pub hold_ms: u64,
+}
+
+impl Default for PointerConfig {
+    fn default() -> Self {
+        Self {
+            click: PointerGestureAction::Paste,
+            double_click: PointerGestureAction::None,
+            hold: PointerGestureAction::None,
+            double_click_ms: 400,
+            hold_ms: 500,
         }
     }
 }
 
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PointerGestureAction {
+    Paste,
+    Select,
+    ViewImage,
+    ViewDetails,
+    Pin,
+    None,
+}
+
 fn default_clipboard_config() -> OptionalConfig {
     OptionalConfig {
         theme: Some(OptionalThemeConfig {
             ribbon: Some(Color(0x550000ff)),
             ..Default::default()
         }),
+        selection_label: Some("Clipboard".to_string()),
         window_position_mode: Some(WindowPositionMode::Monitor),
         ..Default::default()
     }
@@ -219,7 +527,14 @@ fn default_primary_config() -> OptionalConfig {
             ribbon: Some(Color(0x30ff0000)),
             ..Default::default()
         }),
+        selection_label: Some("Primary".to_string()),
         window_position_mode: Some(WindowPositionMode::Pointer),
+        capture_debounce_ms: Some(150),
+        // Every change while drag-selecting text is a new PRIMARY selection
+        merge: Some(OptionalMergeConfig {
+            strategy: Some(MergeStrategy::Substring),
+            ..Default::default()
+        }),
         ..Default::default()
     }
 }
@@ -231,8 +546,7 @@ impl Config {
             SelectionType::PRIMARY => default_primary_config(),
         });
 
-        let config_path = match dirs::config_dir().map(|dir| dir.join("memoni").join("config.toml"))
-        {
+        let config_path = match Config::path() {
             Some(p) if p.exists() => p,
             _ => {
                 info!("config file not found, using default config");
@@ -246,20 +560,78 @@ impl Config {
         let config_set: ConfigSet =
             toml::from_str(&config_content).context("Failed to parse config file")?;
 
-        let config = default_config
+        let mut config = default_config
             .with_optional(config_set.common)
             .with_optional(match selection_type {
                 SelectionType::CLIPBOARD => config_set.clipboard,
                 SelectionType::PRIMARY => config_set.primary,
             });
 
+        config.apply_density();
+
         debug!("config: {config:#?}");
         Ok(config)
     }
+
+    /// $XDG_CONFIG_HOME/memoni/config.toml, whether it exists or not
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("memoni").join("config.toml"))
+    }
+
+    /// This config with the theme of another file, either a config file with a [theme] table or a
+    /// file of theme options only. Colors the file doesn't set stay as they are
+    pub fn with_theme_file(&self, path: &Path) -> Result<Config> {
+        info!("loading theme from {path:?}");
+        let content = fs::read_to_string(path)?;
+        let mut table: toml::Table =
+            toml::from_str(&content).with_context(|| format!("Failed to parse {path:?}"))?;
+        let theme_table = match table.remove("theme") {
+            Some(toml::Value::Table(theme)) => theme,
+            Some(_) => bail!("theme in {path:?} isn't a table"),
+            None => table,
+        };
+        let theme: OptionalThemeConfig = toml::Value::Table(theme_table)
+            .try_into()
+            .with_context(|| format!("Failed to parse the theme in {path:?}"))?;
+
+        let mut config = self.clone();
+        config.theme.apply_optional(theme);
+        Ok(config)
+    }
+
+    // Sizes in the config are those of the comfortable density, the others derive from them
+    fn apply_density(&mut self) {
+        let scale = self.layout.density.scale();
+        if scale == 1.0 {
+            return;
+        }
+        // Text shrinks half as much as the space around it, so it stays readable
+        let font_scale = 1.0 - (1.0 - scale) / 2.0;
+        debug!("applying {:?} density", self.layout.density);
+
+        let layout = &mut self.layout;
+        let scale_xy = |xy: &mut XY<f32>| {
+            xy.x *= scale;
+            xy.y *= scale;
+        };
+        layout.window_padding.x = (layout.window_padding.x as f32 * scale).round() as i8;
+        layout.window_padding.y = (layout.window_padding.y as f32 * scale).round() as i8;
+        scale_xy(&mut layout.button_padding);
+        scale_xy(&mut layout.button_with_preview_padding);
+        layout.button_spacing *= scale;
+        layout.scroll_bar_margin *= scale;
+        layout.preview_size.width = (layout.preview_size.width as f32 * scale).round() as u16;
+        layout.preview_size.height = (layout.preview_size.height as f32 * scale).round() as u16;
+        layout.color_preview_size *= scale;
+        layout.source_icon_size *= scale;
+
+        self.font.size *= font_scale;
+        self.font.secondary_size *= font_scale;
+    }
 }
 
 #[serde_as]
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Clone, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct KeyStroke {
     #[serde_as(as = "FromInto<CharOrNum>")]
@@ -270,12 +642,13 @@ pub struct KeyStroke {
     pub modifiers: Vec<Modifier>,
 }
 
-#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Modifier {
     Control,
     Shift,
     Alt,
     Meta,
+    Super,
 }
 
 impl From<Modifier> for Keysym {
@@ -285,11 +658,38 @@ impl From<Modifier> for Keysym {
             Modifier::Shift => Keysym::Shift_L,
             Modifier::Alt => Keysym::Alt_L,
             Modifier::Meta => Keysym::Meta_L,
+            Modifier::Super => Keysym::Super_L,
         }
     }
 }
 
-#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+/// Actions a key of `macros` can run, in the order given
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MacroAction {
+    Paste,
+    PastePlain,
+    PasteTrimmed,
+    PasteAndEnter,
+    PasteKeepOpen,
+    Pin,
+    Remove,
+    ToggleExpiry,
+    Edit,
+    SaveToFile,
+    Open,
+    ViewImage,
+    ViewDetails,
+    Next,
+    Previous,
+    First,
+    Last,
+    StartSearch,
+    ToggleTimeline,
+    Close,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Color(u32);
 
 impl Deref for Color {
@@ -316,6 +716,18 @@ impl FromStr for Color {
     }
 }
 
+// The form FromStr takes, with the alpha only when it isn't opaque
+impl Display for Color {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let alpha = (**self >> 24) & 0xff;
+        if alpha == 0xff {
+            write!(f, "#{:06x}", **self & 0xffffff)
+        } else {
+            write!(f, "#{:06x}{alpha:02x}", **self & 0xffffff)
+        }
+    }
+}
+
 impl From<Color> for Color32 {
     fn from(value: Color) -> Self {
         let a = ((*value >> 24) & 0xff) as u8;
@@ -341,7 +753,7 @@ impl Display for ParseColorError {
     }
 }
 
-#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
 pub struct XY<T: Default> {
     pub x: T,
     pub y: T,
@@ -353,7 +765,7 @@ impl From<XY<f32>> for egui::Vec2 {
     }
 }
 
-#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Dimensions {
     pub width: u16,
     pub height: u16,
@@ -365,21 +777,132 @@ impl From<Dimensions> for egui::Vec2 {
     }
 }
 
-#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+/// What items are told apart by, instead of all of their data
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DedupeKey {
+    Text,
+    Image,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TextEllipsis {
+    End,
+    Middle,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LabelOverflow {
+    Truncate,
+    Wrap,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LayoutMode {
+    List,
+    Grid,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Density {
+    Compact,
+    Cozy,
+    Comfortable,
+}
+
+impl Density {
+    pub fn scale(self) -> f32 {
+        match self {
+            Density::Compact => 0.75,
+            Density::Cozy => 0.875,
+            Density::Comfortable => 1.0,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum WindowPositionMode {
+    #[serde(alias = "center")]
     Monitor,
     Pointer,
     Dynamic,
+    Last,
+    #[serde(untagged)]
+    Fixed {
+        x: i32,
+        y: i32,
+    },
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    Calculator,
+    Emoji,
+    Timestamp,
+    #[serde(untagged)]
+    Script(ScriptProviderConfig),
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ScriptProviderConfig {
+    pub name: String,
+    pub command: Vec<String>,
+    #[serde(default = "default_script_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn default_script_timeout_ms() -> u64 {
+    1000
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PasteMethod {
+    Auto,
+    Xtest,
+    SendEvent,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageKind {
+    File,
+    Sqlite,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RendererKind {
+    Auto,
+    #[serde(rename = "opengl")]
+    OpenGL,
+    Software,
+    Wgpu,
+}
+
+#[derive(Deserialize, Serialize)]
 #[serde(untagged)]
 enum CharOrNum {
     Char(char),
     Num(u32),
 }
 
+impl From<u32> for CharOrNum {
+    fn from(value: u32) -> Self {
+        match char::from_u32(value) {
+            Some(c) if c.is_ascii_graphic() => CharOrNum::Char(c),
+            _ => CharOrNum::Num(value),
+        }
+    }
+}
+
 impl From<CharOrNum> for u32 {
     fn from(value: CharOrNum) -> Self {
         match value {
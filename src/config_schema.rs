@@ -0,0 +1,608 @@
+//! The shape of the config file, derived from the `Config` type through `MakeOptional`, so
+//! generated files can't drift from what memoni reads. The descriptions of the options are the
+//! comments of sample_config.toml
+
+use std::{collections::HashMap, fmt::Write as _};
+
+use anyhow::{Result, anyhow};
+use serde_json::json;
+use toml::{Table, Value};
+
+use crate::config::{
+    Color, Config, DedupeKey, Density, Dimensions, KeyStroke, LabelOverflow, LayoutMode,
+    MacroAction, MergeStrategy, Modifier, PasteMethod, PointerGestureAction, ProviderKind,
+    RendererKind, ScriptProviderConfig, StorageKind, TextEllipsis, WindowPositionMode, XY,
+    YankPopUndo,
+};
+
+const SAMPLE_CONFIG: &str = include_str!("../sample_config.toml");
+
+pub trait Schema {
+    fn schema() -> SchemaType;
+}
+
+#[derive(Debug, Clone)]
+pub enum SchemaType {
+    Bool,
+    Integer {
+        min: i64,
+        max: u64,
+    },
+    Number,
+    String,
+    /// "#RRGGBB" or "#RRGGBBAA"
+    Color,
+    Array(Box<SchemaType>),
+    /// A single value, or an array of them
+    OneOrMany(Box<SchemaType>),
+    /// A table with any keys
+    Map(Box<SchemaType>),
+    /// One of the strings
+    Enum(&'static [&'static str]),
+    Object(Vec<SchemaField>),
+    /// A struct of the config, written as a [section] of the file
+    Section(Vec<SchemaField>),
+    AnyOf(Vec<SchemaType>),
+}
+
+impl SchemaType {
+    /// Arrays, also those in maps, taking a single value too, as serde_with's OneOrMany does
+    pub fn one_or_many(self) -> Self {
+        match self {
+            SchemaType::Array(item) => SchemaType::OneOrMany(item),
+            SchemaType::Map(value) => SchemaType::Map(Box::new(value.one_or_many())),
+            ty => ty,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SchemaField {
+    pub name: &'static str,
+    pub ty: SchemaType,
+    pub required: bool,
+}
+
+impl SchemaField {
+    fn required(name: &'static str, ty: SchemaType) -> Self {
+        SchemaField {
+            name,
+            ty,
+            required: true,
+        }
+    }
+}
+
+macro_rules! integer_schema {
+    ($($ty:ty),*) => {
+        $(impl Schema for $ty {
+            fn schema() -> SchemaType {
+                SchemaType::Integer {
+                    min: <$ty>::MIN as i64,
+                    max: <$ty>::MAX as u64,
+                }
+            }
+        })*
+    };
+}
+
+integer_schema!(u8, u16, u32, u64, usize, i8, i32);
+
+impl Schema for bool {
+    fn schema() -> SchemaType {
+        SchemaType::Bool
+    }
+}
+
+impl Schema for f32 {
+    fn schema() -> SchemaType {
+        SchemaType::Number
+    }
+}
+
+impl Schema for String {
+    fn schema() -> SchemaType {
+        SchemaType::String
+    }
+}
+
+impl<T: Schema> Schema for Vec<T> {
+    fn schema() -> SchemaType {
+        SchemaType::Array(Box::new(T::schema()))
+    }
+}
+
+impl<T: Schema> Schema for HashMap<String, T> {
+    fn schema() -> SchemaType {
+        SchemaType::Map(Box::new(T::schema()))
+    }
+}
+
+impl Schema for Color {
+    fn schema() -> SchemaType {
+        SchemaType::Color
+    }
+}
+
+impl<T: Schema + Default> Schema for XY<T> {
+    fn schema() -> SchemaType {
+        SchemaType::Object(vec![
+            SchemaField::required("x", T::schema()),
+            SchemaField::required("y", T::schema()),
+        ])
+    }
+}
+
+impl Schema for Dimensions {
+    fn schema() -> SchemaType {
+        SchemaType::Object(vec![
+            SchemaField::required("width", u16::schema()),
+            SchemaField::required("height", u16::schema()),
+        ])
+    }
+}
+
+impl Schema for KeyStroke {
+    fn schema() -> SchemaType {
+        SchemaType::Object(vec![
+            // A character, or a keysym code
+            SchemaField::required(
+                "key",
+                SchemaType::AnyOf(vec![SchemaType::String, u32::schema()]),
+            ),
+            SchemaField {
+                name: "modifier",
+                ty: Vec::<Modifier>::schema().one_or_many(),
+                required: false,
+            },
+        ])
+    }
+}
+
+impl Schema for ScriptProviderConfig {
+    fn schema() -> SchemaType {
+        SchemaType::Object(vec![
+            SchemaField::required("name", SchemaType::String),
+            SchemaField::required("command", Vec::<String>::schema()),
+            SchemaField {
+                name: "timeout_ms",
+                ty: u64::schema(),
+                required: false,
+            },
+        ])
+    }
+}
+
+impl Schema for WindowPositionMode {
+    fn schema() -> SchemaType {
+        SchemaType::AnyOf(vec![
+            SchemaType::Enum(&["monitor", "center", "pointer", "dynamic", "last"]),
+            SchemaType::Object(vec![
+                SchemaField::required("x", i32::schema()),
+                SchemaField::required("y", i32::schema()),
+            ]),
+        ])
+    }
+}
+
+impl Schema for ProviderKind {
+    fn schema() -> SchemaType {
+        SchemaType::AnyOf(vec![
+            SchemaType::Enum(&["calculator", "emoji", "timestamp"]),
+            ScriptProviderConfig::schema(),
+        ])
+    }
+}
+
+macro_rules! enum_schema {
+    ($($ty:ty => [$($value:literal),*]),* $(,)?) => {
+        $(impl Schema for $ty {
+            fn schema() -> SchemaType {
+                SchemaType::Enum(&[$($value),*])
+            }
+        })*
+    };
+}
+
+enum_schema! {
+    Modifier => ["Control", "Shift", "Alt", "Meta", "Super"],
+    DedupeKey => ["text", "image"],
+    TextEllipsis => ["end", "middle"],
+    LabelOverflow => ["truncate", "wrap"],
+    LayoutMode => ["list", "grid"],
+    MergeStrategy => ["off", "substring", "prefix"],
+    Density => ["compact", "cozy", "comfortable"],
+    PasteMethod => ["auto", "xtest", "send_event"],
+    RendererKind => ["auto", "opengl", "software", "wgpu"],
+    StorageKind => ["file", "sqlite"],
+    YankPopUndo => ["undo", "backspace"],
+    PointerGestureAction => ["paste", "select", "view_image", "view_details", "pin", "none"],
+    MacroAction => [
+        "paste", "paste_plain", "paste_trimmed", "paste_and_enter", "paste_keep_open", "pin",
+        "remove", "toggle_expiry", "edit", "save_to_file", "open", "view_image", "view_details",
+        "next", "previous", "first", "last", "start_search", "toggle_timeline", "close"
+    ],
+}
+
+/// Comment blocks above the options and sections of sample_config.toml, by their dotted path,
+/// e.g., "layout.mode". The per-selection examples at its end are left out
+pub fn annotations() -> HashMap<String, String> {
+    let mut annotations = HashMap::new();
+    let mut comment: Vec<&str> = vec![];
+    let mut section = String::new();
+    for line in SAMPLE_CONFIG.lines() {
+        if line.starts_with('#') {
+            comment.push(line);
+            continue;
+        }
+
+        let path = if let Some(name) = line.strip_prefix('[') {
+            let name = name.trim_end_matches(']');
+            if name.starts_with("CLIPBOARD") || name.starts_with("PRIMARY") {
+                break;
+            }
+            section = name.to_string();
+            Some(section.clone())
+        } else if let Some((key, _)) = line.split_once('=')
+            && let key = key.trim()
+            && !key.is_empty()
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            Some(if section.is_empty() {
+                key.to_string()
+            } else {
+                format!("{section}.{key}")
+            })
+        } else {
+            None
+        };
+
+        if let Some(path) = path
+            && !comment.is_empty()
+        {
+            annotations
+                .entry(path)
+                .or_insert_with(|| comment.join("\n"));
+        }
+        comment.clear();
+    }
+    annotations
+}
+
+/// A config file with every option commented out at its default value, under the description
+/// from sample_config.toml. Options in `filled`, e.g., a theme preset, are written uncommented
+pub fn scaffold(filled: &Table) -> Result<String> {
+    let Value::Table(defaults) = Value::try_from(Config::default())? else {
+        return Err(anyhow!("default config isn't a table"));
+    };
+    let SchemaType::Section(fields) = Config::schema() else {
+        return Err(anyhow!("config schema isn't a section"));
+    };
+
+    let mut scaffold = String::from(
+        "\
+# memoni configuration, generated by memoni init-config
+#
+# Options are commented out at their default values, uncomment the ones to change. Any option can
+# also be set for one selection only, under [CLIPBOARD] or [PRIMARY] (e.g., [PRIMARY.theme]).
+",
+    );
+    write_fields(
+        &mut scaffold,
+        "",
+        &fields,
+        &defaults,
+        Some(filled),
+        &annotations(),
+    );
+    Ok(scaffold)
+}
+
+// Values before sections, as TOML puts the keys after a section header in that section
+fn write_fields(
+    out: &mut String,
+    section: &str,
+    fields: &[SchemaField],
+    defaults: &Table,
+    filled: Option<&Table>,
+    annotations: &HashMap<String, String>,
+) {
+    let path_of = |name: &str| {
+        if section.is_empty() {
+            name.to_string()
+        } else {
+            format!("{section}.{name}")
+        }
+    };
+
+    for field in fields {
+        if matches!(field.ty, SchemaType::Section(_)) {
+            continue;
+        }
+        out.push('\n');
+        if let Some(annotation) = annotations.get(&path_of(field.name)) {
+            let _ = writeln!(out, "{annotation}");
+        }
+        match filled.and_then(|filled| filled.get(field.name)) {
+            Some(value) => {
+                let _ = writeln!(out, "{} = {value}", field.name);
+            }
+            None => {
+                if let Some(default) = defaults.get(field.name) {
+                    let _ = writeln!(out, "# {} = {default}", field.name);
+                }
+            }
+        }
+    }
+
+    for field in fields {
+        let SchemaType::Section(section_fields) = &field.ty else {
+            continue;
+        };
+        let path = path_of(field.name);
+        out.push_str("\n\n");
+        if let Some(annotation) = annotations.get(&path) {
+            let _ = writeln!(out, "{annotation}");
+        }
+        let _ = writeln!(out, "[{path}]");
+        let empty = Table::new();
+        write_fields(
+            out,
+            &path,
+            section_fields,
+            defaults
+                .get(field.name)
+                .and_then(Value::as_table)
+                .unwrap_or(&empty),
+            filled
+                .and_then(|filled| filled.get(field.name))
+                .and_then(Value::as_table),
+            annotations,
+        );
+    }
+}
+
+/// JSON Schema (draft 2020-12) of the config file, for editors validating and completing it,
+/// e.g., through a `#:schema` comment with Taplo
+pub fn json_schema() -> Result<serde_json::Value> {
+    let defaults = serde_json::to_value(Value::try_from(Config::default())?)?;
+    let SchemaType::Section(fields) = Config::schema() else {
+        return Err(anyhow!("config schema isn't a section"));
+    };
+    let annotations = annotations();
+
+    let mut config = json_type(
+        &SchemaType::Section(fields),
+        "",
+        Some(&defaults),
+        &annotations,
+    );
+    let mut schema = json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "memoni configuration",
+        "$defs": { "config": config.clone() },
+    });
+    // The root also takes the per-selection overrides, each a config of its own
+    for selection in ["CLIPBOARD", "PRIMARY"] {
+        config["properties"][selection] = json!({
+            "$ref": "#/$defs/config",
+            "description": format!("Overrides for the {selection} selection"),
+        });
+    }
+    if let (Some(schema), Some(config)) = (schema.as_object_mut(), config.as_object()) {
+        schema.extend(config.clone());
+    }
+    Ok(schema)
+}
+
+fn json_type(
+    ty: &SchemaType,
+    path: &str,
+    default: Option<&serde_json::Value>,
+    annotations: &HashMap<String, String>,
+) -> serde_json::Value {
+    let mut schema = match ty {
+        SchemaType::Bool => json!({ "type": "boolean" }),
+        SchemaType::Integer { min, max } => {
+            json!({ "type": "integer", "minimum": min, "maximum": max })
+        }
+        SchemaType::Number => json!({ "type": "number" }),
+        SchemaType::String => json!({ "type": "string" }),
+        SchemaType::Color => json!({
+            "type": "string",
+            "pattern": "^#([0-9a-fA-F]{3,4}|[0-9a-fA-F]{6}|[0-9a-fA-F]{8})$",
+        }),
+        SchemaType::Array(item) => json!({
+            "type": "array",
+            "items": json_type(item, "", None, annotations),
+        }),
+        SchemaType::OneOrMany(item) => {
+            let item = json_type(item, "", None, annotations);
+            json!({ "anyOf": [item.clone(), { "type": "array", "items": item }] })
+        }
+        SchemaType::Map(value) => json!({
+            "type": "object",
+            "additionalProperties": json_type(value, "", None, annotations),
+        }),
+        SchemaType::Enum(values) => json!({ "type": "string", "enum": values }),
+        SchemaType::Object(fields) | SchemaType::Section(fields) => {
+            let mut properties = serde_json::Map::new();
+            for field in fields {
+                let field_path = match path {
+                    "" => field.name.to_string(),
+                    path => format!("{path}.{}", field.name),
+                };
+                // Only sections are annotated, options of value tables like XY aren't
+                let (field_path, field_default) = match ty {
+                    SchemaType::Section(_) => (
+                        field_path,
+                        default.and_then(|default| default.get(field.name)),
+                    ),
+                    _ => (String::new(), None),
+                };
+                properties.insert(
+                    field.name.to_string(),
+                    json_type(&field.ty, &field_path, field_default, annotations),
+                );
+            }
+            let required = fields
+                .iter()
+                .filter(|field| field.required)
+                .map(|field| field.name)
+                .collect::<Vec<_>>();
+            let mut object = json!({
+                "type": "object",
+                "properties": properties,
+                "additionalProperties": false,
+            });
+            if !required.is_empty() {
+                object["required"] = json!(required);
+            }
+            object
+        }
+        SchemaType::AnyOf(types) => json!({
+            "anyOf": types
+                .iter()
+                .map(|ty| json_type(ty, "", None, annotations))
+                .collect::<Vec<_>>(),
+        }),
+    };
+
+    if let Some(annotation) = annotations.get(path) {
+        let description = annotation
+            .lines()
+            .map(|line| line.trim_start_matches('#').trim_start())
+            .collect::<Vec<_>>()
+            .join("\n");
+        schema["description"] = description.trim().into();
+    }
+    if let Some(default) = default
+        && !matches!(ty, SchemaType::Section(_))
+    {
+        schema["default"] = default.clone();
+    }
+    schema
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::de::DeserializeOwned;
+
+    use super::*;
+    use crate::config::{ConfigSet, ThemeConfig};
+
+    fn option_paths(section: &str, fields: &[SchemaField], paths: &mut Vec<String>) {
+        for field in fields {
+            let path = if section.is_empty() {
+                field.name.to_string()
+            } else {
+                format!("{section}.{}", field.name)
+            };
+            if let SchemaType::Section(fields) = &field.ty {
+                option_paths(&path, fields, paths);
+            }
+            paths.push(path);
+        }
+    }
+
+    #[test]
+    fn every_option_is_described_in_sample_config() {
+        let SchemaType::Section(fields) = Config::schema() else {
+            panic!("config schema isn't a section");
+        };
+        let mut paths = vec![];
+        option_paths("", &fields, &mut paths);
+
+        let annotations = annotations();
+        let missing = paths
+            .iter()
+            .filter(|path| !annotations.contains_key(*path))
+            .collect::<Vec<_>>();
+        assert!(missing.is_empty(), "not in sample_config.toml: {missing:?}");
+    }
+
+    #[test]
+    fn scaffold_parses_with_every_option_uncommented() -> Result<()> {
+        let mut filled = Table::new();
+        let Value::Table(theme) = Value::try_from(ThemeConfig::preset("light").unwrap())? else {
+            panic!("theme isn't a table");
+        };
+        filled.insert("theme".to_string(), Value::Table(theme));
+        let scaffold = scaffold(&filled)?;
+        toml::from_str::<ConfigSet>(&scaffold)?;
+
+        let uncommented = scaffold
+            .lines()
+            .map(|line| match line.strip_prefix("# ") {
+                Some(option)
+                    if option.split_once(" = ").is_some_and(|(key, _)| {
+                        key.chars().all(|c| c.is_ascii_lowercase() || c == '_')
+                    }) =>
+                {
+                    option
+                }
+                _ => line,
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        toml::from_str::<ConfigSet>(&uncommented)?;
+        Ok(())
+    }
+
+    #[test]
+    fn json_schema_describes_options() -> Result<()> {
+        let schema = json_schema()?;
+        let item_limit = &schema["properties"]["item_limit"];
+        assert_eq!(item_limit["type"], "integer");
+        assert_eq!(item_limit["default"], 100);
+        assert_eq!(
+            item_limit["description"],
+            "Maximum items kept in history\n\ndefault: 100"
+        );
+        assert_eq!(
+            schema["properties"]["layout"]["properties"]["mode"]["enum"],
+            json!(["list", "grid"])
+        );
+        assert_eq!(schema["properties"]["PRIMARY"]["$ref"], "#/$defs/config");
+        assert!(schema["$defs"]["config"]["properties"]["PRIMARY"].is_null());
+        Ok(())
+    }
+
+    fn assert_values_parse<T: Schema + DeserializeOwned>() -> Result<()> {
+        let values = match T::schema() {
+            SchemaType::Enum(values) => values,
+            SchemaType::AnyOf(types) => types
+                .iter()
+                .find_map(|ty| match ty {
+                    SchemaType::Enum(values) => Some(*values),
+                    _ => None,
+                })
+                .expect("no string values"),
+            ty => panic!("not an enum: {ty:?}"),
+        };
+        for value in values {
+            Value::String(value.to_string()).try_into::<T>()?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn enum_values_parse() -> Result<()> {
+        assert_values_parse::<Modifier>()?;
+        assert_values_parse::<DedupeKey>()?;
+        assert_values_parse::<TextEllipsis>()?;
+        assert_values_parse::<LabelOverflow>()?;
+        assert_values_parse::<LayoutMode>()?;
+        assert_values_parse::<MergeStrategy>()?;
+        assert_values_parse::<Density>()?;
+        assert_values_parse::<PasteMethod>()?;
+        assert_values_parse::<RendererKind>()?;
+        assert_values_parse::<StorageKind>()?;
+        assert_values_parse::<YankPopUndo>()?;
+        assert_values_parse::<PointerGestureAction>()?;
+        assert_values_parse::<MacroAction>()?;
+        assert_values_parse::<WindowPositionMode>()?;
+        assert_values_parse::<ProviderKind>()?;
+        Ok(())
+    }
+}
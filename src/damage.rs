@@ -0,0 +1,132 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::{BuildHasher as _, Hash, Hasher},
+};
+
+use egui::{ClippedPrimitive, Rect, epaint::Primitive};
+
+// Swap chains rarely keep more buffers than this, older ones are repainted in full
+const MAX_BUFFER_AGE: usize = 4;
+const HASH_SEED: usize = 0x5f3759df;
+
+/// Part of the window that needs repainting
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Damage {
+    None,
+    Rect(Rect),
+    Full,
+}
+
+impl Damage {
+    fn union(self, other: Damage) -> Damage {
+        match (self, other) {
+            (Damage::Full, _) | (_, Damage::Full) => Damage::Full,
+            (Damage::None, damage) | (damage, Damage::None) => damage,
+            (Damage::Rect(a), Damage::Rect(b)) => Damage::Rect(a.union(b)),
+        }
+    }
+}
+
+/// Finds what changed between frames by comparing their tessellated meshes, so a buffer that
+/// still holds an older frame only needs the changed parts painted over
+#[derive(Default)]
+pub struct DamageTracker {
+    // Mesh hashes of the previous frame, with the area each covers
+    prev_meshes: HashMap<u64, Rect>,
+    // Damage of the latest swapped frames against the one before them, newest first
+    history: VecDeque<Damage>,
+}
+
+impl DamageTracker {
+    pub fn new() -> Self {
+        DamageTracker {
+            prev_meshes: HashMap::new(),
+            history: VecDeque::with_capacity(MAX_BUFFER_AGE),
+        }
+    }
+
+    /// Forgets earlier frames, so the next one is painted in full, e.g. when the surface is
+    /// recreated or the window got exposed
+    pub fn reset(&mut self) {
+        self.prev_meshes.clear();
+        self.history.clear();
+    }
+
+    /// Records the frame and returns what to repaint of a buffer holding the frame from
+    /// `buffer_age` frames ago, where 0 means its contents are unknown. With `full`, the whole
+    /// frame counts as changed, e.g. when textures got updated
+    pub fn frame_damage(
+        &mut self,
+        clipped_primitives: &[ClippedPrimitive],
+        buffer_age: u32,
+        full: bool,
+    ) -> Damage {
+        let meshes = clipped_primitives
+            .iter()
+            .filter_map(|clipped| match &clipped.primitive {
+                Primitive::Mesh(mesh) => {
+                    let mut hasher = ahash::RandomState::with_seed(HASH_SEED).build_hasher();
+                    for value in [
+                        clipped.clip_rect.min.x,
+                        clipped.clip_rect.min.y,
+                        clipped.clip_rect.max.x,
+                        clipped.clip_rect.max.y,
+                    ] {
+                        value.to_bits().hash(&mut hasher);
+                    }
+                    mesh.texture_id.hash(&mut hasher);
+                    mesh.indices.hash(&mut hasher);
+                    for vertex in &mesh.vertices {
+                        for value in [vertex.pos.x, vertex.pos.y, vertex.uv.x, vertex.uv.y] {
+                            value.to_bits().hash(&mut hasher);
+                        }
+                        vertex.color.to_array().hash(&mut hasher);
+                    }
+                    Some((
+                        hasher.finish(),
+                        mesh.calc_bounds().intersect(clipped.clip_rect),
+                    ))
+                }
+                // Not drawn by memoni
+                Primitive::Callback(_) => None,
+            })
+            .collect::<HashMap<_, _>>();
+
+        // Nothing to compare the first frame against
+        let damage = if full || self.history.is_empty() {
+            Damage::Full
+        } else {
+            // Meshes that appeared or went away, the others are drawn the same
+            meshes
+                .iter()
+                .filter(|(hash, _)| !self.prev_meshes.contains_key(hash))
+                .chain(
+                    self.prev_meshes
+                        .iter()
+                        .filter(|(hash, _)| !meshes.contains_key(hash)),
+                )
+                .filter(|(_, bounds)| bounds.is_positive())
+                .fold(Damage::None, |damage, (_, &bounds)| {
+                    damage.union(Damage::Rect(bounds))
+                })
+        };
+        self.prev_meshes = meshes;
+        // Unchanged frames aren't swapped, so they don't age the buffers either
+        if damage == Damage::None {
+            return Damage::None;
+        }
+        if self.history.len() == MAX_BUFFER_AGE {
+            self.history.pop_back();
+        }
+        self.history.push_front(damage);
+
+        let buffer_age = buffer_age as usize;
+        if buffer_age == 0 || buffer_age > self.history.len() {
+            return Damage::Full;
+        }
+        self.history
+            .iter()
+            .take(buffer_age)
+            .fold(Damage::None, |acc, &damage| acc.union(damage))
+    }
+}
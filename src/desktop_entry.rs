@@ -0,0 +1,104 @@
+//! Icons of applications from their desktop entries, following the freedesktop Desktop Entry
+//! Specification, matched to the WM_CLASS of their windows
+
+use std::{collections::HashMap, fs, path::Path};
+
+use log::{debug, info};
+
+use crate::icon_theme::{data_dirs, parse_ini};
+
+/// The icons of installed applications, read once since entries rarely change while memoni runs
+pub struct DesktopEntries {
+    // Keys are lowercased, as WM_CLASS and desktop file names seldom agree on case
+    icons_by_wm_class: HashMap<String, String>,
+    icons_by_id: HashMap<String, String>,
+}
+
+impl DesktopEntries {
+    pub fn load() -> Self {
+        let mut entries = DesktopEntries {
+            icons_by_wm_class: HashMap::new(),
+            icons_by_id: HashMap::new(),
+        };
+        // Entries of the first directories shadow those with the same id after them
+        for dir in data_dirs() {
+            entries.load_dir(&dir.join("applications"), "");
+        }
+        info!(
+            "loaded icons of {} desktop entries",
+            entries.icons_by_id.len()
+        );
+        entries
+    }
+
+    // Subdirectories are part of the id, e.g., kde/konsole.desktop is kde-konsole
+    fn load_dir(&mut self, dir: &Path, id_prefix: &str) {
+        let Ok(read_dir) = fs::read_dir(dir) else {
+            return;
+        };
+        for entry in read_dir.flatten() {
+            let path = entry.path();
+            let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            if path.is_dir() {
+                self.load_dir(&path, &format!("{id_prefix}{name}-"));
+                continue;
+            }
+            let Some(stem) = name.strip_suffix(".desktop") else {
+                continue;
+            };
+            let id = format!("{id_prefix}{stem}").to_lowercase();
+            if self.icons_by_id.contains_key(&id) {
+                continue;
+            }
+
+            let Ok(text) = fs::read_to_string(&path) else {
+                debug!("failed to read desktop entry {path:?}");
+                continue;
+            };
+            let mut sections = parse_ini(&text);
+            let Some(mut entry) = sections.remove("Desktop Entry") else {
+                continue;
+            };
+            let Some(icon) = entry.remove("Icon").filter(|icon| !icon.is_empty()) else {
+                continue;
+            };
+            if let Some(wm_class) = entry.remove("StartupWMClass") {
+                self.icons_by_wm_class
+                    .entry(wm_class.to_lowercase())
+                    .or_insert_with(|| icon.clone());
+            }
+            self.icons_by_id.insert(id, icon);
+        }
+    }
+
+    /// Icon name, or path of an icon file, of the application with a WM_CLASS class name. Without
+    /// a matching entry, the lowercased class name, as applications are often named after it
+    pub fn icon(&self, wm_class: &str) -> String {
+        let wm_class = wm_class.to_lowercase();
+        let icon = self
+            .icons_by_wm_class
+            .get(&wm_class)
+            .or_else(|| self.icons_by_id.get(&wm_class))
+            // Reverse DNS ids, e.g., org.mozilla.firefox for "firefox"
+            .or_else(|| {
+                self.icons_by_id
+                    .iter()
+                    .filter(|(id, _)| id.strip_suffix(&wm_class).is_some_and(|p| p.ends_with('.')))
+                    .min_by_key(|(id, _)| (id.len(), *id))
+                    .map(|(_, icon)| icon)
+            });
+        match icon {
+            // Some entries name a themed icon with its extension, which the spec doesn't allow
+            Some(icon) if !Path::new(icon).is_absolute() => icon
+                .strip_suffix(".png")
+                .or_else(|| icon.strip_suffix(".svg"))
+                .or_else(|| icon.strip_suffix(".xpm"))
+                .unwrap_or(icon)
+                .to_string(),
+            Some(icon) => icon.clone(),
+            None => wm_class,
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use egui::RichText;
+use unicode_bidi::{BidiInfo, Level};
+use unicode_segmentation::UnicodeSegmentation as _;
+
+use crate::config::ThemeConfig;
+
+// Very very long string causes egui to choke on first render, even when we only display it on a
+// single line
+const MAX_DISPLAY_GRAPHEMES: usize = 10_000;
+
+#[derive(Debug)]
+enum Segment {
+    Text(String),
+    // Glyphs standing in for whitespace
+    Whitespace(String),
+    LineBreak,
+}
+
+/// Label of a text item. With `multi_line`, line breaks are kept for the button to wrap over
+/// several rows instead of being shown inline as ↵
+pub fn build_display_text(
+    s: &str,
+    theme: &ThemeConfig,
+    multi_line: bool,
+    show_whitespace: bool,
+) -> Vec<RichText> {
+    split_segments(s, show_whitespace)
+        .into_iter()
+        .map(|segment| match segment {
+            Segment::Text(text) => to_visual_order(text).into(),
+            Segment::Whitespace(glyphs) => RichText::new(glyphs).color(theme.whitespace_foreground),
+            Segment::LineBreak if multi_line => "\n".into(),
+            Segment::LineBreak => RichText::new('↵').color(theme.muted_foreground),
+        })
+        .collect()
+}
+
+// Whitespace is only made visible where it's easy to miss: leading and trailing blanks, tabs and
+// the non-ASCII spaces that look like regular ones
+fn split_segments(s: &str, show_whitespace: bool) -> Vec<Segment> {
+    let mut graphemes = s.graphemes(true).collect::<Vec<_>>();
+    let truncated = graphemes.len() > MAX_DISPLAY_GRAPHEMES;
+    graphemes.truncate(MAX_DISPLAY_GRAPHEMES);
+
+    let leading_end = graphemes
+        .iter()
+        .position(|g| !is_blank(g))
+        .unwrap_or(graphemes.len());
+
+    let mut segments = vec![];
+    // Blanks that turn out to be trailing if a line break or the end of text follows
+    let mut pending_blanks = vec![];
+    let flush_blanks = |segments: &mut Vec<Segment>, blanks: &mut Vec<&str>, at_edge: bool| {
+        for blank in blanks.drain(..) {
+            push_segment(segments, blank_segment(blank, at_edge, show_whitespace));
+        }
+    };
+
+    for (i, &g) in graphemes.iter().enumerate() {
+        match g {
+            "\r" => {}
+            "\n" | "\r\n" => {
+                flush_blanks(&mut segments, &mut pending_blanks, true);
+                push_segment(&mut segments, Segment::LineBreak);
+            }
+            _ if is_blank(g) && i < leading_end => {
+                push_segment(&mut segments, blank_segment(g, true, show_whitespace));
+            }
+            _ if is_blank(g) => pending_blanks.push(g),
+            _ => {
+                flush_blanks(&mut segments, &mut pending_blanks, false);
+                push_segment(&mut segments, Segment::Text(g.to_string()));
+            }
+        }
+    }
+
+    flush_blanks(&mut segments, &mut pending_blanks, !truncated);
+    if truncated {
+        push_segment(&mut segments, Segment::Text("…".to_string()));
+    }
+
+    segments
+}
+
+fn blank_segment(blank: &str, at_edge: bool, show_whitespace: bool) -> Segment {
+    match blank {
+        "\t" if !show_whitespace => Segment::Text("    ".to_string()),
+        _ if !show_whitespace => Segment::Text(blank.to_string()),
+        " " if at_edge => Segment::Whitespace("·".to_string()),
+        " " => Segment::Text(" ".to_string()),
+        "\t" => Segment::Whitespace(" ⇥ ".to_string()),
+        _ => Segment::Whitespace("⍽".to_string()),
+    }
+}
+
+fn push_segment(segments: &mut Vec<Segment>, segment: Segment) {
+    match (segments.last_mut(), segment) {
+        (Some(Segment::Text(last)), Segment::Text(text)) => last.push_str(&text),
+        (Some(Segment::Whitespace(last)), Segment::Whitespace(glyphs)) => last.push_str(&glyphs),
+        (_, segment) => segments.push(segment),
+    }
+}
+
+fn is_blank(g: &str) -> bool {
+    // Zero width spaces aren't whitespace to Unicode, but are just as invisible
+    g.chars().all(|c| {
+        (c.is_whitespace() && c != '\n' && c != '\r') || c == '\u{200b}' || c == '\u{feff}'
+    })
+}
+
+// egui lays text out in logical order, so right-to-left runs are reordered here. Each segment is
+// its own left-to-right paragraph, isolating RTL text from the rest of the label, and explicit
+// direction controls are dropped since egui would draw them as boxes
+fn to_visual_order(segment: String) -> String {
+    let bidi_info = BidiInfo::new(&segment, Some(Level::ltr()));
+    if !bidi_info.has_rtl() && !segment.chars().any(is_bidi_control) {
+        return segment;
+    }
+
+    bidi_info
+        .paragraphs
+        .iter()
+        .map(|para| bidi_info.reorder_line(para, para.range.clone()))
+        .collect::<String>()
+        .chars()
+        .filter(|c| !is_bidi_control(*c))
+        .collect()
+}
+
+fn is_bidi_control(c: char) -> bool {
+    matches!(
+        c,
+        '\u{061c}' | '\u{200e}' | '\u{200f}' | '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}'
+    )
+}
@@ -0,0 +1,51 @@
+//! Items marked to be removed after a while, e.g., one-time codes. Expiry times are Unix seconds,
+//! so they still hold after a restart
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs())
+}
+
+/// Milliseconds to set the expiry timer to for the item expiring the soonest. Never 0, as that
+/// disarms a timerfd, so items that expired while memoni wasn't running go right away
+pub fn timer_ms(expires_at: u64, now: u64) -> u64 {
+    expires_at.saturating_sub(now).saturating_mul(1000).max(1)
+}
+
+/// Time left shown on the badge of an expiring item, e.g., "4:05" or "1:02:03"
+pub fn countdown(expires_at: u64, now: u64) -> String {
+    let left = expires_at.saturating_sub(now);
+    let (hours, minutes, seconds) = (left / 3600, left / 60 % 60, left % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn countdown_shows_hours_only_when_needed() {
+        assert_eq!(countdown(1000 + 245, 1000), "4:05");
+        assert_eq!(countdown(1000 + 3723, 1000), "1:02:03");
+        assert_eq!(countdown(1000 + 9, 1000), "0:09");
+    }
+
+    #[test]
+    fn countdown_stops_at_zero() {
+        assert_eq!(countdown(1000, 1005), "0:00");
+    }
+
+    #[test]
+    fn timer_fires_right_away_for_expired_items() {
+        assert_eq!(timer_ms(1000, 1000), 1);
+        assert_eq!(timer_ms(1000, 2000), 1);
+        assert_eq!(timer_ms(1300, 1000), 300_000);
+    }
+}
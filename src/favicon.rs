@@ -0,0 +1,82 @@
+use std::{
+    process::{Command, Stdio},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+use anyhow::{Context as _, Result, bail};
+use image::RgbaImage;
+use log::{debug, trace};
+
+use crate::image_decode::decode_image;
+
+// Favicons are tiny, anything bigger or slower isn't worth waiting for
+const MAX_FAVICON_BYTES: &str = "262144";
+const FETCH_TIMEOUT_SECS: &str = "5";
+
+/// Fetches favicons of the domains of link items on a background thread, with curl which must be
+/// installed. Only used when `fetch_favicons` is on, since it tells the sites what was copied
+pub struct FaviconFetcher {
+    sender: Sender<String>,
+    receiver: Receiver<(String, Option<RgbaImage>)>,
+}
+
+impl FaviconFetcher {
+    pub fn new(registry: &mio::Registry, token: mio::Token) -> Result<Self> {
+        let waker = mio::Waker::new(registry, token)?;
+        let (request_sender, request_receiver) = mpsc::channel::<String>();
+        let (result_sender, result_receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            while let Ok(domain) = request_receiver.recv() {
+                trace!("fetching favicon of {domain}");
+                let icon = fetch(&domain)
+                    .inspect_err(|err| debug!("failed to fetch favicon of {domain}: {err}"))
+                    .ok();
+                if result_sender.send((domain, icon)).is_err() {
+                    break;
+                }
+                let _ = waker.wake();
+            }
+            debug!("favicon fetcher stopped");
+        });
+
+        Ok(FaviconFetcher {
+            sender: request_sender,
+            receiver: result_receiver,
+        })
+    }
+
+    pub fn request(&self, domain: String) {
+        let _ = self.sender.send(domain);
+    }
+
+    /// Fetched favicons by domain, None for those that failed
+    pub fn take_results(&self) -> Vec<(String, Option<RgbaImage>)> {
+        self.receiver.try_iter().collect()
+    }
+}
+
+fn fetch(domain: &str) -> Result<RgbaImage> {
+    let output = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location"])
+        .args(["--proto", "=https", "--proto-redir", "=https"])
+        .args(["--max-time", FETCH_TIMEOUT_SECS])
+        .args(["--max-filesize", MAX_FAVICON_BYTES])
+        .args([
+            "--user-agent",
+            concat!("memoni/", env!("CARGO_PKG_VERSION")),
+        ])
+        .arg(format!("https://{domain}/favicon.ico"))
+        .stdin(Stdio::null())
+        .output()
+        .context("failed to run curl")?;
+    if !output.status.success() {
+        bail!(
+            "curl exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    decode_image(&output.stdout)
+}
@@ -0,0 +1,251 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+use anyhow::Result;
+use image::RgbaImage;
+use log::{debug, trace};
+
+#[cfg(feature = "pdf-thumbnails")]
+use crate::freedesktop_cache::{
+    get_cached_thumbnail, has_failure_marker, write_failure_marker, write_thumbnail,
+};
+use crate::thumbnailer;
+
+const LISTED_ENTRIES: usize = 2;
+// Matches the most files the item preview composes together
+#[cfg(feature = "pdf-thumbnails")]
+const MAX_THUMBNAILED_FILES: usize = 4;
+// Same as the "large" size of the freedesktop thumbnail spec
+#[cfg(feature = "pdf-thumbnails")]
+const THUMBNAIL_SIZE: u32 = 256;
+// Bounds the scan of huge directories, the count is shown as a lower bound past this
+const MAX_SCANNED_ENTRIES: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FileKind {
+    Folder,
+    Image,
+    Video,
+    Audio,
+    Pdf,
+    Archive,
+    Other,
+    Missing,
+}
+
+impl FileKind {
+    fn of(path: &Path, metadata: &fs::Metadata) -> Self {
+        if metadata.is_dir() {
+            return FileKind::Folder;
+        }
+
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match extension.as_str() {
+            "png" | "jpg" | "jpeg" | "gif" | "webp" | "bmp" | "svg" | "tif" | "tiff" | "avif"
+            | "heic" | "ico" => FileKind::Image,
+            "mp4" | "mkv" | "webm" | "mov" | "avi" | "m4v" | "wmv" | "flv" => FileKind::Video,
+            "mp3" | "flac" | "ogg" | "opus" | "wav" | "m4a" | "aac" => FileKind::Audio,
+            "pdf" => FileKind::Pdf,
+            "zip" | "tar" | "gz" | "tgz" | "xz" | "bz2" | "zst" | "7z" | "rar" => FileKind::Archive,
+            _ => FileKind::Other,
+        }
+    }
+
+    pub fn noun(&self, count: usize) -> &'static str {
+        let singular = count == 1;
+        match self {
+            FileKind::Folder if singular => "folder",
+            FileKind::Folder => "folders",
+            FileKind::Image if singular => "image",
+            FileKind::Image => "images",
+            FileKind::Video if singular => "video",
+            FileKind::Video => "videos",
+            FileKind::Audio if singular => "audio file",
+            FileKind::Audio => "audio files",
+            FileKind::Pdf if singular => "PDF",
+            FileKind::Pdf => "PDFs",
+            FileKind::Archive if singular => "archive",
+            FileKind::Archive => "archives",
+            FileKind::Other if singular => "file",
+            FileKind::Other => "files",
+            FileKind::Missing => "missing",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FileStats {
+    /// Sorted by kind
+    pub counts: Vec<(FileKind, usize)>,
+    /// Cumulative size of regular files, folders are not walked
+    pub total_size: u64,
+    /// Set when a single directory is copied
+    pub listing: Option<DirListing>,
+    /// First lines, set when a single text file is copied
+    pub text_head: Option<Vec<String>>,
+    /// Generated for documents that have no cached thumbnail
+    pub thumbnails: Vec<(PathBuf, RgbaImage)>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DirListing {
+    /// First few entries, folders first, each folder name ending with '/'
+    pub entries: Vec<String>,
+    pub count: usize,
+    /// The directory has more than `count` entries
+    pub truncated: bool,
+}
+
+struct ScanRequest {
+    item_id: u64,
+    paths: Vec<PathBuf>,
+}
+
+/// Stats copied files on a background thread, since a stalled network mount would otherwise
+/// freeze the UI
+pub struct FileStatsScanner {
+    sender: Sender<ScanRequest>,
+    receiver: Receiver<(u64, FileStats)>,
+}
+
+impl FileStatsScanner {
+    pub fn new(registry: &mio::Registry, token: mio::Token) -> Result<Self> {
+        let waker = mio::Waker::new(registry, token)?;
+        let (request_sender, request_receiver) = mpsc::channel::<ScanRequest>();
+        let (result_sender, result_receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            while let Ok(request) = request_receiver.recv() {
+                trace!(
+                    "scanning {} files of item {}",
+                    request.paths.len(),
+                    request.item_id
+                );
+                let stats = scan(&request.paths);
+                if result_sender.send((request.item_id, stats)).is_err() {
+                    break;
+                }
+                let _ = waker.wake();
+            }
+            debug!("file stats scanner stopped");
+        });
+
+        Ok(FileStatsScanner {
+            sender: request_sender,
+            receiver: result_receiver,
+        })
+    }
+
+    pub fn request(&self, item_id: u64, paths: Vec<PathBuf>) {
+        let _ = self.sender.send(ScanRequest { item_id, paths });
+    }
+
+    pub fn take_results(&self) -> Vec<(u64, FileStats)> {
+        self.receiver.try_iter().collect()
+    }
+}
+
+fn scan(paths: &[PathBuf]) -> FileStats {
+    let mut stats = FileStats::default();
+    let mut kinds = Vec::with_capacity(paths.len());
+    for path in paths {
+        let kind = match fs::metadata(path) {
+            Ok(metadata) => {
+                if metadata.is_file() {
+                    stats.total_size += metadata.len();
+                }
+                FileKind::of(path, &metadata)
+            }
+            Err(err) => {
+                trace!("failed to stat {path:?}: {err}");
+                FileKind::Missing
+            }
+        };
+
+        match stats.counts.iter_mut().find(|(k, _)| *k == kind) {
+            Some((_, count)) => *count += 1,
+            None => stats.counts.push((kind, 1)),
+        }
+        kinds.push(kind);
+    }
+
+    stats.counts.sort();
+
+    if let [path] = paths {
+        match kinds[0] {
+            FileKind::Folder => match list_dir(path) {
+                Ok(listing) => stats.listing = Some(listing),
+                Err(err) => debug!("failed to list {path:?}: {err}"),
+            },
+            FileKind::Other => match thumbnailer::read_text_head(path) {
+                Ok(text_head) => stats.text_head = text_head,
+                Err(err) => debug!("failed to read text of {path:?}: {err}"),
+            },
+            _ => {}
+        }
+    }
+
+    #[cfg(feature = "pdf-thumbnails")]
+    for (path, kind) in paths.iter().zip(&kinds).take(MAX_THUMBNAILED_FILES) {
+        if *kind != FileKind::Pdf
+            || matches!(get_cached_thumbnail(path), Ok(Some(_)))
+            || has_failure_marker(path).unwrap_or(false)
+        {
+            continue;
+        }
+
+        match thumbnailer::render_pdf_page(path, THUMBNAIL_SIZE) {
+            Ok(thumbnail) => {
+                if let Err(err) = write_thumbnail(path, &thumbnail) {
+                    debug!("failed to cache thumbnail of {path:?}: {err}");
+                }
+                stats.thumbnails.push((path.clone(), thumbnail));
+            }
+            Err(err) => {
+                debug!("failed to render thumbnail of {path:?}: {err}");
+                if let Err(err) = write_failure_marker(path) {
+                    debug!("failed to write thumbnail failure marker of {path:?}: {err}");
+                }
+            }
+        }
+    }
+
+    stats
+}
+
+fn list_dir(path: &Path) -> Result<DirListing> {
+    let mut names = vec![];
+    let mut truncated = false;
+    for entry in fs::read_dir(path)? {
+        if names.len() == MAX_SCANNED_ENTRIES {
+            truncated = true;
+            break;
+        }
+
+        let entry = entry?;
+        // file_type doesn't follow symlinks, so a link to a folder is listed as a plain entry
+        let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+        names.push((!is_dir, entry.file_name().to_string_lossy().into_owned()));
+    }
+
+    let count = names.len();
+    names.sort_by_cached_key(|(is_file, name)| (*is_file, name.to_lowercase()));
+    let entries = names
+        .into_iter()
+        .take(LISTED_ENTRIES)
+        .map(|(is_file, name)| if is_file { name } else { name + "/" })
+        .collect();
+
+    Ok(DirListing {
+        entries,
+        count,
+        truncated,
+    })
+}
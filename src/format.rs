@@ -0,0 +1,221 @@
+use std::env;
+
+const SIZE_UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
+const ENGLISH_MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Locale-dependent number formatting for counters and sizes shown to the user
+#[derive(Debug, Clone, Copy)]
+pub struct NumberFormat {
+    thousands_separator: Option<char>,
+    decimal_separator: char,
+}
+
+impl NumberFormat {
+    /// `locale` is a POSIX locale name (e.g., "de_DE.UTF-8"); "auto" picks it up from the
+    /// environment the same way libc does
+    pub fn from_locale(locale: &str) -> Self {
+        let locale = resolve_locale(locale, "LC_NUMERIC");
+        let (name, language) = split_locale(&locale);
+
+        let (thousands_separator, decimal_separator) = match (language, name) {
+            (_, "de_CH" | "de-CH") => (Some('\''), '.'),
+            ("de" | "es" | "it" | "nl" | "pt" | "id" | "tr" | "da" | "el" | "ro", _) => {
+                (Some('.'), ',')
+            }
+            ("fr" | "ru" | "pl" | "cs" | "sk" | "sv" | "fi" | "nb" | "no" | "uk" | "hu", _) => {
+                (Some('\u{a0}'), ',')
+            }
+            ("" | "C" | "POSIX", _) => (None, '.'),
+            _ => (Some(','), '.'),
+        };
+
+        NumberFormat {
+            thousands_separator,
+            decimal_separator,
+        }
+    }
+
+    pub fn thousands_separator(&self) -> Option<char> {
+        self.thousands_separator
+    }
+
+    pub fn decimal_separator(&self) -> char {
+        self.decimal_separator
+    }
+
+    pub fn count(&self, n: usize) -> String {
+        let digits = n.to_string();
+        let Some(separator) = self.thousands_separator else {
+            return digits;
+        };
+
+        let mut formatted = String::with_capacity(digits.len() + digits.len() / 3);
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                formatted.push(separator);
+            }
+            formatted.push(digit);
+        }
+        formatted
+    }
+
+    /// Formats a byte count in decimal units, e.g., "512 B", "1.4 MB", "14 MB"
+    pub fn size(&self, bytes: u64) -> String {
+        let mut value = bytes as f64;
+        let mut unit = 0;
+        while value >= 1000.0 && unit < SIZE_UNITS.len() - 1 {
+            value /= 1000.0;
+            unit += 1;
+        }
+
+        if unit == 0 {
+            format!("{} {}", self.count(bytes as usize), SIZE_UNITS[unit])
+        } else if value < 10.0 {
+            let formatted = format!("{value:.1}").replace('.', &self.decimal_separator.to_string());
+            format!("{formatted} {}", SIZE_UNITS[unit])
+        } else {
+            format!(
+                "{} {}",
+                self.count(value.round() as usize),
+                SIZE_UNITS[unit]
+            )
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DateOrder {
+    // e.g., "Nov 12"
+    MonthDay,
+    // e.g., "12 nov"
+    DayMonth,
+    // e.g., "12. Nov"
+    DayDotMonth,
+}
+
+/// Locale-dependent formatting of dates shown to the user, e.g., timeline headers
+#[derive(Debug, Clone, Copy)]
+pub struct DateFormat {
+    months: [&'static str; 12],
+    order: DateOrder,
+}
+
+impl DateFormat {
+    /// `locale` is a POSIX locale name like in [`NumberFormat::from_locale`], with "auto" following
+    /// LC_TIME instead
+    pub fn from_locale(locale: &str) -> Self {
+        let locale = resolve_locale(locale, "LC_TIME");
+        let (_, language) = split_locale(&locale);
+
+        let (months, order) = match language {
+            "de" => (
+                [
+                    "Jan", "Feb", "Mär", "Apr", "Mai", "Jun", "Jul", "Aug", "Sep", "Okt", "Nov",
+                    "Dez",
+                ],
+                DateOrder::DayDotMonth,
+            ),
+            "fr" => (
+                [
+                    "janv.", "févr.", "mars", "avr.", "mai", "juin", "juil.", "août", "sept.",
+                    "oct.", "nov.", "déc.",
+                ],
+                DateOrder::DayMonth,
+            ),
+            "es" => (
+                [
+                    "ene", "feb", "mar", "abr", "may", "jun", "jul", "ago", "sept", "oct", "nov",
+                    "dic",
+                ],
+                DateOrder::DayMonth,
+            ),
+            "it" => (
+                [
+                    "gen", "feb", "mar", "apr", "mag", "giu", "lug", "ago", "set", "ott", "nov",
+                    "dic",
+                ],
+                DateOrder::DayMonth,
+            ),
+            "pt" => (
+                [
+                    "jan", "fev", "mar", "abr", "mai", "jun", "jul", "ago", "set", "out", "nov",
+                    "dez",
+                ],
+                DateOrder::DayMonth,
+            ),
+            "nl" => (
+                [
+                    "jan", "feb", "mrt", "apr", "mei", "jun", "jul", "aug", "sep", "okt", "nov",
+                    "dec",
+                ],
+                DateOrder::DayMonth,
+            ),
+            "sv" => (
+                [
+                    "jan", "feb", "mars", "apr", "maj", "juni", "juli", "aug", "sep", "okt", "nov",
+                    "dec",
+                ],
+                DateOrder::DayMonth,
+            ),
+            "pl" => (
+                [
+                    "sty", "lut", "mar", "kwi", "maj", "cze", "lip", "sie", "wrz", "paź", "lis",
+                    "gru",
+                ],
+                DateOrder::DayMonth,
+            ),
+            "ru" => (
+                [
+                    "янв", "фев", "мар", "апр", "мая", "июн", "июл", "авг", "сен", "окт", "ноя",
+                    "дек",
+                ],
+                DateOrder::DayMonth,
+            ),
+            _ => (ENGLISH_MONTHS, DateOrder::MonthDay),
+        };
+
+        DateFormat { months, order }
+    }
+
+    /// Formats a day of the year, `month` counting from 1
+    pub fn day(&self, month: u32, day: u32) -> String {
+        let month = self.months[month as usize - 1];
+        match self.order {
+            DateOrder::MonthDay => format!("{month} {day}"),
+            DateOrder::DayMonth => format!("{day} {month}"),
+            DateOrder::DayDotMonth => format!("{day}. {month}"),
+        }
+    }
+
+    /// Formats a day of another year than the current one
+    pub fn day_with_year(&self, year: i64, month: u32, day: u32) -> String {
+        match self.order {
+            DateOrder::MonthDay => format!("{}, {year}", self.day(month, day)),
+            DateOrder::DayMonth | DateOrder::DayDotMonth => {
+                format!("{} {year}", self.day(month, day))
+            }
+        }
+    }
+}
+
+// "auto" picks the locale up from the environment the same way libc does for the category
+fn resolve_locale(locale: &str, category: &str) -> String {
+    if locale == "auto" {
+        ["LC_ALL", category, "LANG"]
+            .iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default()
+    } else {
+        locale.to_string()
+    }
+}
+
+// Drops the encoding and modifier parts, e.g., "de_CH.UTF-8@euro" -> ("de_CH", "de")
+fn split_locale(locale: &str) -> (&str, &str) {
+    let name = locale.split(['.', '@']).next().unwrap_or_default();
+    let language = name.split(['_', '-']).next().unwrap_or_default();
+    (name, language)
+}
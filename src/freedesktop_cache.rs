@@ -1,33 +1,34 @@
 use std::{
-    os::unix::ffi::OsStrExt as _,
+    fs::{self, DirBuilder, File, OpenOptions},
+    io::{BufReader, BufWriter},
+    os::unix::{
+        ffi::OsStrExt as _,
+        fs::{DirBuilderExt as _, OpenOptionsExt as _},
+    },
     path::{self, Path, PathBuf},
+    time::UNIX_EPOCH,
 };
 
 use anyhow::{Result, anyhow};
+use image::{RgbaImage, imageops};
 use log::debug;
 use md5::{Digest, Md5};
 
-use crate::utils::{percent_encode, to_hex_string};
+use crate::{text::percent_encode, utils::to_hex_string};
+
+const NORMAL_SIZE: u32 = 128;
+const LARGE_SIZE: u32 = 256;
+const FAIL_DIR_NAME: &str = concat!("memoni-", env!("CARGO_PKG_VERSION"));
 
 pub fn get_cached_thumbnail<P: AsRef<Path>>(file: P) -> Result<Option<PathBuf>> {
-    let thumbnails_dir = dirs::cache_dir()
-        .ok_or_else(|| anyhow!("cache directory not found"))?
-        .join("thumbnails");
+    let thumbnails_dir = thumbnails_dir()?;
 
     let is_cached_thumbnail = file.as_ref().ancestors().any(|a| a == thumbnails_dir);
     if is_cached_thumbnail {
         return Ok(Some(file.as_ref().to_path_buf()));
     }
 
-    let mut hasher = Md5::new();
-    hasher.update(b"file://");
-    for component in path::absolute(&file)?.components().skip(1) {
-        hasher.update(b"/");
-        hasher.update(percent_encode(component.as_os_str().as_bytes()));
-    }
-
-    let thumbnail_name = to_hex_string(&hasher.finalize());
-    let thumbnail_filename = format!("{}.png", thumbnail_name);
+    let thumbnail_filename = thumbnail_filename(&file_uri(&file)?);
 
     for size in &["normal", "large", "x-large", "xx-large"] {
         let thumbnail = thumbnails_dir.join(size).join(&thumbnail_filename);
@@ -43,3 +44,135 @@ pub fn get_cached_thumbnail<P: AsRef<Path>>(file: P) -> Result<Option<PathBuf>>
     );
     Ok(None)
 }
+
+/// Saves a thumbnail generated by memoni into the shared cache so other apps can reuse it
+pub fn write_thumbnail<P: AsRef<Path>>(file: P, thumbnail: &RgbaImage) -> Result<PathBuf> {
+    let (size_dir, max_side) = if thumbnail.width().max(thumbnail.height()) > NORMAL_SIZE {
+        ("large", LARGE_SIZE)
+    } else {
+        ("normal", NORMAL_SIZE)
+    };
+    let resized;
+    let thumbnail = if thumbnail.width() > max_side || thumbnail.height() > max_side {
+        let scale = max_side as f32 / thumbnail.width().max(thumbnail.height()) as f32;
+        resized = imageops::thumbnail(
+            thumbnail,
+            (thumbnail.width() as f32 * scale).round() as u32,
+            (thumbnail.height() as f32 * scale).round() as u32,
+        );
+        &resized
+    } else {
+        thumbnail
+    };
+
+    let uri = file_uri(&file)?;
+    let thumbnail_path = thumbnails_dir()?
+        .join(size_dir)
+        .join(thumbnail_filename(&uri));
+    write_png(&thumbnail_path, thumbnail, &uri, &file)?;
+
+    debug!(
+        "wrote thumbnail of {:?} to {thumbnail_path:?}",
+        file.as_ref()
+    );
+    Ok(thumbnail_path)
+}
+
+/// Records that memoni can't thumbnail the file, so it isn't retried until the file changes
+pub fn write_failure_marker<P: AsRef<Path>>(file: P) -> Result<()> {
+    let uri = file_uri(&file)?;
+    let marker_path = thumbnails_dir()?
+        .join("fail")
+        .join(FAIL_DIR_NAME)
+        .join(thumbnail_filename(&uri));
+    write_png(&marker_path, &RgbaImage::new(1, 1), &uri, &file)?;
+
+    debug!("wrote thumbnail failure marker of {:?}", file.as_ref());
+    Ok(())
+}
+
+/// Whether thumbnailing the file failed before and the file hasn't been modified since
+pub fn has_failure_marker<P: AsRef<Path>>(file: P) -> Result<bool> {
+    let marker_path = thumbnails_dir()?
+        .join("fail")
+        .join(FAIL_DIR_NAME)
+        .join(thumbnail_filename(&file_uri(&file)?));
+    let Ok(marker) = File::open(&marker_path) else {
+        return Ok(false);
+    };
+
+    let reader = png::Decoder::new(BufReader::new(marker)).read_info()?;
+    let mtime = file_mtime(&file)?.to_string();
+    Ok(reader
+        .info()
+        .uncompressed_latin1_text
+        .iter()
+        .any(|chunk| chunk.keyword == "Thumb::MTime" && chunk.text == mtime))
+}
+
+fn thumbnails_dir() -> Result<PathBuf> {
+    Ok(dirs::cache_dir()
+        .ok_or_else(|| anyhow!("cache directory not found"))?
+        .join("thumbnails"))
+}
+
+fn file_uri<P: AsRef<Path>>(file: P) -> Result<String> {
+    let mut uri = b"file://".to_vec();
+    for component in path::absolute(&file)?.components().skip(1) {
+        uri.push(b'/');
+        uri.extend(percent_encode(component.as_os_str().as_bytes()));
+    }
+
+    // percent_encode leaves only ASCII behind
+    Ok(String::from_utf8(uri)?)
+}
+
+fn thumbnail_filename(uri: &str) -> String {
+    let mut hasher = Md5::new();
+    hasher.update(uri.as_bytes());
+    format!("{}.png", to_hex_string(&hasher.finalize()))
+}
+
+fn file_mtime<P: AsRef<Path>>(file: P) -> Result<u64> {
+    Ok(fs::metadata(file)?
+        .modified()?
+        .duration_since(UNIX_EPOCH)?
+        .as_secs())
+}
+
+// Per the spec: private permissions, the required Thumb::URI and Thumb::MTime keys, and an
+// atomic rename so readers never see a partial file
+fn write_png<P: AsRef<Path>>(path: &Path, image: &RgbaImage, uri: &str, file: P) -> Result<()> {
+    let dir = path
+        .parent()
+        .ok_or_else(|| anyhow!("thumbnail path has no parent: {path:?}"))?;
+    DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+
+    let temp_path = path.with_extension(format!("png.memoni-{}.tmp", std::process::id()));
+    let temp_file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&temp_path)?;
+
+    let result = (|| -> Result<()> {
+        let mut encoder =
+            png::Encoder::new(BufWriter::new(temp_file), image.width(), image.height());
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.add_text_chunk("Thumb::URI".to_string(), uri.to_string())?;
+        encoder.add_text_chunk("Thumb::MTime".to_string(), file_mtime(&file)?.to_string())?;
+        encoder.add_text_chunk("Software".to_string(), "memoni".to_string())?;
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(image.as_raw())?;
+        writer.finish()?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
@@ -0,0 +1,141 @@
+//! Global hotkeys of the server, grabbed on the root window so they work while the window is
+//! hidden
+
+use anyhow::Result;
+use log::{info, warn};
+use x11rb::{
+    connection::Connection as _,
+    protocol::xproto::{ConnectionExt as _, GrabMode, KeyPressEvent, ModMask, Window},
+    xcb_ffi::XCBConnection,
+};
+use xkeysym::{KeyCode, Keysym};
+
+use crate::{
+    config::{Config, KeyStroke},
+    x11_key_converter::X11KeyConverter,
+};
+
+// ModMask::LOCK and ModMask::M2, which Num Lock is bound to on most keymaps
+const CAPS_LOCK_MASK: u16 = 1 << 1;
+const NUM_LOCK_MASK: u16 = 1 << 4;
+// Lock modifiers don't change what a hotkey does, so it's grabbed with each combination of them
+const LOCK_MASKS: [u16; 4] = [
+    0,
+    CAPS_LOCK_MASK,
+    NUM_LOCK_MASK,
+    CAPS_LOCK_MASK | NUM_LOCK_MASK,
+];
+
+struct Grab {
+    keycode: u8,
+    modifiers: u16,
+    action: HotkeyAction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HotkeyAction {
+    /// Pastes the item at the index of the history, pinned items first
+    PasteItem(usize),
+    /// Pastes the unpinned item before the newest one, e.g., after copying over the one needed
+    PastePrevious,
+}
+
+/// The key strokes of `paste_hotkeys` and `paste_previous_hotkeys`
+pub struct Hotkeys {
+    root: Window,
+    grabs: Vec<Grab>,
+}
+
+impl Hotkeys {
+    pub fn grab(
+        conn: &XCBConnection,
+        root: Window,
+        key_converter: &X11KeyConverter,
+        config: &Config,
+    ) -> Result<Self> {
+        let actions = config
+            .paste_hotkeys
+            .iter()
+            .enumerate()
+            .map(|(idx, key_stroke)| (key_stroke, HotkeyAction::PasteItem(idx)))
+            .chain(
+                config
+                    .paste_previous_hotkeys
+                    .iter()
+                    .map(|key_stroke| (key_stroke, HotkeyAction::PastePrevious)),
+            );
+
+        let mut grabs = vec![];
+        'actions: for (key_stroke, action) in actions {
+            let Some(grab) = to_grab(key_converter, key_stroke, action) else {
+                warn!("hotkey {key_stroke:?} isn't on the keyboard, ignoring it");
+                continue;
+            };
+            for lock_mask in LOCK_MASKS {
+                let result = conn
+                    .grab_key(
+                        false,
+                        root,
+                        ModMask::from(grab.modifiers | lock_mask),
+                        grab.keycode,
+                        GrabMode::ASYNC,
+                        GrabMode::ASYNC,
+                    )?
+                    .check();
+                // Another client, e.g., the window manager, already has it
+                if let Err(err) = result {
+                    warn!("failed to grab hotkey {key_stroke:?}: {err}");
+                    continue 'actions;
+                }
+            }
+            grabs.push(grab);
+        }
+        conn.flush()?;
+        info!("{} hotkeys grabbed", grabs.len());
+
+        Ok(Hotkeys { root, grabs })
+    }
+
+    /// What the key press does, if it's a hotkey
+    pub fn action(&self, ev: &KeyPressEvent) -> Option<HotkeyAction> {
+        if ev.event != self.root {
+            return None;
+        }
+        let modifiers = u16::from(ev.state) & !(CAPS_LOCK_MASK | NUM_LOCK_MASK);
+        self.grabs
+            .iter()
+            .find(|grab| grab.keycode == ev.detail && grab.modifiers == modifiers)
+            .map(|grab| grab.action)
+    }
+
+    pub fn ungrab(&self, conn: &XCBConnection) -> Result<()> {
+        for grab in &self.grabs {
+            for lock_mask in LOCK_MASKS {
+                conn.ungrab_key(
+                    grab.keycode,
+                    self.root,
+                    ModMask::from(grab.modifiers | lock_mask),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn to_grab(
+    key_converter: &X11KeyConverter,
+    key_stroke: &KeyStroke,
+    action: HotkeyAction,
+) -> Option<Grab> {
+    let keycode = key_converter.keysym_to_keycode(Keysym::new(key_stroke.key))?;
+    let mut modifiers = 0;
+    for &modifier in &key_stroke.modifiers {
+        let modifier_keycode = key_converter.keysym_to_keycode(modifier.into())?;
+        modifiers |= key_converter.keycode_to_modifier_mask(modifier_keycode);
+    }
+    Some(Grab {
+        keycode: u8::try_from(KeyCode::raw(keycode)).ok()?,
+        modifiers,
+        action,
+    })
+}
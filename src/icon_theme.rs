@@ -0,0 +1,298 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use egui::Vec2;
+use image::RgbaImage;
+use log::{debug, info};
+
+use crate::ui::load_svg;
+
+const FALLBACK_THEME: &str = "hicolor";
+const ICON_EXTENSIONS: &[&str] = &["png", "svg"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DirType {
+    Fixed,
+    Scalable,
+    Threshold,
+}
+
+#[derive(Debug)]
+struct ThemeDir {
+    path: String,
+    size: u32,
+    min_size: u32,
+    max_size: u32,
+    threshold: u32,
+    r#type: DirType,
+}
+
+impl ThemeDir {
+    fn matches_size(&self, size: u32) -> bool {
+        match self.r#type {
+            DirType::Fixed => self.size == size,
+            DirType::Scalable => (self.min_size..=self.max_size).contains(&size),
+            DirType::Threshold => self.size.abs_diff(size) <= self.threshold,
+        }
+    }
+
+    fn size_distance(&self, size: u32) -> u32 {
+        let (min, max) = match self.r#type {
+            DirType::Fixed => (self.size, self.size),
+            DirType::Scalable => (self.min_size, self.max_size),
+            DirType::Threshold => (
+                self.size.saturating_sub(self.threshold),
+                self.size + self.threshold,
+            ),
+        };
+        min.saturating_sub(size).max(size.saturating_sub(max))
+    }
+}
+
+#[derive(Debug)]
+struct Theme {
+    name: String,
+    // The same theme can be spread over several base directories
+    roots: Vec<PathBuf>,
+    dirs: Vec<ThemeDir>,
+    inherits: Vec<String>,
+}
+
+/// Icon lookup following the freedesktop Icon Theme Specification, with rasterized icons cached
+/// per (name, size) since the theme is fixed for the lifetime of the lookup
+pub struct IconTheme {
+    themes: Vec<Theme>,
+    raster_cache: HashMap<(String, u32), Option<RgbaImage>>,
+}
+
+impl IconTheme {
+    /// "auto" picks up the theme from the GTK settings
+    pub fn new(configured_theme: &str) -> Self {
+        let theme_name = if configured_theme == "auto" {
+            gtk_icon_theme_name().unwrap_or_else(|| FALLBACK_THEME.to_string())
+        } else {
+            configured_theme.to_string()
+        };
+        info!("using icon theme '{theme_name}'");
+
+        let base_dirs = base_dirs();
+        let mut themes = vec![];
+        let mut pending = vec![theme_name];
+        let mut seen = HashSet::new();
+        while let Some(name) = pending.pop() {
+            if !seen.insert(name.clone()) {
+                continue;
+            }
+            let Some(theme) = load_theme(&name, &base_dirs) else {
+                debug!("icon theme '{name}' not found");
+                continue;
+            };
+
+            // Parents are searched in the order they're listed, before their own parents
+            pending.extend(theme.inherits.iter().rev().cloned());
+            themes.push(theme);
+        }
+        if !seen.contains(FALLBACK_THEME)
+            && let Some(theme) = load_theme(FALLBACK_THEME, &base_dirs)
+        {
+            themes.push(theme);
+        }
+        debug!(
+            "icon theme lookup order: {:?}",
+            themes.iter().map(|t| &t.name).collect::<Vec<_>>()
+        );
+
+        IconTheme {
+            themes,
+            raster_cache: HashMap::new(),
+        }
+    }
+
+    pub fn load_icon(&mut self, name: &str, size: u32) -> Result<Option<RgbaImage>> {
+        let key = (name.to_string(), size);
+        if let Some(icon) = self.raster_cache.get(&key) {
+            return Ok(icon.clone());
+        }
+
+        let icon = match self.find_icon(name, size) {
+            Some(path) if path.extension().is_some_and(|e| e == "svg") => {
+                Some(load_svg(&fs::read(&path)?, Vec2::splat(size as f32))?.0)
+            }
+            Some(path) => Some(image::load_from_memory(&fs::read(&path)?)?.to_rgba8()),
+            None => None,
+        };
+
+        self.raster_cache.insert(key, icon.clone());
+        Ok(icon)
+    }
+
+    // Desktop entries may name an icon file instead of a themed icon
+    fn find_icon(&self, name: &str, size: u32) -> Option<PathBuf> {
+        if Path::new(name).is_absolute() {
+            return Some(PathBuf::from(name)).filter(|path| path.is_file());
+        }
+
+        for theme in &self.themes {
+            let mut closest: Option<(u32, PathBuf)> = None;
+            for dir in &theme.dirs {
+                for root in &theme.roots {
+                    for ext in ICON_EXTENSIONS {
+                        let path = root.join(&dir.path).join(format!("{name}.{ext}"));
+                        if !path.is_file() {
+                            continue;
+                        }
+                        if dir.matches_size(size) {
+                            return Some(path);
+                        }
+
+                        let distance = dir.size_distance(size);
+                        if closest.as_ref().is_none_or(|(d, _)| distance < *d) {
+                            closest = Some((distance, path));
+                        }
+                    }
+                }
+            }
+            if let Some((_, path)) = closest {
+                return Some(path);
+            }
+        }
+
+        ICON_EXTENSIONS
+            .iter()
+            .map(|ext| PathBuf::from(format!("/usr/share/pixmaps/{name}.{ext}")))
+            .find(|path| path.is_file())
+    }
+}
+
+fn base_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![];
+    if let Some(home) = dirs::home_dir() {
+        dirs.push(home.join(".icons"));
+    }
+    dirs.extend(data_dirs().into_iter().map(|d| d.join("icons")));
+    dirs
+}
+
+/// $XDG_DATA_HOME, then $XDG_DATA_DIRS, in order of precedence
+pub(crate) fn data_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![];
+    if let Some(data_dir) = dirs::data_dir() {
+        dirs.push(data_dir);
+    }
+
+    let data_dirs = env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|d| !d.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+    dirs.extend(
+        data_dirs
+            .split(':')
+            .filter(|d| !d.is_empty())
+            .map(PathBuf::from),
+    );
+
+    dirs
+}
+
+fn load_theme(name: &str, base_dirs: &[PathBuf]) -> Option<Theme> {
+    let roots = base_dirs
+        .iter()
+        .map(|base| base.join(name))
+        .filter(|root| root.is_dir())
+        .collect::<Vec<_>>();
+    let index = roots
+        .iter()
+        .find_map(|root| fs::read_to_string(root.join("index.theme")).ok())?;
+    let sections = parse_ini(&index);
+    let main = sections.get("Icon Theme")?;
+
+    let list = |key: &str| -> Vec<String> {
+        main.get(key)
+            .map(|v| {
+                v.split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    let dirs = list("Directories")
+        .into_iter()
+        .filter_map(|path| {
+            let section = sections.get(path.as_str())?;
+            let number = |key: &str| section.get(key).and_then(|v| v.parse::<u32>().ok());
+            let size = number("Size")?;
+            Some(ThemeDir {
+                size,
+                min_size: number("MinSize").unwrap_or(size),
+                max_size: number("MaxSize").unwrap_or(size),
+                threshold: number("Threshold").unwrap_or(2),
+                r#type: match section.get("Type").map(String::as_str) {
+                    Some("Fixed") => DirType::Fixed,
+                    Some("Scalable") => DirType::Scalable,
+                    _ => DirType::Threshold,
+                },
+                path,
+            })
+        })
+        .collect();
+
+    Some(Theme {
+        name: name.to_string(),
+        roots,
+        dirs,
+        inherits: list("Inherits"),
+    })
+}
+
+pub(crate) fn parse_ini(text: &str) -> HashMap<String, HashMap<String, String>> {
+    let mut sections: HashMap<String, HashMap<String, String>> = HashMap::new();
+    let mut current = None;
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            current = Some(name.to_string());
+            sections.entry(name.to_string()).or_default();
+        } else if let Some(section) = &current
+            && let Some((key, value)) = line.split_once('=')
+        {
+            sections
+                .entry(section.clone())
+                .or_default()
+                .insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    sections
+}
+
+fn gtk_icon_theme_name() -> Option<String> {
+    let config_dir = dirs::config_dir();
+    let home_dir = dirs::home_dir();
+    let candidates = [
+        config_dir.as_ref().map(|d| d.join("gtk-4.0/settings.ini")),
+        config_dir.as_ref().map(|d| d.join("gtk-3.0/settings.ini")),
+        home_dir.as_ref().map(|d| d.join(".gtkrc-2.0")),
+    ];
+
+    candidates
+        .into_iter()
+        .flatten()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .find_map(|text| {
+            text.lines().find_map(|line| {
+                let (key, value) = line.split_once('=')?;
+                (key.trim() == "gtk-icon-theme-name")
+                    .then(|| value.trim().trim_matches('"').to_string())
+                    .filter(|name| !name.is_empty())
+            })
+        })
+}
@@ -0,0 +1,277 @@
+use std::io::Cursor;
+
+use anyhow::Result;
+#[cfg(feature = "heif")]
+use anyhow::anyhow;
+use image::{DynamicImage, ImageDecoder as _, ImageReader, Rgba32FImage, RgbaImage};
+use log::debug;
+use qcms::{CIE_xyY, CIE_xyYTRIPLE, DataType, Intent, Profile, Transform};
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+// Entries of the transfer curve tables built for cICP profiles
+const CURVE_TABLE_SIZE: usize = 1024;
+
+// ITU-T H.273 code points
+const CICP_BT709_PRIMARIES: u8 = 1;
+const CICP_BT2020_PRIMARIES: u8 = 9;
+const CICP_DISPLAY_P3_PRIMARIES: u8 = 12;
+const CICP_BT709_TRANSFER: u8 = 1;
+const CICP_BT601_TRANSFER: u8 = 6;
+const CICP_LINEAR_TRANSFER: u8 = 8;
+const CICP_SRGB_TRANSFER: u8 = 13;
+const CICP_BT2020_10BIT_TRANSFER: u8 = 14;
+const CICP_BT2020_12BIT_TRANSFER: u8 = 15;
+const CICP_PQ_TRANSFER: u8 = 16;
+const CICP_HLG_TRANSFER: u8 = 18;
+// SDR reference white of BT.2408, in nits
+const REFERENCE_WHITE: f32 = 203.0;
+// Scene light of the HLG signal that BT.2408 maps to the reference white
+const HLG_REFERENCE_WHITE: f32 = 0.265;
+// Relative luminance above which highlights get compressed instead of clipped
+const HIGHLIGHT_KNEE: f32 = 0.8;
+
+#[derive(Debug, Clone, Copy)]
+struct Cicp {
+    primaries: u8,
+    transfer: u8,
+}
+
+/// Decodes an image to 8-bit sRGB for previews: PQ and HLG images are tone-mapped, and the PNG
+/// cICP chunk or embedded ICC profile is honored so colors match color-managed apps
+pub fn decode_image(data: &[u8]) -> Result<RgbaImage> {
+    #[cfg(feature = "heif")]
+    if is_heif(data) {
+        return decode_heif(data);
+    }
+
+    let mut decoder = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()?
+        .into_decoder()?;
+    let icc_profile = decoder.icc_profile().unwrap_or_else(|e| {
+        debug!("failed to read ICC profile: {e}");
+        None
+    });
+    let image = DynamicImage::from_decoder(decoder)?;
+
+    let cicp = png_cicp(data);
+    if let Some(cicp) = cicp
+        && matches!(cicp.transfer, CICP_PQ_TRANSFER | CICP_HLG_TRANSFER)
+    {
+        return Ok(tone_map_hdr(&image, cicp));
+    }
+
+    let mut image = match image {
+        DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => {
+            let mut image = image.into_rgba32f();
+            compress_highlights(&mut image);
+            DynamicImage::ImageRgba32F(image).into_rgba8()
+        }
+        image => image.into_rgba8(),
+    };
+
+    // cICP takes precedence over iCCP per the PNG spec
+    let profile = cicp.and_then(cicp_profile).or_else(|| {
+        icc_profile.and_then(|icc| {
+            Profile::new_from_slice(&icc, false).or_else(|| {
+                debug!("failed to parse ICC profile, assuming sRGB");
+                None
+            })
+        })
+    });
+    if let Some(profile) = profile {
+        match Transform::new(
+            &profile,
+            &Profile::new_sRGB(),
+            DataType::RGBA8,
+            Intent::default(),
+        ) {
+            Some(transform) => transform.apply(&mut image),
+            None => debug!("unsupported color profile, assuming sRGB"),
+        }
+    }
+
+    Ok(image)
+}
+
+fn png_cicp(data: &[u8]) -> Option<Cicp> {
+    if !data.starts_with(PNG_SIGNATURE) {
+        return None;
+    }
+
+    let reader = png::Decoder::new(Cursor::new(data)).read_info().ok()?;
+    let cicp = reader.info().coding_independent_code_points?;
+    Some(Cicp {
+        primaries: cicp.color_primaries,
+        transfer: cicp.transfer_function,
+    })
+}
+
+fn cicp_profile(cicp: Cicp) -> Option<Box<Profile>> {
+    if cicp.primaries == CICP_BT709_PRIMARIES && cicp.transfer == CICP_SRGB_TRANSFER {
+        return None;
+    }
+
+    let primaries = cicp_primaries(cicp.primaries);
+    let to_linear = cicp_to_linear(cicp.transfer);
+    let (Some(primaries), Some(to_linear)) = (primaries, to_linear) else {
+        debug!("unsupported cICP {cicp:?}, assuming sRGB");
+        return None;
+    };
+
+    let table: Vec<u16> = (0..CURVE_TABLE_SIZE)
+        .map(|i| {
+            let linear = to_linear(i as f64 / (CURVE_TABLE_SIZE - 1) as f64);
+            (linear.clamp(0.0, 1.0) * u16::MAX as f64).round() as u16
+        })
+        .collect();
+    Profile::new_rgb_with_table(D65_WHITE_POINT, primaries, &table)
+}
+
+// qcms can build profiles from cICP code points but doesn't export them, so the primaries and
+// transfer curves of the common ones are spelled out here
+const D65_WHITE_POINT: CIE_xyY = xy(0.3127, 0.3290);
+
+const fn xy(x: f64, y: f64) -> CIE_xyY {
+    CIE_xyY { x, y, Y: 1.0 }
+}
+
+fn cicp_primaries(code_point: u8) -> Option<CIE_xyYTRIPLE> {
+    let [red, green, blue] = match code_point {
+        CICP_BT709_PRIMARIES => [xy(0.64, 0.33), xy(0.30, 0.60), xy(0.15, 0.06)],
+        CICP_BT2020_PRIMARIES => [xy(0.708, 0.292), xy(0.170, 0.797), xy(0.131, 0.046)],
+        CICP_DISPLAY_P3_PRIMARIES => [xy(0.680, 0.320), xy(0.265, 0.690), xy(0.150, 0.060)],
+        _ => return None,
+    };
+    Some(CIE_xyYTRIPLE { red, green, blue })
+}
+
+/// The function mapping an encoded SDR value to linear light, both in 0..=1, as PQ and HLG
+/// images are tone-mapped instead
+fn cicp_to_linear(code_point: u8) -> Option<fn(f64) -> f64> {
+    Some(match code_point {
+        CICP_BT709_TRANSFER
+        | CICP_BT601_TRANSFER
+        | CICP_BT2020_10BIT_TRANSFER
+        | CICP_BT2020_12BIT_TRANSFER => |v| {
+            if v < 0.081 {
+                v / 4.5
+            } else {
+                ((v + 0.099) / 1.099).powf(1.0 / 0.45)
+            }
+        },
+        CICP_LINEAR_TRANSFER => |v| v,
+        CICP_SRGB_TRANSFER => |v| {
+            if v <= 0.04045 {
+                v / 12.92
+            } else {
+                ((v + 0.055) / 1.055).powf(2.4)
+            }
+        },
+        _ => return None,
+    })
+}
+
+fn tone_map_hdr(image: &DynamicImage, cicp: Cicp) -> RgbaImage {
+    let mut image = image.to_rgba32f();
+    for pixel in image.pixels_mut() {
+        let [r, g, b, _] = &mut pixel.0;
+        let mut rgb = [*r, *g, *b].map(|v| match cicp.transfer {
+            CICP_PQ_TRANSFER => pq_to_nits(v) / REFERENCE_WHITE,
+            _ => hlg_to_scene_light(v) / HLG_REFERENCE_WHITE,
+        });
+        if cicp.primaries == CICP_BT2020_PRIMARIES {
+            rgb = bt2020_to_bt709(rgb);
+        }
+        [*r, *g, *b] = rgb.map(|v| linear_to_srgb(compress_highlight(v)));
+    }
+
+    DynamicImage::ImageRgba32F(image).into_rgba8()
+}
+
+// Float images are linear, so values above 1 are highlights
+fn compress_highlights(image: &mut Rgba32FImage) {
+    for pixel in image.pixels_mut() {
+        let [r, g, b, _] = &mut pixel.0;
+        [*r, *g, *b] = [*r, *g, *b].map(|v| linear_to_srgb(compress_highlight(v)));
+    }
+}
+
+// Leaves the SDR range linear and rolls off everything above the knee toward 1
+fn compress_highlight(v: f32) -> f32 {
+    if v <= HIGHLIGHT_KNEE {
+        return v.max(0.0);
+    }
+
+    let range = 1.0 - HIGHLIGHT_KNEE;
+    HIGHLIGHT_KNEE + range * (1.0 - (-(v - HIGHLIGHT_KNEE) / range).exp())
+}
+
+// SMPTE ST 2084 EOTF
+fn pq_to_nits(v: f32) -> f32 {
+    const M1: f32 = 2610.0 / 16384.0;
+    const M2: f32 = 2523.0 / 4096.0 * 128.0;
+    const C1: f32 = 3424.0 / 4096.0;
+    const C2: f32 = 2413.0 / 4096.0 * 32.0;
+    const C3: f32 = 2392.0 / 4096.0 * 32.0;
+
+    let p = v.max(0.0).powf(1.0 / M2);
+    ((p - C1).max(0.0) / (C2 - C3 * p)).powf(1.0 / M1) * 10000.0
+}
+
+// BT.2100 HLG inverse OETF
+fn hlg_to_scene_light(v: f32) -> f32 {
+    const A: f32 = 0.178_832_77;
+    const B: f32 = 0.284_668_92;
+    const C: f32 = 0.559_910_7;
+
+    if v <= 0.5 {
+        v * v / 3.0
+    } else {
+        (((v - C) / A).exp() + B) / 12.0
+    }
+}
+
+fn bt2020_to_bt709([r, g, b]: [f32; 3]) -> [f32; 3] {
+    [
+        1.6605 * r - 0.5876 * g - 0.0728 * b,
+        -0.1246 * r + 1.1329 * g - 0.0083 * b,
+        -0.0182 * r - 0.1006 * g + 1.1187 * b,
+    ]
+}
+
+fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.003_130_8 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(feature = "heif")]
+fn is_heif(data: &[u8]) -> bool {
+    const BRANDS: &[&[u8]] = &[
+        b"heic", b"heix", b"hevc", b"hevx", b"heim", b"heis", b"mif1",
+    ];
+    data.get(4..8) == Some(b"ftyp") && data.get(8..12).is_some_and(|b| BRANDS.contains(&b))
+}
+
+#[cfg(feature = "heif")]
+fn decode_heif(data: &[u8]) -> Result<RgbaImage> {
+    use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+    let context = HeifContext::read_from_bytes(data)?;
+    let handle = context.primary_image_handle()?;
+    let image = LibHeif::new().decode(&handle, ColorSpace::Rgb(RgbChroma::Rgba), None)?;
+    let plane = image
+        .planes()
+        .interleaved
+        .ok_or_else(|| anyhow!("decoded HEIF image has no interleaved plane"))?;
+
+    let row_len = plane.width as usize * 4;
+    let mut rgba = Vec::with_capacity(row_len * plane.height as usize);
+    for row in plane.data.chunks(plane.stride).take(plane.height as usize) {
+        rgba.extend_from_slice(&row[..row_len]);
+    }
+    RgbaImage::from_raw(plane.width, plane.height, rgba)
+        .ok_or_else(|| anyhow!("HEIF plane doesn't match its dimensions"))
+}
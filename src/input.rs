@@ -9,6 +9,8 @@ pub struct Input<'a> {
     pub egui_input: RawInput,
     window: &'a X11Window<'a>,
     key_converter: &'a X11KeyConverter<'a>,
+    // Pointer root position and window position when the drag started
+    drag_origin: Option<((i16, i16), (i16, i16))>,
 }
 
 impl<'a> Input<'a> {
@@ -26,11 +28,17 @@ impl<'a> Input<'a> {
             egui_input,
             window,
             key_converter,
+            drag_origin: None,
         })
     }
 
-    pub fn handle_event(&mut self, event: &X11Event) {
+    pub fn handle_event(&mut self, event: &X11Event) -> Result<()> {
+        if self.handle_drag_event(event)? {
+            return Ok(());
+        }
+
         let modifiers = &mut self.egui_input.modifiers;
+        let mut typed_char = None;
 
         let egui_event = match event {
             X11Event::ButtonPress(ev) | X11Event::ButtonRelease(ev) if ev.detail <= 3 => {
@@ -99,6 +107,13 @@ impl<'a> Input<'a> {
                         break 'blk None;
                     }
 
+                    // Only used by Search mode, so shortcuts with Ctrl or Alt don't type
+                    if pressed && !modifiers.ctrl && !modifiers.alt {
+                        typed_char = self
+                            .key_converter
+                            .keycode_to_char(keycode.into(), modifiers.shift);
+                    }
+
                     if let Some(key) = keysym_to_egui_key(Keysym::new(keysym.into())) {
                         trace!(
                             "key: {key:?}, pressed={pressed}, keysym={keysym:?}, keycode={keycode}"
@@ -134,9 +149,53 @@ impl<'a> Input<'a> {
         if let Some(egui_event) = egui_event {
             self.egui_input.events.push(egui_event);
         }
+        if let Some(c) = typed_char {
+            self.egui_input.events.push(Event::Text(c.to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Returns true if the event is consumed by window dragging
+    fn handle_drag_event(&mut self, event: &X11Event) -> Result<bool> {
+        match event {
+            X11Event::ButtonPress(ev) if ev.detail == 1 && self.egui_input.modifiers.is_none() => {
+                let (x, y) = self.window.get_current_win_pos();
+                let rel_pos = ((ev.root_x - x) as f32, (ev.root_y - y) as f32);
+                if !self.window.is_in_drag_area(rel_pos) {
+                    return Ok(false);
+                }
+
+                trace!("start dragging window from ({x}, {y})");
+                self.drag_origin = Some(((ev.root_x, ev.root_y), (x, y)));
+                Ok(true)
+            }
+            X11Event::MotionNotify(ev) => {
+                let Some(((start_x, start_y), (win_x, win_y))) = self.drag_origin else {
+                    return Ok(false);
+                };
+
+                self.window.move_window(
+                    win_x.saturating_add(ev.root_x - start_x),
+                    win_y.saturating_add(ev.root_y - start_y),
+                )?;
+                Ok(true)
+            }
+            X11Event::ButtonRelease(ev) if ev.detail == 1 && self.drag_origin.is_some() => {
+                trace!(
+                    "stop dragging window at {:?}",
+                    self.window.get_current_win_pos()
+                );
+                self.drag_origin = None;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
     }
 
     pub fn update_pointer_pos(&mut self) -> Result<()> {
+        self.drag_origin = None;
+
         let pointer = self
             .window
             .conn
@@ -0,0 +1,362 @@
+use std::{
+    collections::BTreeMap,
+    ffi::OsStr,
+    io::{self, Read, Write},
+    os::unix::{ffi::OsStrExt as _, net::UnixStream},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow, bail};
+
+use crate::utils::is_plaintext_mime;
+
+// Clients send a single command per connection, anything longer isn't one
+pub const MAX_COMMAND_LEN: usize = 1024;
+// Data of `memoni add` comes after its command, see read_payload
+const MAX_PAYLOAD_LEN: usize = 64 * 1024 * 1024;
+// The server waits on the client while reading it, so a stalled one mustn't hold it for long
+const PAYLOAD_TIMEOUT: Duration = Duration::from_secs(5);
+// Item data is sent in chunks of at most this many bytes, see write_chunked
+const CHUNK_LEN: usize = 64 * 1024;
+
+/// An item of the history as `memoni get` names it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemRef {
+    Id(u64),
+    /// In history order, pinned items first
+    Index(usize),
+}
+
+/// Commands clients send over the server socket
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientCommand {
+    ShowWindow,
+    Watch,
+    /// Replies with a JSON line per item, in history order
+    List,
+    /// Replies with a JSON line per archived item, the most recently archived first
+    ListArchived,
+    /// Replies with the text of the item, or nothing if it has none
+    Text(u64),
+    /// Replies with the data of the item's `mime` target, or its text without one, in chunks, see
+    /// [`write_chunked`]. Nothing if it has no such target
+    Get {
+        item: ItemRef,
+        mime: Option<String>,
+    },
+    Delete(u64),
+    /// Puts an archived item back in the history, replies with its id, or nothing if it isn't
+    /// archived
+    Restore(u64),
+    /// Pastes the unpinned item at the index, 0 for the newest one, without showing the window,
+    /// marking it to be removed after `expire_secs`
+    PasteRecent {
+        idx: usize,
+        expire_secs: Option<u64>,
+    },
+    /// Adds the data sent after the command as the newest item, replies with its id. With
+    /// `owns`, the server takes the selection too, so the item can be pasted right away
+    Add {
+        mime: String,
+        owns: bool,
+        expire_secs: Option<u64>,
+    },
+    /// Shows the window with the theme of a config file for a number of seconds, without saving it
+    PreviewTheme {
+        path: PathBuf,
+        seconds: u64,
+    },
+}
+
+impl ClientCommand {
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let unknown = || {
+            anyhow!(
+                "unknown client command: {:?}",
+                String::from_utf8_lossy(&bytes[..bytes.len().min(MAX_COMMAND_LEN)])
+            )
+        };
+        match bytes {
+            b"show_win" => Ok(ClientCommand::ShowWindow),
+            b"watch" => Ok(ClientCommand::Watch),
+            b"list" => Ok(ClientCommand::List),
+            b"list_archived" => Ok(ClientCommand::ListArchived),
+            _ => {
+                let space = bytes.iter().position(|&b| b == b' ').ok_or_else(unknown)?;
+                let (name, arg) = (&bytes[..space], &bytes[space + 1..]);
+                match name {
+                    b"text" => Ok(ClientCommand::Text(parse_item_id(arg).ok_or_else(unknown)?)),
+                    b"delete" => Ok(ClientCommand::Delete(
+                        parse_item_id(arg).ok_or_else(unknown)?,
+                    )),
+                    b"restore" => Ok(ClientCommand::Restore(
+                        parse_item_id(arg).ok_or_else(unknown)?,
+                    )),
+                    b"paste_recent" => {
+                        let arg = str::from_utf8(arg).map_err(|_| unknown())?;
+                        let (idx, expire_secs) = match arg.split_once(' ') {
+                            Some((idx, expire_secs)) => (idx, Some(expire_secs)),
+                            None => (arg, None),
+                        };
+                        Ok(ClientCommand::PasteRecent {
+                            idx: idx.parse().map_err(|_| unknown())?,
+                            expire_secs: expire_secs
+                                .map(|secs| secs.parse().map_err(|_| unknown()))
+                                .transpose()?,
+                        })
+                    }
+                    b"get" => {
+                        let arg = str::from_utf8(arg).map_err(|_| unknown())?;
+                        let (mut item, mut mime) = (None, None);
+                        for word in arg.split(' ') {
+                            match word.split_once('=') {
+                                Some(("id", id)) => {
+                                    item = Some(ItemRef::Id(
+                                        parse_item_id(id.as_bytes()).ok_or_else(unknown)?,
+                                    ))
+                                }
+                                Some(("index", idx)) => {
+                                    item = Some(ItemRef::Index(idx.parse().map_err(|_| unknown())?))
+                                }
+                                Some(("mime", m)) if !m.is_empty() => mime = Some(m.to_string()),
+                                _ => return Err(unknown()),
+                            }
+                        }
+                        Ok(ClientCommand::Get {
+                            item: item.ok_or_else(unknown)?,
+                            mime,
+                        })
+                    }
+                    // The data follows the first line
+                    b"add" => {
+                        let header = arg.split(|&b| b == b'\n').next().unwrap_or_default();
+                        let header = str::from_utf8(header).map_err(|_| unknown())?;
+                        let mut words = header.split(' ');
+                        let mime = words.next().filter(|m| !m.is_empty()).ok_or_else(unknown)?;
+                        let (mut owns, mut expire_secs) = (false, None);
+                        for word in words {
+                            match word.split_once('=') {
+                                None if word == "own" => owns = true,
+                                Some(("expire", secs)) => {
+                                    expire_secs = Some(secs.parse().map_err(|_| unknown())?)
+                                }
+                                _ => return Err(unknown()),
+                            }
+                        }
+                        Ok(ClientCommand::Add {
+                            mime: mime.to_string(),
+                            owns,
+                            expire_secs,
+                        })
+                    }
+                    // Seconds first, so the path can have spaces
+                    b"preview_theme" => {
+                        let space = arg.iter().position(|&b| b == b' ').ok_or_else(unknown)?;
+                        let (seconds, path) = (&arg[..space], &arg[space + 1..]);
+                        let seconds = str::from_utf8(seconds)
+                            .ok()
+                            .and_then(|s| s.parse().ok())
+                            .ok_or_else(unknown)?;
+                        let path = Path::new(OsStr::from_bytes(path));
+                        if !path.is_absolute() {
+                            return Err(unknown());
+                        }
+                        Ok(ClientCommand::PreviewTheme {
+                            path: path.to_path_buf(),
+                            seconds,
+                        })
+                    }
+                    _ => Err(unknown()),
+                }
+            }
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            ClientCommand::ShowWindow => b"show_win".to_vec(),
+            ClientCommand::Watch => b"watch".to_vec(),
+            ClientCommand::List => b"list".to_vec(),
+            ClientCommand::ListArchived => b"list_archived".to_vec(),
+            ClientCommand::Text(id) => format!("text {}", format_item_id(*id)).into_bytes(),
+            ClientCommand::Delete(id) => format!("delete {}", format_item_id(*id)).into_bytes(),
+            ClientCommand::Restore(id) => format!("restore {}", format_item_id(*id)).into_bytes(),
+            ClientCommand::PasteRecent {
+                idx,
+                expire_secs: None,
+            } => format!("paste_recent {idx}").into_bytes(),
+            ClientCommand::PasteRecent {
+                idx,
+                expire_secs: Some(secs),
+            } => format!("paste_recent {idx} {secs}").into_bytes(),
+            ClientCommand::Get { item, mime } => {
+                let mut command = match item {
+                    ItemRef::Id(id) => format!("get id={}", format_item_id(*id)),
+                    ItemRef::Index(idx) => format!("get index={idx}"),
+                };
+                if let Some(mime) = mime {
+                    command.push_str(&format!(" mime={mime}"));
+                }
+                command.into_bytes()
+            }
+            ClientCommand::Add {
+                mime,
+                owns,
+                expire_secs,
+            } => {
+                let mut command = format!("add {mime}");
+                if *owns {
+                    command.push_str(" own");
+                }
+                if let Some(secs) = expire_secs {
+                    command.push_str(&format!(" expire={secs}"));
+                }
+                command.push('\n');
+                command.into_bytes()
+            }
+            ClientCommand::PreviewTheme { path, seconds } => [
+                format!("preview_theme {seconds} ").as_bytes(),
+                path.as_os_str().as_bytes(),
+            ]
+            .concat(),
+        }
+    }
+}
+
+/// Item ids as clients see them, e.g., in `memoni watch` output
+pub fn format_item_id(id: u64) -> String {
+    format!("{id:016x}")
+}
+
+// Only the exact form format_item_id gives, so each command has a single spelling
+pub fn parse_item_id(id: &[u8]) -> Option<u64> {
+    if id.len() != 16 || !id.iter().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+        return None;
+    }
+    u64::from_str_radix(str::from_utf8(id).ok()?, 16).ok()
+}
+
+/// Writes item data as chunks, each a little-endian u32 length and up to CHUNK_LEN bytes, ending
+/// with an empty one, so clients can tell a whole reply from one cut short
+pub fn write_chunked(mut writer: impl Write, data: &[u8]) -> io::Result<()> {
+    for chunk in data.chunks(CHUNK_LEN) {
+        writer.write_all(&(chunk.len() as u32).to_le_bytes())?;
+        writer.write_all(chunk)?;
+    }
+    writer.write_all(&0u32.to_le_bytes())
+}
+
+/// Copies the data of a [`write_chunked`] reply to `out` as it comes, returning false if there
+/// was no reply at all
+pub fn read_chunked(mut reader: impl Read, mut out: impl Write) -> Result<bool> {
+    let mut len_buf = [0; 4];
+    let mut chunk = vec![];
+    let mut started = false;
+    loop {
+        match reader.read_exact(&mut len_buf) {
+            Ok(()) => started = true,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof && !started => return Ok(false),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => bail!("reply was cut short"),
+            Err(e) => return Err(e.into()),
+        }
+        let len = u32::from_le_bytes(len_buf) as usize;
+        if len == 0 {
+            return Ok(true);
+        }
+        if len > CHUNK_LEN {
+            bail!("reply has a chunk of {len} bytes, more than {CHUNK_LEN}");
+        }
+        chunk.resize(len, 0);
+        reader.read_exact(&mut chunk).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => anyhow!("reply was cut short"),
+            _ => e.into(),
+        })?;
+        out.write_all(&chunk)?;
+    }
+}
+
+/// Data sent after an `add` command, of what the server read along with it, `received`, and
+/// the rest of the stream
+pub fn read_payload(stream: &mut UnixStream, received: &[u8]) -> Result<Vec<u8>> {
+    let mut data = received
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(vec![], |newline| received[newline + 1..].to_vec());
+    stream.set_read_timeout(Some(PAYLOAD_TIMEOUT))?;
+    stream
+        .take((MAX_PAYLOAD_LEN + 1).saturating_sub(data.len()) as u64)
+        .read_to_end(&mut data)?;
+    if data.len() > MAX_PAYLOAD_LEN {
+        bail!("item data is larger than {MAX_PAYLOAD_LEN} bytes");
+    }
+    Ok(data)
+}
+
+/// Item data of what `memoni add` sent as `mime`. Plain text is kept as UTF8_STRING like captured
+/// text, so it has to be valid UTF-8
+pub fn item_data(mime: &str, data: Vec<u8>) -> Result<BTreeMap<String, Vec<u8>>> {
+    if data.is_empty() {
+        bail!("no data to add");
+    }
+    if !is_plaintext_mime(mime) {
+        return Ok(BTreeMap::from([(mime.to_string(), data)]));
+    }
+    if str::from_utf8(&data).is_err() {
+        bail!("text to add isn't valid UTF-8");
+    }
+    Ok(BTreeMap::from([("UTF8_STRING".to_string(), data)]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(data: &[u8]) -> (bool, Vec<u8>) {
+        let mut reply = vec![];
+        write_chunked(&mut reply, data).unwrap();
+        let mut out = vec![];
+        let found = read_chunked(reply.as_slice(), &mut out).unwrap();
+        (found, out)
+    }
+
+    #[test]
+    fn chunked_reply_round_trips() {
+        let data = (0..CHUNK_LEN * 2 + 10).map(|i| i as u8).collect::<Vec<_>>();
+        assert_eq!(round_trip(&data), (true, data));
+        assert_eq!(round_trip(b""), (true, vec![]));
+    }
+
+    #[test]
+    fn no_reply_is_not_found() {
+        assert!(!read_chunked(&b""[..], io::sink()).unwrap());
+    }
+
+    #[test]
+    fn reply_cut_short_fails() {
+        let mut reply = vec![];
+        write_chunked(&mut reply, b"hello").unwrap();
+        // Without the empty chunk at the end
+        reply.truncate(reply.len() - 4);
+        assert!(read_chunked(reply.as_slice(), io::sink()).is_err());
+        // In the middle of a chunk
+        reply.truncate(reply.len() - 1);
+        assert!(read_chunked(reply.as_slice(), io::sink()).is_err());
+    }
+
+    #[test]
+    fn get_command_round_trips() {
+        for command in [
+            ClientCommand::Get {
+                item: ItemRef::Id(0x0123456789abcdef),
+                mime: None,
+            },
+            ClientCommand::Get {
+                item: ItemRef::Index(3),
+                mime: Some("image/png".to_string()),
+            },
+        ] {
+            assert_eq!(ClientCommand::parse(&command.to_bytes()).unwrap(), command);
+        }
+    }
+}
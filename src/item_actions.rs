@@ -0,0 +1,308 @@
+use std::{
+    env,
+    fs::{self, DirBuilder, OpenOptions},
+    io::{self, Write as _},
+    os::unix::fs::{
+        DirBuilderExt as _, MetadataExt as _, OpenOptionsExt as _, PermissionsExt as _,
+    },
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{
+        Arc,
+        mpsc::{self, Receiver, Sender},
+    },
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Context as _, Result, anyhow, bail};
+use log::{debug, error, info};
+
+use crate::{
+    selection::{CAPTURE_FAILED_MIME, SelectionItem},
+    text::percent_decode,
+    utils::{image_mime_score, is_image_mime, is_url, plaintext_mime_score},
+};
+
+// Opened files are left for the application showing them, and removed once stale by a later open
+// or on exit
+const OPENED_FILE_LIFETIME: Duration = Duration::from_secs(60 * 60);
+const OPENED_FILE_PREFIX: &str = "open-";
+// Suffixes tried when saving over an existing file, before giving up
+const MAX_SAVE_ATTEMPTS: usize = 100;
+
+pub struct ItemEdit {
+    pub item_id: u64,
+    pub mime: String,
+    pub data: Vec<u8>,
+}
+
+/// Runs the external editor in the background and hands the edited text back to the main loop
+pub struct ItemEditor {
+    sender: Sender<ItemEdit>,
+    receiver: Receiver<ItemEdit>,
+    waker: Arc<mio::Waker>,
+}
+
+impl ItemEditor {
+    pub fn new(registry: &mio::Registry, token: mio::Token) -> Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+        Ok(ItemEditor {
+            sender,
+            receiver,
+            waker: Arc::new(mio::Waker::new(registry, token)?),
+        })
+    }
+
+    pub fn edit(&self, item: &SelectionItem, command: &[String]) -> Result<()> {
+        let Some((mime, text)) = item
+            .data
+            .iter()
+            .filter_map(|(mime, data)| plaintext_mime_score(mime).map(|s| (s, mime, data)))
+            .max_by_key(|(score, ..)| *score)
+            .map(|(_, mime, data)| (mime.clone(), data.clone()))
+        else {
+            bail!("item {} has no text to edit", item.id);
+        };
+        let Some((program, args)) = command.split_first() else {
+            bail!("editor command is empty");
+        };
+
+        let file_path = temp_dir()?.join(format!("edit-{}.txt", item.id));
+        write_private_file(&file_path, &text)?;
+
+        info!("editing item {} with {command:?}", item.id);
+        let mut child = Command::new(program)
+            .args(args)
+            .arg(&file_path)
+            .stdin(Stdio::null())
+            .spawn()
+            .with_context(|| format!("failed to run editor {program:?}"))?;
+
+        let item_id = item.id;
+        let sender = self.sender.clone();
+        let waker = self.waker.clone();
+        thread::spawn(move || {
+            let result = (|| -> Result<Option<Vec<u8>>> {
+                let status = child.wait()?;
+                if !status.success() {
+                    bail!("editor exited with {status}");
+                }
+
+                let edited = fs::read(&file_path)?;
+                Ok((edited != text).then_some(edited))
+            })();
+            if let Err(e) = fs::remove_file(&file_path) {
+                debug!("failed to remove edit file {file_path:?}: {e}");
+            }
+
+            match result {
+                Ok(Some(data)) => {
+                    if sender
+                        .send(ItemEdit {
+                            item_id,
+                            mime,
+                            data,
+                        })
+                        .is_ok()
+                    {
+                        let _ = waker.wake();
+                    }
+                }
+                Ok(None) => debug!("item {item_id} left unchanged by editor"),
+                Err(e) => error!("failed to edit item {item_id}: {e}"),
+            }
+        });
+
+        Ok(())
+    }
+
+    pub fn take_edits(&self) -> Vec<ItemEdit> {
+        self.receiver.try_iter().collect()
+    }
+}
+
+/// Writes the most representative data of the item to the download directory
+pub fn save_to_file(item: &SelectionItem) -> Result<PathBuf> {
+    let (mime, data) = preferred_data(item)?;
+    let dir = dirs::download_dir()
+        .or_else(dirs::home_dir)
+        .ok_or_else(|| anyhow!("no directory to save to"))?;
+
+    // Never overwrites, saving the same item again gets a numbered name
+    let extension = mime_extension(mime);
+    for attempt in 0..MAX_SAVE_ATTEMPTS {
+        let name = match attempt {
+            0 => format!("memoni-{:016x}.{extension}", item.id),
+            n => format!("memoni-{:016x}-{n}.{extension}", item.id),
+        };
+        let file_path = dir.join(name);
+        let mut file = match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&file_path)
+        {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        };
+        file.write_all(data)?;
+
+        info!("item {} saved to {file_path:?}", item.id);
+        return Ok(file_path);
+    }
+    bail!("files for item {} already exist in {dir:?}", item.id)
+}
+
+pub fn open(item: &SelectionItem) -> Result<()> {
+    if let Some(uri_list) = item.data.get("text/uri-list")
+        && let Some(uri) = String::from_utf8_lossy(uri_list)
+            .lines()
+            .map(str::trim)
+            // text/uri-list can contain comment (based on RFC 2483)
+            .find(|line| !line.is_empty() && !line.starts_with('#'))
+    {
+        let target = match uri.strip_prefix("file://") {
+            Some(path) => String::from_utf8_lossy(&percent_decode(path.as_bytes())).into_owned(),
+            None => uri.to_string(),
+        };
+        return xdg_open(&target);
+    }
+
+    let (mime, data) = preferred_data(item)?;
+    if plaintext_mime_score(mime).is_some()
+        && let Ok(text) = str::from_utf8(data)
+        && is_url(text)
+    {
+        return xdg_open(text.trim());
+    }
+
+    let dir = temp_dir()?;
+    remove_opened_files(&dir, OPENED_FILE_LIFETIME);
+    let file_path = dir.join(format!(
+        "{OPENED_FILE_PREFIX}{:016x}.{}",
+        item.id,
+        mime_extension(mime)
+    ));
+    write_private_file(&file_path, data)?;
+    xdg_open(&file_path.to_string_lossy())
+}
+
+/// Removes the files written for opening items that outlived OPENED_FILE_LIFETIME
+pub fn remove_stale_opened_files() {
+    match temp_dir() {
+        Ok(dir) => remove_opened_files(&dir, OPENED_FILE_LIFETIME),
+        Err(e) => debug!("no temporary directory to clean up: {e}"),
+    }
+}
+
+fn remove_opened_files(dir: &Path, older_than: Duration) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let is_stale = entry
+            .file_name()
+            .to_string_lossy()
+            .starts_with(OPENED_FILE_PREFIX)
+            && entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .is_ok_and(|modified| modified.elapsed().unwrap_or_default() > older_than);
+        if is_stale && let Err(e) = fs::remove_file(entry.path()) {
+            debug!("failed to remove opened file {:?}: {e}", entry.path());
+        }
+    }
+}
+
+fn preferred_data(item: &SelectionItem) -> Result<(&str, &[u8])> {
+    if item.data.contains_key(CAPTURE_FAILED_MIME) {
+        bail!("item {} holds no captured data", item.id);
+    }
+
+    item.data
+        .iter()
+        .filter_map(|(mime, data)| plaintext_mime_score(mime).map(|s| (s, mime, data)))
+        .max_by_key(|(score, ..)| *score)
+        .or_else(|| {
+            item.data
+                .iter()
+                .filter(|(mime, _)| is_image_mime(mime))
+                .map(|(mime, data)| (image_mime_score(mime), mime, data))
+                .max_by_key(|(score, ..)| *score)
+        })
+        .map(|(_, mime, data)| (mime.as_str(), data.as_slice()))
+        .or_else(|| {
+            item.data
+                .iter()
+                .next()
+                .map(|(mime, data)| (mime.as_str(), data.as_slice()))
+        })
+        .ok_or_else(|| anyhow!("item {} is empty", item.id))
+}
+
+fn mime_extension(mime: &str) -> &str {
+    if plaintext_mime_score(mime).is_some() {
+        return "txt";
+    }
+
+    match mime {
+        "image/jpeg" => "jpg",
+        "image/svg+xml" => "svg",
+        _ => mime
+            .split_once('/')
+            .map(|(_, subtype)| subtype)
+            .filter(|subtype| subtype.chars().all(|c| c.is_ascii_alphanumeric()))
+            .unwrap_or("bin"),
+    }
+}
+
+// Item contents are private, so they go to the user's runtime directory, or a directory only the
+// user can access in /tmp without one
+fn temp_dir() -> Result<PathBuf> {
+    let uid = rustix::process::getuid().as_raw();
+    let dir = match dirs::runtime_dir() {
+        Some(runtime_dir) => runtime_dir.join("memoni"),
+        None => env::temp_dir().join(format!("memoni-{uid}")),
+    };
+    DirBuilder::new().recursive(true).mode(0o700).create(&dir)?;
+
+    // An existing directory might not have been created by us
+    let metadata = fs::symlink_metadata(&dir)?;
+    if !metadata.is_dir() || metadata.uid() != uid {
+        bail!("{dir:?} isn't a directory owned by the user");
+    }
+    if metadata.permissions().mode() & 0o077 != 0 {
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(dir)
+}
+
+fn write_private_file(path: &Path, data: &[u8]) -> Result<()> {
+    // A file left from an earlier run is replaced rather than written through
+    if let Err(e) = fs::remove_file(path)
+        && e.kind() != io::ErrorKind::NotFound
+    {
+        return Err(e.into());
+    }
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?
+        .write_all(data)?;
+    Ok(())
+}
+
+fn xdg_open(target: &str) -> Result<()> {
+    info!("opening {target:?}");
+    let mut child = Command::new("xdg-open")
+        .arg(target)
+        .stdin(Stdio::null())
+        .spawn()
+        .context("failed to run xdg-open")?;
+
+    // Reap the child so it doesn't linger as a zombie
+    thread::spawn(move || child.wait());
+    Ok(())
+}
@@ -1,15 +1,27 @@
-use std::{borrow::Cow, collections::HashMap, fmt, mem, sync::LazyLock};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fmt, mem,
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
 
 use anyhow::Result;
 use egui::{Event, Key, Modifiers, PointerButton, RawInput};
-use log::debug;
+use log::{debug, warn};
 
-use crate::AppMode;
+use crate::{
+    AppMode,
+    config::{Config, MacroAction, PointerConfig},
+    transform::Transform,
+};
 
 pub struct KeymapEntry {
     pub keys: Vec<KeyChord>,
     pub action: Action,
     pub description: &'static str,
+    /// Left out of the help, for entries only differing by a letter, listed once
+    pub hidden: bool,
 }
 
 pub struct KeymapGroup {
@@ -105,6 +117,8 @@ impl fmt::Display for KeyChord {
 pub enum ScrollAction {
     ItemUp,
     ItemDown,
+    ItemLeft,
+    ItemRight,
     HalfUp,
     HalfDown,
     PageUp,
@@ -118,6 +132,9 @@ impl ScrollAction {
         match self {
             ItemUp => ItemDown,
             ItemDown => ItemUp,
+            // Columns aren't flipped with the flow
+            ItemLeft => ItemLeft,
+            ItemRight => ItemRight,
             HalfUp => HalfDown,
             HalfDown => HalfUp,
             PageUp => PageDown,
@@ -128,6 +145,20 @@ impl ScrollAction {
     }
 }
 
+#[derive(Debug, Copy, Clone)]
+pub enum ZoomAction {
+    In,
+    Out,
+    Fit,
+    ActualSize,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum LabelScrollAction {
+    Left,
+    Right,
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum SimpleScrollAction {
     Up,
@@ -138,8 +169,9 @@ pub enum SimpleScrollAction {
 pub struct PasteModifier {
     pub trim: bool,
     pub and_enter: bool,
+    pub keep_open: bool,
+    pub plain_text: bool,
 }
-
 #[derive(Debug, Copy, Clone)]
 pub enum Action {
     Key(KeyAction),
@@ -153,14 +185,51 @@ pub enum KeyAction {
     Scroll(ScrollAction),
     Remove,
     Pin,
+    // Marks the item to be removed after expire_after_secs, or keeps it again
+    ToggleExpiry,
+    Edit,
+    SaveToFile,
+    Open,
     SimpleScroll(SimpleScrollAction),
+    // Scrolls the active item's label sideways
+    ScrollLabel(LabelScrollAction),
     ShowHelp,
     Close,
+    ClearAll,
+    Confirm,
+    ViewImage,
+    ViewDetails,
+    // Adds the transformed text as a new item
+    Transform(Transform),
+    // Pastes the transformed text, leaving the item as it is
+    PasteTransformed(Transform),
+    OpenTransforms,
+    StartSearch,
+    OpenPalette,
+    // Marks the active item with the letter, like m in Vim
+    SetMark(char),
+    // Makes the item marked with the letter the active one, wherever it was pushed down to
+    JumpToMark(char),
+    // Groups the list under a header per day, or shows it plain again
+    ToggleTimeline,
+    DeleteQueryChar,
+    ClearQuery,
+    Zoom(ZoomAction),
 }
 
 #[derive(Debug, Copy, Clone)]
 pub enum PointerAction {
     Paste(PasteModifier),
+    // What the config maps the gesture to
+    Gesture(PointerGesture),
+}
+
+/// Ways of clicking an item with the primary button, told apart by their timing
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PointerGesture {
+    Click,
+    DoubleClick,
+    Hold,
 }
 
 #[rustfmt::skip]
@@ -168,6 +237,8 @@ pub static ACTION_KEYMAPS: LazyLock<Vec<KeymapGroup>> = LazyLock::new(|| {
     use Action::Key as AK;
     use Action::Pointer as AP;
     use KeyAction::*;
+    // Explicitly, as the KeyAction::Transform variant would shadow it
+    use crate::transform::Transform;
     use Key::*;
     use PointerButton::*;
     use KeyChord as KC;
@@ -175,7 +246,7 @@ pub static ACTION_KEYMAPS: LazyLock<Vec<KeymapGroup>> = LazyLock::new(|| {
 
     macro_rules! e {
         ($keys:expr, $action:expr, $desc:expr) => {
-            KeymapEntry { keys: $keys, action: $action, description: $desc }
+            KeymapEntry { keys: $keys, action: $action, description: $desc, hidden: false }
         };
     }
 
@@ -192,6 +263,20 @@ pub static ACTION_KEYMAPS: LazyLock<Vec<KeymapGroup>> = LazyLock::new(|| {
                 e!(vec![KC::of_key_chord(N, M::CTRL)],    AK(Scroll(ScrollAction::ItemDown)),   "Move to next item"),
                 e!(vec![KC::of_key_chord(Tab, M::SHIFT)], AK(Scroll(ScrollAction::ItemUp)),     "Move to previous item"),
                 e!(vec![KC::of_key(Tab)],                 AK(Scroll(ScrollAction::ItemDown)),   "Move to next item"),
+                e!(vec![KC::of_key(ArrowLeft)],           AK(Scroll(ScrollAction::ItemLeft)),   "Move to left item in grid"),
+                e!(vec![KC::of_key(ArrowRight)],          AK(Scroll(ScrollAction::ItemRight)),  "Move to right item in grid"),
+                e!(vec![KC::of_key(H)],                   AK(Scroll(ScrollAction::ItemLeft)),   "Move to left item in grid"),
+                e!(vec![KC::of_key(L)],                   AK(Scroll(ScrollAction::ItemRight)),  "Move to right item in grid"),
+                e!(vec![KC::of_key(Z), KC::of_key(H)],    AK(ScrollLabel(LabelScrollAction::Left)),
+                                                                                                "Scroll item text left"),
+                e!(vec![KC::of_key(Z), KC::of_key(L)],    AK(ScrollLabel(LabelScrollAction::Right)),
+                                                                                                "Scroll item text right"),
+                e!(vec![KC::of_key_chord(ArrowLeft, M::SHIFT)],
+                                                          AK(ScrollLabel(LabelScrollAction::Left)),
+                                                                                                "Scroll item text left"),
+                e!(vec![KC::of_key_chord(ArrowRight, M::SHIFT)],
+                                                          AK(ScrollLabel(LabelScrollAction::Right)),
+                                                                                                "Scroll item text right"),
 
                 e!(vec![KC::of_key_chord(U, M::CTRL)],    AK(Scroll(ScrollAction::HalfUp)),     "Scroll half page up"),
                 e!(vec![KC::of_key_chord(D, M::CTRL)],    AK(Scroll(ScrollAction::HalfDown)),   "Scroll half page down"),
@@ -206,39 +291,49 @@ pub static ACTION_KEYMAPS: LazyLock<Vec<KeymapGroup>> = LazyLock::new(|| {
                                                                                                 "Paste item"),
                 e!(vec![KC::of_key(Space)],               AK(KeyAction::Paste(PasteModifier::default())),
                                                                                                 "Paste item"),
-                e!(vec![KC::of_ptr_btn(Primary)],         AP(PointerAction::Paste(PasteModifier::default())),
-                                                                                                "Paste item"),
+                e!(vec![KC::of_ptr_btn(Primary)],         AP(PointerAction::Gesture(PointerGesture::Click)),
+                                                                                                "Click, double click or hold item"),
 
                 e!(vec![KC::of_key_chord(Enter, M::CTRL)],
-                                                          AK(KeyAction::Paste(PasteModifier { and_enter: true, trim: false })),
+                                                          AK(KeyAction::Paste(PasteModifier { and_enter: true, trim: false, keep_open: false, plain_text: false })),
                                                                                                 "Paste item and press Enter"),
                 e!(vec![KC::of_key_chord(Space, M::CTRL)],
-                                                          AK(KeyAction::Paste(PasteModifier { and_enter: true, trim: false })),
+                                                          AK(KeyAction::Paste(PasteModifier { and_enter: true, trim: false, keep_open: false, plain_text: false })),
                                                                                                 "Paste item and press Enter"),
                 e!(vec![KC::of_ptr_btn_chord(Primary, M::CTRL)],
-                                                          AP(PointerAction::Paste(PasteModifier { and_enter: true, trim: false })),
+                                                          AP(PointerAction::Paste(PasteModifier { and_enter: true, trim: false, keep_open: false, plain_text: false })),
                                                                                                 "Paste item and press Enter"),
 
                 e!(vec![KC::of_key_chord(Enter, M::SHIFT)],
-                                                          AK(KeyAction::Paste(PasteModifier { trim: true, and_enter: false })),
+                                                          AK(KeyAction::Paste(PasteModifier { trim: true, and_enter: false, keep_open: false, plain_text: false })),
                                                                                                 "Paste trimmed item"),
                 e!(vec![KC::of_key_chord(Space, M::SHIFT)],
-                                                          AK(KeyAction::Paste(PasteModifier { trim: true, and_enter: false })),
+                                                          AK(KeyAction::Paste(PasteModifier { trim: true, and_enter: false, keep_open: false, plain_text: false })),
                                                                                                 "Paste trimmed item"),
                 e!(vec![KC::of_ptr_btn_chord(Primary, M::SHIFT)],
-                                                          AP(PointerAction::Paste(PasteModifier { trim: true, and_enter: false })),
+                                                          AP(PointerAction::Paste(PasteModifier { trim: true, and_enter: false, keep_open: false, plain_text: false })),
                                                                                                 "Paste trimmed item"),
 
                 e!(vec![KC::of_key_chord(Enter, M::SHIFT | M::CTRL)],
-                                                          AK(KeyAction::Paste(PasteModifier { trim: true, and_enter: true })),
+                                                          AK(KeyAction::Paste(PasteModifier { trim: true, and_enter: true, keep_open: false, plain_text: false })),
                                                                                                 "Paste trimmed item and press Enter"),
                 e!(vec![KC::of_key_chord(Space, M::SHIFT | M::CTRL)],
-                                                          AK(KeyAction::Paste(PasteModifier { trim: true, and_enter: true })),
+                                                          AK(KeyAction::Paste(PasteModifier { trim: true, and_enter: true, keep_open: false, plain_text: false })),
                                                                                                 "Paste trimmed item and press Enter"),
                 e!(vec![KC::of_ptr_btn_chord(Primary, M::SHIFT | M::CTRL)],
-                                                          AP(PointerAction::Paste(PasteModifier { trim: true, and_enter: true })),
+                                                          AP(PointerAction::Paste(PasteModifier { trim: true, and_enter: true, keep_open: false, plain_text: false })),
                                                                                                 "Paste trimmed item and press Enter"),
 
+                e!(vec![KC::of_key_chord(Enter, M::ALT)],
+                                                          AK(KeyAction::Paste(PasteModifier { keep_open: true, trim: false, and_enter: false, plain_text: false })),
+                                                                                                "Paste item and keep window open"),
+                e!(vec![KC::of_key_chord(Space, M::ALT)],
+                                                          AK(KeyAction::Paste(PasteModifier { keep_open: true, trim: false, and_enter: false, plain_text: false })),
+                                                                                                "Paste item and keep window open"),
+                e!(vec![KC::of_ptr_btn_chord(Primary, M::ALT)],
+                                                          AP(PointerAction::Paste(PasteModifier { keep_open: true, trim: false, and_enter: false, plain_text: false })),
+                                                                                                "Paste item and keep window open"),
+
                 e!(vec![KC::of_key(Num1)],                AK(QuickPaste(0)),                    "Quick paste item 1"),
                 e!(vec![KC::of_key(Num2)],                AK(QuickPaste(1)),                    "Quick paste item 2"),
                 e!(vec![KC::of_key(Num3)],                AK(QuickPaste(2)),                    "Quick paste item 3"),
@@ -252,14 +347,175 @@ pub static ACTION_KEYMAPS: LazyLock<Vec<KeymapGroup>> = LazyLock::new(|| {
 
                 e!(vec![KC::of_key(D), KC::of_key(D)],    AK(Remove),                           "Remove item"),
                 e!(vec![KC::of_key(Delete)],              AK(Remove),                           "Remove item"),
+                e!(vec![KC::of_key_chord(Delete, M::SHIFT)],
+                                                          AK(ClearAll),                         "Remove all unpinned items"),
 
                 e!(vec![KC::of_key(P)],                   AK(Pin),                              "Toggle pin"),
+                e!(vec![KC::of_key(X)],                   AK(ToggleExpiry),                     "Toggle expiry"),
+
+                e!(vec![KC::of_key(E)],                   AK(Edit),                             "Edit item in external editor"),
+                e!(vec![KC::of_key(S)],                   AK(SaveToFile),                       "Save item to file"),
+                e!(vec![KC::of_key(O)],                   AK(Open),                             "Open item with default application"),
 
                 e!(vec![KC::of_key(Escape)],              AK(Close),                            "Close window"),
                 e!(vec![KC::of_key(Q)],                   AK(Close),                            "Close window"),
 
+                e!(vec![KC::of_key(V)],                   AK(ViewImage),                        "View image"),
+                e!(vec![KC::of_key(I)],                   AK(ViewDetails),                      "Show text details"),
+                e!(vec![KC::of_key(T)],                   AK(OpenTransforms),                   "Transform item"),
+                e!(vec![KC::of_key(Slash)],               AK(StartSearch),                      "Search items"),
+                e!(vec![KC::of_key(C)],                   AK(ToggleTimeline),                   "Toggle grouping by day"),
+                e!(vec![KC::of_key_chord(Semicolon, M::SHIFT)],
+                                                          AK(OpenPalette),                      "Open command palette"),
+
                 e!(vec![KC::of_key_chord(Slash, M::SHIFT)],
                                                           AK(ShowHelp),                         "Show help"),
+            ].into_iter().chain(mark_entries()).collect(),
+        },
+        KeymapGroup {
+            mode: AppMode::Preview,
+            name: "Preview",
+            entries: vec![
+                e!(vec![KC::of_key(Plus)],                AK(Zoom(ZoomAction::In)),             "Zoom in"),
+                e!(vec![KC::of_key(Equals)],              AK(Zoom(ZoomAction::In)),             "Zoom in"),
+                e!(vec![KC::of_key_chord(Equals, M::SHIFT)],
+                                                          AK(Zoom(ZoomAction::In)),             "Zoom in"),
+                e!(vec![KC::of_key(Minus)],               AK(Zoom(ZoomAction::Out)),            "Zoom out"),
+                e!(vec![KC::of_key(Num1)],                AK(Zoom(ZoomAction::ActualSize)),     "Actual size"),
+                e!(vec![KC::of_key(Num0)],                AK(Zoom(ZoomAction::Fit)),            "Fit to window"),
+
+                e!(vec![KC::of_key(Escape)],              AK(Close),                            "Close preview"),
+                e!(vec![KC::of_key(Q)],                   AK(Close),                            "Close preview"),
+                e!(vec![KC::of_key(V)],                   AK(Close),                            "Close preview"),
+                e!(vec![KC::of_key(I)],                   AK(Close),                            "Close preview"),
+            ],
+        },
+        KeymapGroup {
+            mode: AppMode::Transform,
+            name: "Transform",
+            entries: vec![
+                e!(vec![KC::of_key(U)],                   AK(PasteTransformed(Transform::UpperCase)),
+                                                                                                "Paste in UPPER CASE"),
+                e!(vec![KC::of_key(L)],                   AK(PasteTransformed(Transform::LowerCase)),
+                                                                                                "Paste in lower case"),
+                e!(vec![KC::of_key(T)],                   AK(PasteTransformed(Transform::TitleCase)),
+                                                                                                "Paste in Title Case"),
+                e!(vec![KC::of_key(C)],                   AK(PasteTransformed(Transform::CamelCase)),
+                                                                                                "Paste as camelCase"),
+                e!(vec![KC::of_key(S)],                   AK(PasteTransformed(Transform::SnakeCase)),
+                                                                                                "Paste as snake_case"),
+                e!(vec![KC::of_key(B)],                   AK(PasteTransformed(Transform::DecodeBase64)),
+                                                                                                "Paste Base64-decoded"),
+                e!(vec![KC::of_key(P)],                   AK(PasteTransformed(Transform::UrlDecode)),
+                                                                                                "Paste URL-decoded"),
+                e!(vec![KC::of_key(F)],                   AK(PasteTransformed(Transform::PrettyJson)),
+                                                                                                "Paste as pretty-printed JSON"),
+                e!(vec![KC::of_key(E)],                   AK(PasteTransformed(Transform::Unescape)),
+                                                                                                "Paste unescaped"),
+                e!(vec![KC::of_key(O)],                   AK(PasteTransformed(Transform::SortLines)),
+                                                                                                "Paste with lines sorted"),
+                e!(vec![KC::of_key(D)],                   AK(PasteTransformed(Transform::UniqueLines)),
+                                                                                                "Paste with duplicate lines removed"),
+                e!(vec![KC::of_key(J)],                   AK(PasteTransformed(Transform::JoinLines)),
+                                                                                                "Paste with lines joined"),
+                e!(vec![KC::of_key(N)],                   AK(PasteTransformed(Transform::NumberLines)),
+                                                                                                "Paste with lines numbered"),
+
+                e!(vec![KC::of_key_chord(U, M::SHIFT)],
+                                                          AK(KeyAction::Transform(Transform::UpperCase)),
+                                                                                                "Copy to new item in UPPER CASE"),
+                e!(vec![KC::of_key_chord(L, M::SHIFT)],
+                                                          AK(KeyAction::Transform(Transform::LowerCase)),
+                                                                                                "Copy to new item in lower case"),
+                e!(vec![KC::of_key_chord(T, M::SHIFT)],
+                                                          AK(KeyAction::Transform(Transform::TitleCase)),
+                                                                                                "Copy to new item in Title Case"),
+                e!(vec![KC::of_key_chord(C, M::SHIFT)],
+                                                          AK(KeyAction::Transform(Transform::CamelCase)),
+                                                                                                "Copy to new item as camelCase"),
+                e!(vec![KC::of_key_chord(S, M::SHIFT)],
+                                                          AK(KeyAction::Transform(Transform::SnakeCase)),
+                                                                                                "Copy to new item as snake_case"),
+                e!(vec![KC::of_key_chord(B, M::SHIFT)],
+                                                          AK(KeyAction::Transform(Transform::DecodeBase64)),
+                                                                                                "Copy to new item Base64-decoded"),
+                e!(vec![KC::of_key_chord(P, M::SHIFT)],
+                                                          AK(KeyAction::Transform(Transform::UrlDecode)),
+                                                                                                "Copy to new item URL-decoded"),
+                e!(vec![KC::of_key_chord(F, M::SHIFT)],
+                                                          AK(KeyAction::Transform(Transform::PrettyJson)),
+                                                                                                "Copy to new item as pretty-printed JSON"),
+                e!(vec![KC::of_key_chord(E, M::SHIFT)],
+                                                          AK(KeyAction::Transform(Transform::Unescape)),
+                                                                                                "Copy to new item unescaped"),
+                e!(vec![KC::of_key_chord(O, M::SHIFT)],
+                                                          AK(KeyAction::Transform(Transform::SortLines)),
+                                                                                                "Copy to new item with lines sorted"),
+                e!(vec![KC::of_key_chord(D, M::SHIFT)],
+                                                          AK(KeyAction::Transform(Transform::UniqueLines)),
+                                                                                                "Copy to new item with duplicate lines removed"),
+                e!(vec![KC::of_key_chord(J, M::SHIFT)],
+                                                          AK(KeyAction::Transform(Transform::JoinLines)),
+                                                                                                "Copy to new item with lines joined"),
+                e!(vec![KC::of_key_chord(N, M::SHIFT)],
+                                                          AK(KeyAction::Transform(Transform::NumberLines)),
+                                                                                                "Copy to new item with lines numbered"),
+
+                e!(vec![KC::of_key(Escape)],              AK(Close),                            "Cancel"),
+                e!(vec![KC::of_key(Q)],                   AK(Close),                            "Cancel"),
+            ],
+        },
+        KeymapGroup {
+            mode: AppMode::Search,
+            name: "Search",
+            entries: vec![
+                e!(vec![KC::of_key(ArrowUp)],             AK(Scroll(ScrollAction::ItemUp)),     "Move to previous item"),
+                e!(vec![KC::of_key(ArrowDown)],           AK(Scroll(ScrollAction::ItemDown)),   "Move to next item"),
+                e!(vec![KC::of_key_chord(P, M::CTRL)],    AK(Scroll(ScrollAction::ItemUp)),     "Move to previous item"),
+                e!(vec![KC::of_key_chord(N, M::CTRL)],    AK(Scroll(ScrollAction::ItemDown)),   "Move to next item"),
+                e!(vec![KC::of_key_chord(Tab, M::SHIFT)], AK(Scroll(ScrollAction::ItemUp)),     "Move to previous item"),
+                e!(vec![KC::of_key(Tab)],                 AK(Scroll(ScrollAction::ItemDown)),   "Move to next item"),
+
+                e!(vec![KC::of_key(Enter)],               AK(KeyAction::Paste(PasteModifier::default())),
+                                                                                                "Paste item"),
+                e!(vec![KC::of_ptr_btn(Primary)],         AP(PointerAction::Gesture(PointerGesture::Click)),
+                                                                                                "Click, double click or hold item"),
+                e!(vec![KC::of_key_chord(Enter, M::CTRL)],
+                                                          AK(KeyAction::Paste(PasteModifier { and_enter: true, trim: false, keep_open: false, plain_text: false })),
+                                                                                                "Paste item and press Enter"),
+                e!(vec![KC::of_key_chord(Enter, M::SHIFT)],
+                                                          AK(KeyAction::Paste(PasteModifier { trim: true, and_enter: false, keep_open: false, plain_text: false })),
+                                                                                                "Paste trimmed item"),
+
+                e!(vec![KC::of_key(Backspace)],           AK(DeleteQueryChar),                  "Delete last character"),
+                e!(vec![KC::of_key_chord(U, M::CTRL)],    AK(ClearQuery),                       "Clear query"),
+
+                e!(vec![KC::of_key(Escape)],              AK(Close),                            "Stop searching"),
+            ],
+        },
+        KeymapGroup {
+            mode: AppMode::Palette,
+            name: "Command palette",
+            entries: vec![
+                e!(vec![KC::of_key(ArrowUp)],             AK(SimpleScroll(SimpleScrollAction::Up)),
+                                                                                                "Select previous command"),
+                e!(vec![KC::of_key(ArrowDown)],           AK(SimpleScroll(SimpleScrollAction::Down)),
+                                                                                                "Select next command"),
+                e!(vec![KC::of_key_chord(P, M::CTRL)],    AK(SimpleScroll(SimpleScrollAction::Up)),
+                                                                                                "Select previous command"),
+                e!(vec![KC::of_key_chord(N, M::CTRL)],    AK(SimpleScroll(SimpleScrollAction::Down)),
+                                                                                                "Select next command"),
+                e!(vec![KC::of_key_chord(Tab, M::SHIFT)], AK(SimpleScroll(SimpleScrollAction::Up)),
+                                                                                                "Select previous command"),
+                e!(vec![KC::of_key(Tab)],                 AK(SimpleScroll(SimpleScrollAction::Down)),
+                                                                                                "Select next command"),
+
+                e!(vec![KC::of_key(Enter)],               AK(Confirm),                          "Run command"),
+
+                e!(vec![KC::of_key(Backspace)],           AK(DeleteQueryChar),                  "Delete last character"),
+                e!(vec![KC::of_key_chord(U, M::CTRL)],    AK(ClearQuery),                       "Clear query"),
+
+                e!(vec![KC::of_key(Escape)],              AK(Close),                            "Close command palette"),
             ],
         },
         KeymapGroup {
@@ -283,15 +539,74 @@ pub static ACTION_KEYMAPS: LazyLock<Vec<KeymapGroup>> = LazyLock::new(|| {
                 e!(vec![KC::of_key(Q)],                   AK(Close),                            "Close help"),
             ],
         },
+        KeymapGroup {
+            mode: AppMode::Confirm,
+            name: "Confirm",
+            entries: vec![
+                e!(vec![KC::of_key(Y)],                   AK(Confirm),                          "Confirm"),
+                e!(vec![KC::of_key(N)],                   AK(Close),                            "Cancel"),
+                e!(vec![KC::of_key(Escape)],              AK(Close),                            "Cancel"),
+            ],
+        },
     ]
 });
 
+/// The shortest keys bound to an action in the mode, as shown in the help, e.g., for hints
+pub fn keys_for(mode: AppMode, is_action: impl Fn(&Action) -> bool) -> Option<String> {
+    ACTION_KEYMAPS
+        .iter()
+        .filter(|group| group.mode == mode)
+        .flat_map(|group| &group.entries)
+        .filter(|entry| is_action(&entry.action))
+        .min_by_key(|entry| entry.keys.len())
+        .map(|entry| {
+            entry
+                .keys
+                .iter()
+                .map(|k| k.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+}
+
+// m and ' followed by any letter, for marks
+fn mark_entries() -> Vec<KeymapEntry> {
+    let letter_keys = Key::ALL
+        .iter()
+        .filter(|&&key| key >= Key::A && key <= Key::Z);
+    let mut entries = vec![];
+    for (i, &key) in letter_keys.enumerate() {
+        let Some(letter) = key.name().chars().next() else {
+            continue;
+        };
+        let letter = letter.to_ascii_lowercase();
+        entries.push(KeymapEntry {
+            keys: vec![KeyChord::of_key(Key::M), KeyChord::of_key(key)],
+            action: Action::Key(KeyAction::SetMark(letter)),
+            description: "Mark item with a letter (a-z)",
+            hidden: i > 0,
+        });
+        entries.push(KeymapEntry {
+            keys: vec![KeyChord::of_key(Key::Quote), KeyChord::of_key(key)],
+            action: Action::Key(KeyAction::JumpToMark(letter)),
+            description: "Jump to item marked with a letter (a-z)",
+            hidden: i > 0,
+        });
+    }
+    entries
+}
+
 pub struct KeymapAction {
-    action_keymap_tries: HashMap<AppMode, Trie<&'static KeyChord, Action>>,
+    // A list of actions, as a key of `macros` in the config runs several
+    action_keymap_tries: HashMap<AppMode, Trie<KeyChord, Vec<Action>>>,
+    click_timing: ClickTiming,
+    count_prefix: bool,
+    // Typed before the keys of the action, and shown with them in pending_keys
+    count: Option<usize>,
     pub pending_keys: Vec<KeyChord>,
 }
 impl KeymapAction {
-    pub fn new() -> Result<Self> {
+    pub fn new(config: &Config) -> Result<Self> {
         let mut action_keymap_tries = HashMap::new();
 
         for group in ACTION_KEYMAPS.iter() {
@@ -299,12 +614,34 @@ impl KeymapAction {
                 .entry(group.mode)
                 .or_insert_with(Trie::default);
             for entry in &group.entries {
-                trie.insert(&entry.keys, entry.action);
+                trie.insert(entry.keys.iter().copied(), vec![entry.action]);
             }
         }
 
+        // After the built-in keymaps, so they take their place
+        let normal_trie = action_keymap_tries
+            .entry(AppMode::Normal)
+            .or_insert_with(Trie::default);
+        for (keys, actions) in &config.macros {
+            let Some(key_chords) = parse_key_chords(keys) else {
+                warn!("invalid macro keys {keys:?}, ignoring them");
+                continue;
+            };
+            debug!("macro {keys:?}: {actions:?}");
+            normal_trie.insert(
+                key_chords,
+                actions
+                    .iter()
+                    .map(|&action| Action::Key(macro_key_action(action)))
+                    .collect(),
+            );
+        }
+
         Ok(KeymapAction {
             action_keymap_tries,
+            click_timing: ClickTiming::new(&config.pointer),
+            count_prefix: config.count_prefix,
+            count: None,
             pending_keys: vec![],
         })
     }
@@ -313,7 +650,7 @@ impl KeymapAction {
         &mut self,
         egui_input: &mut RawInput,
         mode: AppMode,
-    ) -> (Vec<KeyAction>, Vec<PointerAction>) {
+    ) -> (Vec<(KeyAction, Option<usize>)>, Vec<PointerAction>) {
         let mut key_actions = vec![];
         let mut pointer_actions = vec![];
 
@@ -322,7 +659,9 @@ impl KeymapAction {
             None => return (key_actions, pointer_actions),
         };
 
+        let now = Instant::now();
         for event in mem::take(&mut egui_input.events) {
+            let mut gesture = None;
             let key_chord = match event {
                 Event::Key {
                     key,
@@ -343,6 +682,13 @@ impl KeymapAction {
                     ..
                 } => {
                     egui_input.events.push(event);
+                    if button == PointerButton::Primary {
+                        if pressed {
+                            self.click_timing.press(now);
+                        } else {
+                            gesture = Some(self.click_timing.release(now));
+                        }
+                    }
                     // pointer action activated on button release
                     if !pressed {
                         Some(KeyChord::of_ptr_btn_chord(button, modifiers))
@@ -362,27 +708,61 @@ impl KeymapAction {
                 {
                     debug!("received Escape, clearing pending keys");
                     self.pending_keys.clear();
+                    self.count = None;
+                    continue;
+                }
+
+                // Only before the keys of an action, and 0 only after another digit, so it
+                // still quick pastes the tenth item alone
+                let count_digit = count_digit(&key_chord)
+                    .filter(|&digit| digit > 0 || self.count.is_some())
+                    .filter(|_| {
+                        self.count_prefix
+                            && mode == AppMode::Normal
+                            && self.pending_keys.len() == count_len(self.count)
+                    });
+                if let Some(digit) = count_digit {
+                    let count = self.count.unwrap_or(0) * 10 + digit;
+                    if count <= MAX_COUNT {
+                        self.count = Some(count);
+                        self.pending_keys.push(key_chord);
+                        debug!("continuing building count: {count}");
+                    }
                     continue;
                 }
 
                 self.pending_keys.push(key_chord);
-                if let Some(keymap_node) = trie.get_node(&self.pending_keys) {
-                    if let Some(action) = keymap_node.value {
+                let keys = &self.pending_keys[count_len(self.count)..];
+                if let Some(keymap_node) = trie.get_node(keys.iter().copied()) {
+                    if let Some(actions) = &keymap_node.value {
                         debug!(
-                            "converting keymap {:?} to action {action:?}",
+                            "converting keymap {:?} to actions {actions:?}",
                             self.pending_keys
                         );
-                        match action {
-                            Action::Key(key_action) => key_actions.push(key_action),
-                            Action::Pointer(pointer_action) => pointer_actions.push(pointer_action),
+                        for &action in actions {
+                            match action {
+                                Action::Key(key_action) => {
+                                    key_actions.push((key_action, self.count))
+                                }
+                                Action::Pointer(PointerAction::Gesture(_)) => {
+                                    if let Some(gesture) = gesture {
+                                        pointer_actions.push(PointerAction::Gesture(gesture));
+                                    }
+                                }
+                                Action::Pointer(pointer_action) => {
+                                    pointer_actions.push(pointer_action)
+                                }
+                            }
                         }
                         self.pending_keys.clear();
+                        self.count = None;
                     } else {
                         debug!("continuing building keymap: {:?}", self.pending_keys);
                     }
                 } else {
                     debug!("received invalid keymap: {:?}", self.pending_keys);
                     self.pending_keys.clear();
+                    self.count = None;
                 }
             }
         }
@@ -391,6 +771,149 @@ impl KeymapAction {
     }
 }
 
+const MAX_COUNT: usize = 9999;
+
+// Keys of `macros` in the config, written like KeyChord shows them, e.g., "C-S-p" or "g p"
+fn parse_key_chords(keys: &str) -> Option<Vec<KeyChord>> {
+    let key_chords = keys
+        .split_whitespace()
+        .map(|key_chord| {
+            let mut mods = Modifiers::NONE;
+            let mut key = key_chord;
+            // A key of "-" itself is left as it is
+            while key.len() > 2
+                && let Some((prefix, rest)) = key.split_at_checked(2)
+            {
+                match prefix {
+                    "C-" => mods |= Modifiers::CTRL,
+                    "M-" => mods |= Modifiers::ALT,
+                    "S-" => mods |= Modifiers::SHIFT,
+                    _ => break,
+                }
+                key = rest;
+            }
+            // Letters are shown lowercase, but named uppercase
+            let key = if key.len() == 1 && key.chars().all(|c| c.is_ascii_alphabetic()) {
+                Key::from_name(&key.to_ascii_uppercase())
+            } else {
+                Key::from_name(key)
+            }?;
+            Some(KeyChord::of_key_chord(key, mods))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    (!key_chords.is_empty()).then_some(key_chords)
+}
+
+/// What an action of `macros` in the config does
+pub fn macro_key_action(action: MacroAction) -> KeyAction {
+    let paste = KeyAction::Paste;
+    match action {
+        MacroAction::Paste => paste(PasteModifier::default()),
+        MacroAction::PastePlain => paste(PasteModifier {
+            plain_text: true,
+            ..PasteModifier::default()
+        }),
+        MacroAction::PasteTrimmed => paste(PasteModifier {
+            trim: true,
+            ..PasteModifier::default()
+        }),
+        MacroAction::PasteAndEnter => paste(PasteModifier {
+            and_enter: true,
+            ..PasteModifier::default()
+        }),
+        MacroAction::PasteKeepOpen => paste(PasteModifier {
+            keep_open: true,
+            ..PasteModifier::default()
+        }),
+        MacroAction::Pin => KeyAction::Pin,
+        MacroAction::Remove => KeyAction::Remove,
+        MacroAction::ToggleExpiry => KeyAction::ToggleExpiry,
+        MacroAction::Edit => KeyAction::Edit,
+        MacroAction::SaveToFile => KeyAction::SaveToFile,
+        MacroAction::Open => KeyAction::Open,
+        MacroAction::ViewImage => KeyAction::ViewImage,
+        MacroAction::ViewDetails => KeyAction::ViewDetails,
+        MacroAction::Next => KeyAction::Scroll(ScrollAction::ItemDown),
+        MacroAction::Previous => KeyAction::Scroll(ScrollAction::ItemUp),
+        MacroAction::First => KeyAction::Scroll(ScrollAction::ToTop),
+        MacroAction::Last => KeyAction::Scroll(ScrollAction::ToBottom),
+        MacroAction::StartSearch => KeyAction::StartSearch,
+        MacroAction::ToggleTimeline => KeyAction::ToggleTimeline,
+        MacroAction::Close => KeyAction::Close,
+    }
+}
+
+fn count_digit(key_chord: &KeyChord) -> Option<usize> {
+    if key_chord.mods != Modifiers::NONE {
+        return None;
+    }
+    let KeyOrPointerButton::Key(key) = key_chord.key else {
+        return None;
+    };
+    const DIGITS: [Key; 10] = [
+        Key::Num0,
+        Key::Num1,
+        Key::Num2,
+        Key::Num3,
+        Key::Num4,
+        Key::Num5,
+        Key::Num6,
+        Key::Num7,
+        Key::Num8,
+        Key::Num9,
+    ];
+    DIGITS.iter().position(|&digit| digit == key)
+}
+
+// Number of pending keys the count was typed with, as it can't start with 0
+fn count_len(count: Option<usize>) -> usize {
+    count.map_or(0, |count| count.to_string().len())
+}
+
+// Tells a click from the second one of a double click, or from a press held for long. Decided on
+// release like other pointer actions, so a hold acts when the button is let go
+struct ClickTiming {
+    double_click: Duration,
+    hold: Duration,
+    pressed_at: Option<Instant>,
+    last_click_at: Option<Instant>,
+}
+
+impl ClickTiming {
+    fn new(config: &PointerConfig) -> Self {
+        ClickTiming {
+            double_click: Duration::from_millis(config.double_click_ms),
+            hold: Duration::from_millis(config.hold_ms),
+            pressed_at: None,
+            last_click_at: None,
+        }
+    }
+
+    fn press(&mut self, now: Instant) {
+        self.pressed_at = Some(now);
+    }
+
+    fn release(&mut self, now: Instant) -> PointerGesture {
+        let held = self
+            .pressed_at
+            .take()
+            .is_some_and(|at| now.duration_since(at) >= self.hold);
+        if held {
+            self.last_click_at = None;
+            return PointerGesture::Hold;
+        }
+
+        // A third click starts over rather than making another double click
+        match self.last_click_at.take() {
+            Some(at) if now.duration_since(at) < self.double_click => PointerGesture::DoubleClick,
+            _ => {
+                self.last_click_at = Some(now);
+                PointerGesture::Click
+            }
+        }
+    }
+}
+
 // Extremely simple trie implementation
 
 struct Trie<K, V> {
@@ -444,3 +967,180 @@ where
         Some(node)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press_keys(
+        keymap_action: &mut KeymapAction,
+        keys: &[Key],
+    ) -> Vec<(KeyAction, Option<usize>)> {
+        let mut input = RawInput {
+            events: keys
+                .iter()
+                .map(|&key| Event::Key {
+                    key,
+                    physical_key: None,
+                    pressed: true,
+                    repeat: false,
+                    modifiers: Modifiers::NONE,
+                })
+                .collect(),
+            ..Default::default()
+        };
+        keymap_action.process_input(&mut input, AppMode::Normal).0
+    }
+
+    fn counting_keymap_action() -> KeymapAction {
+        KeymapAction::new(&Config {
+            count_prefix: true,
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn count_goes_with_the_next_action() {
+        let mut keymap_action = counting_keymap_action();
+        let actions = press_keys(&mut keymap_action, &[Key::Num1, Key::Num2, Key::J]);
+        assert!(matches!(
+            actions[..],
+            [(KeyAction::Scroll(ScrollAction::ItemDown), Some(12))]
+        ));
+        let actions = press_keys(&mut keymap_action, &[Key::Num3, Key::D, Key::D, Key::K]);
+        assert!(matches!(
+            actions[..],
+            [
+                (KeyAction::Remove, Some(3)),
+                (KeyAction::Scroll(ScrollAction::ItemUp), None)
+            ]
+        ));
+    }
+
+    #[test]
+    fn zero_alone_is_not_a_count() {
+        let mut keymap_action = counting_keymap_action();
+        let actions = press_keys(&mut keymap_action, &[Key::Num0]);
+        assert!(matches!(actions[..], [(KeyAction::QuickPaste(9), None)]));
+    }
+
+    #[test]
+    fn number_keys_quick_paste_without_count_prefix() {
+        let mut keymap_action = KeymapAction::new(&Config::default()).unwrap();
+        let actions = press_keys(&mut keymap_action, &[Key::Num5, Key::J]);
+        assert!(matches!(
+            actions[..],
+            [
+                (KeyAction::QuickPaste(4), None),
+                (KeyAction::Scroll(ScrollAction::ItemDown), None)
+            ]
+        ));
+    }
+
+    #[test]
+    fn parses_macro_keys() {
+        assert_eq!(
+            parse_key_chords("C-S-p"),
+            Some(vec![KeyChord::of_key_chord(
+                Key::P,
+                Modifiers::CTRL | Modifiers::SHIFT
+            )])
+        );
+        assert_eq!(
+            parse_key_chords("g Enter"),
+            Some(vec![KeyChord::of_key(Key::G), KeyChord::of_key(Key::Enter)])
+        );
+        assert_eq!(parse_key_chords("C-pp"), None);
+        assert_eq!(parse_key_chords(""), None);
+    }
+
+    #[test]
+    fn macro_runs_its_actions_in_order() {
+        let mut keymap_action = KeymapAction::new(&Config {
+            macros: HashMap::from([(
+                "S-p".to_string(),
+                vec![MacroAction::Pin, MacroAction::Close],
+            )]),
+            ..Default::default()
+        })
+        .unwrap();
+        let mut input = RawInput::default();
+        input.events.push(Event::Key {
+            key: Key::P,
+            physical_key: None,
+            pressed: true,
+            repeat: false,
+            modifiers: Modifiers::SHIFT,
+        });
+        let actions = keymap_action.process_input(&mut input, AppMode::Normal).0;
+        assert!(matches!(
+            actions[..],
+            [(KeyAction::Pin, None), (KeyAction::Close, None)]
+        ));
+    }
+
+    fn click_timing() -> ClickTiming {
+        ClickTiming::new(&PointerConfig {
+            double_click_ms: 400,
+            hold_ms: 500,
+            ..Default::default()
+        })
+    }
+
+    fn click(timing: &mut ClickTiming, at: Instant, held_ms: u64) -> PointerGesture {
+        timing.press(at);
+        timing.release(at + Duration::from_millis(held_ms))
+    }
+
+    #[test]
+    fn quick_second_click_is_double_click() {
+        let mut timing = click_timing();
+        let now = Instant::now();
+        assert_eq!(click(&mut timing, now, 50), PointerGesture::Click);
+        assert_eq!(
+            click(&mut timing, now + Duration::from_millis(200), 50),
+            PointerGesture::DoubleClick
+        );
+        assert_eq!(
+            click(&mut timing, now + Duration::from_millis(400), 50),
+            PointerGesture::Click
+        );
+    }
+
+    #[test]
+    fn slow_second_click_is_click() {
+        let mut timing = click_timing();
+        let now = Instant::now();
+        click(&mut timing, now, 50);
+        assert_eq!(
+            click(&mut timing, now + Duration::from_millis(1000), 50),
+            PointerGesture::Click
+        );
+    }
+
+    #[test]
+    fn long_press_is_hold() {
+        let mut timing = click_timing();
+        let now = Instant::now();
+        click(&mut timing, now, 50);
+        assert_eq!(
+            click(&mut timing, now + Duration::from_millis(100), 600),
+            PointerGesture::Hold
+        );
+        // Not the second click of a double click either
+        assert_eq!(
+            click(&mut timing, now + Duration::from_millis(800), 50),
+            PointerGesture::Click
+        );
+    }
+
+    #[test]
+    fn keys_for_picks_the_shortest_binding() {
+        let remove_keys = keys_for(AppMode::Normal, |action| {
+            matches!(action, Action::Key(KeyAction::Remove))
+        });
+        assert_eq!(remove_keys.as_deref(), Some("Delete"));
+        assert_eq!(keys_for(AppMode::Normal, |_| false), None);
+    }
+}
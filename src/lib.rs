@@ -1,20 +1,65 @@
+#[cfg(feature = "accessibility")]
+pub mod accessibility;
+pub mod archive;
+pub mod classification;
+pub mod cli;
+pub mod cli_output;
 pub mod color;
 pub mod config;
+pub mod config_schema;
+pub mod damage;
+pub mod desktop_entry;
+pub mod display_text;
+pub mod expiry;
 pub mod ext;
+pub mod favicon;
+pub mod file_stats;
+pub mod format;
 pub mod freedesktop_cache;
+pub mod hotkey;
+pub mod icon_theme;
+pub mod image_decode;
 pub mod input;
+pub mod ipc;
+pub mod item_actions;
 pub mod keymap_action;
+pub mod merge;
+pub mod metrics;
+pub mod migration;
 pub mod opengl_context;
 pub mod ordered_hash_map;
 pub mod persistence;
+pub mod pipe;
+pub mod providers;
+pub mod renderer;
+pub mod script;
+pub mod search;
 pub mod selection;
+#[cfg(feature = "self-update")]
+pub mod self_update;
+pub mod software_renderer;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_storage;
+pub mod text;
+pub mod text_details;
+pub mod text_encoding;
+pub mod texture_cache;
+pub mod thumbnailer;
+pub mod timeline;
 pub mod timerfd_source;
 pub mod transfer_window_pool;
+pub mod transform;
+pub mod tui;
 pub mod ui;
 pub mod utils;
+pub mod virtual_items;
+pub mod watch;
+#[cfg(feature = "wgpu")]
+pub mod wgpu_renderer;
 pub mod widgets;
 pub mod x11_key_converter;
 pub mod x11_window;
+pub mod yank_pop;
 
 pub use ext::ScrollAreaStateExt;
 
@@ -22,4 +67,24 @@ pub use ext::ScrollAreaStateExt;
 pub enum AppMode {
     Normal,
     Help,
+    Confirm,
+    Preview,
+    Transform,
+    Search,
+    Palette,
+}
+
+/// Actions that need to go through the confirmation modal before being carried out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DestructiveAction {
+    ClearAll,
+    RemovePinned(u64),
+}
+impl DestructiveAction {
+    pub fn message(&self) -> &'static str {
+        match self {
+            DestructiveAction::ClearAll => "All unpinned items will be removed.",
+            DestructiveAction::RemovePinned(_) => "This pinned item will be removed.",
+        }
+    }
 }
@@ -1,35 +1,57 @@
-use anyhow::{Result, anyhow, bail};
+use anyhow::{Context as _, Result, anyhow, bail};
 use egui::Modifiers;
 use env_logger::TimestampPrecision;
-use log::{LevelFilter, debug, info, warn};
-use memoni::AppMode;
-use memoni::config::Config;
+use fontconfig::Fontconfig;
+use log::{LevelFilter, debug, error, info, warn};
+use memoni::archive::Archive;
+use memoni::cli::{self, Shell};
+use memoni::cli_output::{self, DEFAULT_OSC52_LIMIT, Multiplexer};
+use memoni::config::{Config, PointerGestureAction, ThemeConfig, WindowPositionMode};
+use memoni::config_schema;
+use memoni::expiry;
+use memoni::favicon::FaviconFetcher;
+use memoni::file_stats::FileStatsScanner;
+use memoni::hotkey::{HotkeyAction, Hotkeys};
 use memoni::input::Input;
+use memoni::ipc::{self, ClientCommand, ItemRef};
+use memoni::item_actions::{self, ItemEditor};
 use memoni::keymap_action::{
-    KeyAction, KeymapAction, PasteModifier, PointerAction, SimpleScrollAction,
+    KeyAction, KeymapAction, PasteModifier, PointerAction, PointerGesture, ScrollAction,
+    SimpleScrollAction,
 };
-use memoni::persistence::Persistence;
-use memoni::selection::Selection;
+use memoni::metrics;
+use memoni::persistence::{self, Storage};
+use memoni::search::Search;
+use memoni::selection::{CAPTURE_FAILED_MIME, Selection};
 use memoni::timerfd_source::TimerfdSource;
-use memoni::ui::{Ui, UiFlow};
+use memoni::transfer_window_pool::MAINTENANCE_INTERVAL;
+use memoni::ui::{FrameState, Ui, UiAction, UiFlow};
+use memoni::virtual_items::VirtualItems;
+use memoni::watch::{self, Watchers};
+use memoni::widgets::toasts::ToastKind;
 use memoni::x11_key_converter::X11KeyConverter;
 use memoni::x11_window::X11Window;
-use memoni::{opengl_context::OpenGLContext, selection::SelectionType};
+use memoni::yank_pop::YankPop;
+use memoni::{AppMode, DestructiveAction};
+use memoni::{renderer, selection::SelectionType};
 use mio::unix::SourceFd;
 use signal_hook::consts::TERM_SIGNALS;
 use signal_hook_mio::v1_0::Signals;
 use std::os::unix::net::{UnixListener, UnixStream};
 use std::{
+    collections::BTreeMap,
     ffi::OsStr,
     fs,
-    io::{self, Read, Write},
+    io::{self, BufRead as _, Read, Write},
+    iter,
     os::fd::{AsFd as _, AsRawFd as _},
-    path::Path,
-    time::Duration,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 use x11rb::connection::Connection;
+use x11rb::errors::{ConnectionError, ReplyError, ReplyOrIdError};
 use x11rb::protocol::Event;
-use x11rb::protocol::xproto::Mapping;
+use x11rb::protocol::xproto::{ConnectionExt as _, Mapping, NotifyDetail, NotifyMode};
 use x11rb::xcb_ffi::XCBConnection;
 
 const SOCKET_DIR: &str = "/tmp/memoni/";
@@ -38,20 +60,152 @@ const SIGNAL_TOKEN: mio::Token = mio::Token(1);
 const MEMONI_TOKEN: mio::Token = mio::Token(2);
 const KEYBOARD_GRAB_RETRY_TOKEN: mio::Token = mio::Token(3);
 const POINTER_GRAB_RETRY_TOKEN: mio::Token = mio::Token(4);
+const CAPTURE_DEBOUNCE_TOKEN: mio::Token = mio::Token(5);
+const ITEM_EDIT_TOKEN: mio::Token = mio::Token(6);
+const FILE_STATS_TOKEN: mio::Token = mio::Token(7);
+const THEME_PREVIEW_TOKEN: mio::Token = mio::Token(8);
+const FAVICON_TOKEN: mio::Token = mio::Token(9);
+const EXPIRY_TOKEN: mio::Token = mio::Token(10);
+#[cfg(feature = "accessibility")]
+const SCREEN_READER_TOKEN: mio::Token = mio::Token(11);
+const TRANSFER_WINDOWS_TOKEN: mio::Token = mio::Token(12);
+
+// Same as in the command table
+const DEFAULT_THEME_PREVIEW_SECONDS: u64 = 30;
+const DEFAULT_ADD_MIME: &str = "text/plain";
 
 enum Args {
     Client(ClientArgs),
     Server(ServerArgs),
+    Watch(ClientArgs),
+    Pipe(PipeArgs),
+    Tui(ClientArgs),
+    Get(GetArgs),
+    List(ListArgs),
+    Restore(RestoreArgs),
+    Paste(PasteArgs),
+    Add(AddArgs),
+    PreviewTheme(PreviewThemeArgs),
+    InitConfig(InitConfigArgs),
+    #[cfg(feature = "self-update")]
+    SelfUpdate {
+        check_only: bool,
+    },
 }
 
 #[derive(Debug)]
 struct ClientArgs {
     selection: SelectionType,
+    profile: Option<String>,
+}
+
+#[derive(Debug)]
+struct PipeArgs {
+    selection: SelectionType,
+    profile: Option<String>,
+    command: String,
+}
+
+#[derive(Debug)]
+struct GetArgs {
+    selection: SelectionType,
+    profile: Option<String>,
+    item: ItemRef,
+    mime: Option<String>,
+    osc52: bool,
+    osc52_limit: usize,
+}
+
+#[derive(Debug)]
+struct ListArgs {
+    selection: SelectionType,
+    profile: Option<String>,
+    archived: bool,
+}
+
+#[derive(Debug)]
+struct RestoreArgs {
+    selection: SelectionType,
+    profile: Option<String>,
+    item_id: u64,
+}
+
+#[derive(Debug)]
+struct PasteArgs {
+    selection: SelectionType,
+    profile: Option<String>,
+    previous: bool,
+    expire_secs: Option<u64>,
+}
+
+#[derive(Debug)]
+struct AddArgs {
+    selection: SelectionType,
+    profile: Option<String>,
+    mime: String,
+    owns: bool,
+    expire_secs: Option<u64>,
+}
+
+#[derive(Debug)]
+struct PreviewThemeArgs {
+    selection: SelectionType,
+    profile: Option<String>,
+    path: PathBuf,
+    seconds: u64,
+}
+
+#[derive(Debug)]
+struct InitConfigArgs {
+    theme: Option<String>,
+    detect_font: bool,
+    print: bool,
+    force: bool,
 }
 
 #[derive(Debug)]
 struct ServerArgs {
     selection: SelectionType,
+    profile: Option<String>,
+    profile_overlay: bool,
+    measure_show_latency: bool,
+}
+
+/// Servers of different X displays or profiles keep separate histories, each with its own socket
+/// and files
+struct Session {
+    display_id: Option<String>,
+    profile: Option<String>,
+}
+impl Session {
+    // e.g., "1" for display ":1", "work" for profile "work", "1_work" for both
+    fn id(&self) -> Option<String> {
+        match (&self.display_id, &self.profile) {
+            (Some(display_id), Some(profile)) => Some(format!("{display_id}_{profile}")),
+            (display_id, profile) => display_id.clone().or(profile.clone()),
+        }
+    }
+
+    fn socket_path(&self, socket_dir: &Path, selection: SelectionType) -> PathBuf {
+        let socket_file_name = if let Some(id) = self.id() {
+            format!("{selection}_{id}.sock")
+        } else {
+            format!("{selection}.sock")
+        };
+        socket_dir.join(socket_file_name)
+    }
+
+    // For error messages, e.g., " on display \"1\" with profile \"work\""
+    fn describe(&self) -> String {
+        let mut description = String::new();
+        if let Some(id) = &self.display_id {
+            description.push_str(&format!(" on display {id:?}"));
+        }
+        if let Some(profile) = &self.profile {
+            description.push_str(&format!(" with profile {profile:?}"));
+        }
+        description
+    }
 }
 
 fn main() -> Result<()> {
@@ -81,26 +235,129 @@ fn main() -> Result<()> {
             info!("starting client mode with selection: {}", args.selection);
             debug!("client args: {args:#?}");
 
-            let socket_file_name = if let Some(id) = &display_id {
-                format!("{}_{}.sock", args.selection, id)
-            } else {
-                format!("{}.sock", args.selection)
+            let session = Session {
+                display_id,
+                profile: args.profile.clone(),
             };
-            let socket_path = socket_dir.join(socket_file_name);
-            client(args, &socket_path, display_id)?
+            let socket_path = session.socket_path(socket_dir, args.selection);
+            client(args, &socket_path, &session)?
         }
         Args::Server(args) => {
             info!("starting server mode with selection: {}", args.selection);
             debug!("server args: {args:#?}");
 
-            let socket_file_name = if let Some(id) = &display_id {
-                format!("{}_{}.sock", args.selection, id)
-            } else {
-                format!("{}.sock", args.selection)
+            let session = Session {
+                display_id,
+                profile: args.profile.clone(),
+            };
+            let socket_path = session.socket_path(socket_dir, args.selection);
+            server(args, &socket_path, &session)?
+        }
+        Args::Watch(args) => {
+            info!("starting watch mode with selection: {}", args.selection);
+            debug!("watch args: {args:#?}");
+
+            let session = Session {
+                display_id,
+                profile: args.profile.clone(),
             };
-            let socket_path = socket_dir.join(socket_file_name);
-            server(args, &socket_path, display_id)?
+            let socket_path = session.socket_path(socket_dir, args.selection);
+            watch(args, &socket_path, &session)?
         }
+        Args::Pipe(args) => {
+            info!("starting pipe mode with selection: {}", args.selection);
+            debug!("pipe args: {args:#?}");
+
+            let session = Session {
+                display_id,
+                profile: args.profile.clone(),
+            };
+            let socket_path = session.socket_path(socket_dir, args.selection);
+            pipe(args, &socket_path, &session)?
+        }
+        Args::Tui(args) => {
+            info!("starting tui mode with selection: {}", args.selection);
+            debug!("tui args: {args:#?}");
+
+            let session = Session {
+                display_id,
+                profile: args.profile.clone(),
+            };
+            let socket_path = session.socket_path(socket_dir, args.selection);
+            tui(args, &socket_path, &session)?
+        }
+        Args::Get(args) => {
+            info!("starting get mode with selection: {}", args.selection);
+            debug!("get args: {args:#?}");
+
+            let session = Session {
+                display_id,
+                profile: args.profile.clone(),
+            };
+            let socket_path = session.socket_path(socket_dir, args.selection);
+            get(args, &socket_path, &session)?
+        }
+        Args::List(args) => {
+            info!("starting list mode with selection: {}", args.selection);
+            debug!("list args: {args:#?}");
+
+            let session = Session {
+                display_id,
+                profile: args.profile.clone(),
+            };
+            let socket_path = session.socket_path(socket_dir, args.selection);
+            list(args, &socket_path, &session)?
+        }
+        Args::Restore(args) => {
+            info!("starting restore mode with selection: {}", args.selection);
+            debug!("restore args: {args:#?}");
+
+            let session = Session {
+                display_id,
+                profile: args.profile.clone(),
+            };
+            let socket_path = session.socket_path(socket_dir, args.selection);
+            restore(args, &socket_path, &session)?
+        }
+        Args::Paste(args) => {
+            info!("starting paste mode with selection: {}", args.selection);
+            debug!("paste args: {args:#?}");
+
+            let session = Session {
+                display_id,
+                profile: args.profile.clone(),
+            };
+            let socket_path = session.socket_path(socket_dir, args.selection);
+            paste(args, &socket_path, &session)?
+        }
+        Args::Add(args) => {
+            info!("starting add mode with selection: {}", args.selection);
+            debug!("add args: {args:#?}");
+
+            let session = Session {
+                display_id,
+                profile: args.profile.clone(),
+            };
+            let socket_path = session.socket_path(socket_dir, args.selection);
+            add(args, &socket_path, &session)?
+        }
+        Args::PreviewTheme(args) => {
+            info!(
+                "starting preview-theme mode with selection: {}",
+                args.selection
+            );
+            debug!("preview-theme args: {args:#?}");
+
+            let session = Session {
+                display_id,
+                profile: args.profile.clone(),
+            };
+            let socket_path = session.socket_path(socket_dir, args.selection);
+            preview_theme(args, &socket_path, &session)?
+        }
+        Args::InitConfig(args) => init_config(args)?,
+        #[cfg(feature = "self-update")]
+        Args::SelfUpdate { check_only } => memoni::self_update::run(check_only)?,
     }
 
     Ok(())
@@ -110,21 +367,60 @@ fn parse_args() -> Result<(Args, LevelFilter)> {
     use lexopt::prelude::*;
 
     let mut parser = lexopt::Parser::from_env();
-    let is_server_mode = parser.try_raw_args().is_some_and(|mut raw_args| {
-        let is_server_mode = raw_args.peek().is_some_and(|a| a.eq(OsStr::new("server")));
-        if is_server_mode {
-            raw_args.next();
-        }
-        is_server_mode
-    });
+    let command = parser
+        .try_raw_args()
+        .and_then(|mut raw_args| {
+            let command = raw_args
+                .peek()
+                .and_then(OsStr::to_str)
+                .and_then(cli::subcommand);
+            if command.is_some() {
+                raw_args.next();
+            }
+            command
+        })
+        .unwrap_or(cli::root());
 
     let mut selection_type = SelectionType::CLIPBOARD;
+    let mut profile = None;
+    let mut profile_overlay = false;
+    let mut measure_show_latency = false;
     let mut log_level = LevelFilter::Warn;
+    let mut positional = None;
+    let mut osc52 = false;
+    let mut archived = false;
+    let mut previous = false;
+    let mut expire_secs = None;
+    let mut mime = None;
+    let mut index = None;
+    let mut owns = false;
+    let mut exec = None;
+    let mut osc52_limit = DEFAULT_OSC52_LIMIT;
+    let mut seconds = DEFAULT_THEME_PREVIEW_SECONDS;
+    let mut theme = None;
+    let mut detect_font = false;
+    let mut print = false;
+    let mut force = false;
+    #[cfg(feature = "self-update")]
+    let mut check_only = false;
     let mut shows_help = false;
     let mut shows_version = false;
     while let Some(arg) = parser.next()? {
-        match arg {
-            Short('s') | Long("selection") => {
+        let flag = match &arg {
+            Short(short) => command.find_short(*short),
+            Long(long) => command.find_long(long),
+            Value(value) if command.positional.is_some() && positional.is_none() => {
+                positional = Some(value.clone().string()?);
+                continue;
+            }
+            Value(_) => None,
+        };
+        let Some(flag) = flag else {
+            return Err(arg.unexpected().into());
+        };
+
+        match flag.long {
+            "selection" => {
                 let selection_str: String = parser.value()?.parse()?;
                 selection_type = match selection_str.as_str() {
                     "PRIMARY" => SelectionType::PRIMARY,
@@ -132,7 +428,27 @@ fn parse_args() -> Result<(Args, LevelFilter)> {
                     _ => bail!("invalid selection type \"{selection_str}\""),
                 };
             }
-            Short('l') | Long("log-level") => {
+            "profile" => {
+                let profile_str: String = parser.value()?.parse()?;
+                // Part of file names, and starting with a letter keeps it apart from display ids
+                let is_valid = profile_str.starts_with(|c: char| c.is_ascii_alphabetic())
+                    && profile_str
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-');
+                if !is_valid {
+                    bail!(
+                        "invalid profile \"{profile_str}\", use letters, digits and '-', starting with a letter"
+                    );
+                }
+                profile = Some(profile_str);
+            }
+            "profile-overlay" => {
+                profile_overlay = true;
+            }
+            "measure-show-latency" => {
+                measure_show_latency = true;
+            }
+            "log-level" => {
                 log_level = parser.value()?.parse().map_err(|err| match err {
                     lexopt::Error::ParsingFailed { value, .. } => {
                         anyhow!("invalid log level \"{value}\"")
@@ -140,46 +456,72 @@ fn parse_args() -> Result<(Args, LevelFilter)> {
                     _ => err.into(),
                 })?;
             }
-            Short('v') | Long("version") if !is_server_mode => {
+            "osc52" => {
+                osc52 = true;
+            }
+            "archived" => {
+                archived = true;
+            }
+            "prev" => {
+                previous = true;
+            }
+            "expire" => {
+                expire_secs = Some(parser.value()?.parse()?);
+            }
+            "mime" => {
+                let mime_str: String = parser.value()?.parse()?;
+                if mime_str.is_empty() || mime_str.contains(char::is_whitespace) {
+                    bail!("invalid mime type \"{mime_str}\"");
+                }
+                mime = Some(mime_str);
+            }
+            "index" => {
+                index = Some(parser.value()?.parse()?);
+            }
+            "own" => {
+                owns = true;
+            }
+            "exec" => {
+                exec = Some(parser.value()?.string()?);
+            }
+            "osc52-limit" => {
+                osc52_limit = parser.value()?.parse()?;
+            }
+            "seconds" => {
+                seconds = parser.value()?.parse()?;
+            }
+            "theme" => {
+                let theme_str: String = parser.value()?.parse()?;
+                if !ThemeConfig::PRESETS.contains(&theme_str.as_str()) {
+                    bail!("invalid theme preset \"{theme_str}\"");
+                }
+                theme = Some(theme_str);
+            }
+            "detect-font" => {
+                detect_font = true;
+            }
+            "print" => {
+                print = true;
+            }
+            "force" => {
+                force = true;
+            }
+            #[cfg(feature = "self-update")]
+            "check" => {
+                check_only = true;
+            }
+            "version" => {
                 shows_version = true;
             }
-            Short('h') | Long("help") => {
+            "help" => {
                 shows_help = true;
             }
-            _ => return Err(arg.unexpected().into()),
+            long => unreachable!("option --{long} is in the command table but not parsed"),
         }
     }
 
     if shows_help {
-        if is_server_mode {
-            println!(
-                        "\
-Start memoni server.
-
-USAGE:
-  memoni server [OPTIONS]
-
-OPTIONS:
-  -s, --selection TYPE    Sets selection type [possible values: CLIPBOARD, PRIMARY] [default: CLIPBOARD]
-  -l, --log-level LEVEL   Sets log level [possible values: off, error, warn, info, debug, trace] [default: warn]
-  -h, --help              Prints help information"
-                    );
-        } else {
-            println!(
-                        "\
-Show memoni window if memoni server is running.
-To run in server mode, use: memoni server [OPTIONS]
-
-USAGE:
-  memoni [OPTIONS]
-
-OPTIONS:
-  -s, --selection TYPE    Sets selection type [possible values: CLIPBOARD, PRIMARY] [default: CLIPBOARD]
-  -l, --log-level LEVEL   Sets log level [possible values: off, error, warn, info, debug, trace] [default: warn]
-  -v, --version           Prints memoni version
-  -h, --help              Prints help information"
-                    );
-        }
+        println!("{}", command.help());
         std::process::exit(0);
     }
 
@@ -188,28 +530,122 @@ OPTIONS:
         std::process::exit(0);
     }
 
+    let positional = match (&command.positional, positional) {
+        (Some(expected), None) if !expected.optional => bail!(
+            "missing {}, see memoni {} --help",
+            expected.name,
+            command.name.unwrap_or_default()
+        ),
+        (_, positional) => positional.unwrap_or_default(),
+    };
+
     Ok((
-        if is_server_mode {
-            Args::Server(ServerArgs {
+        match command.name {
+            Some("server") => Args::Server(ServerArgs {
                 selection: selection_type,
-            })
-        } else {
-            Args::Client(ClientArgs {
+                profile,
+                profile_overlay,
+                measure_show_latency,
+            }),
+            Some("watch") => Args::Watch(ClientArgs {
+                selection: selection_type,
+                profile,
+            }),
+            Some("pipe") => Args::Pipe(PipeArgs {
+                selection: selection_type,
+                profile,
+                command: exec
+                    .filter(|command| !command.trim().is_empty())
+                    .ok_or_else(|| anyhow!("missing --exec CMD, see memoni pipe --help"))?,
+            }),
+            Some("tui") => Args::Tui(ClientArgs {
                 selection: selection_type,
-            })
+                profile,
+            }),
+            Some("get") => Args::Get(GetArgs {
+                selection: selection_type,
+                profile,
+                item: match (index, positional.is_empty()) {
+                    (Some(index), true) => ItemRef::Index(index),
+                    (None, false) => ItemRef::Id(
+                        u64::from_str_radix(&positional, 16)
+                            .map_err(|_| anyhow!("invalid item id \"{positional}\""))?,
+                    ),
+                    (Some(_), false) => bail!("give either an item id or --index, not both"),
+                    (None, true) => bail!("missing ID or --index, see memoni get --help"),
+                },
+                mime,
+                osc52,
+                osc52_limit,
+            }),
+            Some("list") => Args::List(ListArgs {
+                selection: selection_type,
+                profile,
+                archived,
+            }),
+            Some("restore") => Args::Restore(RestoreArgs {
+                selection: selection_type,
+                profile,
+                item_id: u64::from_str_radix(&positional, 16)
+                    .map_err(|_| anyhow!("invalid item id \"{positional}\""))?,
+            }),
+            Some("paste") => Args::Paste(PasteArgs {
+                selection: selection_type,
+                profile,
+                previous,
+                expire_secs,
+            }),
+            Some("add") => Args::Add(AddArgs {
+                selection: selection_type,
+                profile,
+                mime: mime.unwrap_or_else(|| DEFAULT_ADD_MIME.to_string()),
+                owns,
+                expire_secs,
+            }),
+            Some("preview-theme") => Args::PreviewTheme(PreviewThemeArgs {
+                selection: selection_type,
+                profile,
+                path: PathBuf::from(positional),
+                seconds,
+            }),
+            Some("completions") => {
+                print!("{}", Shell::parse(&positional)?.completions());
+                std::process::exit(0);
+            }
+            Some("manpage") => {
+                print!("{}", cli::manpage());
+                std::process::exit(0);
+            }
+            Some("config-schema") => {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&config_schema::json_schema()?)?
+                );
+                std::process::exit(0);
+            }
+            Some("init-config") => Args::InitConfig(InitConfigArgs {
+                theme,
+                detect_font,
+                print,
+                force,
+            }),
+            #[cfg(feature = "self-update")]
+            Some("self-update") => Args::SelfUpdate { check_only },
+            _ => Args::Client(ClientArgs {
+                selection: selection_type,
+                profile,
+            }),
         },
         log_level,
     ))
 }
 
-fn client(args: ClientArgs, socket_path: &Path, display_id: Option<String>) -> Result<()> {
+fn client(args: ClientArgs, socket_path: &Path, session: &Session) -> Result<()> {
     if !fs::exists(socket_path)? {
         eprintln!(
             "Error: memoni server for selection \"{}\"{} is not running",
             args.selection,
-            display_id
-                .map(|id| format!(" on display {:?}", id))
-                .unwrap_or_default()
+            session.describe()
         );
         std::process::exit(1);
     }
@@ -218,57 +654,466 @@ fn client(args: ClientArgs, socket_path: &Path, display_id: Option<String>) -> R
     let mut stream = UnixStream::connect(socket_path)?;
 
     info!("sending 'show_win' to server");
-    stream.write_all(b"show_win")?;
+    stream.write_all(&ClientCommand::ShowWindow.to_bytes())?;
 
     Ok(())
 }
 
-fn server(args: ServerArgs, socket_path: &Path, display_id: Option<String>) -> Result<()> {
+fn watch(args: ClientArgs, socket_path: &Path, session: &Session) -> Result<()> {
+    if !fs::exists(socket_path)? {
+        eprintln!(
+            "Error: memoni server for selection \"{}\"{} is not running",
+            args.selection,
+            session.describe()
+        );
+        std::process::exit(1);
+    }
+
+    debug!("connecting to socket: {socket_path:?}");
+    let mut stream = UnixStream::connect(socket_path)?;
+
+    info!("sending 'watch' to server");
+    stream.write_all(&ClientCommand::Watch.to_bytes())?;
+
+    let mut stdout = io::stdout().lock();
+    for line in io::BufReader::new(stream).lines() {
+        writeln!(stdout, "{}", line?)?;
+        // Status bars read as lines arrive
+        stdout.flush()?;
+    }
+
+    eprintln!("Error: memoni server stopped");
+    std::process::exit(1);
+}
+
+fn pipe(args: PipeArgs, socket_path: &Path, session: &Session) -> Result<()> {
+    if !fs::exists(socket_path)? {
+        eprintln!(
+            "Error: memoni server for selection \"{}\"{} is not running",
+            args.selection,
+            session.describe()
+        );
+        std::process::exit(1);
+    }
+
+    memoni::pipe::run(socket_path, &args.command)
+}
+
+fn tui(args: ClientArgs, socket_path: &Path, session: &Session) -> Result<()> {
+    if !fs::exists(socket_path)? {
+        eprintln!(
+            "Error: memoni server for selection \"{}\"{} is not running",
+            args.selection,
+            session.describe()
+        );
+        std::process::exit(1);
+    }
+
+    memoni::tui::run(socket_path)
+}
+
+fn get(args: GetArgs, socket_path: &Path, session: &Session) -> Result<()> {
+    if !fs::exists(socket_path)? {
+        eprintln!(
+            "Error: memoni server for selection \"{}\"{} is not running",
+            args.selection,
+            session.describe()
+        );
+        std::process::exit(1);
+    }
+
+    debug!("connecting to socket: {socket_path:?}");
+    let mut stream = UnixStream::connect(socket_path)?;
+
+    let command = ClientCommand::Get {
+        item: args.item,
+        mime: args.mime.clone(),
+    };
+    info!("sending {command:?} to server");
+    stream.write_all(&command.to_bytes())?;
+    stream.shutdown(std::net::Shutdown::Write)?;
+    let mut stdout = io::stdout().lock();
+    // Large items go to stdout as they come, unless the sequence needs all of it
+    let found = if args.osc52 {
+        let mut data = vec![];
+        let found = ipc::read_chunked(&mut stream, &mut data)?;
+        if found {
+            stdout.write_all(&cli_output::osc52(
+                &data,
+                Multiplexer::from_env(),
+                args.osc52_limit,
+            )?)?;
+        }
+        found
+    } else {
+        ipc::read_chunked(&mut stream, &mut stdout)?
+    };
+    stdout.flush()?;
+    if !found {
+        let item = match args.item {
+            ItemRef::Id(id) => format!("item {}", ipc::format_item_id(id)),
+            ItemRef::Index(idx) => format!("item at index {idx}"),
+        };
+        let target = args.mime.as_deref().unwrap_or("text");
+        eprintln!("Error: {item} not found or has no {target}");
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn list(args: ListArgs, socket_path: &Path, session: &Session) -> Result<()> {
+    if !fs::exists(socket_path)? {
+        eprintln!(
+            "Error: memoni server for selection \"{}\"{} is not running",
+            args.selection,
+            session.describe()
+        );
+        std::process::exit(1);
+    }
+
+    debug!("connecting to socket: {socket_path:?}");
+    let mut stream = UnixStream::connect(socket_path)?;
+
+    let command = if args.archived {
+        ClientCommand::ListArchived
+    } else {
+        ClientCommand::List
+    };
+    info!("sending {command:?} to server");
+    stream.write_all(&command.to_bytes())?;
+    stream.shutdown(std::net::Shutdown::Write)?;
+    let mut stdout = io::stdout().lock();
+    io::copy(&mut stream, &mut stdout)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+fn restore(args: RestoreArgs, socket_path: &Path, session: &Session) -> Result<()> {
+    if !fs::exists(socket_path)? {
+        eprintln!(
+            "Error: memoni server for selection \"{}\"{} is not running",
+            args.selection,
+            session.describe()
+        );
+        std::process::exit(1);
+    }
+
+    debug!("connecting to socket: {socket_path:?}");
+    let mut stream = UnixStream::connect(socket_path)?;
+
+    info!("sending 'restore' of item {} to server", args.item_id);
+    stream.write_all(&ClientCommand::Restore(args.item_id).to_bytes())?;
+    stream.shutdown(std::net::Shutdown::Write)?;
+    let mut reply = vec![];
+    stream.read_to_end(&mut reply)?;
+    if reply.is_empty() {
+        eprintln!(
+            "Error: item {} not found in the archive",
+            ipc::format_item_id(args.item_id)
+        );
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn paste(args: PasteArgs, socket_path: &Path, session: &Session) -> Result<()> {
+    if !fs::exists(socket_path)? {
+        eprintln!(
+            "Error: memoni server for selection \"{}\"{} is not running",
+            args.selection,
+            session.describe()
+        );
+        std::process::exit(1);
+    }
+
+    debug!("connecting to socket: {socket_path:?}");
+    let mut stream = UnixStream::connect(socket_path)?;
+
+    let idx = usize::from(args.previous);
+    info!("sending 'paste_recent' of item {idx} to server");
+    stream.write_all(
+        &ClientCommand::PasteRecent {
+            idx,
+            expire_secs: args.expire_secs,
+        }
+        .to_bytes(),
+    )?;
+
+    Ok(())
+}
+
+fn add(args: AddArgs, socket_path: &Path, session: &Session) -> Result<()> {
+    if !fs::exists(socket_path)? {
+        eprintln!(
+            "Error: memoni server for selection \"{}\"{} is not running",
+            args.selection,
+            session.describe()
+        );
+        std::process::exit(1);
+    }
+
+    let mut data = vec![];
+    io::stdin().lock().read_to_end(&mut data)?;
+    if data.is_empty() {
+        eprintln!("Error: nothing to add, stdin is empty");
+        std::process::exit(1);
+    }
+
+    debug!("connecting to socket: {socket_path:?}");
+    let mut stream = UnixStream::connect(socket_path)?;
+
+    let command = ClientCommand::Add {
+        mime: args.mime,
+        owns: args.owns,
+        expire_secs: args.expire_secs,
+    };
+    info!("sending {command:?} with {} bytes to server", data.len());
+    stream.write_all(&command.to_bytes())?;
+    stream.write_all(&data)?;
+    stream.shutdown(std::net::Shutdown::Write)?;
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply)?;
+    if reply.is_empty() {
+        eprintln!("Error: the server didn't add the item, see its log");
+        std::process::exit(1);
+    }
+    println!("{reply}");
+    Ok(())
+}
+
+fn init_config(args: InitConfigArgs) -> Result<()> {
+    let mut filled = toml::Table::new();
+    if let Some(theme) = &args.theme {
+        let preset = ThemeConfig::preset(theme).ok_or_else(|| anyhow!("unknown theme {theme}"))?;
+        let mut theme = toml::Table::try_from(preset)?;
+        // Keeps the ribbons telling CLIPBOARD and PRIMARY apart
+        theme.remove("ribbon");
+        filled.insert("theme".to_string(), theme.into());
+    }
+    if args.detect_font {
+        let family = Fontconfig::new()
+            .and_then(|fc| fc.find("sans-serif".to_string(), None))
+            .map(|font| font.name)
+            .ok_or_else(|| anyhow!("fontconfig found no sans-serif font"))?;
+        info!("detected font family: {family}");
+        let mut font = toml::Table::new();
+        font.insert("family".to_string(), vec![family].into());
+        filled.insert("font".to_string(), font.into());
+    }
+    let scaffold = config_schema::scaffold(&filled)?;
+
+    if args.print {
+        print!("{scaffold}");
+        return Ok(());
+    }
+    let path = Config::path().ok_or_else(|| anyhow!("no config directory"))?;
+    if fs::exists(&path)? && !args.force {
+        eprintln!(
+            "Error: {path:?} exists, use --force to overwrite it or --print to see the new one"
+        );
+        std::process::exit(1);
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, scaffold)?;
+    println!("Wrote {}", path.display());
+    Ok(())
+}
+
+fn preview_theme(args: PreviewThemeArgs, socket_path: &Path, session: &Session) -> Result<()> {
+    if !fs::exists(socket_path)? {
+        eprintln!(
+            "Error: memoni server for selection \"{}\"{} is not running",
+            args.selection,
+            session.describe()
+        );
+        std::process::exit(1);
+    }
+
+    // The server loads the file again, but errors are only shown here
+    let path = args
+        .path
+        .canonicalize()
+        .with_context(|| format!("Failed to open {:?}", args.path))?;
+    Config::default().with_theme_file(&path)?;
+
+    debug!("connecting to socket: {socket_path:?}");
+    let mut stream = UnixStream::connect(socket_path)?;
+
+    info!("sending 'preview_theme' of {path:?} to server");
+    let command = ClientCommand::PreviewTheme {
+        path,
+        seconds: args.seconds,
+    };
+    let bytes = command.to_bytes();
+    if bytes.len() > ipc::MAX_COMMAND_LEN {
+        bail!("path of the theme file is too long");
+    }
+    stream.write_all(&bytes)?;
+
+    Ok(())
+}
+
+// Killing the X11 client of the server, as xkill or a window manager giving up on a window does,
+// takes its connection and everything created through it, so it starts over on a new one
+const MAX_RESTARTS: usize = 10;
+const RESTART_PERIOD: Duration = Duration::from_secs(60);
+
+fn server(args: ServerArgs, socket_path: &Path, session: &Session) -> Result<()> {
+    let mut restarted_at: Vec<Instant> = vec![];
+    loop {
+        let Err(err) = run_server(&args, socket_path, session) else {
+            return Ok(());
+        };
+        if !is_x11_connection_lost(&err) {
+            return Err(err);
+        }
+
+        let now = Instant::now();
+        restarted_at.retain(|&at| now.duration_since(at) < RESTART_PERIOD);
+        if restarted_at.len() >= MAX_RESTARTS {
+            return Err(err.context(format!(
+                "lost the X11 connection {MAX_RESTARTS} times within {}s, giving up",
+                RESTART_PERIOD.as_secs()
+            )));
+        }
+        restarted_at.push(now);
+        warn!(
+            "lost the X11 connection, starting over, {} time(s) in the last {}s: {err:?}",
+            restarted_at.len(),
+            RESTART_PERIOD.as_secs()
+        );
+        metrics::record_window_recreation();
+    }
+}
+
+fn is_x11_connection_lost(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        let connection_error = cause
+            .downcast_ref::<ConnectionError>()
+            .or_else(|| match cause.downcast_ref::<ReplyError>() {
+                Some(ReplyError::ConnectionError(e)) => Some(e),
+                _ => match cause.downcast_ref::<ReplyOrIdError>() {
+                    Some(ReplyOrIdError::ConnectionError(e)) => Some(e),
+                    _ => None,
+                },
+            });
+        matches!(
+            connection_error,
+            Some(ConnectionError::IoError(_) | ConnectionError::UnknownError)
+        )
+    })
+}
+
+fn run_server(args: &ServerArgs, socket_path: &Path, session: &Session) -> Result<()> {
     let config = Config::load(args.selection)?;
 
     let window = X11Window::new(&config, args.selection)?;
-    let mut gl_context = OpenGLContext::new(&window, &config)?;
+    let mut renderer = renderer::from_config(&window, &config)?;
     let key_converter = X11KeyConverter::new(&window.conn)?;
     let mut input = Input::new(&window, &key_converter)?;
-    let mut keymap_action = KeymapAction::new()?;
+    let mut keymap_action = KeymapAction::new(&config)?;
+    let mut yank_pop = YankPop::new(&config.yank_pop);
+    let mut hotkeys = Hotkeys::grab(&window.conn, window.screen.root, &key_converter, &config)?;
 
-    let mut persistence = Persistence::new(args.selection, &display_id)?;
+    let mut persistence = persistence::open(config.storage, args.selection, &session.id())?;
+    if config.window_position_mode == WindowPositionMode::Last {
+        window.set_last_win_pos(persistence.load_window_pos());
+    }
+    let mut archive = Archive::open(persistence.archive_file_path(), &config.archive)?;
     let mut selection = Selection::new(
         persistence.load_selection_data()?,
         &window,
         &key_converter,
         args.selection,
         &config,
-        // XFixes sends a SelectionNotify for each change while the user drags the mouse to adjust selection.
-        // Debounce to merge consecutive items with similar text.
-        args.selection == SelectionType::PRIMARY,
     )?;
     let mut ui = Ui::new(&config)?;
-    for (_, item) in &selection.items {
-        ui.build_button_widget(item)?;
+    if args.profile_overlay {
+        ui.enable_profile_overlay();
+    }
+    if !persistence.has_selection_data() {
+        ui.enable_onboarding(match args.selection {
+            SelectionType::CLIPBOARD => "memoni".to_string(),
+            SelectionType::PRIMARY => "memoni --selection PRIMARY".to_string(),
+        });
     }
 
-    let (mut poll, socket_listener, mut signals, keyboard_grab_timer, pointer_grab_timer) =
-        match create_poll(&window.conn, socket_path) {
-            Ok(res) => res,
-            Err(err) => {
-                if let Some(io_err) = err.downcast_ref::<io::Error>()
-                    && io_err.kind() == io::ErrorKind::AddrInUse
-                {
-                    eprintln!(
-                        "Error: another server for selection \"{}\"{} is already running",
-                        args.selection,
-                        display_id
-                            .map(|id| format!(" on display {:?}", id))
-                            .unwrap_or_default()
-                    );
-                    std::process::exit(1);
-                } else {
-                    return Err(err);
-                }
+    let (
+        mut poll,
+        socket_listener,
+        mut signals,
+        keyboard_grab_timer,
+        pointer_grab_timer,
+        capture_debounce_timer,
+        theme_preview_timer,
+    ) = match create_poll(&window.conn, socket_path) {
+        Ok(res) => res,
+        Err(err) => {
+            if let Some(io_err) = err.downcast_ref::<io::Error>()
+                && io_err.kind() == io::ErrorKind::AddrInUse
+            {
+                eprintln!(
+                    "Error: another server for selection \"{}\"{} is already running",
+                    args.selection,
+                    session.describe()
+                );
+                std::process::exit(1);
+            } else {
+                return Err(err);
             }
-        };
+        }
+    };
+
+    debug!("registering item expiry timer source");
+    let expiry_timer =
+        TimerfdSource::new().map_err(|e| anyhow!("failed to create item expiry timerfd: {e}"))?;
+    poll.registry().register(
+        &mut SourceFd(&expiry_timer.as_fd().as_raw_fd()),
+        EXPIRY_TOKEN,
+        mio::Interest::READABLE,
+    )?;
+
+    debug!("registering transfer window maintenance timer source");
+    let transfer_windows_timer = TimerfdSource::new()
+        .map_err(|e| anyhow!("failed to create transfer window maintenance timerfd: {e}"))?;
+    poll.registry().register(
+        &mut SourceFd(&transfer_windows_timer.as_fd().as_raw_fd()),
+        TRANSFER_WINDOWS_TOKEN,
+        mio::Interest::READABLE,
+    )?;
+    transfer_windows_timer.set_timer(MAINTENANCE_INTERVAL.as_millis() as u64)?;
+
     let mut poll_events = mio::Events::with_capacity(8);
+    let item_editor = ItemEditor::new(poll.registry(), ITEM_EDIT_TOKEN)?;
+    ui.set_file_stats_scanner(FileStatsScanner::new(poll.registry(), FILE_STATS_TOKEN)?);
+    if config.fetch_favicons {
+        ui.set_favicon_fetcher(FaviconFetcher::new(poll.registry(), FAVICON_TOKEN)?);
+    }
+    #[cfg(feature = "accessibility")]
+    let mut screen_reader =
+        memoni::accessibility::ScreenReader::new(poll.registry(), SCREEN_READER_TOKEN)?;
+    for (_, item) in &selection.items {
+        ui.build_button_widget(item)?;
+    }
+    schedule_expiry(&selection, &expiry_timer)?;
+    let mut virtual_items = VirtualItems::new(&config);
+    let mut search = Search::new();
+    let mut watchers = Watchers::new();
+
+    #[cfg(feature = "metrics")]
+    if config.metrics_port != 0 {
+        metrics::serve(config.metrics_port, &args.selection.to_string())?;
+    }
+    #[cfg(not(feature = "metrics"))]
+    if config.metrics_port != 0 {
+        warn!("metrics_port is set, but memoni was built without the metrics feature");
+    }
+
+    // Pasting and pressing Enter usually submits something, so there's nothing left to paste into
+    let keeps_open =
+        |modifier: PasteModifier| (config.keep_open || modifier.keep_open) && !modifier.and_enter;
 
     let main_loop_result = (|| -> Result<()> {
         let mut window_shown = false;
@@ -280,16 +1125,27 @@ fn server(args: ServerArgs, socket_path: &Path, display_id: Option<String>) -> R
             .unwrap_or(0);
         let mut mode = AppMode::Normal;
         let mut first_loop = true;
+        // Set when the state the window opens in may have changed while it's hidden
+        let mut warm_up_pending = true;
+        let mut pending_item_actions: Option<(u64, Vec<KeyAction>)> = None;
+        let mut pending_destructive_action = None;
+        let mut disk_quota_warned = false;
+        // Set when the window is mapped with --measure-show-latency, until its first Expose
+        let mut mapped_at: Option<Instant> = None;
 
         info!("starting main event loop");
         'main_loop: loop {
             let mut will_show_window = false;
             let mut will_hide_window = false;
             let mut paste_item_id = None;
+            // Pasted where the pointer is, as the window wasn't opened for it
+            let mut pastes_at_pointer = false;
             let mut paste_modifier = PasteModifier::default();
+            let mut paste_payload = None;
+            let mut frame_times = None;
 
-            // non-blocking when window is visible, blocking otherwise
-            let poll_timeout = if window_shown {
+            // non-blocking when window is visible or a warm-up frame is due, blocking otherwise
+            let poll_timeout = if window_shown || warm_up_pending {
                 Some(Duration::ZERO)
             } else {
                 None
@@ -304,6 +1160,8 @@ fn server(args: ServerArgs, socket_path: &Path, display_id: Option<String>) -> R
                     Err(e)
                 }
             })?;
+            let woke_at = Instant::now();
+            let mut items_updated = false;
             for event in &poll_events {
                 match event.token() {
                     X11_TOKEN => {} // handled below
@@ -320,23 +1178,221 @@ fn server(args: ServerArgs, socket_path: &Path, display_id: Option<String>) -> R
                         info!("accepting client connection");
                         let (mut stream, _) = socket_listener.accept()?;
 
-                        let mut buf = [0u8; 1024];
+                        let mut buf = [0u8; ipc::MAX_COMMAND_LEN];
                         match stream.read(&mut buf) {
                             Ok(0) => {
                                 warn!("client closed without sending command");
                             }
-                            Ok(n) => {
-                                let command = String::from_utf8_lossy(&buf[..n]);
-                                match command.as_ref() {
-                                    "show_win" => {
-                                        info!("received client command: {command}, showing window");
-                                        will_show_window = true;
+                            Ok(n) => match ClientCommand::parse(&buf[..n]) {
+                                Ok(ClientCommand::ShowWindow) => {
+                                    info!("received client command: show_win, showing window");
+                                    will_show_window = true;
+                                }
+                                Ok(ClientCommand::Watch) => {
+                                    info!("received client command: watch");
+                                    let latest_item = selection
+                                        .items
+                                        .get_by_index(selection.metadata.pinned_count)
+                                        .map(|(_, item)| item);
+                                    if let Err(e) = watchers.add(stream, latest_item) {
+                                        warn!("failed to add watcher: {e:?}");
+                                    }
+                                }
+                                Ok(ClientCommand::List) => {
+                                    info!("received client command: list");
+                                    // Clients expect the whole history, not only what has been scrolled to
+                                    while load_next_page(&mut selection, &*persistence, &mut ui)? {}
+                                    let list = watch::item_list(
+                                        &selection.items,
+                                        selection.metadata.pinned_count,
+                                    )?;
+                                    if let Err(e) = stream.write_all(list.as_bytes()) {
+                                        warn!("failed to send item list: {e:?}");
                                     }
-                                    _ => {
-                                        warn!("unknown client command: {command}");
+                                }
+                                Ok(ClientCommand::ListArchived) => {
+                                    info!("received client command: list archived");
+                                    match archive
+                                        .items()
+                                        .and_then(|items| watch::archived_item_list(&items))
+                                    {
+                                        Ok(list) => {
+                                            if let Err(e) = stream.write_all(list.as_bytes()) {
+                                                warn!("failed to send archived item list: {e:?}");
+                                            }
+                                        }
+                                        Err(e) => warn!("failed to read archive: {e:?}"),
                                     }
                                 }
-                            }
+                                Ok(ClientCommand::Text(item_id)) => {
+                                    info!("received client command: text of item {item_id}");
+                                    let text = selection
+                                        .items
+                                        .get(&item_id)
+                                        .and_then(|item| item.text())
+                                        .unwrap_or_default();
+                                    if let Err(e) = stream.write_all(text.as_bytes()) {
+                                        warn!("failed to send item text: {e:?}");
+                                    }
+                                }
+                                Ok(ClientCommand::Get { item, mime }) => {
+                                    info!("received client command: get {item:?}");
+                                    // The item may be in a page not loaded yet
+                                    let item_id = loop {
+                                        let item_id = match item {
+                                            ItemRef::Id(id) => {
+                                                selection.items.contains_key(&id).then_some(id)
+                                            }
+                                            ItemRef::Index(idx) => {
+                                                selection.items.get_by_index(idx).map(|(&id, _)| id)
+                                            }
+                                        };
+                                        if item_id.is_some()
+                                            || !load_next_page(
+                                                &mut selection,
+                                                &*persistence,
+                                                &mut ui,
+                                            )?
+                                        {
+                                            break item_id;
+                                        }
+                                    };
+                                    let data = item_id
+                                        .and_then(|id| selection.items.get(&id))
+                                        .and_then(|item| item.target(mime.as_deref()));
+                                    match data {
+                                        Some(data) => {
+                                            if let Err(e) = ipc::write_chunked(&stream, data) {
+                                                warn!("failed to send item data: {e:?}");
+                                            }
+                                        }
+                                        None => debug!("no {mime:?} target of {item:?} to send"),
+                                    }
+                                }
+                                Ok(ClientCommand::Delete(item_id)) => {
+                                    info!("received client command: delete item {item_id}");
+                                    if let Some(item) = selection.remove_item(item_id) {
+                                        ui.remove_button_widgets(iter::once(item));
+                                        persistence.save_selection_data(
+                                            &selection.items,
+                                            &selection.metadata,
+                                            &selection.cold_pages,
+                                        )?;
+                                        items_updated = true;
+                                    }
+                                }
+                                Ok(ClientCommand::Restore(item_id)) => {
+                                    info!("received client command: restore item {item_id}");
+                                    match archive.take(item_id) {
+                                        Ok(Some(item)) => {
+                                            let removed_items = selection.restore_item(item);
+                                            ui.remove_button_widgets(removed_items);
+                                            if let Some(item) = selection.items.get(&item_id) {
+                                                ui.build_button_widget(item)?;
+                                            }
+                                            persistence.save_selection_data(
+                                                &selection.items,
+                                                &selection.metadata,
+                                                &selection.cold_pages,
+                                            )?;
+                                            items_updated = true;
+                                            let reply = ipc::format_item_id(item_id);
+                                            if let Err(e) = stream.write_all(reply.as_bytes()) {
+                                                warn!("failed to send restored item id: {e:?}");
+                                            }
+                                        }
+                                        Ok(None) => {}
+                                        Err(e) => warn!("failed to restore item {item_id}: {e:?}"),
+                                    }
+                                }
+                                Ok(ClientCommand::PasteRecent { idx, expire_secs }) => {
+                                    info!("received client command: paste recent item {idx}");
+                                    let start = selection.metadata.pinned_count + idx;
+                                    paste_item_id = yank(&selection, &mut yank_pop, start)?;
+                                    pastes_at_pointer = true;
+                                    if let Some(item_id) = paste_item_id
+                                        && let Some(secs) = expire_secs
+                                    {
+                                        info!("selection item {item_id} expires in {secs}s");
+                                        selection.set_expiry(item_id, Some(secs));
+                                        persistence.save_selection_data(
+                                            &selection.items,
+                                            &selection.metadata,
+                                            &selection.cold_pages,
+                                        )?;
+                                        schedule_expiry(&selection, &expiry_timer)?;
+                                    }
+                                }
+                                Ok(ClientCommand::Add {
+                                    mime,
+                                    owns,
+                                    expire_secs,
+                                }) => {
+                                    info!("received client command: add {mime} item");
+                                    let data = ipc::read_payload(&mut stream, &buf[..n])
+                                        .and_then(|data| ipc::item_data(&mime, data));
+                                    match data {
+                                        Ok(data) => {
+                                            let (item_id, removed_items) =
+                                                selection.add_derived_item(data)?;
+                                            ui.remove_button_widgets(removed_items);
+                                            if let Some(item) = selection.items.get(&item_id) {
+                                                ui.build_button_widget(item)?;
+                                            }
+                                            if let Some(secs) = expire_secs {
+                                                selection.set_expiry(item_id, Some(secs));
+                                                schedule_expiry(&selection, &expiry_timer)?;
+                                            }
+                                            if owns {
+                                                selection.own(item_id)?;
+                                            }
+                                            persistence.save_selection_data(
+                                                &selection.items,
+                                                &selection.metadata,
+                                                &selection.cold_pages,
+                                            )?;
+                                            items_updated = true;
+                                            let reply = ipc::format_item_id(item_id);
+                                            if let Err(e) = stream.write_all(reply.as_bytes()) {
+                                                warn!("failed to send added item id: {e:?}");
+                                            }
+                                        }
+                                        Err(e) => warn!("failed to add item from client: {e:?}"),
+                                    }
+                                }
+                                Ok(ClientCommand::PreviewTheme { path, seconds }) => {
+                                    info!(
+                                        "received client command: preview theme of {path:?} for {seconds}s"
+                                    );
+                                    match config.with_theme_file(&path) {
+                                        Ok(preview_config) => {
+                                            renderer.set_background(
+                                                preview_config.theme.background.into(),
+                                            );
+                                            ui.preview_theme(Some(preview_config));
+                                            for (_, item) in &selection.items {
+                                                ui.build_button_widget(item)?;
+                                            }
+                                            for item in virtual_items.iter() {
+                                                ui.build_button_widget(item)?;
+                                            }
+                                            theme_preview_timer
+                                                .set_timer(seconds.max(1).saturating_mul(1000))?;
+                                            will_show_window = true;
+                                        }
+                                        Err(e) => {
+                                            warn!("failed to load theme of {path:?}: {e:?}");
+                                            ui.toast(
+                                                ToastKind::Error,
+                                                format!("Failed to preview theme: {e}"),
+                                            );
+                                        }
+                                    }
+                                }
+                                Err(e) => {
+                                    warn!("{e}");
+                                }
+                            },
                             Err(e) => {
                                 warn!("failed to read client command: {e:?}");
                             }
@@ -350,11 +1406,109 @@ fn server(args: ServerArgs, socket_path: &Path, display_id: Option<String>) -> R
                         pointer_grab_timer.clear_event()?;
                         window.grab_pointer(&pointer_grab_timer)?;
                     }
+                    CAPTURE_DEBOUNCE_TOKEN => {
+                        capture_debounce_timer.clear_event()?;
+                        selection.request_pending_capture()?;
+                    }
+                    ITEM_EDIT_TOKEN => {
+                        for edit in item_editor.take_edits() {
+                            info!("applying edit of item {}", edit.item_id);
+                            let replaced = selection.replace_item_data(
+                                edit.item_id,
+                                BTreeMap::from([(edit.mime, edit.data)]),
+                            );
+                            // The item may have been removed or paged out while it was edited
+                            let (new_item, removed_items) = match replaced {
+                                Ok(replaced) => replaced,
+                                Err(e) => {
+                                    warn!("failed to apply edit of item {}: {e:?}", edit.item_id);
+                                    ui.toast(
+                                        ToastKind::Error,
+                                        format!("Failed to apply edit: {e}"),
+                                    );
+                                    continue;
+                                }
+                            };
+                            ui.remove_button_widgets(removed_items);
+                            if let Some(new_item) = new_item {
+                                ui.build_button_widget(new_item)?;
+                                if active_id == edit.item_id {
+                                    active_id = new_item.id;
+                                }
+                            }
+
+                            if let Err(e) = persistence.save_selection_data(
+                                &selection.items,
+                                &selection.metadata,
+                                &selection.cold_pages,
+                            ) {
+                                warn!("failed to save edited item {}: {e:?}", edit.item_id);
+                                ui.toast(ToastKind::Error, format!("Failed to save edit: {e}"));
+                            }
+                            items_updated = true;
+                        }
+                    }
+                    THEME_PREVIEW_TOKEN => {
+                        theme_preview_timer.clear_event()?;
+                        info!("theme preview ended, going back to the configured theme");
+                        renderer.set_background(config.theme.background.into());
+                        ui.preview_theme(None);
+                        for (_, item) in &selection.items {
+                            ui.build_button_widget(item)?;
+                        }
+                        for item in virtual_items.iter() {
+                            ui.build_button_widget(item)?;
+                        }
+                        items_updated = true;
+                    }
+                    EXPIRY_TOKEN => {
+                        expiry_timer.clear_event()?;
+                        let removed_items = selection.remove_expired();
+                        info!("{} expired items removed", removed_items.len());
+                        ui.remove_button_widgets(removed_items);
+                        persistence.save_selection_data(
+                            &selection.items,
+                            &selection.metadata,
+                            &selection.cold_pages,
+                        )?;
+                        schedule_expiry(&selection, &expiry_timer)?;
+                        items_updated = true;
+                    }
+                    TRANSFER_WINDOWS_TOKEN => {
+                        transfer_windows_timer.clear_event()?;
+                        selection.maintain_transfer_windows()?;
+                        transfer_windows_timer.set_timer(MAINTENANCE_INTERVAL.as_millis() as u64)?;
+                    }
+                    FILE_STATS_TOKEN => {
+                        for item_id in ui.take_file_stats() {
+                            if let Some(item) = selection.items.get(&item_id) {
+                                debug!("file stats of item {item_id} ready");
+                                ui.build_button_widget(item)?;
+                                items_updated = true;
+                            }
+                        }
+                    }
+                    FAVICON_TOKEN => {
+                        for item_id in ui.take_favicons() {
+                            if let Some(item) = selection.items.get(&item_id) {
+                                debug!("favicon of item {item_id} ready");
+                                ui.build_button_widget(item)?;
+                                items_updated = true;
+                            }
+                        }
+                    }
+                    #[cfg(feature = "accessibility")]
+                    SCREEN_READER_TOKEN => {
+                        // Only the shown window has anything to act on
+                        let events = screen_reader.take_events();
+                        if window_shown {
+                            input.egui_input.events.extend(events);
+                        }
+                    }
                     _ => unreachable!(),
                 }
             }
 
-            let mut items_updated = false;
             while let Some(event) = window.conn.poll_for_event()? {
                 if let Event::Error(err) = event {
                     warn!("received X11 error: {err:?}");
@@ -366,21 +1520,71 @@ fn server(args: ServerArgs, socket_path: &Path, display_id: Option<String>) -> R
                 {
                     warn!("main window {} got destroyed", ev.window);
                     window.recreate_main_window()?;
-                    gl_context.recreate_painter()?;
+                    metrics::record_window_recreation();
+                    renderer.recreate()?;
 
                     input = Input::new(&window, &key_converter)?;
                     ui.reset_context();
                     for (_, item) in &selection.items {
                         ui.build_button_widget(item)?;
                     }
+                    for item in virtual_items.iter() {
+                        ui.build_button_widget(item)?;
+                    }
 
                     continue;
                 }
 
+                if let Event::Expose(_) = event {
+                    renderer.invalidate();
+                    if let Some(mapped_at) = mapped_at.take() {
+                        eprintln!(
+                            "show latency: first expose {:?} after mapping",
+                            mapped_at.elapsed()
+                        );
+                    }
+                }
+
                 if let Event::MappingNotify(ev) = event
                     && (ev.request == Mapping::KEYBOARD || ev.request == Mapping::MODIFIER)
                 {
                     key_converter.update_mapping()?;
+                    hotkeys.ungrab(&window.conn)?;
+                    hotkeys =
+                        Hotkeys::grab(&window.conn, window.screen.root, &key_converter, &config)?;
+                    continue;
+                }
+
+                if let Event::KeyPress(ev) = event
+                    && let Some(action) = hotkeys.action(&ev)
+                {
+                    let start = match action {
+                        HotkeyAction::PasteItem(idx) => idx,
+                        HotkeyAction::PastePrevious => selection.metadata.pinned_count + 1,
+                    };
+                    info!("hotkey {action:?} pressed");
+                    paste_item_id = yank(&selection, &mut yank_pop, start)?;
+                    pastes_at_pointer = true;
+                    continue;
+                }
+
+                if let Event::FocusOut(ev) = event
+                    && ev.event == window.win_id.get()
+                    && window.closes_on_focus_out()
+                    && window_shown
+                    && ev.mode != NotifyMode::GRAB
+                    && ev.mode != NotifyMode::UNGRAB
+                    && ev.detail != NotifyDetail::INFERIOR
+                {
+                    info!("window lost focus, hiding window");
+                    will_hide_window = true;
+                }
+
+                if let Event::ClientMessage(ev) = event
+                    && window.is_delete_request(&ev)
+                {
+                    info!("window manager requested closing, hiding window");
+                    will_hide_window = true;
                     continue;
                 }
 
@@ -400,16 +1604,32 @@ fn server(args: ServerArgs, socket_path: &Path, display_id: Option<String>) -> R
                     }
                 }
 
-                input.handle_event(&event);
+                input.handle_event(&event)?;
                 if let Some((new_selection_item, removed_selection_items)) =
-                    selection.handle_event(&event)?
+                    selection.handle_event(&event, &capture_debounce_timer)?
                 {
                     ui.remove_button_widgets(removed_selection_items);
                     if let Some(new_item) = new_selection_item {
                         ui.build_button_widget(new_item)?;
+                        if let Some(owner_name) = new_item.data.get(CAPTURE_FAILED_MIME) {
+                            metrics::record_failed_transfers(1);
+                            ui.toast(
+                                ToastKind::Error,
+                                format!(
+                                    "Failed to capture selection from {}",
+                                    String::from_utf8_lossy(owner_name)
+                                ),
+                            );
+                        } else {
+                            metrics::record_capture();
+                        }
                     }
 
-                    persistence.save_selection_data(&selection.items, &selection.metadata)?;
+                    persistence.save_selection_data(
+                        &selection.items,
+                        &selection.metadata,
+                        &selection.cold_pages,
+                    )?;
                     items_updated = true;
                 }
             }
@@ -424,39 +1644,191 @@ fn server(args: ServerArgs, socket_path: &Path, display_id: Option<String>) -> R
                 will_hide_window = false;
             }
 
+            // While hidden, a frame is built in the state the window opens in whenever that may
+            // have changed, so the font atlas and item textures are uploaded and the text laid out
+            // is still cached by egui when the first visible frame repeats it
+            let warms_up = !window_shown
+                && !will_show_window
+                && (warm_up_pending || items_updated || first_loop);
+            if warms_up {
+                debug!("building a warm-up frame for the next show");
+                warm_up_pending = false;
+            }
+
             if will_show_window {
-                mode = AppMode::Normal;
+                // The frame is built and swapped before the window gets mapped below, and the
+                // buffer may hold a stale one from when the window was last shown
+                renderer.invalidate();
                 window.update_window_pos()?;
                 input.update_pointer_pos()?;
-                ui.reset();
-                active_id = selection
-                    .items
-                    .get_by_index(selection.metadata.pinned_count)
-                    .map(|(id, _)| *id)
-                    .unwrap_or(0);
+            }
+            if will_show_window || warms_up {
+                mode = AppMode::Normal;
+                search.clear();
+                if config.restore_scroll_position
+                    && let Some(last_active_id) = selection.metadata.last_active_id
+                    && selection.items.contains_key(&last_active_id)
+                {
+                    debug!("restoring scroll position at item {last_active_id}");
+                    ui.reset_to(selection.metadata.last_scroll_offset);
+                    active_id = last_active_id;
+                } else {
+                    ui.reset();
+                    active_id = selection
+                        .items
+                        .get_by_index(selection.metadata.pinned_count)
+                        .map(|(id, _)| *id)
+                        .unwrap_or(0);
+                }
             }
 
-            if first_loop || items_updated || window_shown || will_show_window {
-                let (key_actions, pointer_actions) =
+            if window_shown || will_show_window || warms_up {
+                for event in &input.egui_input.events {
+                    if let egui::Event::Text(text) = event {
+                        match mode {
+                            AppMode::Search => search.push_str(text),
+                            AppMode::Palette => ui.command_palette().push_str(text),
+                            _ => {}
+                        }
+                    }
+                }
+                let (mut key_actions, pointer_actions) =
                     keymap_action.process_input(&mut input.egui_input, mode);
+                // Actions picked from the item context menu or the command palette go through the
+                // same handling as their keymaps, applied to the item they were picked for
+                if let Some((item_id, actions)) = pending_item_actions.take() {
+                    active_id = item_id;
+                    key_actions.splice(0..0, actions.into_iter().map(|action| (action, None)));
+                }
                 let mut scroll_actions = vec![];
-                for action in key_actions {
+                for (action, count) in key_actions {
+                    // With count_prefix, the number keys are counts, so a count pastes the item of
+                    // that number in their place
+                    let action = match (action, count) {
+                        (KeyAction::Paste(_), Some(count)) => KeyAction::QuickPaste(count - 1),
+                        _ => action,
+                    };
                     match action {
                         KeyAction::Paste(modifier) => {
-                            info!("paste item {active_id} selected by key action, hiding window");
-                            will_hide_window = true;
+                            if keeps_open(modifier) {
+                                info!("paste item {active_id} selected by key action");
+                            } else {
+                                info!(
+                                    "paste item {active_id} selected by key action, hiding window"
+                                );
+                                will_hide_window = true;
+                            }
                             paste_item_id = Some(active_id);
                             paste_modifier = modifier;
+                            paste_payload = None;
+                        }
+                        KeyAction::Scroll(scroll_action) => match (scroll_action, count) {
+                            // Like G in Vim, goes to the item of that number
+                            (ScrollAction::ToTop | ScrollAction::ToBottom, Some(count)) => {
+                                let shown = virtual_items
+                                    .layered(
+                                        &selection.items,
+                                        &selection.metadata,
+                                        search.matches(),
+                                    )
+                                    .0;
+                                let index = (count - 1).min(shown.len().saturating_sub(1));
+                                if let Some((&id, _)) = shown.get_by_index(index) {
+                                    active_id = id;
+                                }
+                            }
+                            _ => scroll_actions
+                                .extend(iter::repeat_n(scroll_action, count.unwrap_or(1))),
+                        },
+                        KeyAction::Remove
+                        | KeyAction::Pin
+                        | KeyAction::ToggleExpiry
+                        | KeyAction::SetMark(_)
+                            if virtual_items.contains(active_id) =>
+                        {
+                            ui.toast(ToastKind::Info, "Paste the item first to keep it");
+                        }
+                        // The active item and the ones after it, leaving pinned ones as removing
+                        // them needs a confirmation each
+                        KeyAction::Remove if count.is_some_and(|count| count > 1) => {
+                            let shown = virtual_items
+                                .layered(&selection.items, &selection.metadata, search.matches())
+                                .0;
+                            let item_ids: Vec<u64> = shown
+                                .iter()
+                                .skip_while(|&(&id, _)| id != active_id)
+                                .take(count.unwrap_or(1))
+                                .map(|(&id, _)| id)
+                                .filter(|&id| !virtual_items.contains(id))
+                                .collect();
+                            let mut pinned_left = false;
+                            for item_id in item_ids {
+                                if selection.is_pinned(item_id) {
+                                    pinned_left = true;
+                                } else if let Some(item) = selection.remove_item(item_id) {
+                                    ui.remove_button_widgets(iter::once(item));
+                                    info!("selection item {item_id} removed");
+                                }
+                            }
+                            if pinned_left {
+                                ui.toast(ToastKind::Info, "Pinned items are removed one at a time");
+                            }
+                            persistence.save_selection_data(
+                                &selection.items,
+                                &selection.metadata,
+                                &selection.cold_pages,
+                            )?;
+                        }
+                        KeyAction::Remove if selection.is_pinned(active_id) => {
+                            info!("removing pinned item {active_id} needs confirmation");
+                            pending_destructive_action =
+                                Some(DestructiveAction::RemovePinned(active_id));
+                            mode = AppMode::Confirm;
                         }
-                        KeyAction::Scroll(scroll_action) => scroll_actions.push(scroll_action),
                         KeyAction::Remove => {
-                            let removed_item = selection.items.remove(&active_id);
+                            let removed_item = selection.remove_item(active_id);
                             if let Some(item) = removed_item {
-                                ui.remove_button_widgets(std::iter::once(item));
+                                ui.remove_button_widgets(iter::once(item));
                             }
                             info!("selection item {active_id} removed");
-                            persistence
-                                .save_selection_data(&selection.items, &selection.metadata)?;
+                            persistence.save_selection_data(
+                                &selection.items,
+                                &selection.metadata,
+                                &selection.cold_pages,
+                            )?;
+                        }
+                        KeyAction::ClearAll => {
+                            info!("clearing all items needs confirmation");
+                            pending_destructive_action = Some(DestructiveAction::ClearAll);
+                            mode = AppMode::Confirm;
+                        }
+                        KeyAction::Confirm if mode == AppMode::Palette => {
+                            mode = AppMode::Normal;
+                            let actions = ui.command_palette().pick();
+                            info!("running {actions:?} from the command palette");
+                            pending_item_actions = Some((active_id, actions));
+                        }
+                        KeyAction::Confirm => {
+                            mode = AppMode::Normal;
+                            match pending_destructive_action.take() {
+                                Some(DestructiveAction::ClearAll) => {
+                                    let removed_items = selection.clear_unpinned();
+                                    info!("{} unpinned items removed", removed_items.len());
+                                    ui.remove_button_widgets(removed_items);
+                                }
+                                Some(DestructiveAction::RemovePinned(item_id)) => {
+                                    if let Some(item) = selection.remove_item(item_id) {
+                                        ui.remove_button_widgets(iter::once(item));
+                                    }
+                                    info!("pinned selection item {item_id} removed");
+                                }
+                                None => continue,
+                            }
+                            persistence.save_selection_data(
+                                &selection.items,
+                                &selection.metadata,
+                                &selection.cold_pages,
+                            )?;
                         }
                         KeyAction::Pin => {
                             let is_pinned = selection.toggle_pin(active_id)?;
@@ -465,23 +1837,267 @@ fn server(args: ServerArgs, socket_path: &Path, display_id: Option<String>) -> R
                             } else {
                                 info!("selection item {active_id} unpinned");
                             }
-                            persistence
-                                .save_selection_data(&selection.items, &selection.metadata)?;
+                            persistence.save_selection_data(
+                                &selection.items,
+                                &selection.metadata,
+                                &selection.cold_pages,
+                            )?;
                         }
-                        KeyAction::QuickPaste(index) => {
-                            if let Some((&id, _)) = selection.items.get_by_index(index) {
+                        KeyAction::ToggleExpiry => {
+                            if selection.toggle_expiry(active_id, config.expire_after_secs)? {
                                 info!(
-                                    "quickpaste item {id} (index {index}) selected by key action, hiding window"
+                                    "selection item {active_id} expires in {}s",
+                                    config.expire_after_secs
                                 );
-                                will_hide_window = true;
+                            } else {
+                                info!("selection item {active_id} no longer expires");
+                            }
+                            persistence.save_selection_data(
+                                &selection.items,
+                                &selection.metadata,
+                                &selection.cold_pages,
+                            )?;
+                            schedule_expiry(&selection, &expiry_timer)?;
+                        }
+                        KeyAction::QuickPaste(index) => {
+                            // Indexes follow the list as shown, virtual items included
+                            let quick_paste_id = virtual_items
+                                .layered(&selection.items, &selection.metadata, search.matches())
+                                .0
+                                .get_by_index(index)
+                                .map(|(&id, _)| id);
+                            if let Some(id) = quick_paste_id {
+                                if keeps_open(PasteModifier::default()) {
+                                    info!(
+                                        "quickpaste item {id} (index {index}) selected by key action"
+                                    );
+                                } else {
+                                    info!(
+                                        "quickpaste item {id} (index {index}) selected by key action, hiding window"
+                                    );
+                                    will_hide_window = true;
+                                }
                                 paste_item_id = Some(id);
+                                paste_payload = None;
+                            }
+                        }
+                        KeyAction::Edit => {
+                            if let Some(item) = selection.items.get(&active_id) {
+                                // Keep the window open on failure so the error toast can be seen
+                                if let Err(err) = item_editor.edit(item, &config.editor_command) {
+                                    warn!("failed to edit item {active_id}: {err:?}");
+                                    ui.toast(ToastKind::Error, format!("Failed to edit: {err}"));
+                                } else {
+                                    info!("editing item {active_id}, hiding window");
+                                    will_hide_window = true;
+                                }
+                            }
+                        }
+                        KeyAction::SaveToFile => {
+                            if let Some(item) = selection.items.get(&active_id) {
+                                match item_actions::save_to_file(item) {
+                                    Ok(file_path) => ui.toast(
+                                        ToastKind::Info,
+                                        format!("Saved to {}", file_path.display()),
+                                    ),
+                                    Err(err) => {
+                                        warn!("failed to save item {active_id} to file: {err:?}");
+                                        ui.toast(
+                                            ToastKind::Error,
+                                            format!("Failed to save: {err}"),
+                                        );
+                                    }
+                                }
+                            }
+                        }
+                        KeyAction::Open => {
+                            if let Some(item) = selection.items.get(&active_id) {
+                                if let Err(err) = item_actions::open(item) {
+                                    warn!("failed to open item {active_id}: {err:?}");
+                                    ui.toast(ToastKind::Error, format!("Failed to open: {err}"));
+                                } else {
+                                    info!("opening item {active_id}, hiding window");
+                                    will_hide_window = true;
+                                }
+                            }
+                        }
+
+                        KeyAction::ViewImage => {
+                            if let Some(item) = selection.items.get(&active_id) {
+                                match ui.open_image_viewer(item) {
+                                    Ok(true) => {
+                                        info!("viewing image of item {active_id}");
+                                        mode = AppMode::Preview;
+                                    }
+                                    Ok(false) => ui.toast(ToastKind::Info, "Item has no image"),
+                                    Err(err) => {
+                                        warn!("failed to load image of item {active_id}: {err:?}");
+                                        ui.toast(
+                                            ToastKind::Error,
+                                            format!("Failed to load image: {err}"),
+                                        );
+                                    }
+                                }
+                            }
+                        }
+                        KeyAction::ViewDetails => {
+                            if let Some(item) = selection.items.get(&active_id) {
+                                if ui.open_details_viewer(item) {
+                                    info!("viewing details of item {active_id}");
+                                    mode = AppMode::Preview;
+                                } else {
+                                    ui.toast(ToastKind::Info, "Item has no text");
+                                }
+                            }
+                        }
+                        KeyAction::OpenTransforms => {
+                            info!("switching to Transform mode");
+                            ui.open_transform_picker();
+                            mode = AppMode::Transform;
+                        }
+                        KeyAction::StartSearch => {
+                            info!("switching to Search mode");
+                            mode = AppMode::Search;
+                        }
+                        KeyAction::OpenPalette => {
+                            info!("switching to Palette mode");
+                            ui.command_palette().open();
+                            mode = AppMode::Palette;
+                        }
+                        KeyAction::SetMark(letter) => {
+                            selection.set_mark(letter, active_id)?;
+                            info!("selection item {active_id} marked with '{letter}'");
+                            ui.toast(ToastKind::Info, format!("Marked with '{letter}'"));
+                            persistence.save_selection_data(
+                                &selection.items,
+                                &selection.metadata,
+                                &selection.cold_pages,
+                            )?;
+                        }
+                        KeyAction::JumpToMark(letter) => {
+                            match selection.metadata.marks.get(&letter).copied() {
+                                Some(item_id) => {
+                                    // Pushed down into a page not loaded yet
+                                    let mut found = selection.items.contains_key(&item_id);
+                                    while !found
+                                        && load_next_page(&mut selection, &*persistence, &mut ui)?
+                                    {
+                                        found = selection.items.contains_key(&item_id);
+                                    }
+                                    if found {
+                                        info!("jumping to item {item_id} marked with '{letter}'");
+                                        active_id = item_id;
+                                    } else {
+                                        warn!("item {item_id} marked with '{letter}' is gone");
+                                    }
+                                }
+                                None => {
+                                    ui.toast(
+                                        ToastKind::Info,
+                                        format!("No item marked with '{letter}'"),
+                                    );
+                                }
+                            }
+                        }
+                        KeyAction::ToggleTimeline => {
+                            if ui.toggle_timeline() {
+                                info!("grouping items by day");
+                            } else {
+                                info!("showing items ungrouped");
+                            }
+                        }
+                        KeyAction::DeleteQueryChar if mode == AppMode::Palette => {
+                            ui.command_palette().pop()
+                        }
+                        KeyAction::ClearQuery if mode == AppMode::Palette => {
+                            ui.command_palette().clear_query()
+                        }
+                        KeyAction::DeleteQueryChar => search.pop(),
+                        KeyAction::ClearQuery => search.clear(),
+                        KeyAction::PasteTransformed(transform) => {
+                            ui.close_transform_picker();
+                            mode = AppMode::Normal;
+                            if let Some(item) = selection.items.get(&active_id) {
+                                match transform.apply_to_item(item, &config) {
+                                    Ok(Some(text)) => {
+                                        info!(
+                                            "paste item {active_id} with {transform:?} selected by key action, hiding window"
+                                        );
+                                        paste_item_id = Some(active_id);
+                                        paste_modifier = PasteModifier::default();
+                                        paste_payload = Some(BTreeMap::from([
+                                            ("UTF8_STRING".to_string(), text.clone().into_bytes()),
+                                            (
+                                                "text/plain;charset=utf-8".to_string(),
+                                                text.into_bytes(),
+                                            ),
+                                        ]));
+                                        will_hide_window = true;
+                                    }
+                                    Ok(None) => ui.toast(ToastKind::Info, "Item has no text"),
+                                    Err(err) => {
+                                        warn!(
+                                            "failed to apply {transform:?} to item {active_id}: {err:?}"
+                                        );
+                                        ui.toast(
+                                            ToastKind::Error,
+                                            format!("{}: {err}", transform.name()),
+                                        );
+                                    }
+                                }
+                            }
+                        }
+                        KeyAction::Transform(transform) => {
+                            ui.close_transform_picker();
+                            mode = AppMode::Normal;
+                            if let Some(item) = selection.items.get(&active_id) {
+                                match transform.apply_to_item(item, &config) {
+                                    Ok(Some(text)) => {
+                                        let (new_item_id, removed_items) = selection
+                                            .add_derived_item(BTreeMap::from([(
+                                                "UTF8_STRING".to_string(),
+                                                text.into_bytes(),
+                                            )]))?;
+                                        ui.remove_button_widgets(removed_items);
+                                        if let Some(new_item) = selection.items.get(&new_item_id) {
+                                            ui.build_button_widget(new_item)?;
+                                        }
+                                        info!(
+                                            "{transform:?} of item {active_id} added as item {new_item_id}"
+                                        );
+                                        active_id = new_item_id;
+                                        persistence.save_selection_data(
+                                            &selection.items,
+                                            &selection.metadata,
+                                            &selection.cold_pages,
+                                        )?;
+                                    }
+                                    Ok(None) => ui.toast(ToastKind::Info, "Item has no text"),
+                                    Err(err) => {
+                                        warn!(
+                                            "failed to apply {transform:?} to item {active_id}: {err:?}"
+                                        );
+                                        ui.toast(
+                                            ToastKind::Error,
+                                            format!("{}: {err}", transform.name()),
+                                        );
+                                    }
+                                }
                             }
                         }
+                        KeyAction::Zoom(zoom_action) => ui.zoom_image_viewer(zoom_action),
+                        KeyAction::ScrollLabel(action) => ui.scroll_label(active_id, action),
 
                         KeyAction::ShowHelp => {
                             info!("switching to Help mode");
                             mode = AppMode::Help;
                         }
+                        KeyAction::SimpleScroll(direction) if mode == AppMode::Palette => {
+                            ui.command_palette().move_selection(match direction {
+                                SimpleScrollAction::Up => -1,
+                                SimpleScrollAction::Down => 1,
+                            });
+                        }
                         KeyAction::SimpleScroll(direction) => {
                             let key = match direction {
                                 SimpleScrollAction::Up => egui::Key::ArrowUp,
@@ -504,6 +2120,10 @@ fn server(args: ServerArgs, socket_path: &Path, display_id: Option<String>) -> R
                         }
 
                         KeyAction::Close => match mode {
+                            AppMode::Normal if ui.is_context_menu_open() => {
+                                debug!("closing item context menu");
+                                ui.close_context_menu();
+                            }
                             AppMode::Normal => {
                                 info!("received Close action in Normal mode, hiding window");
                                 will_hide_window = true;
@@ -512,6 +2132,31 @@ fn server(args: ServerArgs, socket_path: &Path, display_id: Option<String>) -> R
                                 info!("switching to Normal mode");
                                 mode = AppMode::Normal;
                             }
+                            AppMode::Confirm => {
+                                info!("action cancelled, switching to Normal mode");
+                                pending_destructive_action = None;
+                                mode = AppMode::Normal;
+                            }
+                            AppMode::Palette => {
+                                info!("command palette closed, switching to Normal mode");
+                                ui.command_palette().close();
+                                mode = AppMode::Normal;
+                            }
+                            AppMode::Search => {
+                                info!("search cancelled, switching to Normal mode");
+                                search.clear();
+                                mode = AppMode::Normal;
+                            }
+                            AppMode::Transform => {
+                                info!("transform cancelled, switching to Normal mode");
+                                ui.close_transform_picker();
+                                mode = AppMode::Normal;
+                            }
+                            AppMode::Preview => {
+                                info!("closing preview, switching to Normal mode");
+                                ui.close_preview();
+                                mode = AppMode::Normal;
+                            }
                         },
                     }
                 }
@@ -521,25 +2166,142 @@ fn server(args: ServerArgs, socket_path: &Path, display_id: Option<String>) -> R
                 } else {
                     UiFlow::TopToBottom
                 };
-                let (full_output, clicked_item) = ui.run(
+                // Saved items past the loaded ones come in a page at a time as the list is
+                // scrolled to its end, and all at once for searching
+                if mode == AppMode::Search && !search.query().is_empty() {
+                    while load_next_page(&mut selection, &*persistence, &mut ui)? {}
+                } else if ui.list_end_visible() && search.matches().is_none() {
+                    load_next_page(&mut selection, &*persistence, &mut ui)?;
+                }
+                let matches_updated = search.update(&selection.items);
+                if let Some(previous_items) =
+                    virtual_items.refresh(&selection.items, &selection.metadata, search.query())?
+                {
+                    ui.remove_button_widgets(previous_items);
+                    for item in virtual_items.iter() {
+                        ui.build_button_widget(item)?;
+                    }
+                }
+                let (shown_items, shown_pinned_count) =
+                    virtual_items.layered(&selection.items, &selection.metadata, search.matches());
+                // The best match gets selected as the query changes
+                if matches_updated
+                    && search.matches().is_some()
+                    && let Some((&first_id, _)) = shown_items.get_by_index(0)
+                {
+                    active_id = first_id;
+                }
+                let ui_started_at = Instant::now();
+                let (full_output, clicked_item, ui_action) = ui.run(
                     input.egui_input.take(),
                     &mut active_id,
-                    &selection.items,
-                    &selection.metadata,
-                    ui_flow,
-                    &scroll_actions,
-                    &keymap_action.pending_keys,
-                    mode == AppMode::Help,
+                    &FrameState {
+                        selection_items: &shown_items,
+                        pinned_count: shown_pinned_count,
+                        expiries: &selection.metadata.expiries,
+                        copied_at: &selection.metadata.copied_at,
+                        search_query: (mode == AppMode::Search).then_some(search.query()),
+                        flow: ui_flow,
+                        scroll_actions: &scroll_actions,
+                        pending_keys: &keymap_action.pending_keys,
+                        show_help: mode == AppMode::Help,
+                        confirm_message: pending_destructive_action
+                            .filter(|_| mode == AppMode::Confirm)
+                            .map(|action| action.message()),
+                        keyboard_unavailable: window.is_keyboard_grab_failed(),
+                    },
                 )?;
+                let ui_time = ui_started_at.elapsed();
+
+                match ui_action {
+                    Some(UiAction::RetryKeyboardGrab) => {
+                        info!("keyboard grab retry requested from ui");
+                        window.retry_grab_keyboard(&keyboard_grab_timer)?;
+                    }
+                    Some(UiAction::Close) => {
+                        info!("close button clicked, hiding window");
+                        will_hide_window = true;
+                    }
+                    Some(UiAction::ShowHelp) => {
+                        info!("help requested from ui, switching to Help mode");
+                        mode = AppMode::Help;
+                    }
+                    Some(UiAction::ItemAction(item_id, action)) => {
+                        debug!("item context menu action {action:?} picked for item {item_id}");
+                        pending_item_actions = Some((item_id, vec![action]));
+                    }
+                    Some(UiAction::RunCommand) => {
+                        mode = AppMode::Normal;
+                        let actions = ui.command_palette().pick();
+                        info!("running {actions:?} clicked in the command palette");
+                        pending_item_actions = Some((active_id, actions));
+                    }
+                    // Answers from the confirmation modal are handled as their keymaps would be
+                    Some(UiAction::Confirm(confirmed)) => {
+                        debug!("confirmation answered with {confirmed} from ui");
+                        let action = if confirmed {
+                            KeyAction::Confirm
+                        } else {
+                            KeyAction::Close
+                        };
+                        pending_item_actions = Some((active_id, vec![action]));
+                    }
+                    None => {}
+                }
 
                 if let Some(clicked_id) = clicked_item {
                     for action in pointer_actions {
                         match action {
                             PointerAction::Paste(modifier) => {
-                                info!("paste item {clicked_id} selected by pointer, hiding window");
-                                will_hide_window = true;
+                                if keeps_open(modifier) {
+                                    info!("paste item {clicked_id} selected by pointer");
+                                } else {
+                                    info!(
+                                        "paste item {clicked_id} selected by pointer, hiding window"
+                                    );
+                                    will_hide_window = true;
+                                }
                                 paste_item_id = Some(clicked_id);
                                 paste_modifier = modifier;
+                                paste_payload = None;
+                            }
+                            PointerAction::Gesture(gesture) => {
+                                let gesture_action = match gesture {
+                                    PointerGesture::Click => config.pointer.click,
+                                    PointerGesture::DoubleClick => config.pointer.double_click,
+                                    PointerGesture::Hold => config.pointer.hold,
+                                };
+                                debug!("{gesture:?} on item {clicked_id}: {gesture_action:?}");
+                                let key_action = match gesture_action {
+                                    PointerGestureAction::Paste => {
+                                        if keeps_open(PasteModifier::default()) {
+                                            info!("paste item {clicked_id} selected by pointer");
+                                        } else {
+                                            info!(
+                                                "paste item {clicked_id} selected by pointer, hiding window"
+                                            );
+                                            will_hide_window = true;
+                                        }
+                                        paste_item_id = Some(clicked_id);
+                                        paste_modifier = PasteModifier::default();
+                                        paste_payload = None;
+                                        None
+                                    }
+                                    PointerGestureAction::Select => {
+                                        active_id = clicked_id;
+                                        None
+                                    }
+                                    PointerGestureAction::ViewImage => Some(KeyAction::ViewImage),
+                                    PointerGestureAction::ViewDetails => {
+                                        Some(KeyAction::ViewDetails)
+                                    }
+                                    PointerGestureAction::Pin => Some(KeyAction::Pin),
+                                    PointerGestureAction::None => None,
+                                };
+                                // Handled as their keymaps would be, on the clicked item
+                                if let Some(key_action) = key_action {
+                                    pending_item_actions = Some((clicked_id, vec![key_action]));
+                                }
                             }
                         }
                     }
@@ -547,48 +2309,277 @@ fn server(args: ServerArgs, socket_path: &Path, display_id: Option<String>) -> R
                     debug!("pointer actions received when no items getting clicked");
                 }
 
-                gl_context.render(&ui.egui_ctx, full_output)?;
+                #[cfg(feature = "accessibility")]
+                let full_output = screen_reader.forward_update(full_output);
+
+                let render_started_at = Instant::now();
+                let render_timings = renderer.render(&ui.egui_ctx, full_output)?;
+                let render_time = render_started_at.elapsed();
+                metrics::record_render(render_time);
+                ui.record_frame(ui_time, render_timings);
+                frame_times = Some((ui_time, render_time));
             }
 
+            // Mapped only after the first frame got swapped, so it doesn't show up empty
             if will_show_window {
+                let map_started_at = Instant::now();
                 window.show_window()?;
                 window.grab_keyboard(&keyboard_grab_timer)?;
-                window.grab_pointer(&pointer_grab_timer)?;
+                if window.closes_on_focus_out() {
+                    window.focus_window()?;
+                } else {
+                    window.grab_pointer(&pointer_grab_timer)?;
+                }
                 window.enable_events()?;
                 window.conn.flush()?;
                 window_shown = true;
+                #[cfg(feature = "accessibility")]
+                screen_reader.set_focused(true);
                 info!("window shown");
+
+                let show_time = woke_at.elapsed();
+                metrics::record_show(show_time);
+                if args.measure_show_latency {
+                    let (ui_time, render_time) = frame_times.unwrap_or_default();
+                    let map_time = map_started_at.elapsed();
+                    eprintln!(
+                        "show latency: {show_time:?} (events and setup {:?}, ui {ui_time:?}, render {render_time:?}, map and grabs {map_time:?})",
+                        show_time.saturating_sub(ui_time + render_time + map_time)
+                    );
+                    mapped_at = Some(Instant::now());
+                }
             }
 
             if will_hide_window {
+                if config.window_position_mode == WindowPositionMode::Last
+                    && let Some(pos) = window.update_last_win_pos()?
+                {
+                    persistence.save_window_pos(pos)?;
+                }
+
+                // Must happen while still mapped, otherwise focus has already reverted to root
+                if window.closes_on_focus_out() {
+                    window.restore_focus()?;
+                }
                 window.hide_window()?;
                 window.cancel_grab_retries(&keyboard_grab_timer, &pointer_grab_timer)?;
                 window.ungrab_input()?;
                 window.disable_events()?;
                 window.conn.flush()?;
                 window_shown = false;
+                #[cfg(feature = "accessibility")]
+                screen_reader.set_focused(false);
                 input.egui_input.modifiers = Modifiers::NONE;
+                warm_up_pending = true;
                 info!("window hidden");
+
+                if config.restore_scroll_position
+                    && let Some(scroll_offset) = ui.scroll_offset()
+                    && (selection.metadata.last_active_id != Some(active_id)
+                        || selection.metadata.last_scroll_offset != scroll_offset)
+                {
+                    selection.metadata.last_active_id = Some(active_id);
+                    selection.metadata.last_scroll_offset = scroll_offset;
+                    persistence.save_selection_data(
+                        &selection.items,
+                        &selection.metadata,
+                        &selection.cold_pages,
+                    )?;
+                }
             }
 
             if let Some(id) = paste_item_id {
-                selection.paste(id, window.win_opened_pointer_pos.get(), paste_modifier)?;
+                // Virtual items become part of the history once pasted
+                let id = if let Some(item) = virtual_items.get(id) {
+                    let (new_item_id, removed_items) = selection.add_virtual_item(item)?;
+                    ui.remove_button_widgets(removed_items);
+                    if let Some(new_item) = selection.items.get(&new_item_id) {
+                        ui.build_button_widget(new_item)?;
+                    }
+                    info!("virtual item {id} added as item {new_item_id}");
+                    if active_id == id {
+                        active_id = new_item_id;
+                    }
+                    persistence.save_selection_data(
+                        &selection.items,
+                        &selection.metadata,
+                        &selection.cold_pages,
+                    )?;
+                    new_item_id
+                } else {
+                    id
+                };
+
+                // The window stays open, so hand input back to the target window for the paste
+                // and take it again afterwards
+                if window_shown {
+                    window.cancel_grab_retries(&keyboard_grab_timer, &pointer_grab_timer)?;
+                    window.disable_events()?;
+                    if window.closes_on_focus_out() {
+                        window.restore_focus()?;
+                    }
+                    window.ungrab_input()?;
+                }
+
+                let (pointer_pos, pointer_anchor) = if pastes_at_pointer {
+                    let pointer = window.conn.query_pointer(window.screen.root)?.reply()?;
+                    ((pointer.root_x, pointer.root_y), None)
+                } else {
+                    (
+                        window.win_opened_pointer_pos.get(),
+                        window.win_opened_pointer_anchor.get(),
+                    )
+                };
+                selection.paste(
+                    id,
+                    pointer_pos,
+                    pointer_anchor,
+                    paste_modifier,
+                    paste_payload,
+                )?;
+
+                if window_shown {
+                    window.grab_keyboard(&keyboard_grab_timer)?;
+                    if window.closes_on_focus_out() {
+                        window.focus_window()?;
+                    } else {
+                        window.grab_pointer(&pointer_grab_timer)?;
+                    }
+                    window.enable_events()?;
+                    window.conn.flush()?;
+                }
             }
 
+            let disk_usage = persistence.disk_usage();
+            ui.set_disk_usage(disk_usage);
+            if config.max_disk_mb > 0 {
+                let quota = config.max_disk_mb.saturating_mul(1_000_000);
+                if disk_usage > quota {
+                    let item_count = selection.items.len() + selection.cold_pages.len();
+                    let cold_size = persistence.cold_pages_size(&selection.cold_pages);
+                    let removed = selection.enforce_disk_quota(disk_usage - quota, cold_size);
+                    ui.remove_button_widgets(removed);
+                    // Nothing left to drop when all that's left is pinned
+                    if selection.items.len() + selection.cold_pages.len() < item_count {
+                        persistence.save_selection_data(
+                            &selection.items,
+                            &selection.metadata,
+                            &selection.cold_pages,
+                        )?;
+                    }
+                }
+
+                let percent = disk_usage.saturating_mul(100) / quota;
+                if percent >= 90 && !disk_quota_warned {
+                    warn!("history takes {percent}% of max_disk_mb");
+                    ui.toast(
+                        ToastKind::Info,
+                        format!("History is at {percent}% of its disk quota, old items go first"),
+                    );
+                }
+                disk_quota_warned = percent >= 90;
+            }
+
+            archive_evicted_items(&mut selection, &*persistence, &mut archive);
+            watchers.update(
+                selection
+                    .items
+                    .get_by_index(selection.metadata.pinned_count)
+                    .map(|(_, item)| item),
+            )?;
+
+            let event_loop_time = woke_at.elapsed();
+            metrics::record_event_loop(event_loop_time);
+            ui.record_event_loop(event_loop_time);
             first_loop = false;
         }
         Ok(())
     })();
 
     info!("cleaning up");
-    window.ungrab_input()?;
-    gl_context.destroy();
+    // Fails without the X11 connection, which mustn't leave the socket file behind
+    if let Err(e) = window
+        .ungrab_input()
+        .and_then(|()| hotkeys.ungrab(&window.conn))
+    {
+        warn!("failed to release input grabs: {e:?}");
+    }
+    renderer.destroy();
+    item_actions::remove_stale_opened_files();
     debug!("removing socket file");
     fs::remove_file(socket_path)?;
 
     main_loop_result
 }
 
+// Returns false if every saved item is loaded already
+fn load_next_page(
+    selection: &mut Selection,
+    persistence: &dyn Storage,
+    ui: &mut Ui,
+) -> Result<bool> {
+    let Some(page) = selection.cold_pages.pop_front() else {
+        return Ok(false);
+    };
+    match persistence.load_page(&page) {
+        Ok(items) => {
+            let item_ids = selection.append_page(items);
+            for item_id in &item_ids {
+                if let Some(item) = selection.items.get(item_id) {
+                    ui.build_button_widget(item)?;
+                }
+            }
+            info!("{} saved items loaded", item_ids.len());
+        }
+        // Dropped, so a broken page isn't tried again on every frame
+        Err(e) => warn!("failed to load saved items, dropping them: {e}"),
+    }
+    Ok(true)
+}
+
+// Arms the timer for the item expiring the soonest
+fn schedule_expiry(selection: &Selection, expiry_timer: &TimerfdSource) -> Result<()> {
+    match selection.next_expiry() {
+        Some(expires_at) => expiry_timer.set_timer(expiry::timer_ms(expires_at, expiry::now()))?,
+        None => expiry_timer.disarm()?,
+    }
+    Ok(())
+}
+
+// Pastes from outside the window, starting at the history index `start`, or at an older item
+// when pasting again with yank_pop. Returns the item to paste
+fn yank(selection: &Selection, yank_pop: &mut YankPop, start: usize) -> Result<Option<u64>> {
+    let (item_idx, undo) = yank_pop.next(start, selection.items.len(), Instant::now());
+    let Some((&item_id, item)) = selection.items.get_by_index(item_idx) else {
+        warn!("no item {item_idx} to paste");
+        return Ok(None);
+    };
+    if let Some(undo) = undo {
+        selection.undo_paste(undo)?;
+    }
+    yank_pop.pasted(item.text());
+    info!("pasting item {item_id} at {item_idx}");
+    Ok(Some(item_id))
+}
+
+// Loaded before the next save, which deletes the pages of evicted items that weren't loaded
+fn archive_evicted_items(
+    selection: &mut Selection,
+    persistence: &dyn Storage,
+    archive: &mut Archive,
+) {
+    let (mut items, pages) = selection.take_evicted();
+    for page in pages {
+        match persistence.load_page(&page) {
+            Ok(page_items) => items.extend(page_items),
+            Err(e) => warn!("failed to load items to archive, dropping them: {e}"),
+        }
+    }
+    if let Err(e) = archive.add(items) {
+        error!("failed to archive items: {e:?}");
+    }
+}
+
 fn create_poll<P: AsRef<Path> + std::fmt::Debug>(
     conn: &XCBConnection,
     socket_path: P,
@@ -598,6 +2589,8 @@ fn create_poll<P: AsRef<Path> + std::fmt::Debug>(
     Signals,
     TimerfdSource,
     TimerfdSource,
+    TimerfdSource,
+    TimerfdSource,
 )> {
     let poll = mio::Poll::new()?;
 
@@ -652,11 +2645,31 @@ fn create_poll<P: AsRef<Path> + std::fmt::Debug>(
         mio::Interest::READABLE,
     )?;
 
+    debug!("registering capture debounce timer source");
+    let capture_debounce_timer = TimerfdSource::new()
+        .map_err(|e| anyhow!("failed to create capture debounce timerfd: {e}"))?;
+    poll.registry().register(
+        &mut SourceFd(&capture_debounce_timer.as_fd().as_raw_fd()),
+        CAPTURE_DEBOUNCE_TOKEN,
+        mio::Interest::READABLE,
+    )?;
+
+    debug!("registering theme preview timer source");
+    let theme_preview_timer =
+        TimerfdSource::new().map_err(|e| anyhow!("failed to create theme preview timerfd: {e}"))?;
+    poll.registry().register(
+        &mut SourceFd(&theme_preview_timer.as_fd().as_raw_fd()),
+        THEME_PREVIEW_TOKEN,
+        mio::Interest::READABLE,
+    )?;
+
     Ok((
         poll,
         listener,
         signals,
         keyboard_grab_timer,
         pointer_grab_timer,
+        capture_debounce_timer,
+        theme_preview_timer,
     ))
 }
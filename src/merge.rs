@@ -0,0 +1,97 @@
+//! When a new item replaces the previous one instead of being added, e.g., while dragging the mouse
+//! to adjust a PRIMARY selection, where every change is a new selection
+
+use std::time::Duration;
+
+use crate::config::{MergeConfig, MergeStrategy};
+
+pub struct MergePolicy {
+    strategy: MergeStrategy,
+    window: Duration,
+}
+
+impl MergePolicy {
+    pub fn new(config: &MergeConfig) -> Self {
+        MergePolicy {
+            strategy: config.strategy,
+            window: Duration::from_millis(config.window_ms),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.strategy != MergeStrategy::Off
+    }
+
+    /// Whether the text of a new item, taken `elapsed` after the previous one from the same
+    /// owner, replaces the previous one
+    pub fn should_merge(&self, prev_text: &[u8], new_text: &[u8], elapsed: Duration) -> bool {
+        if elapsed >= self.window {
+            return false;
+        }
+        match self.strategy {
+            MergeStrategy::Off => false,
+            MergeStrategy::Substring => {
+                contains(new_text, prev_text) || contains(prev_text, new_text)
+            }
+            MergeStrategy::Prefix => {
+                new_text.starts_with(prev_text) || prev_text.starts_with(new_text)
+            }
+        }
+    }
+}
+
+// Dumb algorithm here is fine I guess
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    needle.is_empty() || haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(strategy: MergeStrategy) -> MergePolicy {
+        MergePolicy::new(&MergeConfig {
+            strategy,
+            window_ms: 1000,
+        })
+    }
+
+    const SOON: Duration = Duration::from_millis(100);
+
+    #[test]
+    fn substring_merges_either_way() {
+        let policy = policy(MergeStrategy::Substring);
+        assert!(policy.should_merge(b"hello", b"hello world", SOON));
+        assert!(policy.should_merge(b"hello world", b"lo wo", SOON));
+        assert!(!policy.should_merge(b"hello", b"world", SOON));
+    }
+
+    #[test]
+    fn prefix_merges_only_from_the_start() {
+        let policy = policy(MergeStrategy::Prefix);
+        assert!(policy.should_merge(b"hello", b"hello world", SOON));
+        assert!(policy.should_merge(b"hello world", b"hell", SOON));
+        assert!(!policy.should_merge(b"hello world", b"world", SOON));
+    }
+
+    #[test]
+    fn off_never_merges() {
+        let policy = policy(MergeStrategy::Off);
+        assert!(!policy.is_enabled());
+        assert!(!policy.should_merge(b"hello", b"hello world", SOON));
+    }
+
+    #[test]
+    fn merges_only_within_window() {
+        let policy = policy(MergeStrategy::Substring);
+        assert!(policy.should_merge(b"a", b"ab", Duration::from_millis(999)));
+        assert!(!policy.should_merge(b"a", b"ab", Duration::from_millis(1000)));
+    }
+
+    #[test]
+    fn empty_text_is_contained() {
+        let policy = policy(MergeStrategy::Substring);
+        assert!(policy.should_merge(b"", b"a", SOON));
+        assert!(policy.should_merge(b"a", b"", SOON));
+    }
+}
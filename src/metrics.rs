@@ -0,0 +1,211 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+#[cfg(feature = "metrics")]
+use {
+    anyhow::Result,
+    log::{debug, info, warn},
+    std::{
+        io::{BufRead as _, BufReader, Write as _},
+        net::{Ipv4Addr, TcpListener, TcpStream},
+        sync::atomic::AtomicBool,
+        thread,
+    },
+};
+
+// Recorded regardless of the feature, as a few atomic additions are cheaper than gating every
+// call site
+static ITEMS: AtomicU64 = AtomicU64::new(0);
+static HISTORY_BYTES: AtomicU64 = AtomicU64::new(0);
+static CAPTURES: AtomicU64 = AtomicU64::new(0);
+static FAILED_TRANSFERS: AtomicU64 = AtomicU64::new(0);
+static WINDOW_RECREATIONS: AtomicU64 = AtomicU64::new(0);
+static RENDER: Timing = Timing::new();
+static EVENT_LOOP: Timing = Timing::new();
+static SHOW: Timing = Timing::new();
+// The server starting over on a new X11 connection keeps serving from the first listener
+#[cfg(feature = "metrics")]
+static SERVING: AtomicBool = AtomicBool::new(false);
+
+struct Timing {
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+}
+
+impl Timing {
+    const fn new() -> Self {
+        Timing {
+            count: AtomicU64::new(0),
+            sum_micros: AtomicU64::new(0),
+        }
+    }
+
+    fn record(&self, duration: Duration) {
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+}
+
+/// Number of items in the history and the size it takes when saved
+pub fn set_history(items: usize, bytes: usize) {
+    ITEMS.store(items as u64, Ordering::Relaxed);
+    HISTORY_BYTES.store(bytes as u64, Ordering::Relaxed);
+}
+
+pub fn record_capture() {
+    CAPTURES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Captures that failed, and pastes the receiving window never finished
+pub fn record_failed_transfers(count: usize) {
+    FAILED_TRANSFERS.fetch_add(count as u64, Ordering::Relaxed);
+}
+
+/// The main window getting destroyed by someone else and created again
+pub fn record_window_recreation() {
+    WINDOW_RECREATIONS.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn record_render(duration: Duration) {
+    RENDER.record(duration);
+}
+
+/// Time spent handling events after each wake up of the main loop
+pub fn record_event_loop(duration: Duration) {
+    EVENT_LOOP.record(duration);
+}
+
+/// Time from the wake up asking for the window until it's mapped with its first frame
+pub fn record_show(duration: Duration) {
+    SHOW.record(duration);
+}
+
+/// Serves the metrics in Prometheus text format at http://127.0.0.1:<port>/metrics, from a
+/// background thread
+#[cfg(feature = "metrics")]
+pub fn serve(port: u16, selection: &str) -> Result<()> {
+    if SERVING.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+    SERVING.store(true, Ordering::Relaxed);
+    info!("serving metrics on http://127.0.0.1:{port}/metrics");
+
+    let selection = selection.to_string();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let result = stream
+                .map_err(anyhow::Error::from)
+                .and_then(|stream| respond(stream, &selection));
+            if let Err(e) = result {
+                warn!("failed to serve metrics: {e:?}");
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(feature = "metrics")]
+fn respond(mut stream: TcpStream, selection: &str) -> Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    debug!("metrics request: {}", request_line.trim_end());
+
+    let (status, body) = match request_line.split_whitespace().take(2).collect::<Vec<_>>()[..] {
+        ["GET", "/metrics"] => ("200 OK", render(selection)),
+        _ => ("404 Not Found", "Not found\n".to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    Ok(())
+}
+
+#[cfg(feature = "metrics")]
+fn render(selection: &str) -> String {
+    let label = format!("{{selection=\"{selection}\"}}");
+    let mut out = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, value: String| {
+        out.push_str(&format!(
+            "# HELP {name} {help}\n# TYPE {name} {kind}\n{name}{label} {value}\n"
+        ));
+    };
+    let load = |value: &AtomicU64| value.load(Ordering::Relaxed).to_string();
+    let seconds = |timing: &Timing| {
+        (timing.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0).to_string()
+    };
+
+    metric(
+        "memoni_items",
+        "gauge",
+        "Items in the history",
+        load(&ITEMS),
+    );
+    metric(
+        "memoni_history_bytes",
+        "gauge",
+        "Size of the saved history",
+        load(&HISTORY_BYTES),
+    );
+    metric(
+        "memoni_captures_total",
+        "counter",
+        "Selections captured",
+        load(&CAPTURES),
+    );
+    metric(
+        "memoni_failed_transfers_total",
+        "counter",
+        "Failed captures and unfinished pastes",
+        load(&FAILED_TRANSFERS),
+    );
+    metric(
+        "memoni_window_recreations_total",
+        "counter",
+        "Main windows recreated after getting destroyed",
+        load(&WINDOW_RECREATIONS),
+    );
+    metric(
+        "memoni_render_seconds_total",
+        "counter",
+        "Time spent rendering frames",
+        seconds(&RENDER),
+    );
+    metric(
+        "memoni_renders_total",
+        "counter",
+        "Frames rendered",
+        load(&RENDER.count),
+    );
+    metric(
+        "memoni_event_loop_seconds_total",
+        "counter",
+        "Time spent handling events in the main loop",
+        seconds(&EVENT_LOOP),
+    );
+    metric(
+        "memoni_event_loop_iterations_total",
+        "counter",
+        "Main loop wake ups",
+        load(&EVENT_LOOP.count),
+    );
+    metric(
+        "memoni_show_seconds_total",
+        "counter",
+        "Time spent showing the window",
+        seconds(&SHOW),
+    );
+    metric(
+        "memoni_shows_total",
+        "counter",
+        "Times the window was shown",
+        load(&SHOW.count),
+    );
+    out
+}
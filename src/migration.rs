@@ -0,0 +1,336 @@
+//! Saved histories of every binary version, upgraded one version at a time until they're laid out
+//! like the current one. A new version adds a variant for the layout it replaces, decoded under
+//! its old version and upgraded to the next, so each step can be checked on its own
+
+use std::collections::{BTreeMap, VecDeque};
+
+use anyhow::{Result, bail};
+use bincode::Decode;
+use log::debug;
+
+use crate::{
+    ordered_hash_map::OrderedHashMap,
+    persistence::{DECODE_CONFIG, PageRef},
+    selection::{SelectionItem, SelectionMetadata},
+};
+
+type Items = OrderedHashMap<u64, SelectionItem>;
+
+/// The history as laid out by a binary version
+pub enum Snapshot {
+    /// Items only, before the version was written in front of them
+    V1(VecDeque<ItemVersion3>),
+    V2(ItemsVersion3, MetadataVersion2),
+    V3(ItemsVersion3, MetadataVersion5),
+    V4(Items, MetadataVersion5),
+    V5(Items, MetadataVersion5, Vec<PageRef>),
+    V6(Items, MetadataVersion6, Vec<PageRef>),
+    V7(Items, MetadataVersion7, Vec<PageRef>),
+    /// Since version 8, as version 9 only added a checksum in front of the same layout
+    Current(Items, SelectionMetadata, Vec<PageRef>),
+}
+
+impl Snapshot {
+    /// Decodes what follows the version, past the checksum for versions that have one
+    pub fn decode(version: u32, data: &[u8]) -> Result<Snapshot> {
+        Ok(match version {
+            2 => {
+                let (items, metadata) = decode(data)?;
+                Snapshot::V2(items, metadata)
+            }
+            3 => {
+                let (items, metadata) = decode(data)?;
+                Snapshot::V3(items, metadata)
+            }
+            4 => {
+                let (items, metadata) = decode(data)?;
+                Snapshot::V4(items, metadata)
+            }
+            5 => {
+                let (items, metadata, page_refs) = decode(data)?;
+                Snapshot::V5(items, metadata, page_refs)
+            }
+            6 => {
+                let (items, metadata, page_refs) = decode(data)?;
+                Snapshot::V6(items, metadata, page_refs)
+            }
+            7 => {
+                let (items, metadata, page_refs) = decode(data)?;
+                Snapshot::V7(items, metadata, page_refs)
+            }
+            8 | 9 => {
+                let (items, metadata, page_refs) = decode(data)?;
+                Snapshot::Current(items, metadata, page_refs)
+            }
+            _ => bail!("invalid binary version"),
+        })
+    }
+
+    /// Metadata only, without items, as kept by storages that save items on their own
+    pub fn decode_metadata(version: u32, data: &[u8]) -> Result<Snapshot> {
+        Ok(match version {
+            2 => Snapshot::V2(ItemsVersion3::new(), decode(data)?),
+            3 => Snapshot::V3(ItemsVersion3::new(), decode(data)?),
+            4 => Snapshot::V4(Items::new(), decode(data)?),
+            5 => Snapshot::V5(Items::new(), decode(data)?, vec![]),
+            6 => Snapshot::V6(Items::new(), decode(data)?, vec![]),
+            7 => Snapshot::V7(Items::new(), decode(data)?, vec![]),
+            8 | 9 => Snapshot::Current(Items::new(), decode(data)?, vec![]),
+            _ => bail!("invalid binary version"),
+        })
+    }
+
+    /// Version 1 has no version field unfortunately, so it's decoded from the start of the file
+    pub fn decode_version_1(data: &[u8]) -> Result<Snapshot> {
+        Ok(Snapshot::V1(decode(data)?))
+    }
+
+    pub fn version(&self) -> u32 {
+        match self {
+            Snapshot::V1(..) => 1,
+            Snapshot::V2(..) => 2,
+            Snapshot::V3(..) => 3,
+            Snapshot::V4(..) => 4,
+            Snapshot::V5(..) => 5,
+            Snapshot::V6(..) => 6,
+            Snapshot::V7(..) => 7,
+            Snapshot::Current(..) => 8,
+        }
+    }
+
+    /// The same history laid out like the next version, unchanged if it's the current one already
+    pub fn upgrade(self) -> Snapshot {
+        match self {
+            Snapshot::V1(old_items) => {
+                let mut items = OrderedHashMap::new();
+                for item in old_items {
+                    items.push_back(item.id, item);
+                }
+                Snapshot::V2(items, MetadataVersion2 { pinned_count: 0 })
+            }
+            Snapshot::V2(items, metadata) => Snapshot::V3(
+                items,
+                MetadataVersion5 {
+                    pinned_count: metadata.pinned_count,
+                    last_active_id: None,
+                    last_scroll_offset: 0.0,
+                },
+            ),
+            Snapshot::V3(old_items, metadata) => {
+                let mut items = OrderedHashMap::new();
+                for (id, item) in old_items {
+                    items.push_back(id, item.into());
+                }
+                Snapshot::V4(items, metadata)
+            }
+            Snapshot::V4(items, metadata) => Snapshot::V5(items, metadata, vec![]),
+            Snapshot::V5(items, metadata, page_refs) => Snapshot::V6(
+                items,
+                MetadataVersion6 {
+                    pinned_count: metadata.pinned_count,
+                    last_active_id: metadata.last_active_id,
+                    last_scroll_offset: metadata.last_scroll_offset,
+                    expiries: BTreeMap::new(),
+                },
+                page_refs,
+            ),
+            Snapshot::V6(items, metadata, page_refs) => Snapshot::V7(
+                items,
+                MetadataVersion7 {
+                    pinned_count: metadata.pinned_count,
+                    last_active_id: metadata.last_active_id,
+                    last_scroll_offset: metadata.last_scroll_offset,
+                    expiries: metadata.expiries,
+                    copied_at: BTreeMap::new(),
+                },
+                page_refs,
+            ),
+            Snapshot::V7(items, metadata, page_refs) => Snapshot::Current(
+                items,
+                SelectionMetadata {
+                    pinned_count: metadata.pinned_count,
+                    last_active_id: metadata.last_active_id,
+                    last_scroll_offset: metadata.last_scroll_offset,
+                    expiries: metadata.expiries,
+                    copied_at: metadata.copied_at,
+                    ..Default::default()
+                },
+                page_refs,
+            ),
+            current @ Snapshot::Current(..) => current,
+        }
+    }
+
+    pub fn into_current(mut self) -> (Items, SelectionMetadata, Vec<PageRef>) {
+        loop {
+            match self {
+                Snapshot::Current(items, metadata, page_refs) => {
+                    return (items, metadata, page_refs);
+                }
+                snapshot => {
+                    debug!("upgrading history from version {}", snapshot.version());
+                    self = snapshot.upgrade();
+                }
+            }
+        }
+    }
+}
+
+fn decode<T: Decode<()>>(data: &[u8]) -> Result<T> {
+    Ok(bincode::decode_from_slice(data, DECODE_CONFIG)?.0)
+}
+
+/// Items before the source application was recorded
+#[derive(Decode)]
+#[cfg_attr(test, derive(bincode::Encode))]
+pub struct ItemVersion3 {
+    id: u64,
+    data: BTreeMap<String, Vec<u8>>,
+}
+
+impl From<ItemVersion3> for SelectionItem {
+    fn from(value: ItemVersion3) -> Self {
+        SelectionItem {
+            id: value.id,
+            data: value.data,
+            source: None,
+        }
+    }
+}
+
+type ItemsVersion3 = OrderedHashMap<u64, ItemVersion3>;
+
+#[derive(Decode)]
+#[cfg_attr(test, derive(bincode::Encode))]
+pub struct MetadataVersion2 {
+    pinned_count: usize,
+}
+
+/// Metadata before items could be marked to expire
+#[derive(Decode)]
+#[cfg_attr(test, derive(bincode::Encode))]
+pub struct MetadataVersion5 {
+    pinned_count: usize,
+    last_active_id: Option<u64>,
+    last_scroll_offset: f32,
+}
+
+/// Metadata before copy times were recorded
+#[derive(Decode)]
+#[cfg_attr(test, derive(bincode::Encode))]
+pub struct MetadataVersion6 {
+    pinned_count: usize,
+    last_active_id: Option<u64>,
+    last_scroll_offset: f32,
+    expiries: BTreeMap<u64, u64>,
+}
+
+/// Metadata before items could be marked
+#[derive(Decode)]
+#[cfg_attr(test, derive(bincode::Encode))]
+pub struct MetadataVersion7 {
+    pinned_count: usize,
+    last_active_id: Option<u64>,
+    last_scroll_offset: f32,
+    expiries: BTreeMap<u64, u64>,
+    copied_at: BTreeMap<u64, u64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistence::BINCODE_CONFIG;
+
+    fn item(id: u64, text: &str) -> ItemVersion3 {
+        ItemVersion3 {
+            id,
+            data: BTreeMap::from([("text/plain".to_string(), text.as_bytes().to_vec())]),
+        }
+    }
+
+    fn encode(value: impl bincode::Encode) -> Vec<u8> {
+        bincode::encode_to_vec(value, BINCODE_CONFIG).unwrap()
+    }
+
+    fn texts(items: &Items) -> Vec<(u64, &str)> {
+        items
+            .iter()
+            .map(|(&id, item)| (id, item.text().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn version_1_is_upgraded_to_the_current_one() {
+        let data = encode(VecDeque::from([item(1, "a"), item(2, "b")]));
+        let (items, metadata, page_refs) =
+            Snapshot::decode_version_1(&data).unwrap().into_current();
+        assert_eq!(texts(&items), [(1, "a"), (2, "b")]);
+        assert!(items.iter().all(|(_, item)| item.source.is_none()));
+        assert_eq!(metadata.pinned_count, 0);
+        assert!(page_refs.is_empty());
+    }
+
+    #[test]
+    fn version_2_keeps_pinned_count() {
+        let mut items = ItemsVersion3::new();
+        items.push_back(1, item(1, "a"));
+        let data = encode((items, MetadataVersion2 { pinned_count: 1 }));
+        let snapshot = Snapshot::decode(2, &data).unwrap();
+        assert_eq!(snapshot.version(), 2);
+        let (items, metadata, _) = snapshot.into_current();
+        assert_eq!(texts(&items), [(1, "a")]);
+        assert_eq!(metadata.pinned_count, 1);
+        assert_eq!(metadata.last_active_id, None);
+    }
+
+    #[test]
+    fn each_step_upgrades_one_version() {
+        let mut snapshot =
+            Snapshot::decode_version_1(&encode(VecDeque::<ItemVersion3>::new())).unwrap();
+        for version in 1..8 {
+            assert_eq!(snapshot.version(), version);
+            snapshot = snapshot.upgrade();
+        }
+        assert!(matches!(snapshot, Snapshot::Current(..)));
+        assert_eq!(snapshot.upgrade().version(), 8);
+    }
+
+    #[test]
+    fn version_7_keeps_expiries_and_copy_times() {
+        let metadata = MetadataVersion7 {
+            pinned_count: 0,
+            last_active_id: Some(1),
+            last_scroll_offset: 12.0,
+            expiries: BTreeMap::from([(1, 100)]),
+            copied_at: BTreeMap::from([(1, 50)]),
+        };
+        let data = encode((Items::new(), metadata, Vec::<PageRef>::new()));
+        let (_, metadata, _) = Snapshot::decode(7, &data).unwrap().into_current();
+        assert_eq!(metadata.last_active_id, Some(1));
+        assert_eq!(metadata.last_scroll_offset, 12.0);
+        assert_eq!(metadata.expiries, BTreeMap::from([(1, 100)]));
+        assert_eq!(metadata.copied_at, BTreeMap::from([(1, 50)]));
+        assert!(metadata.marks.is_empty());
+    }
+
+    #[test]
+    fn metadata_is_upgraded_on_its_own() {
+        let data = encode(MetadataVersion5 {
+            pinned_count: 2,
+            last_active_id: Some(3),
+            last_scroll_offset: 4.0,
+        });
+        let (items, metadata, _) = Snapshot::decode_metadata(5, &data).unwrap().into_current();
+        assert!(items.is_empty());
+        assert_eq!(metadata.pinned_count, 2);
+        assert_eq!(metadata.last_active_id, Some(3));
+        assert!(metadata.expiries.is_empty());
+    }
+
+    #[test]
+    fn unknown_versions_fail() {
+        assert!(Snapshot::decode(1, &[]).is_err());
+        assert!(Snapshot::decode(10, &[]).is_err());
+        assert!(Snapshot::decode_metadata(1, &[]).is_err());
+    }
+}
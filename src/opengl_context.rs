@@ -1,8 +1,13 @@
-use crate::{config::Config, x11_window::X11Window};
+use crate::{
+    config::Config,
+    damage::{Damage, DamageTracker},
+    renderer::{RenderTimings, Renderer},
+    x11_window::X11Window,
+};
 use anyhow::{Context as _, Result, bail};
-use egui::Color32;
+use egui::{Color32, Rect};
 use egui_glow::Painter;
-use glow::Context as GlowContext;
+use glow::{Context as GlowContext, HasContext as _};
 use glutin::{
     config::ConfigTemplateBuilder,
     context::{ContextApi, ContextAttributesBuilder, NotCurrentContext, PossiblyCurrentContext},
@@ -19,8 +24,13 @@ use std::{
     num::{NonZero, NonZeroU32},
     ptr::NonNull,
     sync::Arc,
+    thread,
+    time::{Duration, Instant},
 };
 
+// Pacing of frames that are skipped, as there's no swap to wait for vsync on
+const FRAME_INTERVAL: Duration = Duration::from_micros(16_667);
+
 pub struct OpenGLContext<'a> {
     pub dimensions: [u32; 2],
     pub background: (f32, f32, f32, f32),
@@ -31,14 +41,14 @@ pub struct OpenGLContext<'a> {
     surface: Surface<WindowSurface>,
     context: Option<PossiblyCurrentContext>,
     gl: Arc<GlowContext>,
+    damage_tracker: DamageTracker,
+    last_swapped_at: Option<Instant>,
 }
 
 impl<'a> OpenGLContext<'a> {
     pub fn new(window: &'a X11Window, config: &Config) -> Result<Self> {
         info!("creating GL display via EGL");
 
-        let background_color: Color32 = config.theme.background.into();
-        let (r, g, b, a) = background_color.to_tuple();
         let dimensions = [
             config.layout.window_dimensions.width as _,
             config.layout.window_dimensions.height as _,
@@ -96,41 +106,16 @@ impl<'a> OpenGLContext<'a> {
             display: gl_display,
             config: display_config,
             dimensions,
-            background: (
-                r as f32 / 255.0,
-                g as f32 / 255.0,
-                b as f32 / 255.0,
-                a as f32 / 255.0,
-            ),
+            background: gl_color(config.theme.background.into()),
             surface,
             context: Some(context),
             gl,
             painter,
+            damage_tracker: DamageTracker::new(),
+            last_swapped_at: None,
         })
     }
 
-    pub fn recreate_painter(&mut self) -> Result<()> {
-        info!("recreating egui_glow painter");
-
-        self.painter.destroy();
-        let not_current_ctx = self.context.take().unwrap().make_not_current()?;
-
-        let (painter, surface, context, gl) = Self::create_painter(
-            self.window.win_id.get(),
-            &self.display,
-            &self.config,
-            not_current_ctx,
-            self.dimensions,
-        )?;
-
-        self.painter = painter;
-        self.surface = surface;
-        self.context = Some(context);
-        self.gl = gl;
-
-        Ok(())
-    }
-
     fn create_painter(
         win_id: u32,
         gl_display: &Display,
@@ -166,12 +151,37 @@ impl<'a> OpenGLContext<'a> {
 
         Ok((painter, surface, ctx, gl))
     }
+}
+
+impl Renderer for OpenGLContext<'_> {
+    fn recreate(&mut self) -> Result<()> {
+        info!("recreating egui_glow painter");
+
+        self.painter.destroy();
+        let not_current_ctx = self.context.take().unwrap().make_not_current()?;
 
-    pub fn render(
+        let (painter, surface, context, gl) = Self::create_painter(
+            self.window.win_id.get(),
+            &self.display,
+            &self.config,
+            not_current_ctx,
+            self.dimensions,
+        )?;
+
+        self.painter = painter;
+        self.surface = surface;
+        self.context = Some(context);
+        self.gl = gl;
+        self.damage_tracker.reset();
+
+        Ok(())
+    }
+
+    fn render(
         &mut self,
         egui_ctx: &egui::Context,
         full_output: egui::FullOutput,
-    ) -> Result<()> {
+    ) -> Result<RenderTimings> {
         trace!("rendering frame");
         let egui::FullOutput {
             platform_output: _,
@@ -181,30 +191,129 @@ impl<'a> OpenGLContext<'a> {
             viewport_output: _,
         } = full_output;
 
-        let (r, g, b, a) = self.background;
-        self.painter.clear(self.dimensions, [r, g, b, a]);
-
+        let started_at = Instant::now();
         let shapes = std::mem::take(&mut shapes);
         let clipped_primitives = egui_ctx.tessellate(shapes, pixels_per_point);
+        let tessellated_at = Instant::now();
+
+        let textures_changed = !textures_delta.set.is_empty() || !textures_delta.free.is_empty();
+        let damage = self.damage_tracker.frame_damage(
+            &clipped_primitives,
+            self.surface.buffer_age(),
+            textures_changed,
+        );
+        let damaged_area = match damage {
+            Damage::None => {
+                trace!("frame unchanged, skipping render");
+                if let Some(last_swapped_at) = self.last_swapped_at {
+                    thread::sleep(FRAME_INTERVAL.saturating_sub(last_swapped_at.elapsed()));
+                }
+                self.last_swapped_at = Some(Instant::now());
+                return Ok(RenderTimings {
+                    tessellate: tessellated_at - started_at,
+                    paint: Duration::ZERO,
+                    swap: Duration::ZERO,
+                });
+            }
+            Damage::Rect(area) => Some((area, gl_rect(area, pixels_per_point, self.dimensions))),
+            Damage::Full => None,
+        };
+
+        let (r, g, b, a) = self.background;
+        let clipped_primitives = match damaged_area {
+            Some((area, rect)) => {
+                trace!("repainting damaged area {area:?}");
+                unsafe {
+                    self.gl.enable(glow::SCISSOR_TEST);
+                    self.gl.scissor(rect.x, rect.y, rect.width, rect.height);
+                    self.gl.clear_color(r, g, b, a);
+                    self.gl.clear(glow::COLOR_BUFFER_BIT);
+                    self.gl.disable(glow::SCISSOR_TEST);
+                }
+                // The painter scissors each mesh to its clip rect, so narrowing those keeps the
+                // rest of the buffer untouched
+                clipped_primitives
+                    .into_iter()
+                    .filter_map(|mut clipped| {
+                        clipped.clip_rect = clipped.clip_rect.intersect(area);
+                        clipped.clip_rect.is_positive().then_some(clipped)
+                    })
+                    .collect()
+            }
+            None => {
+                self.painter.clear(self.dimensions, [r, g, b, a]);
+                clipped_primitives
+            }
+        };
         self.painter.paint_and_update_textures(
             self.dimensions,
             pixels_per_point,
             &clipped_primitives,
             &textures_delta,
         );
+        let painted_at = Instant::now();
 
-        self.surface.swap_buffers(self.context.as_ref().unwrap())?;
+        let context = self.context.as_ref().unwrap();
+        match (damaged_area, &self.surface, context) {
+            (Some((_, rect)), Surface::Egl(surface), PossiblyCurrentContext::Egl(context)) => {
+                if let Err(err) = surface.swap_buffers_with_damage(context, &[rect]) {
+                    trace!("swapping with damage failed, swapping in full: {err}");
+                    surface.swap_buffers(context)?;
+                }
+            }
+            // Only EGL surfaces can be swapped with damage
+            _ => self.surface.swap_buffers(context)?,
+        }
+        self.last_swapped_at = Some(Instant::now());
         trace!("frame rendered");
 
-        Ok(())
+        Ok(RenderTimings {
+            tessellate: tessellated_at - started_at,
+            paint: painted_at - tessellated_at,
+            swap: painted_at.elapsed(),
+        })
     }
 
-    pub fn destroy(&mut self) {
+    fn invalidate(&mut self) {
+        self.damage_tracker.reset();
+    }
+
+    fn set_background(&mut self, color: Color32) {
+        self.background = gl_color(color);
+        self.damage_tracker.reset();
+    }
+
+    fn destroy(&mut self) {
         info!("destroying painter");
         self.painter.destroy();
     }
 }
 
+// GL and EGL count from the bottom left, in whole pixels
+fn gl_rect(area: Rect, pixels_per_point: f32, dimensions: [u32; 2]) -> glutin::surface::Rect {
+    let [width, height] = dimensions.map(|d| d as i32);
+    let min_x = ((area.min.x * pixels_per_point).floor() as i32).max(0);
+    let min_y = ((area.min.y * pixels_per_point).floor() as i32).max(0);
+    let max_x = ((area.max.x * pixels_per_point).ceil() as i32).min(width);
+    let max_y = ((area.max.y * pixels_per_point).ceil() as i32).min(height);
+    glutin::surface::Rect::new(
+        min_x,
+        height - max_y,
+        (max_x - min_x).max(0),
+        (max_y - min_y).max(0),
+    )
+}
+
+fn gl_color(color: Color32) -> (f32, f32, f32, f32) {
+    let (r, g, b, a) = color.to_tuple();
+    (
+        r as f32 / 255.0,
+        g as f32 / 255.0,
+        b as f32 / 255.0,
+        a as f32 / 255.0,
+    )
+}
+
 fn get_xlib_display_handle(screen: c_int) -> Result<XlibDisplayHandle> {
     unsafe {
         let lib = libloading::Library::new("libX11.so.6")?;
@@ -1,5 +1,5 @@
 use std::cmp::Ordering;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::hash::Hash;
 
 use bincode::{Decode, Encode};
@@ -115,6 +115,13 @@ where
         self.map.is_empty()
     }
 
+    /// Whether each key is in the order once and has a value, which decoded data may break
+    pub fn is_consistent(&self) -> bool {
+        self.keys.len() == self.map.len()
+            && self.keys.iter().collect::<HashSet<_>>().len() == self.keys.len()
+            && self.keys.iter().all(|key| self.map.contains_key(key))
+    }
+
     pub fn clear(&mut self) {
         self.map.clear();
         self.keys.clear();
@@ -1,10 +1,12 @@
-use anyhow::{Result, anyhow};
-use log::{debug, error, info};
+use anyhow::{Result, anyhow, bail};
+use bincode::{Decode, Encode};
+use log::{debug, error, info, warn};
 use std::{
-    collections::VecDeque,
+    collections::{HashMap, HashSet, VecDeque},
     fs::{self, File},
-    io::{Read, Write as _},
-    path::PathBuf,
+    io::{self, Write as _},
+    mem,
+    path::{Path, PathBuf},
     sync::{
         Arc,
         atomic::{AtomicBool, Ordering},
@@ -13,70 +15,308 @@ use std::{
     thread,
 };
 
+#[cfg(feature = "sqlite")]
+use crate::sqlite_storage::SqliteStorage;
 use crate::{
+    config::StorageKind,
+    metrics,
+    migration::{ItemVersion3, Snapshot},
     ordered_hash_map::OrderedHashMap,
     selection::{SelectionItem, SelectionMetadata, SelectionType},
+    utils::is_plaintext_mime,
 };
 
-const BINCODE_CONFIG: bincode::config::Configuration = bincode::config::standard();
-const BINARY_VERSION: u32 = 2;
+pub(crate) const BINCODE_CONFIG: bincode::config::Configuration = bincode::config::standard();
+// Decoding allocates what length prefixes claim before reading the data, so a corrupted one could
+// ask for more memory than there is. Far above any history memoni saves
+const DECODE_LIMIT: usize = 2 * 1024 * 1024 * 1024;
+pub(crate) const DECODE_CONFIG: bincode::config::Configuration<
+    bincode::config::LittleEndian,
+    bincode::config::Varint,
+    bincode::config::Limit<DECODE_LIMIT>,
+> = BINCODE_CONFIG.with_limit::<DECODE_LIMIT>();
+pub(crate) const BINARY_VERSION: u32 = 9;
+// The version is followed by a CRC-32 of the rest since then, so torn writes and bitrot are told
+// apart from valid data
+const FIRST_CHECKSUM_VERSION: u32 = 9;
+// Pages only hold items, which haven't changed since pages came in with version 5, so pages of
+// any version since then can be read
+const FIRST_PAGE_VERSION: u32 = 5;
+// Items past the hot ones are saved to page files, only loaded once the list is scrolled to them
+// or searched, so a large item_limit doesn't slow down startup or every save
+pub(crate) const HOT_ITEMS: usize = 200;
+pub(crate) const PAGE_ITEMS: usize = 100;
+const PAGE_HASH_SEED: usize = 0x9e3779b97f4a7c15;
 
 struct SaveRequest {
     serialized_data: Vec<u8>,
+    // Written even if the save gets cancelled, as later saves count on them being there
+    new_pages: Vec<(String, Vec<u8>)>,
+    obsolete_pages: Vec<String>,
     cancel_token: Arc<AtomicBool>,
 }
 
+/// A saved page of items, of which only those still in the history are loaded
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct PageRef {
+    file_name: String,
+    ids: Vec<u64>,
+}
+
+impl PageRef {
+    pub fn new(file_name: String, ids: Vec<u64>) -> Self {
+        PageRef { file_name, ids }
+    }
+
+    pub fn ids(&self) -> &[u64] {
+        &self.ids
+    }
+}
+
+/// Saved items older than the loaded ones, in history order
+#[derive(Debug, Default)]
+pub struct ColdPages {
+    pages: VecDeque<PageRef>,
+    // Dropped items to be archived, whose pages are kept until they're taken
+    evicted: Vec<PageRef>,
+}
+
+impl ColdPages {
+    pub fn from_pages(pages: Vec<PageRef>) -> Self {
+        ColdPages {
+            pages: pages.into(),
+            evicted: vec![],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.pages.iter().map(|page| page.ids.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+
+    /// Drops the item from its page without loading it, returns false if it isn't in any
+    pub fn remove(&mut self, item_id: u64) -> bool {
+        let Some(page_idx) = self.pages.iter().position(|p| p.ids.contains(&item_id)) else {
+            return false;
+        };
+        let page = &mut self.pages[page_idx];
+        page.ids.retain(|&id| id != item_id);
+        if page.ids.is_empty() {
+            self.pages.remove(page_idx);
+        }
+        true
+    }
+
+    /// Drops up to `count` of the oldest items, returning how many were dropped. With `archive`,
+    /// they're kept for [`take_evicted`](Self::take_evicted)
+    pub fn truncate_back(&mut self, count: usize, archive: bool) -> usize {
+        let mut dropped = 0;
+        while dropped < count
+            && let Some(page) = self.pages.back_mut()
+        {
+            let page_dropped = (count - dropped).min(page.ids.len());
+            let dropped_ids = page.ids.split_off(page.ids.len() - page_dropped);
+            if archive {
+                self.evicted.push(PageRef {
+                    file_name: page.file_name.clone(),
+                    ids: dropped_ids,
+                });
+            }
+            dropped += page_dropped;
+            if page.ids.is_empty() {
+                self.pages.pop_back();
+            }
+        }
+        dropped
+    }
+
+    /// Items dropped for archiving since the last call, to be loaded with
+    /// [`Storage::load_page`] before the next save
+    pub fn take_evicted(&mut self) -> Vec<PageRef> {
+        mem::take(&mut self.evicted)
+    }
+
+    /// Takes the page right after the loaded items
+    pub fn pop_front(&mut self) -> Option<PageRef> {
+        self.pages.pop_front()
+    }
+
+    pub fn clear(&mut self) {
+        self.pages.clear();
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.pages.iter().flat_map(|page| page.ids.iter().copied())
+    }
+
+    /// Ids of the items dropped for archiving but not taken yet
+    pub fn evicted_ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.evicted
+            .iter()
+            .flat_map(|page| page.ids.iter().copied())
+    }
+}
+
+/// Where the history is saved, see `storage` in the config
+pub trait Storage {
+    fn save_selection_data(
+        &mut self,
+        items: &OrderedHashMap<u64, SelectionItem>,
+        metadata: &SelectionMetadata,
+        cold_pages: &ColdPages,
+    ) -> Result<()>;
+
+    /// Whether any history has ever been saved, even if it has been emptied since
+    fn has_selection_data(&self) -> bool;
+
+    fn load_selection_data(
+        &mut self,
+    ) -> Result<(
+        OrderedHashMap<u64, SelectionItem>,
+        SelectionMetadata,
+        ColdPages,
+    )>;
+
+    /// Items of a page still in the history, in history order
+    fn load_page(&self, page: &PageRef) -> Result<Vec<SelectionItem>>;
+
+    /// Bytes the history takes on disk, as of the last save
+    fn disk_usage(&self) -> u64;
+
+    /// Bytes of the items not loaded yet
+    fn cold_pages_size(&self, cold_pages: &ColdPages) -> u64;
+
+    /// Where items pushed out of the history are archived, see [`Archive`](crate::archive::Archive)
+    fn archive_file_path(&self) -> &Path;
+
+    fn save_window_pos(&self, pos: (i16, i16)) -> Result<()>;
+
+    fn load_window_pos(&self) -> Option<(i16, i16)>;
+}
+
+/// Opens the history of `selection_type` in the storage of `kind`
+pub fn open(
+    kind: StorageKind,
+    selection_type: SelectionType,
+    session_id: &Option<String>,
+) -> Result<Box<dyn Storage>> {
+    match kind {
+        StorageKind::File => Ok(Box::new(Persistence::new(selection_type, session_id)?)),
+        #[cfg(feature = "sqlite")]
+        StorageKind::Sqlite => Ok(Box::new(SqliteStorage::open(selection_type, session_id)?)),
+        #[cfg(not(feature = "sqlite"))]
+        StorageKind::Sqlite => {
+            warn!("storage is \"sqlite\", but memoni was built without the sqlite feature");
+            Ok(Box::new(Persistence::new(selection_type, session_id)?))
+        }
+    }
+}
+
+/// Path of a file of the history of `selection_type` in the data directory, e.g.,
+/// "clipboard_pages" for `kind` "pages"
+pub(crate) fn data_file_path(
+    selection_type: SelectionType,
+    session_id: &Option<String>,
+    kind: &str,
+) -> Result<PathBuf> {
+    let xdg_data_home = dirs::data_dir()
+        .ok_or_else(|| anyhow!("data directory not found"))?
+        .join("memoni");
+    fs::create_dir_all(&xdg_data_home)?;
+
+    let selection = selection_type.to_string().to_lowercase();
+    Ok(xdg_data_home.join(match session_id {
+        Some(id) => format!("{selection}_{id}_{kind}"),
+        None => format!("{selection}_{kind}"),
+    }))
+}
+
 pub struct Persistence {
     file_path: PathBuf,
+    // The previous generation of the history file, loaded when the current one is corrupted
+    backup_file_path: PathBuf,
+    pages_dir_path: PathBuf,
+    window_pos_file_path: PathBuf,
+    quarantine_file_path: PathBuf,
+    archive_file_path: PathBuf,
     sender: mpsc::Sender<SaveRequest>,
     current_cancel_token: Option<Arc<AtomicBool>>,
+    // Page files on disk, or about to be written by the save thread, with their sizes
+    page_files: HashMap<String, u64>,
+    // Size of the history file as last saved or loaded
+    file_size: u64,
 }
 
 impl Persistence {
-    pub fn new(selection_type: SelectionType, display_id: &Option<String>) -> Result<Self> {
-        let xdg_data_home = dirs::data_dir()
-            .ok_or_else(|| anyhow!("data directory not found"))?
-            .join("memoni");
-        fs::create_dir_all(&xdg_data_home)?;
-
-        let file_name = if let Some(id) = display_id {
-            format!(
-                "{}_{}_selections",
-                selection_type.to_string().to_lowercase(),
-                id
-            )
-        } else {
-            format!("{}_selections", selection_type.to_string().to_lowercase())
-        };
-        let file_path = xdg_data_home.join(file_name);
+    pub fn new(selection_type: SelectionType, session_id: &Option<String>) -> Result<Self> {
+        let file_path = data_file_path(selection_type, session_id, "selections")?;
+        let pages_dir_path = data_file_path(selection_type, session_id, "pages")?;
+        let window_pos_file_path = data_file_path(selection_type, session_id, "window_pos")?;
+        let quarantine_file_path = data_file_path(selection_type, session_id, "quarantine")?;
+        let archive_file_path = data_file_path(selection_type, session_id, "archive")?;
         let temp_file_path = file_path.with_extension("tmp");
+        let backup_file_path = file_path.with_extension("bak");
 
         let (sender, receiver) = mpsc::channel::<SaveRequest>();
         let file_path_clone = file_path.clone();
+        let backup_file_path_clone = backup_file_path.clone();
+        let pages_dir_path_clone = pages_dir_path.clone();
         thread::spawn(move || {
+            // Dropped by the last completed save but still referenced by the backup, so they go
+            // once the next save makes that one the backup
+            let mut backup_pages: Vec<String> = vec![];
             while let Ok(request) = receiver.recv() {
-                if let Err(e) = write_to_disk(
+                for (file_name, data) in &request.new_pages {
+                    backup_pages.retain(|page| page != file_name);
+                    if let Err(e) = write_page(&pages_dir_path_clone, file_name, data) {
+                        error!("failed to save page {file_name} in background: {e}");
+                    }
+                }
+                match write_to_disk(
                     &file_path_clone,
+                    &backup_file_path_clone,
                     &temp_file_path,
                     &request.serialized_data,
                     &request.cancel_token,
                 ) {
-                    error!("failed to save selection items in background: {e}");
+                    // Pages dropped by a cancelled save may still be referenced by the file
+                    Ok(true) => {
+                        for file_name in mem::replace(&mut backup_pages, request.obsolete_pages) {
+                            if let Err(e) = fs::remove_file(pages_dir_path_clone.join(&file_name)) {
+                                debug!("failed to remove obsolete page {file_name}: {e}");
+                            }
+                        }
+                    }
+                    Ok(false) => {}
+                    Err(e) => error!("failed to save selection items in background: {e}"),
                 }
             }
         });
 
         Ok(Persistence {
             file_path,
+            backup_file_path,
+            pages_dir_path,
+            window_pos_file_path,
+            quarantine_file_path,
+            archive_file_path,
             sender,
             current_cancel_token: None,
+            page_files: HashMap::new(),
+            file_size: 0,
         })
     }
+}
 
-    pub fn save_selection_data(
+impl Storage for Persistence {
+    fn save_selection_data(
         &mut self,
         items: &OrderedHashMap<u64, SelectionItem>,
         metadata: &SelectionMetadata,
+        cold_pages: &ColdPages,
     ) -> Result<()> {
         info!("saving selection items to {:?}", self.file_path);
 
@@ -87,105 +327,430 @@ impl Persistence {
         let cancel_token = Arc::new(AtomicBool::new(false));
         self.current_cancel_token = Some(cancel_token.clone());
 
-        let serialized_data = bincode::encode_to_vec((items, metadata), BINCODE_CONFIG)?;
+        // Paged from the oldest end, so items leaving the hot ones don't shift the pages after them
+        let hot_count = HOT_ITEMS.max(metadata.pinned_count).min(items.len());
+        let inline_count = hot_count + (items.len() - hot_count) % PAGE_ITEMS;
+        let mut inline_items = OrderedHashMap::new();
+        for (&id, item) in items.iter().take(inline_count) {
+            inline_items.push_back(id, item);
+        }
+
+        let mut page_refs = vec![];
+        let mut new_pages = vec![];
+        let paged_items = items.iter().skip(inline_count).collect::<Vec<_>>();
+        for chunk in paged_items.chunks(PAGE_ITEMS) {
+            let ids = chunk.iter().map(|&(&id, _)| id).collect::<Vec<_>>();
+            let file_name = format!(
+                "{:016x}",
+                ahash::RandomState::with_seed(PAGE_HASH_SEED).hash_one(&ids)
+            );
+            if !self.page_files.contains_key(&file_name) {
+                let page_items = chunk.iter().map(|&(_, item)| item).collect::<Vec<_>>();
+                let payload = bincode::encode_to_vec(&page_items, BINCODE_CONFIG)?;
+                let mut data = file_header(&payload).to_vec();
+                data.extend(payload);
+                self.page_files.insert(file_name.clone(), data.len() as u64);
+                new_pages.push((file_name.clone(), data));
+            }
+            page_refs.push(PageRef { file_name, ids });
+        }
+        page_refs.extend(cold_pages.pages.iter().cloned());
+
+        let referenced = page_refs
+            .iter()
+            .chain(&cold_pages.evicted)
+            .map(|page| &page.file_name)
+            .collect::<HashSet<_>>();
+        let obsolete_pages = self
+            .page_files
+            .keys()
+            .filter(|file_name| !referenced.contains(file_name))
+            .cloned()
+            .collect::<Vec<_>>();
+        for file_name in &obsolete_pages {
+            self.page_files.remove(file_name);
+        }
+        debug!(
+            "saving {inline_count} items inline and {} pages, {} new, {} obsolete",
+            page_refs.len(),
+            new_pages.len(),
+            obsolete_pages.len()
+        );
+
+        let serialized_data =
+            bincode::encode_to_vec((inline_items, metadata, page_refs), BINCODE_CONFIG)?;
+        self.file_size = serialized_data.len() as u64;
+        metrics::set_history(items.len() + cold_pages.len(), serialized_data.len());
         self.sender.send(SaveRequest {
             serialized_data,
+            new_pages,
+            obsolete_pages,
             cancel_token,
         })?;
 
         Ok(())
     }
 
-    pub fn load_selection_data(
-        &self,
-    ) -> Result<(OrderedHashMap<u64, SelectionItem>, SelectionMetadata)> {
-        if !self.file_path.exists() {
+    fn has_selection_data(&self) -> bool {
+        self.file_path.exists() || self.backup_file_path.exists()
+    }
+
+    fn load_selection_data(
+        &mut self,
+    ) -> Result<(
+        OrderedHashMap<u64, SelectionItem>,
+        SelectionMetadata,
+        ColdPages,
+    )> {
+        if !self.has_selection_data() {
             info!("no persisted selection items file presented, skip loading");
-            return Ok((OrderedHashMap::new(), SelectionMetadata::default()));
+            return Ok((
+                OrderedHashMap::new(),
+                SelectionMetadata::default(),
+                ColdPages::default(),
+            ));
         }
 
         info!("loading selection items from {:?}", self.file_path);
-        let mut file = File::open(&self.file_path)?;
-
-        let mut version_buf = [0u8; 4];
-        file.read_exact(&mut version_buf)?;
-        let version = u32::from_le_bytes(version_buf);
+        let (data, (mut items, mut metadata, cold_pages), backup) = match read_selection_data(
+            &self.file_path,
+        ) {
+            Ok((data, decoded)) => {
+                let backup = read_selection_data(&self.backup_file_path).ok();
+                (data, decoded, backup.map(|(_, (_, _, pages))| pages))
+            }
+            Err(err) => {
+                warn!(
+                    "failed to load selection items: {err}, loading the previous ones from {:?}",
+                    self.backup_file_path
+                );
+                let (data, decoded) =
+                    read_selection_data(&self.backup_file_path).map_err(|_| err)?;
+                (data, decoded, None)
+            }
+        };
+        self.quarantine_undecodable_items(&mut items, &mut metadata);
+        // Pages only the backup references are kept until the next save makes them obsolete
+        self.page_files = cold_pages
+            .pages
+            .iter()
+            .chain(backup.iter().flat_map(|backup| &backup.pages))
+            .map(|page| {
+                let size = fs::metadata(self.pages_dir_path.join(&page.file_name))
+                    .map_or(0, |metadata| metadata.len());
+                (page.file_name.clone(), size)
+            })
+            .collect();
+        self.file_size = data.len() as u64;
+        self.remove_unreferenced_pages();
 
-        let mut data = Vec::new();
-        file.read_to_end(&mut data)?;
+        info!(
+            "{} items loaded, {} more in {} pages",
+            items.len(),
+            cold_pages.len(),
+            cold_pages.pages.len()
+        );
+        metrics::set_history(items.len() + cold_pages.len(), data.len());
+        Ok((items, metadata, cold_pages))
+    }
 
-        let items: Result<(OrderedHashMap<u64, SelectionItem>, SelectionMetadata)> = match version {
-            // version 1 does not have version field unfortunately
-            2 => bincode::decode_from_slice(&data, BINCODE_CONFIG)
-                .map(|(items, _)| items)
-                .map_err(Into::into),
-            _ => Err(anyhow!("invalid binary version")),
+    fn load_page(&self, page: &PageRef) -> Result<Vec<SelectionItem>> {
+        debug!("loading page {}", page.file_name);
+        let data = fs::read(self.pages_dir_path.join(&page.file_name))?;
+        let Some((version_buf, versioned_data)) = data.split_first_chunk::<4>() else {
+            bail!("page {} too short", page.file_name);
         };
+        let version = u32::from_le_bytes(*version_buf);
+        if !(FIRST_PAGE_VERSION..=BINARY_VERSION).contains(&version) {
+            bail!("page {} has an invalid binary version", page.file_name);
+        }
+        let payload = checked_payload(version, versioned_data)
+            .map_err(|e| anyhow!("page {}: {e}", page.file_name))?;
+        let (page_items, _): (Vec<SelectionItem>, _) =
+            bincode::decode_from_slice(payload, DECODE_CONFIG)?;
+
+        let mut page_items = page_items
+            .into_iter()
+            .map(|item| (item.id, item))
+            .collect::<HashMap<_, _>>();
+        let (items, undecodable) = page
+            .ids
+            .iter()
+            .filter_map(|id| page_items.remove(id))
+            .partition::<Vec<_>, _>(|item| !is_undecodable(item));
+        if !undecodable.is_empty() {
+            self.quarantine(undecodable);
+        }
+        Ok(items)
+    }
+
+    fn disk_usage(&self) -> u64 {
+        self.file_size + self.page_files.values().sum::<u64>()
+    }
+
+    fn cold_pages_size(&self, cold_pages: &ColdPages) -> u64 {
+        cold_pages
+            .pages
+            .iter()
+            .filter_map(|page| self.page_files.get(&page.file_name))
+            .sum()
+    }
+
+    fn archive_file_path(&self) -> &Path {
+        &self.archive_file_path
+    }
 
-        let items = (match items {
-            Ok(items) => Ok(items),
+    fn save_window_pos(&self, pos: (i16, i16)) -> Result<()> {
+        debug!(
+            "saving window position {pos:?} to {:?}",
+            self.window_pos_file_path
+        );
+        fs::write(
+            &self.window_pos_file_path,
+            bincode::encode_to_vec(pos, BINCODE_CONFIG)?,
+        )?;
+        Ok(())
+    }
+
+    fn load_window_pos(&self) -> Option<(i16, i16)> {
+        let data = fs::read(&self.window_pos_file_path).ok()?;
+        match bincode::decode_from_slice(&data, BINCODE_CONFIG) {
+            Ok((pos, _)) => {
+                debug!("loaded window position {pos:?}");
+                Some(pos)
+            }
             Err(err) => {
-                debug!("decoding failed, trying to decode using version 1 format");
-                data.splice(0..0, version_buf);
-                decode_version_1(&data).map_err(|ver1_err| {
-                    debug!("decoding using version 1 format failed: {ver1_err}");
-                    err
-                })
+                error!("failed to decode persisted window position: {err}");
+                None
             }
-        })?;
+        }
+    }
+}
 
-        info!("{} items loaded", items.0.len());
-        Ok(items)
+impl Persistence {
+    // Left behind by saves that got cancelled before the file referencing them was written
+    fn remove_unreferenced_pages(&self) {
+        let Ok(entries) = fs::read_dir(&self.pages_dir_path) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let file_name = entry.file_name();
+            if file_name
+                .to_str()
+                .is_some_and(|name| !self.page_files.contains_key(name))
+            {
+                debug!("removing unreferenced page {file_name:?}");
+                if let Err(e) = fs::remove_file(entry.path()) {
+                    warn!("failed to remove unreferenced page {file_name:?}: {e}");
+                }
+            }
+        }
+    }
+
+    // Text targets are expected to be UTF-8 everywhere else, so a corrupted item is moved aside
+    // for inspection instead of failing the whole history
+    fn quarantine_undecodable_items(
+        &self,
+        items: &mut OrderedHashMap<u64, SelectionItem>,
+        metadata: &mut SelectionMetadata,
+    ) {
+        let undecodable_ids = items
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, item))| is_undecodable(item))
+            .map(|(idx, (&id, _))| (idx, id))
+            .collect::<Vec<_>>();
+        if undecodable_ids.is_empty() {
+            return;
+        }
+
+        let pinned_count = metadata.pinned_count;
+        let mut undecodable = vec![];
+        for &(idx, id) in &undecodable_ids {
+            if idx < pinned_count {
+                metadata.pinned_count -= 1;
+            }
+            undecodable.extend(items.remove(&id));
+        }
+        self.quarantine(undecodable);
+    }
+
+    fn quarantine(&self, items: Vec<SelectionItem>) {
+        for item in &items {
+            warn!(
+                "item {} has text that isn't valid UTF-8, quarantining it",
+                item.id
+            );
+        }
+        let count = items.len();
+        let mut quarantined = fs::read(&self.quarantine_file_path)
+            .ok()
+            .and_then(|data| decode_quarantined_items(&data))
+            .unwrap_or_default();
+        quarantined.extend(items);
+
+        let result = bincode::encode_to_vec(&quarantined, BINCODE_CONFIG)
+            .map_err(anyhow::Error::from)
+            .and_then(|data| Ok(fs::write(&self.quarantine_file_path, data)?));
+        match result {
+            Ok(()) => info!(
+                "{count} items quarantined to {:?}",
+                self.quarantine_file_path
+            ),
+            Err(err) => error!("failed to write quarantined items: {err}"),
+        }
+    }
+}
+
+// The quarantine file has no version, items of older versions are told apart by not decoding to
+// the end of it
+fn decode_quarantined_items(data: &[u8]) -> Option<Vec<SelectionItem>> {
+    match bincode::decode_from_slice::<Vec<SelectionItem>, _>(data, BINCODE_CONFIG) {
+        Ok((items, len)) if len == data.len() => Some(items),
+        _ => bincode::decode_from_slice::<Vec<ItemVersion3>, _>(data, BINCODE_CONFIG)
+            .ok()
+            .map(|(items, _)| items.into_iter().map(Into::into).collect()),
     }
 }
 
-fn decode_version_1(
+pub(crate) fn is_undecodable(item: &SelectionItem) -> bool {
+    item.data
+        .iter()
+        .any(|(mime, data)| is_utf8_mime(mime) && str::from_utf8(data).is_err())
+}
+
+fn is_utf8_mime(mime: &str) -> bool {
+    is_plaintext_mime(mime) || mime == "text/uri-list" || mime == "x-special/gnome-copied-files"
+}
+
+/// Decodes saved history of any version, which may come from a corrupted or truncated file
+pub fn decode_selection_data(
     data: &[u8],
-) -> Result<(OrderedHashMap<u64, SelectionItem>, SelectionMetadata)> {
-    let old_items: VecDeque<SelectionItem> = bincode::decode_from_slice(data, BINCODE_CONFIG)?.0;
-    let mut new_items = OrderedHashMap::new();
-    for item in old_items {
-        new_items.push_back(item.id, item);
+) -> Result<(
+    OrderedHashMap<u64, SelectionItem>,
+    SelectionMetadata,
+    ColdPages,
+)> {
+    let Some((version_buf, versioned_data)) = data.split_first_chunk::<4>() else {
+        return Err(anyhow!("selection data too short"));
+    };
+    let version = u32::from_le_bytes(*version_buf);
+
+    let (items, mut metadata, page_refs) = match checked_payload(version, versioned_data)
+        .and_then(|payload| Snapshot::decode(version, payload))
+    {
+        Ok(snapshot) => snapshot.into_current(),
+        Err(err) => {
+            debug!("decoding failed, trying to decode using version 1 format");
+            Snapshot::decode_version_1(data)
+                .map_err(|ver1_err| {
+                    debug!("decoding using version 1 format failed: {ver1_err}");
+                    err
+                })?
+                .into_current()
+        }
+    };
+    if !items.is_consistent() {
+        return Err(anyhow!("selection data has items out of order"));
     }
+    metadata.pinned_count = metadata.pinned_count.min(items.len());
+    Ok((items, metadata, ColdPages::from_pages(page_refs)))
+}
 
-    Ok((new_items, SelectionMetadata::default()))
+type SelectionData = (
+    OrderedHashMap<u64, SelectionItem>,
+    SelectionMetadata,
+    ColdPages,
+);
+
+// Missing files are errors too, so a missing history file falls back to the backup
+fn read_selection_data(file_path: &Path) -> Result<(Vec<u8>, SelectionData)> {
+    let data = fs::read(file_path)?;
+    let decoded = decode_selection_data(&data)?;
+    Ok((data, decoded))
 }
 
+// Returns false if a newer save cancelled it
 fn write_to_disk(
     file_path: &PathBuf,
+    backup_file_path: &PathBuf,
     temp_file_path: &PathBuf,
     serialized_data: &[u8],
     cancel_token: &Arc<AtomicBool>,
-) -> Result<()> {
+) -> Result<bool> {
     const CHUNK_SIZE: usize = 64 * 1024;
 
     if cancel_token.load(Ordering::Relaxed) {
         debug!("saving selection items in background cancelled before doing anything");
-        return Ok(());
+        return Ok(false);
     }
     let mut f = File::create(temp_file_path)?;
-    f.write_all(&BINARY_VERSION.to_le_bytes())?;
+    f.write_all(&file_header(serialized_data))?;
 
     for (i, chunk) in serialized_data.chunks(CHUNK_SIZE).enumerate() {
         if cancel_token.load(Ordering::Relaxed) {
             debug!("saving selection items in background cancelled before writing chunk {i}");
-            return Ok(());
+            return Ok(false);
         }
         f.write_all(chunk)?;
     }
 
     if cancel_token.load(Ordering::Relaxed) {
         debug!("saving selection items in background cancelled before syncing to file");
-        return Ok(());
+        return Ok(false);
     }
     f.sync_all()?;
 
     if cancel_token.load(Ordering::Relaxed) {
         debug!("saving selection items in background cancelled before moving temp file to file");
-        return Ok(());
+        return Ok(false);
+    }
+    // Linked rather than moved, so there's a history file at every point in between
+    match fs::remove_file(backup_file_path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+    if file_path.exists()
+        && let Err(e) = fs::hard_link(file_path, backup_file_path)
+    {
+        debug!("failed to link the backup, copying it instead: {e}");
+        fs::copy(file_path, backup_file_path)?;
     }
     fs::rename(temp_file_path, file_path)?;
 
     debug!("saving selection items in background completed");
+    Ok(true)
+}
+
+// The version and the checksum of the data following them
+fn file_header(payload: &[u8]) -> [u8; 8] {
+    let mut header = [0; 8];
+    header[..4].copy_from_slice(&BINARY_VERSION.to_le_bytes());
+    header[4..].copy_from_slice(&crc32fast::hash(payload).to_le_bytes());
+    header
+}
+
+// What follows the version, past the checksum for versions that have one, once it checks out
+fn checked_payload(version: u32, versioned_data: &[u8]) -> Result<&[u8]> {
+    if !(FIRST_CHECKSUM_VERSION..=BINARY_VERSION).contains(&version) {
+        return Ok(versioned_data);
+    }
+    let Some((checksum_buf, payload)) = versioned_data.split_first_chunk::<4>() else {
+        bail!("data too short for its checksum");
+    };
+    if crc32fast::hash(payload) != u32::from_le_bytes(*checksum_buf) {
+        bail!("checksum mismatch, the data is corrupted");
+    }
+    Ok(payload)
+}
+
+fn write_page(pages_dir_path: &Path, file_name: &str, data: &[u8]) -> Result<()> {
+    fs::create_dir_all(pages_dir_path)?;
+    let page_path = pages_dir_path.join(file_name);
+    let temp_page_path = page_path.with_extension("tmp");
+    let mut f = File::create(&temp_page_path)?;
+    f.write_all(data)?;
+    f.sync_all()?;
+    fs::rename(temp_page_path, page_path)?;
     Ok(())
 }
@@ -0,0 +1,88 @@
+//! `memoni pipe`, a client running a shell command with each new text item on stdin, e.g., to
+//! append copied citations to a notes file. It follows the server like `memoni watch` and gets the
+//! full text of each item it's told about
+
+use std::{
+    io::{self, BufRead as _, Write as _},
+    net::Shutdown,
+    os::unix::net::UnixStream,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use anyhow::{Context as _, Result, anyhow, bail};
+use log::{debug, info, warn};
+use serde::Deserialize;
+
+use crate::ipc::{self, ClientCommand, ItemRef, parse_item_id};
+
+#[derive(Deserialize)]
+struct ItemEvent {
+    event: String,
+    id: String,
+    kind: String,
+    redacted: bool,
+}
+
+/// Runs `command` with `sh -c` for each item that becomes the newest one until the server stops.
+/// The newest item at the start and items that look like secrets are skipped
+pub fn run(socket_path: &Path, command: &str) -> Result<()> {
+    debug!("connecting to socket: {socket_path:?}");
+    let mut stream = UnixStream::connect(socket_path)?;
+    stream.write_all(&ClientCommand::Watch.to_bytes())?;
+
+    for line in io::BufReader::new(stream).lines() {
+        let event: ItemEvent = serde_json::from_str(&line?)?;
+        if event.event != "new_item" || event.kind != "text" || event.redacted {
+            continue;
+        }
+        let item_id = parse_item_id(event.id.as_bytes())
+            .ok_or_else(|| anyhow!("invalid item id {:?}", event.id))?;
+
+        // Removed already, e.g., merged into the next one
+        let Some(text) = item_text(socket_path, item_id)? else {
+            debug!("item {} is gone, skipping it", event.id);
+            continue;
+        };
+        // Commands are run one at a time, so what they append stays in order
+        if let Err(e) = pipe(command, &text) {
+            warn!("failed to pipe item {}: {e:?}", event.id);
+        }
+    }
+
+    bail!("memoni server stopped")
+}
+
+fn item_text(socket_path: &Path, item_id: u64) -> Result<Option<Vec<u8>>> {
+    let mut stream = UnixStream::connect(socket_path)?;
+    let command = ClientCommand::Get {
+        item: ItemRef::Id(item_id),
+        mime: None,
+    };
+    stream.write_all(&command.to_bytes())?;
+    stream.shutdown(Shutdown::Write)?;
+    let mut text = vec![];
+    Ok(ipc::read_chunked(&mut stream, &mut text)?.then_some(text))
+}
+
+fn pipe(command: &str, text: &[u8]) -> Result<()> {
+    info!("piping {} bytes to {command:?}", text.len());
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to run {command:?}"))?;
+    // A command that doesn't read all of it closes the pipe early, which isn't an error
+    if let Some(mut stdin) = child.stdin.take()
+        && let Err(e) = stdin.write_all(text)
+        && e.kind() != io::ErrorKind::BrokenPipe
+    {
+        return Err(e.into());
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        bail!("{command:?} exited with {status}");
+    }
+    Ok(())
+}
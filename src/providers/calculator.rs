@@ -0,0 +1,274 @@
+use super::{ProvidedItem, Provider, ProviderContext};
+use crate::format::NumberFormat;
+
+/// Offers the result of the search query when it's an arithmetic expression, or of the newest
+/// item ending in "=" when not searching
+pub struct Calculator {
+    number_format: NumberFormat,
+}
+
+impl Calculator {
+    pub fn new(number_format: NumberFormat) -> Self {
+        Calculator { number_format }
+    }
+}
+
+impl Provider for Calculator {
+    fn name(&self) -> &str {
+        "Calculator"
+    }
+
+    fn provide(&mut self, context: &ProviderContext) -> Vec<ProvidedItem> {
+        // Dates and phone numbers copied are arithmetic too, so an item has to ask for its result
+        let expression = if context.query.is_empty() {
+            context.text.filter(|text| text.trim_end().ends_with('='))
+        } else {
+            Some(context.query)
+        };
+        expression
+            .and_then(|text| evaluate(text, &self.number_format))
+            .map(|value| ProvidedItem {
+                text: format_result(value, &self.number_format),
+                detail: None,
+            })
+            .into_iter()
+            .collect()
+    }
+}
+
+// Expressions longer than this are unlikely to be arithmetic typed by hand
+const MAX_EXPRESSION_LEN: usize = 200;
+const MAX_DECIMALS: usize = 10;
+
+/// Evaluates a simple arithmetic expression: numbers, + - * / % ^, parentheses and unary signs,
+/// with the decimal and digit group separators of the locale. None if the text isn't one,
+/// including a bare number, which has nothing to compute
+pub fn evaluate(text: &str, number_format: &NumberFormat) -> Option<f64> {
+    let text = text.trim().trim_end_matches('=').trim_end();
+    if text.is_empty() || text.len() > MAX_EXPRESSION_LEN {
+        return None;
+    }
+
+    let mut parser = Parser {
+        tokens: tokenize(text, number_format)?,
+        pos: 0,
+    };
+    let has_operator = parser
+        .tokens
+        .iter()
+        .skip(1)
+        .any(|t| matches!(t, Token::Op(_)));
+    if !has_operator {
+        return None;
+    }
+
+    let value = parser.expression()?;
+    (parser.pos == parser.tokens.len() && value.is_finite()).then_some(value)
+}
+
+/// Formats a result the way it'd be typed: no exponent, no trailing zeros
+pub fn format_result(value: f64, number_format: &NumberFormat) -> String {
+    let formatted = format!("{value:.MAX_DECIMALS$}");
+    let formatted = formatted.trim_end_matches('0').trim_end_matches('.');
+    match formatted {
+        "-0" => "0".to_string(),
+        _ => formatted.replace('.', &number_format.decimal_separator().to_string()),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+    Number(f64),
+    Op(char),
+    Open,
+    Close,
+}
+
+fn tokenize(text: &str, number_format: &NumberFormat) -> Option<Vec<Token>> {
+    let decimal_separator = number_format.decimal_separator();
+    let mut tokens = vec![];
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            _ if c.is_whitespace() => {
+                chars.next();
+            }
+            _ if c.is_ascii_digit() || c == decimal_separator => {
+                let mut number = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_ascii_digit() {
+                        number.push(c);
+                    } else if c == decimal_separator {
+                        number.push('.');
+                    } else if c == '_' || Some(c) == number_format.thousands_separator() {
+                        // Skipped as in 1_000 or 1,000, but only before a group of three digits,
+                        // so 1,5 isn't 15
+                        let mut group = chars.clone();
+                        group.next();
+                        if group.take_while(char::is_ascii_digit).count() != 3 {
+                            break;
+                        }
+                    } else {
+                        break;
+                    }
+                    chars.next();
+                }
+                // Like the 01 of 2024-01-15 or the 0x of 0x10, which aren't arithmetic
+                let mut digits = number.chars();
+                if digits.next() == Some('0')
+                    && (digits.next().is_some_and(|c| c.is_ascii_digit())
+                        || chars.peek() == Some(&'x'))
+                {
+                    return None;
+                }
+                tokens.push(Token::Number(number.parse().ok()?));
+            }
+            '+' | '-' | '*' | '/' | '%' | '^' => {
+                chars.next();
+                tokens.push(Token::Op(c));
+            }
+            '×' | 'x' | '·' => {
+                chars.next();
+                tokens.push(Token::Op('*'));
+            }
+            '÷' => {
+                chars.next();
+                tokens.push(Token::Op('/'));
+            }
+            '−' => {
+                chars.next();
+                tokens.push(Token::Op('-'));
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            _ => return None,
+        }
+    }
+    Some(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<Token> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn next_op_if(&mut self, ops: &[char]) -> Option<char> {
+        match self.peek() {
+            Some(Token::Op(op)) if ops.contains(&op) => {
+                self.pos += 1;
+                Some(op)
+            }
+            _ => None,
+        }
+    }
+
+    fn expression(&mut self) -> Option<f64> {
+        let mut value = self.term()?;
+        while let Some(op) = self.next_op_if(&['+', '-']) {
+            let rhs = self.term()?;
+            value = if op == '+' { value + rhs } else { value - rhs };
+        }
+        Some(value)
+    }
+
+    fn term(&mut self) -> Option<f64> {
+        let mut value = self.unary()?;
+        while let Some(op) = self.next_op_if(&['*', '/', '%']) {
+            let rhs = self.unary()?;
+            value = match op {
+                '*' => value * rhs,
+                '/' => value / rhs,
+                _ => value % rhs,
+            };
+        }
+        Some(value)
+    }
+
+    // Binds looser than ^, so -2^2 is -4 as in math notation
+    fn unary(&mut self) -> Option<f64> {
+        match self.next_op_if(&['+', '-']) {
+            Some('-') => Some(-self.unary()?),
+            Some(_) => self.unary(),
+            None => self.power(),
+        }
+    }
+
+    // Right associative, 2^3^2 is 2^9
+    fn power(&mut self) -> Option<f64> {
+        let base = self.primary()?;
+        if self.next_op_if(&['^']).is_some() {
+            let exponent = self.unary()?;
+            return Some(base.powf(exponent));
+        }
+        Some(base)
+    }
+
+    fn primary(&mut self) -> Option<f64> {
+        let token = self.peek()?;
+        self.pos += 1;
+        match token {
+            Token::Number(n) => Some(n),
+            Token::Open => {
+                let value = self.expression()?;
+                (self.peek()? == Token::Close).then(|| {
+                    self.pos += 1;
+                    value
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluates_with_locale_separators() {
+        let en = NumberFormat::from_locale("en_US.UTF-8");
+        assert_eq!(evaluate("12 * (3 + 4)", &en), Some(84.0));
+        assert_eq!(evaluate("1,000 / 8 =", &en), Some(125.0));
+        assert_eq!(evaluate("1,5 + 1", &en), None);
+
+        let de = NumberFormat::from_locale("de_DE.UTF-8");
+        assert_eq!(evaluate("1,5 + 1.000", &de), Some(1001.5));
+        assert_eq!(format_result(2.5, &de), "2,5");
+    }
+
+    #[test]
+    fn rejects_dates_and_hex() {
+        let en = NumberFormat::from_locale("en_US.UTF-8");
+        assert_eq!(evaluate("2024-01-15", &en), None);
+        assert_eq!(evaluate("0x10", &en), None);
+        assert_eq!(evaluate("0.5 x 4", &en), Some(2.0));
+    }
+
+    #[test]
+    fn items_need_a_trailing_equals_sign() {
+        let mut calculator = Calculator::new(NumberFormat::from_locale("en_US.UTF-8"));
+        let provide = |calculator: &mut Calculator, text| {
+            calculator
+                .provide(&ProviderContext {
+                    query: "",
+                    text: Some(text),
+                })
+                .into_iter()
+                .map(|item| item.text)
+                .collect::<Vec<_>>()
+        };
+        assert!(provide(&mut calculator, "555-1234").is_empty());
+        assert_eq!(provide(&mut calculator, "555-1234 ="), ["-679"]);
+    }
+}
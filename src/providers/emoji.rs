@@ -0,0 +1,499 @@
+use crate::search::fuzzy_score;
+
+use super::{ProvidedItem, Provider, ProviderContext};
+
+const MAX_RESULTS: usize = 30;
+
+/// Lists emoji and symbols whose name matches a search query starting with ':'
+pub struct Emoji;
+
+impl Provider for Emoji {
+    fn name(&self) -> &str {
+        "Emoji"
+    }
+
+    fn provide(&mut self, context: &ProviderContext) -> Vec<ProvidedItem> {
+        let Some(pattern) = context.query.strip_prefix(':') else {
+            return vec![];
+        };
+
+        let mut scored = EMOJI
+            .iter()
+            .filter_map(|&(symbol, name)| fuzzy_score(pattern, name).map(|s| (s, symbol, name)))
+            .collect::<Vec<_>>();
+        // Stable, so equally good matches keep the table order, most common first
+        scored.sort_by_key(|&(score, _, _)| std::cmp::Reverse(score));
+        scored
+            .into_iter()
+            .take(MAX_RESULTS)
+            .map(|(_, symbol, name)| ProvidedItem {
+                text: symbol.to_string(),
+                detail: Some(name.to_string()),
+            })
+            .collect()
+    }
+}
+
+// Roughly by how often they're used. Names follow Unicode's CLDR short names
+#[rustfmt::skip]
+const EMOJI: &[(&str, &str)] = &[
+    ("😂", "face with tears of joy"),
+    ("❤️", "red heart"),
+    ("🤣", "rolling on the floor laughing"),
+    ("👍", "thumbs up"),
+    ("😭", "loudly crying face"),
+    ("🙏", "folded hands"),
+    ("😘", "face blowing a kiss"),
+    ("🥰", "smiling face with hearts"),
+    ("😍", "smiling face with heart-eyes"),
+    ("😊", "smiling face with smiling eyes"),
+    ("🎉", "party popper"),
+    ("😁", "beaming face with smiling eyes"),
+    ("💕", "two hearts"),
+    ("🥺", "pleading face"),
+    ("😅", "grinning face with sweat"),
+    ("🔥", "fire"),
+    ("☺️", "smiling face"),
+    ("🤦", "person facepalming"),
+    ("♥️", "heart suit"),
+    ("🤷", "person shrugging"),
+    ("🙄", "face with rolling eyes"),
+    ("😆", "grinning squinting face"),
+    ("🤗", "smiling face with open hands"),
+    ("😉", "winking face"),
+    ("🎂", "birthday cake"),
+    ("🤔", "thinking face"),
+    ("👏", "clapping hands"),
+    ("🙂", "slightly smiling face"),
+    ("😳", "flushed face"),
+    ("🥳", "partying face"),
+    ("😎", "smiling face with sunglasses"),
+    ("👌", "OK hand"),
+    ("💜", "purple heart"),
+    ("😔", "pensive face"),
+    ("💪", "flexed biceps"),
+    ("✨", "sparkles"),
+    ("💖", "sparkling heart"),
+    ("👀", "eyes"),
+    ("😋", "face savoring food"),
+    ("😏", "smirking face"),
+    ("😢", "crying face"),
+    ("👉", "backhand index pointing right"),
+    ("👈", "backhand index pointing left"),
+    ("👆", "backhand index pointing up"),
+    ("👇", "backhand index pointing down"),
+    ("💗", "growing heart"),
+    ("😩", "weary face"),
+    ("💯", "hundred points"),
+    ("🌹", "rose"),
+    ("💞", "revolving hearts"),
+    ("🎈", "balloon"),
+    ("💙", "blue heart"),
+    ("💚", "green heart"),
+    ("💛", "yellow heart"),
+    ("🧡", "orange heart"),
+    ("🖤", "black heart"),
+    ("🤍", "white heart"),
+    ("💔", "broken heart"),
+    ("😃", "grinning face with big eyes"),
+    ("😀", "grinning face"),
+    ("😄", "grinning face with smiling eyes"),
+    ("😇", "smiling face with halo"),
+    ("🙃", "upside-down face"),
+    ("😜", "winking face with tongue"),
+    ("😛", "face with tongue"),
+    ("🤪", "zany face"),
+    ("🤨", "face with raised eyebrow"),
+    ("🧐", "face with monocle"),
+    ("🤓", "nerd face"),
+    ("🤩", "star-struck"),
+    ("😒", "unamused face"),
+    ("😞", "disappointed face"),
+    ("😟", "worried face"),
+    ("😕", "confused face"),
+    ("☹️", "frowning face"),
+    ("😣", "persevering face"),
+    ("😖", "confounded face"),
+    ("😫", "tired face"),
+    ("😤", "face with steam from nose"),
+    ("😠", "angry face"),
+    ("😡", "enraged face"),
+    ("🤬", "face with symbols on mouth"),
+    ("🤯", "exploding head"),
+    ("😱", "face screaming in fear"),
+    ("😨", "fearful face"),
+    ("😰", "anxious face with sweat"),
+    ("😥", "sad but relieved face"),
+    ("😓", "downcast face with sweat"),
+    ("🤭", "face with hand over mouth"),
+    ("🤫", "shushing face"),
+    ("🤥", "lying face"),
+    ("😶", "face without mouth"),
+    ("😐", "neutral face"),
+    ("😑", "expressionless face"),
+    ("😬", "grimacing face"),
+    ("😮", "face with open mouth"),
+    ("😲", "astonished face"),
+    ("🥱", "yawning face"),
+    ("😴", "sleeping face"),
+    ("🤤", "drooling face"),
+    ("😪", "sleepy face"),
+    ("😵", "face with crossed-out eyes"),
+    ("🤐", "zipper-mouth face"),
+    ("🥴", "woozy face"),
+    ("🤢", "nauseated face"),
+    ("🤮", "face vomiting"),
+    ("🤧", "sneezing face"),
+    ("😷", "face with medical mask"),
+    ("🤒", "face with thermometer"),
+    ("🤕", "face with head-bandage"),
+    ("🤑", "money-mouth face"),
+    ("🤠", "cowboy hat face"),
+    ("😈", "smiling face with horns"),
+    ("👿", "angry face with horns"),
+    ("💀", "skull"),
+    ("☠️", "skull and crossbones"),
+    ("💩", "pile of poo"),
+    ("🤡", "clown face"),
+    ("👻", "ghost"),
+    ("👽", "alien"),
+    ("🤖", "robot"),
+    ("🎃", "jack-o-lantern"),
+    ("😺", "grinning cat"),
+    ("🙈", "see-no-evil monkey"),
+    ("🙉", "hear-no-evil monkey"),
+    ("🙊", "speak-no-evil monkey"),
+    ("👋", "waving hand"),
+    ("🤚", "raised back of hand"),
+    ("✋", "raised hand"),
+    ("🖖", "vulcan salute"),
+    ("✌️", "victory hand"),
+    ("🤞", "crossed fingers"),
+    ("🤟", "love-you gesture"),
+    ("🤘", "sign of the horns"),
+    ("🤙", "call me hand"),
+    ("👎", "thumbs down"),
+    ("✊", "raised fist"),
+    ("👊", "oncoming fist"),
+    ("🙌", "raising hands"),
+    ("👐", "open hands"),
+    ("🤝", "handshake"),
+    ("✍️", "writing hand"),
+    ("🫶", "heart hands"),
+    ("🧠", "brain"),
+    ("👶", "baby"),
+    ("🧑", "person"),
+    ("👨", "man"),
+    ("👩", "woman"),
+    ("🙋", "person raising hand"),
+    ("🙅", "person gesturing no"),
+    ("🙆", "person gesturing OK"),
+    ("💁", "person tipping hand"),
+    ("🏃", "person running"),
+    ("💃", "woman dancing"),
+    ("🐶", "dog face"),
+    ("🐱", "cat face"),
+    ("🐭", "mouse face"),
+    ("🐰", "rabbit face"),
+    ("🦊", "fox"),
+    ("🐻", "bear"),
+    ("🐼", "panda"),
+    ("🐨", "koala"),
+    ("🐯", "tiger face"),
+    ("🦁", "lion"),
+    ("🐮", "cow face"),
+    ("🐷", "pig face"),
+    ("🐸", "frog"),
+    ("🐵", "monkey face"),
+    ("🐔", "chicken"),
+    ("🐧", "penguin"),
+    ("🐦", "bird"),
+    ("🦆", "duck"),
+    ("🦉", "owl"),
+    ("🐝", "honeybee"),
+    ("🐛", "bug"),
+    ("🦋", "butterfly"),
+    ("🐢", "turtle"),
+    ("🐍", "snake"),
+    ("🐙", "octopus"),
+    ("🐟", "fish"),
+    ("🐳", "spouting whale"),
+    ("🦀", "crab"),
+    ("🦄", "unicorn"),
+    ("🌸", "cherry blossom"),
+    ("🌻", "sunflower"),
+    ("🌷", "tulip"),
+    ("🌱", "seedling"),
+    ("🌲", "evergreen tree"),
+    ("🍀", "four leaf clover"),
+    ("🍁", "maple leaf"),
+    ("🌍", "globe showing Europe-Africa"),
+    ("🌙", "crescent moon"),
+    ("⭐", "star"),
+    ("🌟", "glowing star"),
+    ("☀️", "sun"),
+    ("⛅", "sun behind cloud"),
+    ("☁️", "cloud"),
+    ("🌧️", "cloud with rain"),
+    ("⛈️", "cloud with lightning and rain"),
+    ("❄️", "snowflake"),
+    ("⚡", "high voltage"),
+    ("🌈", "rainbow"),
+    ("☔", "umbrella with rain drops"),
+    ("💧", "droplet"),
+    ("🌊", "water wave"),
+    ("🍎", "red apple"),
+    ("🍌", "banana"),
+    ("🍉", "watermelon"),
+    ("🍓", "strawberry"),
+    ("🍑", "peach"),
+    ("🍒", "cherries"),
+    ("🥑", "avocado"),
+    ("🍕", "pizza"),
+    ("🍔", "hamburger"),
+    ("🍟", "french fries"),
+    ("🌮", "taco"),
+    ("🍣", "sushi"),
+    ("🍜", "steaming bowl"),
+    ("🍰", "shortcake"),
+    ("🍩", "doughnut"),
+    ("🍪", "cookie"),
+    ("🍫", "chocolate bar"),
+    ("🍿", "popcorn"),
+    ("☕", "hot beverage"),
+    ("🍵", "teacup without handle"),
+    ("🍺", "beer mug"),
+    ("🍻", "clinking beer mugs"),
+    ("🍷", "wine glass"),
+    ("🥂", "clinking glasses"),
+    ("⚽", "soccer ball"),
+    ("🏀", "basketball"),
+    ("🏆", "trophy"),
+    ("🥇", "1st place medal"),
+    ("🎮", "video game"),
+    ("🎲", "game die"),
+    ("🎵", "musical note"),
+    ("🎶", "musical notes"),
+    ("🎸", "guitar"),
+    ("🎧", "headphone"),
+    ("🎬", "clapper board"),
+    ("🎨", "artist palette"),
+    ("🎁", "wrapped gift"),
+    ("🎊", "confetti ball"),
+    ("🎄", "Christmas tree"),
+    ("🚀", "rocket"),
+    ("✈️", "airplane"),
+    ("🚗", "automobile"),
+    ("🚲", "bicycle"),
+    ("🚌", "bus"),
+    ("🚆", "train"),
+    ("🏠", "house"),
+    ("🏢", "office building"),
+    ("⛺", "tent"),
+    ("🗺️", "world map"),
+    ("⌛", "hourglass done"),
+    ("⏰", "alarm clock"),
+    ("⏱️", "stopwatch"),
+    ("📅", "calendar"),
+    ("📌", "pushpin"),
+    ("📎", "paperclip"),
+    ("📋", "clipboard"),
+    ("📝", "memo"),
+    ("✏️", "pencil"),
+    ("📚", "books"),
+    ("📖", "open book"),
+    ("📦", "package"),
+    ("📧", "e-mail"),
+    ("📨", "incoming envelope"),
+    ("📞", "telephone receiver"),
+    ("📱", "mobile phone"),
+    ("💻", "laptop"),
+    ("🖥️", "desktop computer"),
+    ("⌨️", "keyboard"),
+    ("🖱️", "computer mouse"),
+    ("💾", "floppy disk"),
+    ("📷", "camera"),
+    ("🔍", "magnifying glass tilted left"),
+    ("💡", "light bulb"),
+    ("🔋", "battery"),
+    ("🔌", "electric plug"),
+    ("🔑", "key"),
+    ("🔒", "locked"),
+    ("🔓", "unlocked"),
+    ("🔔", "bell"),
+    ("🔗", "link"),
+    ("🔧", "wrench"),
+    ("🔨", "hammer"),
+    ("⚙️", "gear"),
+    ("🧪", "test tube"),
+    ("🐞", "lady beetle"),
+    ("🩹", "adhesive bandage"),
+    ("💊", "pill"),
+    ("💰", "money bag"),
+    ("💸", "money with wings"),
+    ("💳", "credit card"),
+    ("📈", "chart increasing"),
+    ("📉", "chart decreasing"),
+    ("📊", "bar chart"),
+    ("🗑️", "wastebasket"),
+    ("🚧", "construction"),
+    ("🚨", "police car light"),
+    ("🚫", "prohibited"),
+    ("⛔", "no entry"),
+    ("⚠️", "warning"),
+    ("❗", "red exclamation mark"),
+    ("❓", "red question mark"),
+    ("‼️", "double exclamation mark"),
+    ("⁉️", "exclamation question mark"),
+    ("✅", "check mark button"),
+    ("✔️", "check mark"),
+    ("☑️", "check box with check"),
+    ("❌", "cross mark"),
+    ("❎", "cross mark button"),
+    ("➕", "plus"),
+    ("➖", "minus"),
+    ("➗", "divide"),
+    ("✖️", "multiply"),
+    ("♻️", "recycling symbol"),
+    ("🆗", "OK button"),
+    ("🆕", "NEW button"),
+    ("🆓", "FREE button"),
+    ("🔴", "red circle"),
+    ("🟠", "orange circle"),
+    ("🟡", "yellow circle"),
+    ("🟢", "green circle"),
+    ("🔵", "blue circle"),
+    ("🟣", "purple circle"),
+    ("⚫", "black circle"),
+    ("⚪", "white circle"),
+    ("🟥", "red square"),
+    ("🟩", "green square"),
+    ("🟦", "blue square"),
+    ("🏁", "chequered flag"),
+    ("🚩", "triangular flag"),
+    ("🏳️", "white flag"),
+    ("🏴", "black flag"),
+    ("🏳️‍🌈", "rainbow flag"),
+    ("→", "rightwards arrow"),
+    ("←", "leftwards arrow"),
+    ("↑", "upwards arrow"),
+    ("↓", "downwards arrow"),
+    ("↔", "left right arrow"),
+    ("↕", "up down arrow"),
+    ("⇒", "rightwards double arrow"),
+    ("⇐", "leftwards double arrow"),
+    ("⇔", "left right double arrow"),
+    ("↩", "leftwards arrow with hook"),
+    ("↪", "rightwards arrow with hook"),
+    ("⏎", "return symbol"),
+    ("⌘", "place of interest sign, command key"),
+    ("⌥", "option key"),
+    ("⇧", "upwards white arrow, shift key"),
+    ("⌫", "erase to the left, backspace key"),
+    ("⎋", "broken circle with northwest arrow, escape key"),
+    ("•", "bullet"),
+    ("·", "middle dot"),
+    ("…", "horizontal ellipsis"),
+    ("–", "en dash"),
+    ("—", "em dash"),
+    ("‘", "left single quotation mark"),
+    ("’", "right single quotation mark, apostrophe"),
+    ("“", "left double quotation mark"),
+    ("”", "right double quotation mark"),
+    ("«", "left-pointing double angle quotation mark"),
+    ("»", "right-pointing double angle quotation mark"),
+    ("„", "double low-9 quotation mark"),
+    ("¡", "inverted exclamation mark"),
+    ("¿", "inverted question mark"),
+    ("§", "section sign"),
+    ("¶", "pilcrow sign, paragraph"),
+    ("†", "dagger"),
+    ("‡", "double dagger"),
+    ("©", "copyright sign"),
+    ("®", "registered sign"),
+    ("™", "trade mark sign"),
+    ("°", "degree sign"),
+    ("℃", "degree celsius"),
+    ("℉", "degree fahrenheit"),
+    ("€", "euro sign"),
+    ("£", "pound sign"),
+    ("¥", "yen sign"),
+    ("¢", "cent sign"),
+    ("₹", "indian rupee sign"),
+    ("₩", "won sign"),
+    ("₽", "ruble sign"),
+    ("₿", "bitcoin sign"),
+    ("±", "plus-minus sign"),
+    ("×", "multiplication sign"),
+    ("÷", "division sign"),
+    ("≠", "not equal to"),
+    ("≈", "almost equal to"),
+    ("≡", "identical to"),
+    ("≤", "less-than or equal to"),
+    ("≥", "greater-than or equal to"),
+    ("∞", "infinity"),
+    ("√", "square root"),
+    ("∑", "n-ary summation, sum"),
+    ("∏", "n-ary product"),
+    ("∫", "integral"),
+    ("∂", "partial differential"),
+    ("∆", "increment, delta"),
+    ("∇", "nabla"),
+    ("∈", "element of"),
+    ("∉", "not an element of"),
+    ("∅", "empty set"),
+    ("∩", "intersection"),
+    ("∪", "union"),
+    ("⊂", "subset of"),
+    ("⊆", "subset of or equal to"),
+    ("∀", "for all"),
+    ("∃", "there exists"),
+    ("¬", "not sign"),
+    ("∧", "logical and"),
+    ("∨", "logical or"),
+    ("⊕", "circled plus, xor"),
+    ("‰", "per mille sign"),
+    ("½", "vulgar fraction one half"),
+    ("⅓", "vulgar fraction one third"),
+    ("¼", "vulgar fraction one quarter"),
+    ("¾", "vulgar fraction three quarters"),
+    ("²", "superscript two, squared"),
+    ("³", "superscript three, cubed"),
+    ("α", "greek small letter alpha"),
+    ("β", "greek small letter beta"),
+    ("γ", "greek small letter gamma"),
+    ("δ", "greek small letter delta"),
+    ("ε", "greek small letter epsilon"),
+    ("θ", "greek small letter theta"),
+    ("λ", "greek small letter lambda"),
+    ("μ", "greek small letter mu, micro"),
+    ("π", "greek small letter pi"),
+    ("σ", "greek small letter sigma"),
+    ("τ", "greek small letter tau"),
+    ("φ", "greek small letter phi"),
+    ("ω", "greek small letter omega"),
+    ("Δ", "greek capital letter delta"),
+    ("Σ", "greek capital letter sigma"),
+    ("Ω", "greek capital letter omega, ohm"),
+    ("✓", "check mark symbol"),
+    ("✗", "ballot x"),
+    ("★", "black star"),
+    ("☆", "white star"),
+    ("♪", "eighth note"),
+    ("☐", "ballot box"),
+    ("☒", "ballot box with x"),
+    ("■", "black square"),
+    ("□", "white square"),
+    ("▲", "black up-pointing triangle"),
+    ("▼", "black down-pointing triangle"),
+    ("▶", "black right-pointing triangle"),
+    ("◀", "black left-pointing triangle"),
+    ("●", "black circle symbol"),
+    ("○", "white circle symbol"),
+    ("◆", "black diamond"),
+    ("◇", "white diamond"),
+    ("\u{a0}", "no-break space"),
+    ("\u{200b}", "zero width space"),
+    ("\u{2011}", "non-breaking hyphen"),
+    ("\u{ad}", "soft hyphen"),
+];
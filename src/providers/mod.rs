@@ -0,0 +1,52 @@
+pub mod calculator;
+pub mod emoji;
+pub mod script;
+pub mod timestamp;
+
+use crate::{
+    config::{Config, ProviderKind},
+    format::NumberFormat,
+};
+
+/// What providers base their items on
+pub struct ProviderContext<'a> {
+    /// Query of Search mode, empty when not searching
+    pub query: &'a str,
+    /// Text of the newest unpinned item, if it has any
+    pub text: Option<&'a str>,
+}
+
+#[derive(Clone)]
+pub struct ProvidedItem {
+    pub text: String,
+    /// Shown below the text, e.g. the name of an emoji
+    pub detail: Option<String>,
+}
+
+/// A source of virtual items: entries listed along with the history that are only kept once
+/// pasted
+pub trait Provider {
+    /// Shown on the items it offers
+    fn name(&self) -> &str;
+
+    /// Items to offer for the context, most relevant first
+    fn provide(&mut self, context: &ProviderContext) -> Vec<ProvidedItem>;
+}
+
+/// Creates the providers enabled in config, in their configured order
+pub fn from_config(config: &Config) -> Vec<Box<dyn Provider>> {
+    config
+        .providers
+        .iter()
+        .map(|kind| -> Box<dyn Provider> {
+            match kind {
+                ProviderKind::Calculator => Box::new(calculator::Calculator::new(
+                    NumberFormat::from_locale(&config.locale),
+                )),
+                ProviderKind::Emoji => Box::new(emoji::Emoji),
+                ProviderKind::Timestamp => Box::new(timestamp::Timestamp),
+                ProviderKind::Script(config) => Box::new(script::Script::new(config.clone())),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,158 @@
+use std::{
+    io::{Read as _, Write as _},
+    process::{Command, Stdio},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context as _, Result, anyhow, bail};
+use log::{debug, trace, warn};
+use serde::Deserialize;
+
+use crate::config::ScriptProviderConfig;
+
+use super::{ProvidedItem, Provider, ProviderContext};
+
+const MAX_ITEMS: usize = 50;
+const WAIT_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Deserialize)]
+struct ScriptItem {
+    text: String,
+    #[serde(default)]
+    detail: Option<String>,
+}
+
+/// Runs a command with the search query on stdin, reading items from its stdout as JSON lines
+/// like {"text": "...", "detail": "..."}. The command runs on a background thread, so items show
+/// up once it's done
+pub struct Script {
+    name: String,
+    sender: Sender<String>,
+    receiver: Receiver<(String, Vec<ProvidedItem>)>,
+    requested_query: String,
+    items: Vec<ProvidedItem>,
+}
+
+impl Script {
+    pub fn new(config: ScriptProviderConfig) -> Self {
+        let (query_sender, query_receiver) = mpsc::channel::<String>();
+        let (result_sender, result_receiver) = mpsc::channel();
+        let name = config.name.clone();
+
+        thread::spawn(move || {
+            while let Ok(mut query) = query_receiver.recv() {
+                // Only the latest query matters when typing outpaces the command
+                while let Ok(newer_query) = query_receiver.try_recv() {
+                    query = newer_query;
+                }
+
+                let items = match run(&config, &query) {
+                    Ok(items) => items,
+                    Err(e) => {
+                        warn!("provider script {:?} failed: {e:?}", config.name);
+                        vec![]
+                    }
+                };
+                if result_sender.send((query, items)).is_err() {
+                    break;
+                }
+            }
+            debug!("provider script {:?} stopped", config.name);
+        });
+
+        Script {
+            name,
+            sender: query_sender,
+            receiver: result_receiver,
+            requested_query: String::new(),
+            items: vec![],
+        }
+    }
+}
+
+impl Provider for Script {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn provide(&mut self, context: &ProviderContext) -> Vec<ProvidedItem> {
+        if context.query != self.requested_query {
+            self.requested_query = context.query.to_string();
+            self.items.clear();
+            if !context.query.is_empty() {
+                let _ = self.sender.send(context.query.to_string());
+            }
+        }
+
+        for (query, items) in self.receiver.try_iter() {
+            if query == self.requested_query {
+                self.items = items;
+            }
+        }
+
+        self.items.clone()
+    }
+}
+
+fn run(config: &ScriptProviderConfig, query: &str) -> Result<Vec<ProvidedItem>> {
+    let Some((program, args)) = config.command.split_first() else {
+        bail!("command is empty");
+    };
+
+    trace!("running provider script {:?} for {query:?}", config.name);
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("failed to run {program:?}"))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // The script may not read its input at all, that's fine
+        let _ = writeln!(stdin, "{query}");
+    }
+    // Read alongside waiting, as a full pipe would block the script until the timeout
+    let mut stdout = child.stdout.take().context("stdout not captured")?;
+    let reader = thread::spawn(move || {
+        let mut output = String::new();
+        stdout.read_to_string(&mut output).map(|_| output)
+    });
+
+    let deadline = Instant::now() + Duration::from_millis(config.timeout_ms);
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            bail!("timed out after {} ms", config.timeout_ms);
+        }
+        thread::sleep(WAIT_INTERVAL);
+    };
+    if !status.success() {
+        bail!("exited with {status}");
+    }
+
+    let output = reader
+        .join()
+        .map_err(|_| anyhow!("output reader panicked"))??;
+    Ok(output
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str::<ScriptItem>(line) {
+            Ok(item) => Some(ProvidedItem {
+                text: item.text,
+                detail: item.detail,
+            }),
+            Err(e) => {
+                warn!("ignoring invalid line from provider script: {e}");
+                None
+            }
+        })
+        .take(MAX_ITEMS)
+        .collect())
+}
@@ -0,0 +1,56 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{ProvidedItem, Provider, ProviderContext};
+
+/// Offers the current time, as an ISO 8601 UTC date time and as a Unix timestamp
+pub struct Timestamp;
+
+impl Provider for Timestamp {
+    fn name(&self) -> &str {
+        "Timestamp"
+    }
+
+    fn provide(&mut self, _context: &ProviderContext) -> Vec<ProvidedItem> {
+        let Ok(now) = SystemTime::now().duration_since(UNIX_EPOCH) else {
+            return vec![];
+        };
+        let secs = now.as_secs();
+        vec![
+            ProvidedItem {
+                text: format_utc(secs),
+                detail: Some("ISO 8601, UTC".to_string()),
+            },
+            ProvidedItem {
+                text: secs.to_string(),
+                detail: Some("Unix time".to_string()),
+            },
+        ]
+    }
+}
+
+// e.g., "2025-01-31T08:30:00Z"
+fn format_utc(secs: u64) -> String {
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let time = secs % 86400;
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+// Days since 1970-01-01 to a proleptic Gregorian date
+// https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
@@ -0,0 +1,65 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use egui::Color32;
+use log::warn;
+
+use crate::{
+    config::{Config, RendererKind},
+    opengl_context::OpenGLContext,
+    software_renderer::SoftwareRenderer,
+    x11_window::X11Window,
+};
+
+/// Where the time of a frame went. GL calls are queued, so painting only covers issuing them and
+/// the GPU work mostly shows up in the swap
+#[derive(Debug, Clone, Copy)]
+pub struct RenderTimings {
+    pub tessellate: Duration,
+    pub paint: Duration,
+    pub swap: Duration,
+}
+
+/// A way of drawing egui output into the main window
+pub trait Renderer {
+    /// Binds the renderer to the main window again after it got recreated
+    fn recreate(&mut self) -> Result<()>;
+
+    fn render(
+        &mut self,
+        egui_ctx: &egui::Context,
+        full_output: egui::FullOutput,
+    ) -> Result<RenderTimings>;
+
+    /// Makes the next frame repaint the whole window, as its contents are gone
+    fn invalidate(&mut self) {}
+
+    /// Changes the color the window is cleared with, from the next frame on
+    fn set_background(&mut self, color: Color32);
+
+    fn destroy(&mut self);
+}
+
+/// Creates the renderer selected in config
+pub fn from_config<'a>(window: &'a X11Window, config: &Config) -> Result<Box<dyn Renderer + 'a>> {
+    Ok(match config.renderer {
+        RendererKind::Auto => match OpenGLContext::new(window, config) {
+            Ok(gl_context) => Box::new(gl_context),
+            Err(err) => {
+                warn!("failed to set up OpenGL, falling back to software rendering: {err:?}");
+                Box::new(SoftwareRenderer::new(window, config)?)
+            }
+        },
+        RendererKind::OpenGL => Box::new(OpenGLContext::new(window, config)?),
+        RendererKind::Software => Box::new(SoftwareRenderer::new(window, config)?),
+        #[cfg(feature = "wgpu")]
+        RendererKind::Wgpu => Box::new(crate::wgpu_renderer::WgpuRenderer::new(window, config)?),
+        #[cfg(not(feature = "wgpu"))]
+        RendererKind::Wgpu => {
+            warn!(
+                "renderer is \"wgpu\", but memoni was built without the wgpu feature, using OpenGL"
+            );
+            Box::new(OpenGLContext::new(window, config)?)
+        }
+    })
+}
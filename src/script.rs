@@ -0,0 +1,86 @@
+//! Writing systems that need fonts of their own, found in item text so their fallback fonts only
+//! get loaded once the history has something written in them
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Script {
+    /// Chinese, Japanese and Korean
+    Cjk,
+    Arabic,
+    Devanagari,
+}
+
+impl Script {
+    pub fn contains(self, c: char) -> bool {
+        match self {
+            Script::Cjk => matches!(c,
+                '\u{1100}'..='\u{11FF}' // Hangul Jamo
+                | '\u{2E80}'..='\u{2FDF}' // Radicals
+                | '\u{3000}'..='\u{30FF}' // Punctuation, Hiragana and Katakana
+                | '\u{3130}'..='\u{318F}' // Hangul compatibility Jamo
+                | '\u{3400}'..='\u{4DBF}' // Extension A
+                | '\u{4E00}'..='\u{9FFF}' // Unified ideographs
+                | '\u{AC00}'..='\u{D7AF}' // Hangul syllables
+                | '\u{F900}'..='\u{FAFF}' // Compatibility ideographs
+                | '\u{FF00}'..='\u{FFEF}' // Halfwidth and fullwidth forms
+                | '\u{20000}'..='\u{3134F}' // Extensions B to G
+            ),
+            Script::Arabic => matches!(c,
+                '\u{0600}'..='\u{06FF}'
+                | '\u{0750}'..='\u{077F}' // Supplement
+                | '\u{08A0}'..='\u{08FF}' // Extended-A
+                | '\u{FB50}'..='\u{FDFF}' // Presentation forms A
+                | '\u{FE70}'..='\u{FEFF}' // Presentation forms B
+            ),
+            Script::Devanagari => matches!(c, '\u{0900}'..='\u{097F}' | '\u{A8E0}'..='\u{A8FF}'),
+        }
+    }
+}
+
+/// Which of `scripts` `text` has characters of, in the order of `scripts`
+pub fn scripts_in(text: &str, scripts: &[Script]) -> Vec<Script> {
+    let mut found = vec![false; scripts.len()];
+    for c in text.chars() {
+        // ASCII is in none of them, and most text is mostly ASCII
+        if c.is_ascii() {
+            continue;
+        }
+        for (script, found) in scripts.iter().zip(&mut found) {
+            *found |= script.contains(c);
+        }
+        if found.iter().all(|&found| found) {
+            break;
+        }
+    }
+    scripts
+        .iter()
+        .zip(found)
+        .filter_map(|(&script, found)| found.then_some(script))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Script; 3] = [Script::Cjk, Script::Arabic, Script::Devanagari];
+
+    #[test]
+    fn finds_scripts_in_text() {
+        assert_eq!(scripts_in("hello, café", &ALL), []);
+        assert_eq!(scripts_in("東京 and 서울", &ALL), [Script::Cjk]);
+        assert_eq!(scripts_in("カタカナ", &ALL), [Script::Cjk]);
+        assert_eq!(
+            scripts_in("नमस्ते مرحبا", &ALL),
+            [Script::Arabic, Script::Devanagari]
+        );
+    }
+
+    #[test]
+    fn only_looks_for_given_scripts() {
+        assert_eq!(
+            scripts_in("東京 مرحبا", &[Script::Arabic]),
+            [Script::Arabic]
+        );
+        assert_eq!(scripts_in("東京", &[]), []);
+    }
+}
@@ -0,0 +1,359 @@
+use std::collections::{HashMap, HashSet};
+
+use log::{debug, trace};
+
+use crate::{
+    classification::{ItemKind, Link, classify},
+    ordered_hash_map::OrderedHashMap,
+    selection::SelectionItem,
+};
+
+// Only the start of long items is matched, so typing stays responsive with large texts
+const MAX_MATCH_LEN: usize = 64 * 1024;
+
+/// Query of Search mode and the history items matching it, ranked best first
+#[derive(Default)]
+pub struct Search {
+    query: String,
+    index: SearchIndex,
+    matches: Vec<u64>,
+    // Item ids and lowercased query the matches were computed for, to notice history changes and
+    // narrow down from the previous matches while the query is being typed
+    matched_ids: Vec<u64>,
+    matched_pattern: String,
+    stale: bool,
+}
+
+impl Search {
+    pub fn new() -> Self {
+        Search {
+            query: String::new(),
+            index: SearchIndex::default(),
+            matches: vec![],
+            matched_ids: vec![],
+            matched_pattern: String::new(),
+            stale: false,
+        }
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn push_str(&mut self, text: &str) {
+        self.query.push_str(text);
+        self.stale = true;
+    }
+
+    pub fn pop(&mut self) {
+        if self.query.pop().is_some() {
+            self.stale = true;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        if !self.query.is_empty() {
+            self.query.clear();
+            self.stale = true;
+        }
+    }
+
+    /// Ranks the items against the query if either changed. Returns true if the matches did
+    pub fn update(&mut self, selection_items: &OrderedHashMap<u64, SelectionItem>) -> bool {
+        let items_changed = self.matched_ids.len() != selection_items.len()
+            || self
+                .matched_ids
+                .iter()
+                .zip(selection_items)
+                .any(|(matched_id, (id, _))| matched_id != id);
+        if !self.stale && !items_changed {
+            return false;
+        }
+
+        if items_changed {
+            self.index.sync(selection_items);
+        }
+        self.stale = false;
+        self.matched_ids = selection_items.iter().map(|(&id, _)| id).collect();
+        let pattern = self.query.to_lowercase();
+        // Anything matching a longer query matches the shorter one it was typed from
+        let narrowing = !items_changed
+            && !self.matched_pattern.is_empty()
+            && pattern.starts_with(&self.matched_pattern);
+        self.matched_pattern.clone_from(&pattern);
+        if pattern.is_empty() {
+            self.matches.clear();
+            return true;
+        }
+
+        // None when the query is too short to narrow down by, and every item is a candidate
+        let candidates = self.index.candidates(&pattern);
+        let previous_matches =
+            narrowing.then(|| self.matches.iter().copied().collect::<HashSet<_>>());
+        let mut scored = selection_items
+            .iter()
+            .filter(|&(id, _)| {
+                candidates.as_ref().is_none_or(|ids| ids.contains(id))
+                    && previous_matches
+                        .as_ref()
+                        .is_none_or(|previous| previous.contains(id))
+            })
+            .filter_map(|(&id, item)| {
+                let mut best = None;
+                searchable_texts(item, |text| {
+                    best = best.max(fuzzy_score_lowercase(&pattern, text));
+                });
+                best.map(|score| (id, score))
+            })
+            .collect::<Vec<_>>();
+        // Stable, so equally good matches keep the history order
+        scored.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+        self.matches = scored.into_iter().map(|(id, _)| id).collect();
+        debug!(
+            "{} of {} candidate items match search query {:?}",
+            self.matches.len(),
+            candidates
+                .as_ref()
+                .map_or(selection_items.len(), HashSet::len),
+            self.query
+        );
+
+        true
+    }
+
+    /// Ids of the matching items, best first. None when there's no query, as everything is shown
+    pub fn matches(&self) -> Option<&[u64]> {
+        (!self.query.is_empty()).then_some(self.matches.as_slice())
+    }
+}
+
+type Trigram = [char; 3];
+
+/// The items each trigram of the lowercased history texts appears in. Kept in step with the
+/// history, so typing a query only scores the items having all of its trigrams instead of
+/// scanning every item on each keystroke
+#[derive(Default)]
+struct SearchIndex {
+    indexed: HashSet<u64>,
+    items_by_trigram: HashMap<Trigram, HashSet<u64>>,
+}
+
+impl SearchIndex {
+    // Items never change once captured, so only added and removed ones need indexing
+    fn sync(&mut self, selection_items: &OrderedHashMap<u64, SelectionItem>) {
+        let removed = self
+            .indexed
+            .iter()
+            .filter(|id| !selection_items.contains_key(id))
+            .copied()
+            .collect::<HashSet<_>>();
+        if !removed.is_empty() {
+            self.indexed.retain(|id| !removed.contains(id));
+            // Their texts are gone with them, so every posting is looked through once instead
+            self.items_by_trigram.retain(|_, ids| {
+                ids.retain(|id| !removed.contains(id));
+                !ids.is_empty()
+            });
+        }
+
+        let mut added = 0;
+        for (&id, item) in selection_items {
+            if self.indexed.insert(id) {
+                searchable_texts(item, |text| {
+                    for trigram in trigrams(text) {
+                        self.items_by_trigram.entry(trigram).or_default().insert(id);
+                    }
+                });
+                added += 1;
+            }
+        }
+        trace!(
+            "search index: {added} items added, {} removed, {} indexed",
+            removed.len(),
+            self.indexed.len()
+        );
+    }
+
+    /// Items having every trigram of the words of the lowercased pattern. None if its words are
+    /// all shorter than a trigram, leaving nothing to narrow down by
+    fn candidates(&self, pattern: &str) -> Option<HashSet<u64>> {
+        let mut sets = vec![];
+        for trigram in pattern.split_whitespace().flat_map(trigrams) {
+            match self.items_by_trigram.get(&trigram) {
+                Some(ids) => sets.push(ids),
+                None => return Some(HashSet::new()),
+            }
+        }
+        sets.sort_by_key(|ids| ids.len());
+        let (smallest, rest) = sets.split_first()?;
+        Some(
+            smallest
+                .iter()
+                .filter(|id| rest.iter().all(|ids| ids.contains(id)))
+                .copied()
+                .collect(),
+        )
+    }
+}
+
+// The text of an item and the page title of links, as far as they're matched
+fn searchable_texts(item: &SelectionItem, mut f: impl FnMut(&str)) {
+    let kind = classify(item);
+    if let Some(text) = kind.text() {
+        f(match_prefix(&text));
+    }
+    if let ItemKind::Link(Link {
+        title: Some(title), ..
+    }) = &kind
+    {
+        f(match_prefix(title));
+    }
+}
+
+fn trigrams(text: &str) -> impl Iterator<Item = Trigram> + '_ {
+    let mut chars = text.chars().flat_map(char::to_lowercase);
+    let mut window = [chars.next(), chars.next()];
+    chars.filter_map(move |c| {
+        let trigram = [window[0]?, window[1]?, c];
+        window = [window[1], Some(c)];
+        Some(trigram)
+    })
+}
+
+fn match_prefix(text: &str) -> &str {
+    let mut end = text.len().min(MAX_MATCH_LEN);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// Scores how well `pattern` matches `text`, ignoring case: all of its characters must appear in
+/// order. Runs of consecutive characters and matches at word starts score higher, and a plain
+/// substring beats any scattered match
+pub fn fuzzy_score(pattern: &str, text: &str) -> Option<u32> {
+    fuzzy_score_lowercase(&pattern.to_lowercase(), text)
+}
+
+// Lowercases the text as it goes, so long items aren't copied for each query
+fn fuzzy_score_lowercase(pattern: &str, text: &str) -> Option<u32> {
+    if pattern.is_empty() {
+        return Some(0);
+    }
+
+    let mut score = 0;
+    let mut pattern_chars = pattern.chars().peekable();
+    let mut prev = None;
+    let mut run = 0;
+    for c in text.chars().flat_map(char::to_lowercase) {
+        let Some(&p) = pattern_chars.peek() else {
+            break;
+        };
+        if c == p {
+            pattern_chars.next();
+            run += 1;
+            score += run;
+            if prev.is_none_or(|prev: char| !prev.is_alphanumeric()) {
+                score += 3;
+            }
+        } else {
+            run = 0;
+        }
+        prev = Some(c);
+    }
+    if pattern_chars.peek().is_some() {
+        return None;
+    }
+
+    if let Some(pos) = find_lowercase(text, pattern) {
+        score += 100;
+        if pos == 0 {
+            score += 50;
+        }
+    }
+    Some(score)
+}
+
+// Byte position of the lowercase pattern in the text, ignoring the case of the text
+fn find_lowercase(text: &str, pattern: &str) -> Option<usize> {
+    text.char_indices().map(|(pos, _)| pos).find(|&pos| {
+        let mut text_chars = text[pos..].chars().flat_map(char::to_lowercase);
+        pattern.chars().all(|p| text_chars.next() == Some(p))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn text_item(id: u64, text: &str) -> SelectionItem {
+        SelectionItem {
+            id,
+            data: BTreeMap::from([("UTF8_STRING".to_string(), text.as_bytes().to_vec())]),
+            source: None,
+        }
+    }
+
+    #[test]
+    fn matches_follow_query_and_history() {
+        let mut items = OrderedHashMap::new();
+        for (id, text) in [(1, "Apple pie"), (2, "banana"), (3, "pineapple")] {
+            items.push_back(id, text_item(id, text));
+        }
+        let mut search = Search::new();
+
+        search.push_str("ap");
+        assert!(search.update(&items));
+        assert_eq!(search.matches(), Some(&[1, 3][..]));
+        assert!(!search.update(&items));
+
+        // Narrowed down from the previous matches
+        search.push_str("ple p");
+        assert!(search.update(&items));
+        assert_eq!(search.matches(), Some(&[1][..]));
+
+        search.clear();
+        search.push_str("ap");
+        items.push_front(4, text_item(4, "grape"));
+        items.remove(&1);
+        assert!(search.update(&items));
+        assert_eq!(search.matches(), Some(&[4, 3][..]));
+        assert!(!search.index.indexed.contains(&1));
+        assert!(
+            search
+                .index
+                .items_by_trigram
+                .values()
+                .all(|ids| !ids.contains(&1))
+        );
+
+        search.push_str("z");
+        assert!(search.update(&items));
+        assert_eq!(search.matches(), Some(&[][..]));
+    }
+
+    #[test]
+    fn candidates_have_every_trigram_of_the_query() {
+        let mut items = OrderedHashMap::new();
+        for (id, text) in [
+            (1, "Apple pie"),
+            (2, "banana"),
+            (3, "PINEAPPLE"),
+            (4, "pin"),
+        ] {
+            items.push_back(id, text_item(id, text));
+        }
+        let mut index = SearchIndex::default();
+        index.sync(&items);
+
+        assert_eq!(index.candidates("apple"), Some(HashSet::from([1, 3])));
+        // Words are matched on their own
+        assert_eq!(index.candidates("pie  app"), Some(HashSet::from([1])));
+        assert_eq!(index.candidates("pin"), Some(HashSet::from([3, 4])));
+        assert_eq!(index.candidates("nap"), Some(HashSet::new()));
+        // Too short to narrow down by, so all of them are scored
+        assert_eq!(index.candidates("pi e"), None);
+    }
+}
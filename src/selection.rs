@@ -7,8 +7,8 @@
 extern crate x11rb;
 
 use std::{
-    cell::RefCell,
-    collections::{BTreeMap, HashMap},
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, HashMap, HashSet},
     fmt, mem,
     time::{Duration, Instant},
 };
@@ -26,18 +26,27 @@ use x11rb::xcb_ffi::XCBConnection;
 use x11rb::{
     connection::Connection,
     connection::RequestConnection as _,
-    protocol::{xfixes, xtest::ConnectionExt as _},
+    errors::ReplyError,
+    protocol::{xfixes, xtest, xtest::ConnectionExt as _},
 };
 use xkeysym::Keysym;
 
 use crate::{
-    config::{Config, KeyStroke, Modifier},
+    config::{Config, DedupeKey, KeyStroke, Modifier, PasteMethod},
+    expiry,
+    image_decode::decode_image,
     keymap_action::PasteModifier,
+    merge::MergePolicy,
+    metrics,
     ordered_hash_map::OrderedHashMap,
+    persistence::{ColdPages, PageRef},
+    text_encoding::legacy_text_to_utf8,
+    timerfd_source::TimerfdSource,
     transfer_window_pool::{TransferWindow, TransferWindowPool},
     utils::{image_mime_score, is_image_mime, is_plaintext_mime, plaintext_mime_score},
     x11_key_converter::X11KeyConverter,
     x11_window::X11Window,
+    yank_pop::PasteUndo,
 };
 
 const HASH_SEED: usize = 0xfd9aadcf54cc0f35;
@@ -45,6 +54,16 @@ const BINCODE_CONFIG: bincode::config::Configuration = bincode::config::standard
 const OVERDUE_TIMEOUT: Duration = Duration::from_secs(3);
 const MAX_INCR_SIZE: usize = 10 * 1024 * 1024;
 const INCR_CHUNK_SIZE: usize = 1024 * 1024 - 1;
+// Items at least this large go first when the history is over its disk quota
+const LARGE_ITEM_SIZE: u64 = 256 * 1024;
+
+/// Mime of the placeholder item recorded when an owner never delivers its selection. The data is
+/// the name of the owner application.
+pub const CAPTURE_FAILED_MIME: &str = "application/x-memoni-capture-failed";
+
+/// Mime of items offered in the list without being captured, like a calculator result. The data
+/// describes what offered it. The mime is dropped once the item is pasted and kept.
+pub const VIRTUAL_MIME: &str = "application/x-memoni-virtual";
 
 x11rb::atom_manager! {
     pub Atoms: AtomsCookie {
@@ -61,7 +80,9 @@ x11rb::atom_manager! {
         INSERT_PROPERTY,
         INSERT_SELECTION,
 
+        UTF8_STRING,
         _NET_WM_NAME,
+        WM_CLIENT_LEADER,
     }
 }
 
@@ -71,12 +92,65 @@ type Owner = u32;
 #[derive(Debug, Default, Encode, Decode)]
 pub struct SelectionMetadata {
     pub pinned_count: usize,
+    // Where the window was left when last hidden, see restore_scroll_position
+    pub last_active_id: Option<u64>,
+    pub last_scroll_offset: f32,
+    /// When items marked to expire are removed, in Unix seconds by item id
+    pub expiries: BTreeMap<u64, u64>,
+    /// When items last became the newest one, in Unix seconds by item id. Missing for items
+    /// copied before it was recorded
+    pub copied_at: BTreeMap<u64, u64>,
+    /// Item ids by the letter they're marked with, to jump back to them
+    pub marks: BTreeMap<char, u64>,
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode)]
 pub struct SelectionItem {
     pub id: u64,
     pub data: SelectionData,
+    /// WM_CLASS class name of the application the item was copied from, if known. Not part of the
+    /// id, so the same data copied from another application is still a duplicate
+    pub source: Option<String>,
+}
+
+impl SelectionItem {
+    /// Text of the item in its best plain text mime, if valid UTF-8
+    pub fn text(&self) -> Option<&str> {
+        self.data
+            .iter()
+            .filter_map(|(mime, data)| plaintext_mime_score(mime).map(|s| (s, data)))
+            .max_by_key(|(score, _)| *score)
+            .and_then(|(_, data)| str::from_utf8(data).ok())
+    }
+
+    /// Data of the `mime` target, the text for any plain text mime, or without one, the text or
+    /// else the best image
+    pub fn target(&self, mime: Option<&str>) -> Option<&[u8]> {
+        match mime {
+            Some(mime) if is_plaintext_mime(mime) => self.text().map(str::as_bytes),
+            Some(mime) => self.data.get(mime).map(Vec::as_slice),
+            None => self.text().map(str::as_bytes).or_else(|| {
+                self.data
+                    .iter()
+                    .filter(|(mime, _)| is_image_mime(mime))
+                    .max_by_key(|(mime, _)| image_mime_score(mime))
+                    .map(|(_, data)| data.as_slice())
+            }),
+        }
+    }
+
+    /// A text item that isn't part of the history, `description` telling what offered it
+    pub fn new_virtual(description: &str, text: String) -> Result<Self> {
+        let data = BTreeMap::from([
+            ("UTF8_STRING".to_string(), text.into_bytes()),
+            (VIRTUAL_MIME.to_string(), description.as_bytes().to_vec()),
+        ]);
+        Ok(SelectionItem {
+            id: hash_selection_data(&data, &[])?,
+            data,
+            source: None,
+        })
+    }
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -106,6 +180,17 @@ enum RequestTaskState {
     },
 }
 
+#[derive(Debug)]
+struct RequestTaskMetadata {
+    transfer_atom: Atom,
+    owner: Owner,
+    // Target of the latest conversion request, used to skip late replies to abandoned targets
+    target: Atom,
+    // Lower-ranked plaintext and image targets, best first, to retry with when the chosen ones
+    // never arrive
+    fallback_mimes: Vec<(Atom, String)>,
+}
+
 #[derive(Debug)]
 enum IncrPasteTaskState {
     TransferingIncr {
@@ -142,31 +227,42 @@ impl<S, M> Task<S, M> {
 pub struct Selection<'a> {
     pub items: OrderedHashMap<u64, SelectionItem>,
     pub metadata: SelectionMetadata,
+    /// Saved items after `items`, not loaded yet
+    pub cold_pages: ColdPages,
+    // Loaded items pushed out by the item limit, kept for the archive
+    evicted: Vec<SelectionItem>,
 
     window: &'a X11Window<'a>,
     screen: &'a Screen,
     key_converter: &'a X11KeyConverter<'a>,
     config: &'a Config,
-    merge_consecutive_similar_items: bool,
+    merge_policy: MergePolicy,
     selection_atom: Atom,
     atoms: Atoms,
-    request_tasks: HashMap<Window, Task<RequestTaskState, (Atom, Owner)>>,
+    request_tasks: HashMap<Window, Task<RequestTaskState, RequestTaskMetadata>>,
     incr_paste_tasks: HashMap<(Window, Atom), Task<IncrPasteTaskState>>,
     transfer_windows: TransferWindowPool<'a>,
     mime_atoms: RefCell<HashMap<String, Atom>>,
     paste_item_id: Option<u64>,
+    // Served in place of the paste item's data, for pasting a transformed item
+    paste_payload: Option<SelectionData>,
     next_paste_modifier: Option<PasteModifier>,
     prev_item_metadata: Option<(u32, Instant, bool)>,
+    pending_capture_owner: Option<Owner>,
+    paste_method: PasteMethod,
 }
 
 impl<'a> Selection<'a> {
     pub fn new(
-        initial_data: (OrderedHashMap<u64, SelectionItem>, SelectionMetadata),
+        initial_data: (
+            OrderedHashMap<u64, SelectionItem>,
+            SelectionMetadata,
+            ColdPages,
+        ),
         window: &'a X11Window,
         key_converter: &'a X11KeyConverter,
         selection_type: SelectionType,
         config: &'a Config,
-        merge_consecutive_similar_items: bool,
     ) -> Result<Self> {
         let conn = &window.conn;
         let root = window.screen.root;
@@ -188,14 +284,31 @@ impl<'a> Selection<'a> {
             SelectionEventMask::SET_SELECTION_OWNER,
         )?;
 
+        conn.prefetch_extension_information(xtest::X11_EXTENSION_NAME)?;
+        let has_xtest = conn
+            .extension_information(xtest::X11_EXTENSION_NAME)?
+            .is_some();
+        let paste_method = match config.paste_method {
+            PasteMethod::Auto if has_xtest => PasteMethod::Xtest,
+            PasteMethod::Auto => {
+                warn!("XTest not found, falling back to synthetic events for pasting");
+                PasteMethod::SendEvent
+            }
+            PasteMethod::Xtest if !has_xtest => bail!("XTest not found"),
+            paste_method => paste_method,
+        };
+        debug!("using paste method: {paste_method:?}");
+
         Ok(Selection {
             items: initial_data.0,
             metadata: initial_data.1,
+            cold_pages: initial_data.2,
+            evicted: vec![],
             window,
             screen: &window.screen,
             key_converter,
             config,
-            merge_consecutive_similar_items,
+            merge_policy: MergePolicy::new(&config.merge),
             selection_atom,
             atoms,
             request_tasks: HashMap::new(),
@@ -203,14 +316,18 @@ impl<'a> Selection<'a> {
             transfer_windows: TransferWindowPool::new(conn, root, selection_type)?,
             mime_atoms: RefCell::new(HashMap::new()),
             paste_item_id: None,
+            paste_payload: None,
             next_paste_modifier: None,
             prev_item_metadata: None,
+            pending_capture_owner: None,
+            paste_method,
         })
     }
 
     pub fn handle_event(
         &mut self,
         event: &Event,
+        capture_debounce_timer: &TimerfdSource,
     ) -> Result<Option<(Option<&SelectionItem>, Vec<SelectionItem>)>> {
         let conn = &self.window.conn;
         let paste_window = self.window.win_id.get();
@@ -220,30 +337,27 @@ impl<'a> Selection<'a> {
             match event {
                 // Capture copied data
                 Event::XfixesSelectionNotify(ev) => {
+                    // Conversions always go to the current owner, so whatever is still in flight
+                    // would only fetch the new selection anyway
+                    self.cancel_request_tasks();
+
                     if ev.owner == paste_window {
                         debug!("ignoring selection notification from ourselves");
+                        if self.pending_capture_owner.take().is_some() {
+                            capture_debounce_timer.disarm()?;
+                        }
                         break 'blk;
                     }
 
                     info!("selection notification received from owner {}", ev.owner);
-                    let transfer_window = self.transfer_windows.get()?;
-                    info!("requesting selection with transfer window: {transfer_window:?}");
-                    conn.convert_selection(
-                        transfer_window.id,
-                        ev.selection,
-                        atoms.TARGETS,
-                        transfer_window.atom,
-                        x11rb::CURRENT_TIME,
-                    )?
-                    .check()?;
-
-                    self.request_tasks.insert(
-                        transfer_window.id,
-                        Task::new(
-                            RequestTaskState::TargetsRequest,
-                            (transfer_window.atom, ev.owner),
-                        ),
-                    );
+                    let debounce_ms = self.config.capture_debounce_ms;
+                    if debounce_ms == 0 {
+                        self.request_targets(ev.owner)?;
+                    } else {
+                        debug!("waiting for selection to be stable for {debounce_ms} ms");
+                        self.pending_capture_owner = Some(ev.owner);
+                        capture_debounce_timer.set_timer(debounce_ms)?;
+                    }
                 }
                 Event::SelectionNotify(ev) => {
                     let transfer_window = ev.requestor;
@@ -253,7 +367,16 @@ impl<'a> Selection<'a> {
                         );
                         break 'blk;
                     };
-                    let (transfer_atom, owner) = task.metadata;
+                    let transfer_atom = task.metadata.transfer_atom;
+                    let owner = task.metadata.owner;
+
+                    if ev.target != task.metadata.target {
+                        debug!(
+                            "ignoring late selection notification for abandoned target {}",
+                            ev.target
+                        );
+                        break 'blk;
+                    }
 
                     let property = if ev.property == x11rb::NONE {
                         None
@@ -321,6 +444,7 @@ impl<'a> Selection<'a> {
                             }
                             debug!("unfiltered targets: {mimes:?}");
 
+                            let fallback_mimes = get_fallback_mimes(&mimes);
                             let mimes = filter_mimes(mimes);
                             if mimes.is_empty() {
                                 warn!("no usable targets returned, dropping selection");
@@ -337,7 +461,12 @@ impl<'a> Selection<'a> {
                                     x11rb::CURRENT_TIME,
                                 )?
                                 .check()?;
+                                task.metadata.target = target_atom;
                             }
+                            task.metadata.fallback_mimes = fallback_mimes
+                                .into_iter()
+                                .filter(|(atom, _)| !mimes.contains_key(atom))
+                                .collect();
 
                             task.set_state(RequestTaskState::PendingSelection {
                                 mimes,
@@ -420,7 +549,8 @@ impl<'a> Selection<'a> {
                         );
                         break 'blk;
                     };
-                    let (transfer_atom, owner) = task.metadata;
+                    let transfer_atom = task.metadata.transfer_atom;
+                    let owner = task.metadata.owner;
 
                     let incr_task_state = if let RequestTaskState::PendingIncr {
                         mimes,
@@ -562,7 +692,13 @@ impl<'a> Selection<'a> {
                     let mut supported_atoms = Vec::new();
                     supported_atoms.push(self.atoms.TARGETS);
                     let mut requested_data = None;
-                    for (atom_name, data) in &item.data {
+                    let plain_text_only = modifier.is_some_and(|m| m.plain_text);
+                    let item_data = self.paste_payload.as_ref().unwrap_or(&item.data);
+                    for (atom_name, data) in item_data {
+                        if plain_text_only && !is_plaintext_mime(atom_name) {
+                            continue;
+                        }
+
                         let atom =
                             get_or_create_mime_atom(conn, self.mime_atoms.get_mut(), atom_name)?;
                         if atom != x11rb::NONE {
@@ -601,7 +737,7 @@ impl<'a> Selection<'a> {
                     let (raw_data, atom_name) = requested_data.unwrap();
                     let data = if modifier.is_some_and(|m| m.trim) {
                         if is_plaintext_mime(atom_name) {
-                            trim_unicode_utf8(raw_data).unwrap()
+                            trim_unicode_utf8(raw_data).unwrap_or(raw_data)
                         } else {
                             debug!("trim-pasting on a non-text item");
                             raw_data
@@ -660,8 +796,12 @@ impl<'a> Selection<'a> {
                     reply(property)?;
 
                     if modifier.is_some_and(|m| m.and_enter) {
-                        let (key, _, keycode) =
-                            get_input_utils(conn, self.screen, self.key_converter);
+                        let (key, _, keycode) = get_input_utils(
+                            conn,
+                            self.screen,
+                            self.key_converter,
+                            self.paste_method,
+                        )?;
 
                         // Release previously pressed Enter key if it's mapped as paste action
                         key(KEY_RELEASE_EVENT, keycode(Keysym::Return)?)?;
@@ -703,9 +843,10 @@ impl<'a> Selection<'a> {
                                 };
 
                             if let Some(data) = &self
-                                .items
-                                .get(&item_id)
-                                .and_then(|i| i.data.get(data_atom_name))
+                                .paste_payload
+                                .as_ref()
+                                .or_else(|| self.items.get(&item_id).map(|i| &i.data))
+                                .and_then(|data| data.get(data_atom_name))
                             {
                                 let end = offset.saturating_add(INCR_CHUNK_SIZE).min(data.len());
                                 let chunk = &data[*offset..end];
@@ -718,8 +859,12 @@ impl<'a> Selection<'a> {
                                     end_transfering(&mut self.incr_paste_tasks)?;
 
                                     if modifier.is_some_and(|m| m.and_enter) {
-                                        let (key, _, keycode) =
-                                            get_input_utils(conn, self.screen, self.key_converter);
+                                        let (key, _, keycode) = get_input_utils(
+                                            conn,
+                                            self.screen,
+                                            self.key_converter,
+                                            self.paste_method,
+                                        )?;
 
                                         // Release previously pressed Enter key if it's mapped as paste action
                                         key(KEY_RELEASE_EVENT, keycode(Keysym::Return)?)?;
@@ -769,9 +914,64 @@ impl<'a> Selection<'a> {
             }
         }
 
-        self.purge_overdue_tasks();
+        match self.purge_overdue_tasks()? {
+            Some((data, owner)) => self.add_item(data, owner),
+            None => Ok(None),
+        }
+    }
+
+    pub fn request_pending_capture(&mut self) -> Result<()> {
+        if let Some(owner) = self.pending_capture_owner.take() {
+            self.request_targets(owner)?;
+        }
+
+        Ok(())
+    }
+
+    fn request_targets(&mut self, owner: Owner) -> Result<()> {
+        let transfer_window = self.transfer_windows.get()?;
+        info!("requesting selection with transfer window: {transfer_window:?}");
+        self.window
+            .conn
+            .convert_selection(
+                transfer_window.id,
+                self.selection_atom,
+                self.atoms.TARGETS,
+                transfer_window.atom,
+                x11rb::CURRENT_TIME,
+            )?
+            .check()?;
+
+        self.request_tasks.insert(
+            transfer_window.id,
+            Task::new(
+                RequestTaskState::TargetsRequest,
+                RequestTaskMetadata {
+                    transfer_atom: transfer_window.atom,
+                    owner,
+                    target: self.atoms.TARGETS,
+                    fallback_mimes: Vec::new(),
+                },
+            ),
+        );
 
-        Ok(None)
+        Ok(())
+    }
+
+    fn cancel_request_tasks(&mut self) {
+        if !self.request_tasks.is_empty() {
+            debug!(
+                "cancelling superseded request tasks: {:?}",
+                self.request_tasks.keys()
+            );
+        }
+
+        for (transfer_window, task) in self.request_tasks.drain() {
+            self.transfer_windows.release(TransferWindow {
+                id: transfer_window,
+                atom: task.metadata.transfer_atom,
+            });
+        }
     }
 
     fn process_selection_data(
@@ -807,6 +1007,7 @@ impl<'a> Selection<'a> {
         }
 
         if let Some(&next_atom) = mimes.keys().next() {
+            task.metadata.target = next_atom;
             self.request_tasks.insert(transfer_window.id, task);
             self.window
                 .conn
@@ -828,15 +1029,24 @@ impl<'a> Selection<'a> {
             return Ok(None);
         }
 
+        self.add_item(mem::take(data), owner)
+    }
+
+    fn add_item(
+        &mut self,
+        mut data: SelectionData,
+        owner: Owner,
+    ) -> Result<Option<(Option<&SelectionItem>, Vec<SelectionItem>)>> {
+        normalize_plaintext(&mut data);
+        let source = get_owner_class(&self.window.conn, &self.atoms, owner);
         let prev_item = self.items.front();
-        let new_item_id = hash_selection_data(data)?;
+        let new_item_id = hash_selection_data(&data, &self.config.dedupe_keys)?;
         let mut removed = Vec::new();
 
         // We only support merge plaintext items without any other type of data
-        if self.merge_consecutive_similar_items
+        if self.merge_policy.is_enabled()
             && let Some((prev_owner, prev_time, is_previously_seen)) = self.prev_item_metadata
             && prev_owner == owner
-            && prev_time.elapsed() < Duration::from_secs(1)
             // ---
             // If the item has existed before, we should not merge it
             && !is_previously_seen
@@ -849,7 +1059,9 @@ impl<'a> Selection<'a> {
             && prev_item.data.len() == 1
             && let Some(prev_text) = prev_item.data.get(mime)
             // ---
-            && (contains(new_text, prev_text) || contains(prev_text, new_text))
+            && self
+                .merge_policy
+                .should_merge(prev_text, new_text, prev_time.elapsed())
         {
             debug!("merging selection with the previous one");
             removed.push(self.items.pop_front().unwrap().1);
@@ -867,26 +1079,34 @@ impl<'a> Selection<'a> {
                 debug!("selection is duplicated, old one is pinned, keeping old selection");
             } else {
                 debug!("selection is duplicated, removing old one");
-                let previous_seen_item = self.items.remove(&new_item_id).unwrap();
+                let mut previous_seen_item = self.items.remove(&new_item_id).unwrap();
+                previous_seen_item.source = source.or(previous_seen_item.source);
                 self.items
                     .insert(self.metadata.pinned_count, new_item_id, previous_seen_item);
+                self.metadata.copied_at.insert(new_item_id, expiry::now());
             }
 
             is_previously_seen = true;
         } else {
+            if self.cold_pages.remove(new_item_id) {
+                debug!("selection is duplicated, removing old one not loaded yet");
+                is_previously_seen = true;
+            } else {
+                // Left by an item removed some other way, which shouldn't expire this one
+                self.metadata.expiries.remove(&new_item_id);
+            }
             self.items.insert(
                 self.metadata.pinned_count,
                 new_item_id,
                 SelectionItem {
                     id: new_item_id,
-                    data: mem::take(data),
+                    data: mem::take(&mut data),
+                    source,
                 },
             );
+            self.metadata.copied_at.insert(new_item_id, expiry::now());
 
-            if self.items.len() > self.config.item_limit {
-                let removed_map = self.items.split_off(self.config.item_limit);
-                removed.extend(removed_map.into_iter().map(|(_, i)| i));
-            };
+            removed.extend(self.enforce_item_limit());
 
             new_item = self.items.get(&new_item_id);
         }
@@ -896,34 +1116,25 @@ impl<'a> Selection<'a> {
         Ok(Some((new_item, removed)))
     }
 
-    fn purge_overdue_tasks(&mut self) {
+    /// Returns the data to record once an overdue request task runs out of targets to retry. Only
+    /// one task is finalized per call since the caller can only handle a single new item at a time;
+    /// the remaining ones are picked up on subsequent calls.
+    fn purge_overdue_tasks(&mut self) -> Result<Option<(SelectionData, Owner)>> {
         let now = Instant::now();
 
-        let (request_kept, request_removed): (HashMap<_, _>, HashMap<_, _>) = self
+        let overdue_windows = self
             .request_tasks
-            .drain()
-            .partition(|(_, task)| now.duration_since(task.last_update) < OVERDUE_TIMEOUT);
-        self.request_tasks = request_kept;
-
-        if !request_removed.is_empty() {
-            warn!(
-                "purging overdue request tasks: {:?}",
-                request_removed.keys()
-            );
-        }
-
-        for (
-            transfer_window,
-            Task {
-                metadata: (transfer_atom, _),
-                ..
-            },
-        ) in request_removed
-        {
-            self.transfer_windows.release(TransferWindow {
-                id: transfer_window,
-                atom: transfer_atom,
-            });
+            .iter()
+            .filter(|(_, task)| now.duration_since(task.last_update) >= OVERDUE_TIMEOUT)
+            .map(|(&transfer_window, _)| transfer_window)
+            .collect::<Vec<_>>();
+
+        let mut finalized = None;
+        for transfer_window in overdue_windows {
+            finalized = self.retry_overdue_request_task(transfer_window)?;
+            if finalized.is_some() {
+                break;
+            }
         }
 
         let (paste_kept, paste_removed): (HashMap<_, _>, HashMap<_, _>) = self
@@ -934,19 +1145,129 @@ impl<'a> Selection<'a> {
 
         if !paste_removed.is_empty() {
             warn!("purging overdue paste tasks: {:?}", paste_removed.keys());
+            metrics::record_failed_transfers(paste_removed.len());
         }
+
+        Ok(finalized)
+    }
+
+    fn retry_overdue_request_task(
+        &mut self,
+        transfer_window: Window,
+    ) -> Result<Option<(SelectionData, Owner)>> {
+        let conn = &self.window.conn;
+        let Some(task) = self.request_tasks.get_mut(&transfer_window) else {
+            return Ok(None);
+        };
+        let RequestTaskMetadata {
+            transfer_atom,
+            owner,
+            target,
+            ref mut fallback_mimes,
+        } = task.metadata;
+
+        let (mut mimes, data, overdue_mime) = match &mut task.state {
+            RequestTaskState::TargetsRequest => {
+                // Some old clients never implemented TARGETS, ask for plain text directly instead,
+                // falling back to Latin-1 STRING for those predating UTF8_STRING too
+                warn!("owner {owner} did not respond to TARGETS request");
+                *fallback_mimes = vec![(AtomEnum::STRING.into(), "STRING".to_string())];
+                (
+                    HashMap::from([(self.atoms.UTF8_STRING, "UTF8_STRING".to_string())]),
+                    BTreeMap::new(),
+                    None,
+                )
+            }
+            RequestTaskState::PendingSelection { mimes, data } => {
+                let mut mimes = mem::take(mimes);
+                let overdue_mime = mimes.remove(&target);
+                warn!("owner {owner} did not respond to target {overdue_mime:?}");
+                (mimes, mem::take(data), overdue_mime)
+            }
+            RequestTaskState::PendingIncr {
+                mimes,
+                data,
+                current_mime_name,
+                ..
+            } => {
+                warn!("owner {owner} stalled INCR transfer of target {current_mime_name:?}");
+                (
+                    mem::take(mimes),
+                    mem::take(data),
+                    Some(mem::take(current_mime_name)),
+                )
+            }
+        };
+
+        let mut next_atom = None;
+        if let Some(overdue_mime) = overdue_mime
+            && let Some(pos) = fallback_mimes.iter().position(|(_, mime)| {
+                is_plaintext_mime(mime) == is_plaintext_mime(&overdue_mime)
+                    && is_image_mime(mime) == is_image_mime(&overdue_mime)
+            })
+        {
+            let (atom, mime) = fallback_mimes.remove(pos);
+            info!("retrying with next-best target {mime:?}");
+            mimes.insert(atom, mime);
+            next_atom = Some(atom);
+        }
+
+        if let Some(next_atom) = next_atom.or_else(|| mimes.keys().next().copied()) {
+            conn.convert_selection(
+                transfer_window,
+                self.selection_atom,
+                next_atom,
+                transfer_atom,
+                x11rb::CURRENT_TIME,
+            )?
+            .check()?;
+            task.metadata.target = next_atom;
+            task.set_state(RequestTaskState::PendingSelection { mimes, data });
+            return Ok(None);
+        }
+
+        self.request_tasks.remove(&transfer_window);
+        self.transfer_windows.release(TransferWindow {
+            id: transfer_window,
+            atom: transfer_atom,
+        });
+
+        if !data.is_empty() {
+            warn!("no targets left to retry, keeping partially captured data");
+            return Ok(Some((data, owner)));
+        }
+
+        let owner_name = get_window_class(conn, owner)
+            .ok()
+            .flatten()
+            .map(|(_, class_name)| class_name)
+            .filter(|class_name| !class_name.is_empty())
+            .unwrap_or_else(|| format!("window {owner:#x}"));
+        warn!("capturing selection from {owner_name} failed");
+        Ok(Some((
+            BTreeMap::from([(CAPTURE_FAILED_MIME.to_string(), owner_name.into_bytes())]),
+            owner,
+        )))
     }
 
     pub fn paste(
         &mut self,
         item_id: u64,
         pointer_original_pos: (i16, i16),
+        pointer_anchor: Option<(Window, (i16, i16))>,
         modifier: PasteModifier,
+        payload: Option<SelectionData>,
     ) -> Result<()> {
         // Move paste item to the top
         let Some(item_idx) = self.items.iter().position(|(&id, _)| id == item_id) else {
             bail!("item not found: {item_id}");
         };
+        if let Some((_, item)) = self.items.get_by_index(item_idx)
+            && item.data.contains_key(CAPTURE_FAILED_MIME)
+        {
+            warn!("capture failure placeholder {item_id} has nothing to paste");
+            return Ok(());
+        }
         if item_idx >= self.metadata.pinned_count {
             let item = self.items.remove(&item_id).unwrap();
             self.items.insert(self.metadata.pinned_count, item_id, item);
@@ -964,7 +1285,8 @@ impl<'a> Selection<'a> {
         conn.set_selection_owner(paste_window, self.selection_atom, x11rb::CURRENT_TIME)?
             .check()?;
 
-        let (key, move_pointer, keycode) = get_input_utils(conn, self.screen, self.key_converter);
+        let (key, move_pointer, keycode) =
+            get_input_utils(conn, self.screen, self.key_converter, self.paste_method)?;
         if self.selection_atom == self.atoms.CLIPBOARD {
             let app_paste_keymaps = &self.config.app_paste_keymaps;
             let keymap = if let Some((instance_name, class_name)) =
@@ -982,12 +1304,7 @@ impl<'a> Selection<'a> {
             };
 
             // Prevent holding modifiers from interfering with pasting keymap
-            key(KEY_RELEASE_EVENT, keycode(Keysym::Control_L)?)?;
-            key(KEY_RELEASE_EVENT, keycode(Keysym::Control_R)?)?;
-            key(KEY_RELEASE_EVENT, keycode(Keysym::Shift_L)?)?;
-            key(KEY_RELEASE_EVENT, keycode(Keysym::Shift_R)?)?;
-            key(KEY_RELEASE_EVENT, keycode(Keysym::Alt_L)?)?;
-            key(KEY_RELEASE_EVENT, keycode(Keysym::Alt_R)?)?;
+            release_modifiers(&key, &keycode)?;
 
             info!("pasting into {focused_window} using keymap: {keymap:?}");
             for key_stroke in keymap {
@@ -1001,33 +1318,410 @@ impl<'a> Selection<'a> {
                 }
             }
         } else if self.selection_atom == self.atoms.PRIMARY {
-            info!(
-                "pasting into {focused_window} using middle mouse button at {pointer_original_pos:?}"
-            );
-            let pointer_current_pos = conn.query_pointer(self.screen.root)?.reply()?;
-            move_pointer(pointer_original_pos.0, pointer_original_pos.1)?;
+            let paste_pos = match pointer_anchor {
+                Some((anchor, (x, y))) => {
+                    match conn
+                        .translate_coordinates(anchor, self.screen.root, x, y)?
+                        .reply()
+                    {
+                        Ok(translated) => Some((translated.dst_x, translated.dst_y)),
+                        Err(ReplyError::X11Error(err)) => {
+                            warn!(
+                                "pointer anchor window {anchor} no longer exists ({:?}), skipping middle-click paste",
+                                err.error_kind
+                            );
+                            None
+                        }
+                        Err(err) => return Err(err.into()),
+                    }
+                }
+                None => Some(pointer_original_pos),
+            };
 
-            // middle mouse button
-            key(BUTTON_PRESS_EVENT, 2)?;
-            key(BUTTON_RELEASE_EVENT, 2)?;
+            if let Some(paste_pos) = paste_pos {
+                info!("pasting into {focused_window} using middle mouse button at {paste_pos:?}");
+                let pointer_current_pos = conn.query_pointer(self.screen.root)?.reply()?;
+                move_pointer(paste_pos.0, paste_pos.1)?;
 
-            move_pointer(pointer_current_pos.root_x, pointer_current_pos.root_y)?;
+                // middle mouse button
+                key(BUTTON_PRESS_EVENT, 2)?;
+                key(BUTTON_RELEASE_EVENT, 2)?;
+
+                move_pointer(pointer_current_pos.root_x, pointer_current_pos.root_y)?;
+            }
         }
         conn.flush()?;
 
         self.paste_item_id = Some(item_id);
+        self.paste_payload = payload;
         self.next_paste_modifier = Some(modifier);
 
         Ok(())
     }
 
-    /// Returns true if item is pinned, false if item is unpinned
-    pub fn toggle_pin(&mut self, item_id: u64) -> Result<bool> {
-        let is_pinned = self
+    /// Takes the selection with the item, as if it was copied, without pasting it anywhere
+    pub fn own(&mut self, item_id: u64) -> Result<()> {
+        let conn = &self.window.conn;
+        conn.set_selection_owner(
+            self.window.win_id.get(),
+            self.selection_atom,
+            x11rb::CURRENT_TIME,
+        )?
+        .check()?;
+        conn.flush()?;
+        info!("owning selection with item {item_id}");
+
+        self.paste_item_id = Some(item_id);
+        self.paste_payload = None;
+        self.next_paste_modifier = None;
+        Ok(())
+    }
+
+    /// Takes back the last paste in the focused window, before yank-pop pastes over it
+    pub fn undo_paste(&self, undo: PasteUndo) -> Result<()> {
+        let conn = &self.window.conn;
+        let (key, _, keycode) =
+            get_input_utils(conn, self.screen, self.key_converter, self.paste_method)?;
+        release_modifiers(&key, &keycode)?;
+
+        info!("undoing last paste with {undo:?}");
+        match undo {
+            PasteUndo::Undo => {
+                key(KEY_PRESS_EVENT, keycode(Keysym::Control_L)?)?;
+                key(KEY_PRESS_EVENT, keycode(Keysym::z)?)?;
+                key(KEY_RELEASE_EVENT, keycode(Keysym::z)?)?;
+                key(KEY_RELEASE_EVENT, keycode(Keysym::Control_L)?)?;
+            }
+            PasteUndo::Backspace(count) => {
+                let backspace = keycode(Keysym::BackSpace)?;
+                for _ in 0..count {
+                    key(KEY_PRESS_EVENT, backspace)?;
+                    key(KEY_RELEASE_EVENT, backspace)?;
+                }
+            }
+        }
+        conn.flush()?;
+
+        Ok(())
+    }
+
+    /// Replaces the data of an item in place. Since item ids are derived from the data, the item
+    /// gets a new id, and an existing item with the same data is dropped in favor of it.
+    pub fn replace_item_data(
+        &mut self,
+        item_id: u64,
+        data: SelectionData,
+    ) -> Result<(Option<&SelectionItem>, Vec<SelectionItem>)> {
+        let Some(item_idx) = self.items.iter().position(|(&id, _)| id == item_id) else {
+            bail!("item not found: {item_id}");
+        };
+        let new_item_id = hash_selection_data(&data, &self.config.dedupe_keys)?;
+        if new_item_id == item_id {
+            return Ok((None, vec![]));
+        }
+
+        let mut removed = vec![self.items.remove(&item_id).unwrap()];
+        let source = removed[0].source.clone();
+        let mut new_item_idx = item_idx;
+        if let Some(duplicate_idx) = self.items.iter().position(|(&id, _)| id == new_item_id) {
+            debug!("replaced data duplicates item {new_item_id}, removing it");
+            if duplicate_idx < self.metadata.pinned_count {
+                self.metadata.pinned_count -= 1;
+            }
+            if duplicate_idx < new_item_idx {
+                new_item_idx -= 1;
+            }
+            removed.push(self.items.remove(&new_item_id).unwrap());
+        } else {
+            self.cold_pages.remove(new_item_id);
+        }
+        // Edited in place, so it stays in its day and keeps its marks
+        match self.metadata.copied_at.remove(&item_id) {
+            Some(copied_at) => self.metadata.copied_at.insert(new_item_id, copied_at),
+            None => self.metadata.copied_at.remove(&new_item_id),
+        };
+        for id in self.metadata.marks.values_mut() {
+            if *id == item_id {
+                *id = new_item_id;
+            }
+        }
+
+        self.items.insert(
+            new_item_idx,
+            new_item_id,
+            SelectionItem {
+                id: new_item_id,
+                data,
+                source,
+            },
+        );
+        info!("item {item_id} replaced with {new_item_id}");
+
+        Ok((self.items.get(&new_item_id), removed))
+    }
+
+    /// Adds an item not captured from the selection, such as a transform result or one sent by
+    /// `memoni add`, as the newest unpinned item. Returns its id, whether it's new, and the items pushed out by the item limit
+    pub fn add_derived_item(&mut self, data: SelectionData) -> Result<(u64, Vec<SelectionItem>)> {
+        let new_item_id = hash_selection_data(&data, &self.config.dedupe_keys)?;
+        let mut removed = vec![];
+
+        if self.is_pinned(new_item_id) {
+            debug!("derived item {new_item_id} is already pinned, keeping it");
+        } else if let Some(item) = self.items.remove(&new_item_id) {
+            debug!("derived item {new_item_id} already exists, moving it to the top");
+            self.items
+                .insert(self.metadata.pinned_count, new_item_id, item);
+            self.metadata.copied_at.insert(new_item_id, expiry::now());
+        } else {
+            self.cold_pages.remove(new_item_id);
+            self.items.insert(
+                self.metadata.pinned_count,
+                new_item_id,
+                SelectionItem {
+                    id: new_item_id,
+                    data,
+                    source: None,
+                },
+            );
+            self.metadata.copied_at.insert(new_item_id, expiry::now());
+            removed.extend(self.enforce_item_limit());
+            info!("derived item {new_item_id} added");
+        }
+
+        Ok((new_item_id, removed))
+    }
+
+    /// Keeps a virtual item as a real one, see [`add_derived_item`](Self::add_derived_item)
+    pub fn add_virtual_item(&mut self, item: &SelectionItem) -> Result<(u64, Vec<SelectionItem>)> {
+        let mut data = item.data.clone();
+        data.remove(VIRTUAL_MIME);
+        self.add_derived_item(data)
+    }
+
+    /// Appends the items of a loaded page after the loaded ones, returning their ids
+    pub fn append_page(&mut self, items: Vec<SelectionItem>) -> Vec<u64> {
+        let mut ids = vec![];
+        for item in items {
+            // Saved pages never repeat an item, unless the file was tampered with
+            if !self.items.contains_key(&item.id) {
+                ids.push(item.id);
+                self.items.push_back(item.id, item);
+            }
+        }
+        ids
+    }
+
+    /// Puts an archived item back as the newest unpinned item, returning the items pushed out by
+    /// the item limit
+    pub fn restore_item(&mut self, item: SelectionItem) -> Vec<SelectionItem> {
+        let item_id = item.id;
+        if self.is_pinned(item_id) {
+            return vec![];
+        }
+        self.items.remove(&item_id);
+        self.cold_pages.remove(item_id);
+        self.items.insert(self.metadata.pinned_count, item_id, item);
+        self.metadata.copied_at.insert(item_id, expiry::now());
+        info!("archived item {item_id} restored");
+        self.enforce_item_limit()
+    }
+
+    /// Items pushed out by the item limit since the last call when archiving is on, the loaded
+    /// ones and the pages of those not loaded
+    pub fn take_evicted(&mut self) -> (Vec<SelectionItem>, Vec<PageRef>) {
+        (mem::take(&mut self.evicted), self.cold_pages.take_evicted())
+    }
+
+    // Saved items not loaded yet count towards the limit too, and go first as the oldest
+    fn enforce_item_limit(&mut self) -> Vec<SelectionItem> {
+        let total = self.items.len() + self.cold_pages.len();
+        if total <= self.config.item_limit {
+            return vec![];
+        }
+        let archive = self.config.archive.enabled;
+        let over = total - self.config.item_limit;
+        let loaded_over = over - self.cold_pages.truncate_back(over, archive);
+        let removed = self
+            .items
+            .split_off(self.items.len() - loaded_over)
+            .into_iter()
+            .map(|(_, item)| item)
+            .collect::<Vec<_>>();
+        if archive {
+            self.evicted.extend(removed.iter().cloned());
+        }
+        self.forget_dropped_items();
+        removed
+    }
+
+    /// Drops unpinned items until about `over` bytes are freed, the not loaded ones first as the
+    /// oldest, then large loaded ones from the oldest, then any loaded ones. `cold_size` is what
+    /// the pages of the not loaded ones take
+    pub fn enforce_disk_quota(&mut self, over: u64, cold_size: u64) -> Vec<SelectionItem> {
+        let archive = self.config.archive.enabled;
+        let mut over = over;
+
+        // Their sizes aren't known one by one without loading them
+        let cold_count = self.cold_pages.len() as u64;
+        let mut cold_dropped = 0;
+        if let Some(item_size) = cold_size.checked_div(cold_count) {
+            let item_size = item_size.max(1);
+            let count = over.div_ceil(item_size).min(cold_count);
+            cold_dropped = self.cold_pages.truncate_back(count as usize, archive) as u64;
+            over = over.saturating_sub(cold_dropped * item_size);
+        }
+
+        let unpinned = self
             .items
+            .iter()
+            .skip(self.metadata.pinned_count)
+            .rev()
+            .map(|(&id, item)| (id, item_size(item)))
+            .collect::<Vec<_>>();
+        let mut dropped_ids = HashSet::new();
+        for min_size in [LARGE_ITEM_SIZE, 0] {
+            for &(id, size) in &unpinned {
+                if over == 0 {
+                    break;
+                }
+                if size >= min_size && dropped_ids.insert(id) {
+                    over = over.saturating_sub(size);
+                }
+            }
+        }
+
+        let mut removed = vec![];
+        for id in dropped_ids {
+            self.metadata.expiries.remove(&id);
+            if let Some(item) = self.items.remove(&id) {
+                removed.push(item);
+            }
+        }
+        if archive {
+            self.evicted.extend(removed.iter().cloned());
+        }
+        self.forget_dropped_items();
+        info!(
+            "dropped {cold_dropped} saved and {} loaded items to stay within the disk quota",
+            removed.len()
+        );
+        removed
+    }
+
+    // Items pushed out without remove_item leave their copy times and marks behind
+    fn forget_dropped_items(&mut self) {
+        let kept = self
+            .items
+            .iter()
+            .map(|(&id, _)| id)
+            .chain(self.cold_pages.ids())
+            .collect::<HashSet<_>>();
+        self.metadata.copied_at.retain(|id, _| kept.contains(id));
+        self.metadata.marks.retain(|_, id| kept.contains(id));
+    }
+
+    pub fn is_pinned(&self, item_id: u64) -> bool {
+        self.items
             .iter()
             .take(self.metadata.pinned_count)
-            .any(|(&id, _)| id == item_id);
+            .any(|(&id, _)| id == item_id)
+    }
+
+    pub fn remove_item(&mut self, item_id: u64) -> Option<SelectionItem> {
+        if self.is_pinned(item_id) {
+            self.metadata.pinned_count -= 1;
+        }
+        self.metadata.expiries.remove(&item_id);
+        self.metadata.copied_at.remove(&item_id);
+        self.metadata.marks.retain(|_, &mut id| id != item_id);
+        self.items.remove(&item_id)
+    }
+
+    /// Marks the item with the letter, which the item marked with it before loses
+    pub fn set_mark(&mut self, letter: char, item_id: u64) -> Result<()> {
+        if !self.items.contains_key(&item_id) {
+            bail!("item not found: {item_id}");
+        }
+        self.metadata.marks.insert(letter, item_id);
+        Ok(())
+    }
+
+    /// Marks the item to be removed `secs` from now, or with None, to be kept again
+    pub fn set_expiry(&mut self, item_id: u64, secs: Option<u64>) {
+        match secs {
+            Some(secs) => {
+                self.metadata
+                    .expiries
+                    .insert(item_id, expiry::now().saturating_add(secs));
+            }
+            None => {
+                self.metadata.expiries.remove(&item_id);
+            }
+        }
+    }
+
+    /// Returns true if the item now expires `secs` from now, false if it's kept again
+    pub fn toggle_expiry(&mut self, item_id: u64, secs: u64) -> Result<bool> {
+        if !self.items.contains_key(&item_id) {
+            bail!("item not found: {item_id}");
+        }
+        let expires = !self.metadata.expiries.contains_key(&item_id);
+        self.set_expiry(item_id, expires.then_some(secs));
+        Ok(expires)
+    }
+
+    /// Recreates dead transfer windows and shrinks the pool after a burst of pastes
+    pub fn maintain_transfer_windows(&mut self) -> Result<()> {
+        self.transfer_windows.maintain()?;
+        self.window.conn.flush()?;
+        Ok(())
+    }
+
+    /// Unix seconds of the soonest expiry
+    pub fn next_expiry(&self) -> Option<u64> {
+        self.metadata.expiries.values().min().copied()
+    }
+
+    /// Removes the items whose expiry has passed, returning the loaded ones
+    pub fn remove_expired(&mut self) -> Vec<SelectionItem> {
+        let now = expiry::now();
+        let expired = self
+            .metadata
+            .expiries
+            .iter()
+            .filter(|&(_, &expires_at)| expires_at <= now)
+            .map(|(&id, _)| id)
+            .collect::<Vec<_>>();
+        let mut removed = vec![];
+        for item_id in expired {
+            match self.remove_item(item_id) {
+                Some(item) => removed.push(item),
+                None => {
+                    self.metadata.expiries.remove(&item_id);
+                    self.cold_pages.remove(item_id);
+                }
+            }
+        }
+        removed
+    }
+
+    /// Removes every unpinned item
+    pub fn clear_unpinned(&mut self) -> Vec<SelectionItem> {
+        self.cold_pages.clear();
+        let removed = self
+            .items
+            .split_off(self.metadata.pinned_count)
+            .into_iter()
+            .map(|(_, item)| item)
+            .collect();
+        self.forget_dropped_items();
+        removed
+    }
+
+    /// Returns true if item is pinned, false if item is unpinned
+    pub fn toggle_pin(&mut self, item_id: u64) -> Result<bool> {
+        let is_pinned = self.is_pinned(item_id);
 
         let Some(item) = self.items.remove(&item_id) else {
             bail!("item not found: {item_id}");
@@ -1097,6 +1791,42 @@ fn filter_mimes(mimes: HashMap<Atom, String>) -> HashMap<Atom, String> {
     filtered_mimes
 }
 
+// Legacy encodings are converted once on capture, so the stored text is always UTF-8
+fn normalize_plaintext(data: &mut SelectionData) {
+    let converted = data
+        .iter()
+        .filter(|(mime, _)| is_plaintext_mime(mime))
+        .find_map(|(mime, value)| {
+            legacy_text_to_utf8(mime, value).map(|text| (mime.clone(), text))
+        });
+    if let Some((mime, text)) = converted {
+        debug!("converted {mime} target to UTF8_STRING");
+        data.remove(&mime);
+        data.insert("UTF8_STRING".to_string(), text.into_bytes());
+    }
+}
+
+fn get_fallback_mimes(mimes: &HashMap<Atom, String>) -> Vec<(Atom, String)> {
+    let mut fallback_mimes = mimes
+        .iter()
+        .filter_map(|(&atom, mime)| {
+            if let Some(score) = plaintext_mime_score(mime) {
+                Some((score, atom, mime))
+            } else if is_image_mime(mime) {
+                Some((image_mime_score(mime), atom, mime))
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    fallback_mimes.sort_by(|(score1, ..), (score2, ..)| score2.cmp(score1));
+
+    fallback_mimes
+        .into_iter()
+        .map(|(_, atom, mime)| (atom, mime.to_string()))
+        .collect()
+}
+
 fn get_window_class(conn: &XCBConnection, window: Window) -> Result<Option<(String, String)>> {
     let reply: GetPropertyReply = conn
         .get_property(
@@ -1122,16 +1852,77 @@ fn get_window_class(conn: &XCBConnection, window: Window) -> Result<Option<(Stri
     Ok(Some((instance_name, class_name)))
 }
 
-fn hash_selection_data(data: &SelectionData) -> Result<u64> {
+// Selections are often owned by a hidden window of the application, which may only have WM_CLASS on
+// its client leader
+fn get_owner_class(conn: &XCBConnection, atoms: &Atoms, owner: Owner) -> Option<String> {
+    let class_of = |window| {
+        get_window_class(conn, window)
+            .ok()
+            .flatten()
+            .map(|(_, class_name)| class_name)
+            .filter(|class_name| !class_name.is_empty())
+    };
+    class_of(owner).or_else(|| {
+        let leader = conn
+            .get_property(false, owner, atoms.WM_CLIENT_LEADER, AtomEnum::WINDOW, 0, 1)
+            .ok()?
+            .reply()
+            .ok()?
+            .value32()?
+            .next()
+            .filter(|&leader| leader != owner && leader != x11rb::NONE)?;
+        class_of(leader)
+    })
+}
+
+// Items are told apart by the first dedupe key that applies to them, or else by all of their data
+fn hash_selection_data(data: &SelectionData, dedupe_keys: &[DedupeKey]) -> Result<u64> {
+    let hasher = ahash::RandomState::with_seed(HASH_SEED);
+    for key in dedupe_keys {
+        match key {
+            DedupeKey::Text if !data.keys().any(|mime| is_image_mime(mime)) => {
+                let text = data
+                    .iter()
+                    .filter_map(|(mime, data)| plaintext_mime_score(mime).map(|s| (s, data)))
+                    .max_by_key(|(score, _)| *score);
+                if let Some((_, text)) = text {
+                    return Ok(hasher.hash_one(("text", text)));
+                }
+            }
+            DedupeKey::Text => {}
+            DedupeKey::Image => {
+                let image = data
+                    .iter()
+                    .filter(|(mime, _)| is_image_mime(mime))
+                    .max_by_key(|(mime, _)| image_mime_score(mime));
+                if let Some((mime, image)) = image {
+                    match decode_image(image) {
+                        Ok(pixels) => {
+                            return Ok(hasher.hash_one((
+                                "image",
+                                pixels.dimensions(),
+                                pixels.as_raw(),
+                            )));
+                        }
+                        Err(err) => debug!("failed to decode {mime} for deduping: {err}"),
+                    }
+                }
+            }
+        }
+    }
+
     let data_bin = bincode::encode_to_vec(data, BINCODE_CONFIG)?;
-    let hash = ahash::RandomState::with_seed(HASH_SEED).hash_one(&data_bin);
+    let hash = hasher.hash_one(&data_bin);
 
     Ok(hash)
 }
 
-// Dumb algorithm here is fine I guess
-fn contains(haystack: &[u8], needle: &[u8]) -> bool {
-    haystack.windows(needle.len()).any(|w| w == needle)
+// Roughly what the item takes when saved, its targets and their data
+fn item_size(item: &SelectionItem) -> u64 {
+    item.data
+        .iter()
+        .map(|(mime, data)| (mime.len() + data.len()) as u64)
+        .sum()
 }
 
 fn trim_unicode_utf8(bytes: &[u8]) -> Result<&[u8]> {
@@ -1144,29 +1935,135 @@ fn trim_unicode_utf8(bytes: &[u8]) -> Result<&[u8]> {
     Ok(&bytes[start..end])
 }
 
+fn release_modifiers(
+    key: &impl Fn(u8, u8) -> Result<()>,
+    keycode: &impl Fn(Keysym) -> Result<u8>,
+) -> Result<()> {
+    key(KEY_RELEASE_EVENT, keycode(Keysym::Control_L)?)?;
+    key(KEY_RELEASE_EVENT, keycode(Keysym::Control_R)?)?;
+    key(KEY_RELEASE_EVENT, keycode(Keysym::Shift_L)?)?;
+    key(KEY_RELEASE_EVENT, keycode(Keysym::Shift_R)?)?;
+    key(KEY_RELEASE_EVENT, keycode(Keysym::Alt_L)?)?;
+    key(KEY_RELEASE_EVENT, keycode(Keysym::Alt_R)?)?;
+    // Held with paste_hotkeys
+    for super_key in [Keysym::Super_L, Keysym::Super_R] {
+        if let Ok(super_keycode) = keycode(super_key) {
+            key(KEY_RELEASE_EVENT, super_keycode)?;
+        }
+    }
+    Ok(())
+}
+
 fn get_input_utils(
     conn: &XCBConnection,
     screen: &Screen,
     key_converter: &X11KeyConverter,
-) -> (
+    paste_method: PasteMethod,
+) -> Result<(
     impl Fn(u8, u8) -> Result<()>,
     impl Fn(i16, i16) -> Result<()>,
     impl Fn(Keysym) -> Result<u8>,
-) {
-    let key = |type_, code| {
-        conn.xtest_fake_input(type_, code, x11rb::CURRENT_TIME, screen.root, 1, 1, 0)?;
+)> {
+    let focused_window = if paste_method == PasteMethod::SendEvent {
+        conn.get_input_focus()?.reply()?.focus
+    } else {
+        x11rb::NONE
+    };
+    // Synthetic events carry their own modifier state, so track what has been pressed so far
+    let modifier_state = Cell::new(0u16);
+
+    let key = move |type_, code| {
+        if paste_method != PasteMethod::SendEvent {
+            conn.xtest_fake_input(type_, code, x11rb::CURRENT_TIME, screen.root, 1, 1, 0)?;
+            return Ok(());
+        }
+
+        match type_ {
+            KEY_PRESS_EVENT | KEY_RELEASE_EVENT => {
+                let event = KeyPressEvent {
+                    response_type: type_,
+                    detail: code,
+                    sequence: 0,
+                    time: x11rb::CURRENT_TIME,
+                    root: screen.root,
+                    event: focused_window,
+                    child: x11rb::NONE,
+                    root_x: 1,
+                    root_y: 1,
+                    event_x: 1,
+                    event_y: 1,
+                    state: modifier_state.get().into(),
+                    same_screen: true,
+                };
+                let event_mask = if type_ == KEY_PRESS_EVENT {
+                    EventMask::KEY_PRESS
+                } else {
+                    EventMask::KEY_RELEASE
+                };
+                conn.send_event(true, focused_window, event_mask, event)?;
+
+                let modifier_mask = key_converter.keycode_to_modifier_mask(code.into());
+                if type_ == KEY_PRESS_EVENT {
+                    modifier_state.set(modifier_state.get() | modifier_mask);
+                } else {
+                    modifier_state.set(modifier_state.get() & !modifier_mask);
+                }
+            }
+            BUTTON_PRESS_EVENT | BUTTON_RELEASE_EVENT => {
+                // Deliver to the deepest window under the pointer, as the server would do
+                let pointer = conn.query_pointer(screen.root)?.reply()?;
+                let mut target = screen.root;
+                let (event_x, event_y) = loop {
+                    let translated = conn
+                        .translate_coordinates(screen.root, target, pointer.root_x, pointer.root_y)?
+                        .reply()?;
+                    if translated.child == x11rb::NONE {
+                        break (translated.dst_x, translated.dst_y);
+                    }
+                    target = translated.child;
+                };
+
+                let (event_mask, button_mask) = if type_ == BUTTON_PRESS_EVENT {
+                    (EventMask::BUTTON_PRESS, 0)
+                } else {
+                    (EventMask::BUTTON_RELEASE, 1 << (7 + code))
+                };
+                let event = ButtonPressEvent {
+                    response_type: type_,
+                    detail: code,
+                    sequence: 0,
+                    time: x11rb::CURRENT_TIME,
+                    root: screen.root,
+                    event: target,
+                    child: x11rb::NONE,
+                    root_x: pointer.root_x,
+                    root_y: pointer.root_y,
+                    event_x,
+                    event_y,
+                    state: (modifier_state.get() | button_mask).into(),
+                    same_screen: true,
+                };
+                conn.send_event(true, target, event_mask, event)?;
+            }
+            _ => bail!("unsupported synthetic event type: {type_}"),
+        }
+
         Ok(())
     };
-    let move_pointer = |x, y| {
-        conn.xtest_fake_input(
-            MOTION_NOTIFY_EVENT,
-            0,
-            x11rb::CURRENT_TIME,
-            screen.root,
-            x,
-            y,
-            0,
-        )?;
+    let move_pointer = move |x, y| {
+        if paste_method == PasteMethod::SendEvent {
+            conn.warp_pointer(x11rb::NONE, screen.root, 0, 0, 0, 0, x, y)?;
+        } else {
+            conn.xtest_fake_input(
+                MOTION_NOTIFY_EVENT,
+                0,
+                x11rb::CURRENT_TIME,
+                screen.root,
+                x,
+                y,
+                0,
+            )?;
+        }
         Ok(())
     };
     let keycode = |keysym| {
@@ -1181,5 +2078,5 @@ fn get_input_utils(
             })
     };
 
-    (key, move_pointer, keycode)
+    Ok((key, move_pointer, keycode))
 }
@@ -0,0 +1,161 @@
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write as _},
+    os::unix::fs::{OpenOptionsExt as _, PermissionsExt as _},
+    path::{Path, PathBuf},
+    process::{self, Command, Stdio},
+};
+
+use anyhow::{Context as _, Result, anyhow, bail};
+use log::{debug, info};
+use serde::Deserialize;
+use sha2::{Digest as _, Sha256};
+
+use crate::utils::to_hex_string;
+
+const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/manhdv2103/memoni/releases/latest";
+// Tries at finding a free name for the download, in case earlier runs left theirs behind
+const MAX_TEMP_FILE_ATTEMPTS: u32 = 100;
+
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+/// Replaces the running binary with the one of the latest GitHub release, after checking it
+/// against the release's SHA-256 checksum. Downloads with curl, which must be installed. With
+/// `check_only`, only tells if there's a newer release
+pub fn run(check_only: bool) -> Result<()> {
+    let current = env!("CARGO_PKG_VERSION");
+    info!("checking latest release at {LATEST_RELEASE_URL}");
+    let release: Release = serde_json::from_slice(&curl(LATEST_RELEASE_URL)?)
+        .context("failed to parse the latest release")?;
+    let latest = release.tag_name.trim_start_matches('v');
+
+    if parse_version(latest)? <= parse_version(current)? {
+        println!("memoni v{current} is up to date");
+        return Ok(());
+    }
+    if check_only {
+        println!("memoni v{latest} is available (current: v{current})");
+        return Ok(());
+    }
+
+    // e.g., memoni-x86_64-linux and memoni-x86_64-linux.sha256
+    let binary_name = format!("memoni-{}-linux", env::consts::ARCH);
+    let checksum_name = format!("{binary_name}.sha256");
+    let find_asset = |name: &str| {
+        release
+            .assets
+            .iter()
+            .find(|asset| asset.name == name)
+            .ok_or_else(|| anyhow!("release v{latest} has no {name}"))
+    };
+    let binary_asset = find_asset(&binary_name)?;
+    let checksum_asset = find_asset(&checksum_name)?;
+
+    let checksum = String::from_utf8(curl(&checksum_asset.browser_download_url)?)?;
+    let expected = checksum
+        .split_whitespace()
+        .next()
+        .filter(|hash| hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit()))
+        .ok_or_else(|| anyhow!("invalid checksum in {checksum_name}"))?
+        .to_ascii_lowercase();
+
+    let exe = env::current_exe()?.canonicalize()?;
+    let dir = exe
+        .parent()
+        .ok_or_else(|| anyhow!("binary at {exe:?} has no parent directory"))?;
+    info!("downloading {}", binary_asset.browser_download_url);
+    let data = download_verified(&binary_asset.browser_download_url, &expected)?;
+
+    // In the same directory, so the rename below replaces the binary atomically
+    let tmp_path = write_temp_file(dir, &data)?;
+    debug!("new binary written to {tmp_path:?}");
+    if let Err(err) = fs::rename(&tmp_path, &exe) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(err).with_context(|| format!("failed to replace the binary at {exe:?}"));
+    }
+
+    println!("memoni updated from v{current} to v{latest}, restart running servers to use it");
+    Ok(())
+}
+
+fn download_verified(url: &str, expected_sha256: &str) -> Result<Vec<u8>> {
+    let data = curl(url)?;
+    let actual = to_hex_string(&Sha256::digest(&data));
+    debug!("sha256 of download: {actual}, expected: {expected_sha256}");
+    if actual != expected_sha256 {
+        bail!("checksum mismatch of the download, expected {expected_sha256}, got {actual}");
+    }
+    Ok(data)
+}
+
+// Never opens an existing file, so nothing placed in the directory beforehand gets written through
+fn write_temp_file(dir: &Path, data: &[u8]) -> Result<PathBuf> {
+    for attempt in 0..MAX_TEMP_FILE_ATTEMPTS {
+        let path = dir.join(format!(".memoni.update-{}-{attempt}", process::id()));
+        let mut file = match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o755)
+            .open(&path)
+        {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err).with_context(|| format!("failed to create {path:?}")),
+        };
+        // Executable by everyone like the binary it replaces, whatever the umask
+        let result = file
+            .write_all(data)
+            .and_then(|()| file.set_permissions(fs::Permissions::from_mode(0o755)))
+            .and_then(|()| file.sync_all());
+        if let Err(err) = result {
+            let _ = fs::remove_file(&path);
+            return Err(err).with_context(|| format!("failed to write {path:?}"));
+        }
+        return Ok(path);
+    }
+    bail!("no free name for the download in {dir:?}")
+}
+
+fn curl(url: &str) -> Result<Vec<u8>> {
+    let output = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location"])
+        .args(["--proto", "=https", "--tlsv1.2"])
+        .args(["--header", "Accept: application/vnd.github+json"])
+        .args([
+            "--user-agent",
+            concat!("memoni/", env!("CARGO_PKG_VERSION")),
+        ])
+        .arg(url)
+        .stdin(Stdio::null())
+        .output()
+        .context("failed to run curl")?;
+    if !output.status.success() {
+        bail!(
+            "curl exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output.stdout)
+}
+
+// Pre-release and build suffixes, e.g., "-rc.1", are ignored
+fn parse_version(version: &str) -> Result<(u64, u64, u64)> {
+    let core = version.split(['-', '+']).next().unwrap_or_default();
+    let mut parts = core.split('.').map(str::parse::<u64>);
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(major)), Some(Ok(minor)), Some(Ok(patch)), None) => Ok((major, minor, patch)),
+        _ => bail!("invalid version \"{version}\""),
+    }
+}
@@ -0,0 +1,323 @@
+use std::{
+    collections::HashMap,
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use egui::{
+    Color32, ImageData, Pos2, Rect, TextureFilter, TextureId, epaint::Primitive, epaint::Vertex,
+};
+use log::{debug, info, trace};
+use x11rb::{
+    connection::{Connection as _, RequestConnection as _},
+    protocol::xproto::{ConnectionExt as _, CreateGCAux, ImageFormat, ImageOrder},
+};
+
+use crate::{
+    config::Config,
+    renderer::{RenderTimings, Renderer},
+    x11_window::X11Window,
+};
+
+// Without vsync to wait on, frames are paced like they would be on a 60 Hz display
+const FRAME_INTERVAL: Duration = Duration::from_micros(16_667);
+// Room for the PutImage request header in each strip
+const PUT_IMAGE_HEADER_BYTES: usize = 64;
+
+struct Texture {
+    size: [usize; 2],
+    pixels: Vec<Color32>,
+    filter: TextureFilter,
+}
+
+/// Rasterizes egui meshes on the CPU and uploads the frame with PutImage, for when no usable
+/// EGL/GL is around. Slower than the GL renderer, but only the window contents go through it
+pub struct SoftwareRenderer<'a> {
+    pub dimensions: [u32; 2],
+    window: &'a X11Window<'a>,
+    gc: u32,
+    depth: u8,
+    background: Color32,
+    textures: HashMap<TextureId, Texture>,
+    // Premultiplied, like egui's colors
+    frame: Vec<Color32>,
+    last_presented_at: Option<Instant>,
+}
+
+impl<'a> SoftwareRenderer<'a> {
+    pub fn new(window: &'a X11Window, config: &Config) -> Result<Self> {
+        info!("creating software renderer");
+        let dimensions = [
+            config.layout.window_dimensions.width as u32,
+            config.layout.window_dimensions.height as u32,
+        ];
+
+        let gc = window.conn.generate_id()?;
+        window
+            .conn
+            .create_gc(gc, window.win_id.get(), &CreateGCAux::new())?
+            .check()?;
+        let depth = window
+            .conn
+            .get_geometry(window.win_id.get())?
+            .reply()?
+            .depth;
+        debug!("software rendering to window of depth {depth}");
+
+        Ok(SoftwareRenderer {
+            dimensions,
+            window,
+            gc,
+            depth,
+            background: config.theme.background.into(),
+            textures: HashMap::new(),
+            frame: vec![Color32::TRANSPARENT; (dimensions[0] * dimensions[1]) as usize],
+            last_presented_at: None,
+        })
+    }
+
+    fn update_texture(&mut self, id: TextureId, delta: &egui::epaint::ImageDelta) {
+        let ImageData::Color(image) = &delta.image;
+        let filter = delta.options.magnification;
+        match delta.pos {
+            None => {
+                self.textures.insert(
+                    id,
+                    Texture {
+                        size: image.size,
+                        pixels: image.pixels.clone(),
+                        filter,
+                    },
+                );
+            }
+            Some([x, y]) => {
+                let Some(texture) = self.textures.get_mut(&id) else {
+                    return;
+                };
+                let [width, height] = image.size;
+                for row in 0..height.min(texture.size[1].saturating_sub(y)) {
+                    let columns = width.min(texture.size[0].saturating_sub(x));
+                    let start = (y + row) * texture.size[0] + x;
+                    texture.pixels[start..start + columns]
+                        .copy_from_slice(&image.pixels[row * width..row * width + columns]);
+                }
+            }
+        }
+    }
+
+    fn present(&self) -> Result<()> {
+        let conn = &self.window.conn;
+        let [width, height] = self.dimensions;
+        let lsb_first = conn.setup().image_byte_order == ImageOrder::LSB_FIRST;
+
+        let row_bytes = width as usize * 4;
+        let max_rows = ((conn.maximum_request_bytes() - PUT_IMAGE_HEADER_BYTES) / row_bytes).max(1);
+        let mut data = Vec::with_capacity(row_bytes * max_rows.min(height as usize));
+        for (strip_index, strip) in self.frame.chunks(width as usize * max_rows).enumerate() {
+            data.clear();
+            for pixel in strip {
+                let [r, g, b, a] = pixel.to_array();
+                if lsb_first {
+                    data.extend_from_slice(&[b, g, r, a]);
+                } else {
+                    data.extend_from_slice(&[a, r, g, b]);
+                }
+            }
+            conn.put_image(
+                ImageFormat::Z_PIXMAP,
+                self.window.win_id.get(),
+                self.gc,
+                width as u16,
+                (strip.len() / width as usize) as u16,
+                0,
+                (strip_index * max_rows) as i16,
+                0,
+                self.depth,
+                &data,
+            )?;
+        }
+        conn.flush()?;
+        Ok(())
+    }
+}
+
+impl Renderer for SoftwareRenderer<'_> {
+    /// The window is recreated with the same visual, so only its depth is checked again
+    fn recreate(&mut self) -> Result<()> {
+        self.depth = self
+            .window
+            .conn
+            .get_geometry(self.window.win_id.get())?
+            .reply()?
+            .depth;
+        Ok(())
+    }
+
+    fn render(
+        &mut self,
+        egui_ctx: &egui::Context,
+        full_output: egui::FullOutput,
+    ) -> Result<RenderTimings> {
+        trace!("rendering frame in software");
+        let egui::FullOutput {
+            textures_delta,
+            shapes,
+            pixels_per_point,
+            ..
+        } = full_output;
+
+        let started_at = Instant::now();
+        let clipped_primitives = egui_ctx.tessellate(shapes, pixels_per_point);
+        let tessellated_at = Instant::now();
+
+        for (id, delta) in textures_delta.set {
+            self.update_texture(id, &delta);
+        }
+        self.frame.fill(self.background);
+        for clipped in &clipped_primitives {
+            let Primitive::Mesh(mesh) = &clipped.primitive else {
+                continue;
+            };
+            let Some(texture) = self.textures.get(&mesh.texture_id) else {
+                continue;
+            };
+            let clip_rect = Rect::from_min_max(
+                (clipped.clip_rect.min.to_vec2() * pixels_per_point).to_pos2(),
+                (clipped.clip_rect.max.to_vec2() * pixels_per_point).to_pos2(),
+            );
+            for triangle in mesh.indices.chunks_exact(3) {
+                let [a, b, c] = [0, 1, 2].map(|i| {
+                    let mut vertex = mesh.vertices[triangle[i] as usize];
+                    vertex.pos = (vertex.pos.to_vec2() * pixels_per_point).to_pos2();
+                    vertex
+                });
+                rasterize_triangle(
+                    &mut self.frame,
+                    self.dimensions,
+                    clip_rect,
+                    texture,
+                    [a, b, c],
+                );
+            }
+        }
+        for id in textures_delta.free {
+            self.textures.remove(&id);
+        }
+        let painted_at = Instant::now();
+
+        self.present()?;
+        if let Some(last_presented_at) = self.last_presented_at {
+            thread::sleep(FRAME_INTERVAL.saturating_sub(last_presented_at.elapsed()));
+        }
+        self.last_presented_at = Some(Instant::now());
+        trace!("frame rendered");
+
+        Ok(RenderTimings {
+            tessellate: tessellated_at - started_at,
+            paint: painted_at - tessellated_at,
+            swap: painted_at.elapsed(),
+        })
+    }
+
+    // Every frame is filled from scratch, so there's nothing to invalidate
+    fn set_background(&mut self, color: Color32) {
+        self.background = color;
+    }
+
+    fn destroy(&mut self) {
+        info!("destroying software renderer");
+        let _ = self.window.conn.free_gc(self.gc);
+        self.textures.clear();
+    }
+}
+
+fn rasterize_triangle(
+    frame: &mut [Color32],
+    dimensions: [u32; 2],
+    clip_rect: Rect,
+    texture: &Texture,
+    [a, b, c]: [Vertex; 3],
+) {
+    let edge = |p: Pos2, q: Pos2, r: Pos2| (q.x - p.x) * (r.y - p.y) - (q.y - p.y) * (r.x - p.x);
+    let area = edge(a.pos, b.pos, c.pos);
+    if area.abs() < f32::EPSILON {
+        return;
+    }
+
+    let bounds = Rect::from_points(&[a.pos, b.pos, c.pos])
+        .intersect(clip_rect)
+        .intersect(Rect::from_min_max(
+            Pos2::ZERO,
+            Pos2::new(dimensions[0] as f32, dimensions[1] as f32),
+        ));
+    if !bounds.is_positive() {
+        return;
+    }
+
+    let colors = [a.color, b.color, c.color].map(|color| color.to_array());
+    let width = dimensions[0] as usize;
+    for y in bounds.min.y.floor() as usize..bounds.max.y.ceil() as usize {
+        for x in bounds.min.x.floor() as usize..bounds.max.x.ceil() as usize {
+            let p = Pos2::new(x as f32 + 0.5, y as f32 + 0.5);
+            // Dividing by the signed area makes the weights positive inside either winding
+            let wa = edge(b.pos, c.pos, p) / area;
+            let wb = edge(c.pos, a.pos, p) / area;
+            let wc = edge(a.pos, b.pos, p) / area;
+            if wa < 0.0 || wb < 0.0 || wc < 0.0 {
+                continue;
+            }
+
+            let uv = a.uv.to_vec2() * wa + b.uv.to_vec2() * wb + c.uv.to_vec2() * wc;
+            let color = [0, 1, 2, 3].map(|i| {
+                colors[0][i] as f32 * wa + colors[1][i] as f32 * wb + colors[2][i] as f32 * wc
+            });
+            let texel = sample(texture, uv.to_pos2());
+            let src = [0, 1, 2, 3].map(|i| texel[i] * color[i] / 255.0);
+
+            // Premultiplied alpha blending, the same as egui_glow's
+            let dst = &mut frame[y * width + x];
+            let inverse_alpha = 1.0 - src[3] / 255.0;
+            *dst = Color32::from_rgba_premultiplied(
+                (src[0] + dst.r() as f32 * inverse_alpha).round() as u8,
+                (src[1] + dst.g() as f32 * inverse_alpha).round() as u8,
+                (src[2] + dst.b() as f32 * inverse_alpha).round() as u8,
+                (src[3] + dst.a() as f32 * inverse_alpha).round() as u8,
+            );
+        }
+    }
+}
+
+fn sample(texture: &Texture, uv: Pos2) -> [f32; 4] {
+    let [width, height] = texture.size;
+    if width == 0 || height == 0 {
+        return [0.0; 4];
+    }
+    let texel = |x: usize, y: usize| {
+        texture.pixels[y.min(height - 1) * width + x.min(width - 1)]
+            .to_array()
+            .map(f32::from)
+    };
+
+    let x = uv.x * width as f32 - 0.5;
+    let y = uv.y * height as f32 - 0.5;
+    match texture.filter {
+        TextureFilter::Nearest => texel((x + 0.5).max(0.0) as usize, (y + 0.5).max(0.0) as usize),
+        TextureFilter::Linear => {
+            let (x0, y0) = (x.floor().max(0.0), y.floor().max(0.0));
+            let (fx, fy) = ((x - x0).clamp(0.0, 1.0), (y - y0).clamp(0.0, 1.0));
+            let (x0, y0) = (x0 as usize, y0 as usize);
+            let [top_left, top_right, bottom_left, bottom_right] = [
+                texel(x0, y0),
+                texel(x0 + 1, y0),
+                texel(x0, y0 + 1),
+                texel(x0 + 1, y0 + 1),
+            ];
+            [0, 1, 2, 3].map(|i| {
+                let top = top_left[i] + (top_right[i] - top_left[i]) * fx;
+                let bottom = bottom_left[i] + (bottom_right[i] - bottom_left[i]) * fx;
+                top + (bottom - top) * fy
+            })
+        }
+    }
+}
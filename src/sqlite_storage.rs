@@ -0,0 +1,560 @@
+//! The history in a single SQLite database instead of the history file and its pages, with
+//! `storage = "sqlite"`. It's in WAL mode, so other tools can read it while memoni runs, e.g.,
+//! `sqlite3 ~/.local/share/memoni/clipboard_selections.sqlite "SELECT text FROM items"`, or
+//! searching the full-text index with `... "SELECT text FROM items_fts WHERE items_fts MATCH 'foo'"`
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs, iter, mem,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Result, bail};
+use log::{debug, error, info, warn};
+use rusqlite::{Connection, OptionalExtension as _, params};
+
+use crate::{
+    metrics,
+    migration::Snapshot,
+    ordered_hash_map::OrderedHashMap,
+    persistence::{
+        BINARY_VERSION, BINCODE_CONFIG, ColdPages, HOT_ITEMS, PAGE_ITEMS, PageRef, Persistence,
+        Storage, data_file_path, is_undecodable,
+    },
+    selection::{SelectionItem, SelectionMetadata, SelectionType},
+};
+
+const SCHEMA_VERSION: i64 = 2;
+// Of the metadata in databases saved before its version was
+const UNVERSIONED_METADATA_VERSION: u32 = 9;
+// Item ids are stored with their bits as is, as SQLite integers are signed
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS items (
+        id INTEGER PRIMARY KEY,
+        -- In history order, NULL for dropped items that haven't been archived yet
+        position INTEGER,
+        source TEXT,
+        -- Plain text of the item, for tools searching the history
+        text TEXT
+    );
+    CREATE INDEX IF NOT EXISTS items_position ON items (position);
+    CREATE TABLE IF NOT EXISTS blobs (
+        item_id INTEGER NOT NULL,
+        mime TEXT NOT NULL,
+        data BLOB NOT NULL,
+        PRIMARY KEY (item_id, mime)
+    );
+    CREATE TABLE IF NOT EXISTS meta (
+        key TEXT PRIMARY KEY,
+        value BLOB NOT NULL
+    );
+    -- Items whose text isn't valid UTF-8, kept out of the history for inspection
+    CREATE TABLE IF NOT EXISTS quarantine (
+        item_id INTEGER PRIMARY KEY
+    );
+    -- Full-text index of the text, kept in step with the items by the triggers
+    CREATE VIRTUAL TABLE IF NOT EXISTS items_fts USING fts5 (
+        text,
+        content = 'items',
+        content_rowid = 'id'
+    );
+    CREATE TRIGGER IF NOT EXISTS items_fts_insert AFTER INSERT ON items BEGIN
+        INSERT INTO items_fts (rowid, text) VALUES (new.id, new.text);
+    END;
+    CREATE TRIGGER IF NOT EXISTS items_fts_delete AFTER DELETE ON items BEGIN
+        INSERT INTO items_fts (items_fts, rowid, text) VALUES ('delete', old.id, old.text);
+    END;
+    CREATE TRIGGER IF NOT EXISTS items_fts_update AFTER UPDATE OF text ON items BEGIN
+        INSERT INTO items_fts (items_fts, rowid, text) VALUES ('delete', old.id, old.text);
+        INSERT INTO items_fts (rowid, text) VALUES (new.id, new.text);
+    END;
+";
+
+// Everything written goes through the save thread and its connection, as the one for loading
+// would wait on it for no longer than its busy timeout
+enum Request {
+    Save(SaveRequest),
+    // Kept when they drop out of the history, instead of being deleted
+    Quarantine(u64),
+    WindowPos(Vec<u8>),
+}
+
+struct SaveRequest {
+    // Every item of the history, loaded or not, in history order
+    ids: Vec<u64>,
+    // Dropped items to be archived, kept until they're taken
+    evicted_ids: Vec<u64>,
+    // Items that aren't in the database yet
+    new_items: Vec<SelectionItem>,
+    metadata: Vec<u8>,
+}
+
+pub struct SqliteStorage {
+    db_path: PathBuf,
+    archive_file_path: PathBuf,
+    // For loading, saves go through a connection of their own on the save thread
+    conn: Connection,
+    sender: mpsc::Sender<Request>,
+    // Items in the database, or about to be saved to it
+    stored_ids: HashSet<u64>,
+}
+
+impl SqliteStorage {
+    pub fn open(selection_type: SelectionType, session_id: &Option<String>) -> Result<Self> {
+        let db_path =
+            data_file_path(selection_type, session_id, "selections")?.with_extension("sqlite");
+        let archive_file_path = data_file_path(selection_type, session_id, "archive")?;
+
+        let mut writer = connect(&db_path)?;
+        let version: i64 = writer.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version > SCHEMA_VERSION {
+            bail!("{db_path:?} was created by a newer memoni (schema version {version})");
+        }
+        writer.execute_batch(SCHEMA)?;
+        // The full-text index came with version 2, after the items it has to cover
+        if version < 2 {
+            writer.execute("INSERT INTO items_fts (items_fts) VALUES ('rebuild')", [])?;
+        }
+        writer.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        if !has_metadata(&writer) {
+            import_history_files(&mut writer, selection_type, session_id);
+        }
+        let conn = connect(&db_path)?;
+
+        let (sender, receiver) = mpsc::channel::<Request>();
+        thread::spawn(move || {
+            while let Ok(request) = receiver.recv() {
+                let mut save: Option<SaveRequest> = None;
+                for request in iter::once(request).chain(receiver.try_iter()) {
+                    match request {
+                        // Only the latest order matters, but the items of the skipped saves are
+                        // still new
+                        Request::Save(newer) => {
+                            save = Some(match save {
+                                Some(mut request) => {
+                                    let mut new_items = mem::take(&mut request.new_items);
+                                    new_items.extend(newer.new_items);
+                                    SaveRequest { new_items, ..newer }
+                                }
+                                None => newer,
+                            });
+                        }
+                        Request::Quarantine(id) => {
+                            if let Err(e) = writer.execute(
+                                "INSERT OR IGNORE INTO quarantine (item_id) VALUES (?1)",
+                                params![id as i64],
+                            ) {
+                                error!("failed to quarantine item {id} in background: {e}");
+                            }
+                        }
+                        Request::WindowPos(pos) => {
+                            if let Err(e) = writer.execute(
+                                "INSERT OR REPLACE INTO meta (key, value) VALUES ('window_pos', ?1)",
+                                params![pos],
+                            ) {
+                                error!("failed to save window position in background: {e}");
+                            }
+                        }
+                    }
+                }
+                if let Some(save) = save {
+                    match write(&mut writer, &save) {
+                        Ok(()) => debug!("saving selection items in background completed"),
+                        Err(e) => error!("failed to save selection items in background: {e}"),
+                    }
+                }
+            }
+        });
+
+        Ok(SqliteStorage {
+            db_path,
+            archive_file_path,
+            conn,
+            sender,
+            stored_ids: HashSet::new(),
+        })
+    }
+
+    // None for items that are gone, or whose text isn't valid UTF-8 as expected everywhere else,
+    // which are quarantined
+    fn load_item(&self, id: u64) -> Result<Option<SelectionItem>> {
+        let Some(source) = self
+            .conn
+            .prepare_cached("SELECT source FROM items WHERE id = ?1")?
+            .query_row(params![id as i64], |row| row.get::<_, Option<String>>(0))
+            .optional()?
+        else {
+            return Ok(None);
+        };
+        let data = self
+            .conn
+            .prepare_cached("SELECT mime, data FROM blobs WHERE item_id = ?1")?
+            .query_map(params![id as i64], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<BTreeMap<_, _>>>()?;
+
+        let item = SelectionItem { id, data, source };
+        if is_undecodable(&item) {
+            warn!("item {id} has text that isn't valid UTF-8, quarantining it");
+            self.sender.send(Request::Quarantine(id))?;
+            return Ok(None);
+        }
+        Ok(Some(item))
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn save_selection_data(
+        &mut self,
+        items: &OrderedHashMap<u64, SelectionItem>,
+        metadata: &SelectionMetadata,
+        cold_pages: &ColdPages,
+    ) -> Result<()> {
+        info!("saving selection items to {:?}", self.db_path);
+
+        let ids = items
+            .iter()
+            .map(|(&id, _)| id)
+            .chain(cold_pages.ids())
+            .collect::<Vec<_>>();
+        let evicted_ids = cold_pages.evicted_ids().collect::<Vec<_>>();
+        let new_items = items
+            .iter()
+            .filter(|&(id, _)| !self.stored_ids.contains(id))
+            .map(|(_, item)| item.clone())
+            .collect::<Vec<_>>();
+        self.stored_ids = ids.iter().chain(&evicted_ids).copied().collect();
+        debug!("saving {} items, {} new", ids.len(), new_items.len());
+
+        metrics::set_history(ids.len(), self.disk_usage() as usize);
+        self.sender.send(Request::Save(SaveRequest {
+            ids,
+            evicted_ids,
+            new_items,
+            metadata: bincode::encode_to_vec(metadata, BINCODE_CONFIG)?,
+        }))?;
+
+        Ok(())
+    }
+
+    fn has_selection_data(&self) -> bool {
+        has_metadata(&self.conn)
+    }
+
+    fn load_selection_data(
+        &mut self,
+    ) -> Result<(
+        OrderedHashMap<u64, SelectionItem>,
+        SelectionMetadata,
+        ColdPages,
+    )> {
+        let metadata = self
+            .conn
+            .query_row("SELECT value FROM meta WHERE key = 'metadata'", [], |row| {
+                row.get::<_, Vec<u8>>(0)
+            })
+            .optional()?;
+        let Some(metadata) = metadata else {
+            info!("no persisted selection items presented, skip loading");
+            return Ok((
+                OrderedHashMap::new(),
+                SelectionMetadata::default(),
+                ColdPages::default(),
+            ));
+        };
+
+        info!("loading selection items from {:?}", self.db_path);
+        let version = self
+            .conn
+            .query_row(
+                "SELECT value FROM meta WHERE key = 'metadata_version'",
+                [],
+                |row| row.get::<_, u32>(0),
+            )
+            .optional()?
+            .unwrap_or(UNVERSIONED_METADATA_VERSION);
+        if version > BINARY_VERSION {
+            bail!(
+                "{:?} was saved by a newer memoni (version {version})",
+                self.db_path
+            );
+        }
+        let (_, mut metadata, _) = Snapshot::decode_metadata(version, &metadata)?.into_current();
+        let ids = self
+            .conn
+            .prepare("SELECT id FROM items WHERE position IS NOT NULL ORDER BY position")?
+            .query_map([], |row| row.get::<_, i64>(0))?
+            .map(|id| Ok(id? as u64))
+            .collect::<Result<Vec<_>>>()?;
+
+        // Loaded and paged like the history file, so startup doesn't wait on old items
+        let hot_count = HOT_ITEMS.max(metadata.pinned_count).min(ids.len());
+        let pinned_count = metadata.pinned_count;
+        let mut items = OrderedHashMap::new();
+        for (idx, &id) in ids[..hot_count].iter().enumerate() {
+            match self.load_item(id)? {
+                Some(item) => {
+                    items.push_back(id, item);
+                }
+                None if idx < pinned_count => metadata.pinned_count -= 1,
+                None => {}
+            }
+        }
+        metadata.pinned_count = metadata.pinned_count.min(items.len());
+        let cold_pages = ColdPages::from_pages(
+            ids[hot_count..]
+                .chunks(PAGE_ITEMS)
+                .enumerate()
+                .map(|(i, chunk)| {
+                    PageRef::new(
+                        format!("rows {}", hot_count + i * PAGE_ITEMS),
+                        chunk.to_vec(),
+                    )
+                })
+                .collect(),
+        );
+        self.stored_ids = ids.into_iter().collect();
+
+        info!(
+            "{} items loaded, {} more not loaded yet",
+            items.len(),
+            cold_pages.len()
+        );
+        metrics::set_history(items.len() + cold_pages.len(), self.disk_usage() as usize);
+        Ok((items, metadata, cold_pages))
+    }
+
+    fn load_page(&self, page: &PageRef) -> Result<Vec<SelectionItem>> {
+        debug!("loading a page of {} items", page.ids().len());
+        let mut items = vec![];
+        for &id in page.ids() {
+            items.extend(self.load_item(id)?);
+        }
+        Ok(items)
+    }
+
+    fn disk_usage(&self) -> u64 {
+        [&self.db_path, &self.db_path.with_extension("sqlite-wal")]
+            .into_iter()
+            .filter_map(|path| fs::metadata(path).ok())
+            .map(|metadata| metadata.len())
+            .sum()
+    }
+
+    fn cold_pages_size(&self, cold_pages: &ColdPages) -> u64 {
+        let item_size = |id: u64| -> rusqlite::Result<i64> {
+            self.conn
+                .prepare_cached(
+                    "SELECT COALESCE(SUM(length(data)), 0) FROM blobs WHERE item_id = ?1",
+                )?
+                .query_row(params![id as i64], |row| row.get(0))
+        };
+        cold_pages
+            .ids()
+            .map(|id| item_size(id).unwrap_or(0) as u64)
+            .sum()
+    }
+
+    fn archive_file_path(&self) -> &Path {
+        &self.archive_file_path
+    }
+
+    fn save_window_pos(&self, pos: (i16, i16)) -> Result<()> {
+        debug!("saving window position {pos:?} to {:?}", self.db_path);
+        self.sender.send(Request::WindowPos(bincode::encode_to_vec(
+            pos,
+            BINCODE_CONFIG,
+        )?))?;
+        Ok(())
+    }
+
+    fn load_window_pos(&self) -> Option<(i16, i16)> {
+        let data: Vec<u8> = self
+            .conn
+            .query_row(
+                "SELECT value FROM meta WHERE key = 'window_pos'",
+                [],
+                |row| row.get(0),
+            )
+            .ok()?;
+        match bincode::decode_from_slice(&data, BINCODE_CONFIG) {
+            Ok((pos, _)) => {
+                debug!("loaded window position {pos:?}");
+                Some(pos)
+            }
+            Err(err) => {
+                error!("failed to decode persisted window position: {err}");
+                None
+            }
+        }
+    }
+}
+
+fn has_metadata(conn: &Connection) -> bool {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM meta WHERE key = 'metadata')",
+        [],
+        |row| row.get(0),
+    )
+    .unwrap_or(false)
+}
+
+// The history saved to files before switching storage is copied over once, so it isn't lost.
+// The files are left in place, for switching back
+fn import_history_files(
+    conn: &mut Connection,
+    selection_type: SelectionType,
+    session_id: &Option<String>,
+) {
+    let result = Persistence::new(selection_type, session_id).and_then(|mut files| {
+        if !files.has_selection_data() {
+            return Ok(None);
+        }
+        let (mut items, metadata, mut cold_pages) = files.load_selection_data()?;
+        while let Some(page) = cold_pages.pop_front() {
+            for item in files.load_page(&page)? {
+                items.push_back(item.id, item);
+            }
+        }
+        let count = items.len();
+        write(
+            conn,
+            &SaveRequest {
+                ids: items.iter().map(|(&id, _)| id).collect(),
+                evicted_ids: vec![],
+                new_items: items.into_iter().map(|(_, item)| item).collect(),
+                metadata: bincode::encode_to_vec(&metadata, BINCODE_CONFIG)?,
+            },
+        )?;
+        Ok(Some(count))
+    });
+    match result {
+        Ok(Some(count)) => info!("imported {count} items from the history files"),
+        Ok(None) => {}
+        Err(err) => warn!("failed to import the history files, which are left in place: {err}"),
+    }
+}
+
+fn connect(db_path: &Path) -> Result<Connection> {
+    let conn = Connection::open(db_path)?;
+    // Readers don't block the save thread, nor the other way around
+    conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+    conn.busy_timeout(Duration::from_secs(1))?;
+    Ok(conn)
+}
+
+// All at once, so a crash leaves the previous save in place
+fn write(conn: &mut Connection, request: &SaveRequest) -> Result<()> {
+    let tx = conn.transaction()?;
+    {
+        // Updated in place rather than replaced, as replacing skips the delete trigger of the
+        // full-text index
+        let mut insert_item = tx.prepare_cached(
+            "INSERT INTO items (id, source, text) VALUES (?1, ?2, ?3)
+            ON CONFLICT (id) DO UPDATE SET source = excluded.source, text = excluded.text",
+        )?;
+        let mut insert_blob = tx.prepare_cached(
+            "INSERT OR REPLACE INTO blobs (item_id, mime, data) VALUES (?1, ?2, ?3)",
+        )?;
+        for item in &request.new_items {
+            insert_item.execute(params![item.id as i64, item.source, item.text()])?;
+            for (mime, data) in &item.data {
+                insert_blob.execute(params![item.id as i64, mime, data])?;
+            }
+        }
+    }
+
+    tx.execute_batch(
+        "CREATE TEMP TABLE IF NOT EXISTS kept (id INTEGER PRIMARY KEY);
+        DELETE FROM kept;
+        UPDATE items SET position = NULL;",
+    )?;
+    {
+        let mut set_position = tx.prepare_cached("UPDATE items SET position = ?2 WHERE id = ?1")?;
+        let mut keep = tx.prepare_cached("INSERT OR IGNORE INTO kept (id) VALUES (?1)")?;
+        for (position, &id) in request.ids.iter().enumerate() {
+            set_position.execute(params![id as i64, position as i64])?;
+            keep.execute(params![id as i64])?;
+        }
+        for &id in &request.evicted_ids {
+            keep.execute(params![id as i64])?;
+        }
+    }
+    tx.execute_batch(
+        "INSERT OR IGNORE INTO kept (id) SELECT item_id FROM quarantine;
+        DELETE FROM blobs WHERE item_id NOT IN (SELECT id FROM kept);
+        DELETE FROM items WHERE id NOT IN (SELECT id FROM kept);",
+    )?;
+    tx.execute(
+        "INSERT OR REPLACE INTO meta (key, value) VALUES ('metadata', ?1)",
+        params![request.metadata],
+    )?;
+    tx.execute(
+        "INSERT OR REPLACE INTO meta (key, value) VALUES ('metadata_version', ?1)",
+        params![BINARY_VERSION],
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_item(id: u64, text: &str) -> SelectionItem {
+        SelectionItem {
+            id,
+            data: BTreeMap::from([("UTF8_STRING".to_string(), text.as_bytes().to_vec())]),
+            source: None,
+        }
+    }
+
+    fn save(conn: &mut Connection, ids: &[u64], new_items: Vec<SelectionItem>) -> Result<()> {
+        write(
+            conn,
+            &SaveRequest {
+                ids: ids.to_vec(),
+                evicted_ids: vec![],
+                new_items,
+                metadata: vec![],
+            },
+        )
+    }
+
+    fn search(conn: &Connection, query: &str) -> Result<Vec<i64>> {
+        Ok(conn
+            .prepare("SELECT rowid FROM items_fts WHERE items_fts MATCH ?1 ORDER BY rowid")?
+            .query_map([query], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    #[test]
+    fn keeps_quarantined_items_and_the_text_index() -> Result<()> {
+        let mut conn = Connection::open_in_memory()?;
+        conn.execute_batch(SCHEMA)?;
+        save(
+            &mut conn,
+            &[1, 2],
+            vec![text_item(1, "apple pie"), text_item(2, "banana split")],
+        )?;
+        // Saved again as new, like after the history got reloaded
+        save(&mut conn, &[1, 2], vec![text_item(1, "apple pie")])?;
+        assert_eq!(search(&conn, "apple")?, [1]);
+        // As when opening a database of schema version 1
+        conn.execute("INSERT INTO items_fts (items_fts) VALUES ('rebuild')", [])?;
+        assert_eq!(search(&conn, "apple")?, [1]);
+
+        conn.execute("INSERT INTO quarantine (item_id) VALUES (2)", [])?;
+        save(&mut conn, &[3], vec![text_item(3, "cherry")])?;
+        let ids = conn
+            .prepare("SELECT id FROM items ORDER BY id")?
+            .query_map([], |row| row.get::<_, i64>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        assert_eq!(ids, [2, 3]);
+        assert!(search(&conn, "apple")?.is_empty());
+        assert_eq!(search(&conn, "banana OR cherry")?, [2, 3]);
+        Ok(())
+    }
+}
@@ -0,0 +1,176 @@
+//! Decoding and encoding of text the way other X clients put it in selections: UTF-16 from
+//! browsers, and percent-encoded URIs from file managers. Everything here takes arbitrary bytes
+//! without panicking
+
+use std::borrow::Cow;
+
+/// Decodes little-endian UTF-16, as Firefox puts it in text/x-moz-url. Unpaired surrogates and a
+/// trailing odd byte, which can't be a code unit, become U+FFFD
+pub fn utf16le_to_string(bytes: &[u8]) -> String {
+    let chunks = bytes.chunks_exact(2);
+    let has_odd_byte = !chunks.remainder().is_empty();
+    let mut string = char::decode_utf16(chunks.map(|unit| u16::from_le_bytes([unit[0], unit[1]])))
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect::<String>();
+    if has_odd_byte {
+        string.push(char::REPLACEMENT_CHARACTER);
+    }
+    string
+}
+
+/// Decodes %XX escapes of URIs. A '%' not followed by two hex digits is kept as is, and input
+/// without escapes is returned without copying
+pub fn percent_decode(input: &[u8]) -> Cow<'_, [u8]> {
+    let Some(first_escape) = input.iter().position(|&b| b == b'%') else {
+        return Cow::Borrowed(input);
+    };
+
+    let mut out = Vec::with_capacity(input.len());
+    out.extend_from_slice(&input[..first_escape]);
+    let mut i = first_escape;
+    while i < input.len() {
+        if input[i] == b'%'
+            && let Some(&[x, y]) = input.get(i + 1..i + 3)
+            && let (Some(hi), Some(lo)) = (hex_value(x), hex_value(y))
+        {
+            out.push((hi << 4) | lo);
+            i += 3;
+        } else {
+            out.push(input[i]);
+            i += 1;
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// Escapes everything but the unreserved characters and sub-delimiters of RFC 3986, with ':' and
+/// '@', as freedesktop thumbnail URIs expect of path segments
+pub fn percent_encode(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len());
+    for b in input {
+        match b {
+            b'A'..=b'Z'
+            | b'a'..=b'z'
+            | b'0'..=b'9'
+            | b'!'
+            | b'$'
+            | b'&'
+            | b'\''
+            | b'('
+            | b')'
+            | b'*'
+            | b'+'
+            | b','
+            | b'-'
+            | b'.'
+            | b':'
+            | b'='
+            | b'@'
+            | b'_'
+            | b'~' => out.push(*b),
+            _ => {
+                out.push(b'%');
+                out.append(&mut format!("{:02X}", b).into_bytes());
+            }
+        }
+    }
+    out
+}
+
+fn hex_value(digit: u8) -> Option<u8> {
+    (digit as char).to_digit(16).map(|v| v as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    #[test]
+    fn utf16le_decodes_text() {
+        assert_eq!(utf16le_to_string(&[]), "");
+        assert_eq!(
+            utf16le_to_string(&utf16le("https://example.com\nTitle")),
+            "https://example.com\nTitle"
+        );
+        // Surrogate pairs and characters outside Latin-1
+        assert_eq!(
+            utf16le_to_string(&utf16le("héllo 世界 🦀")),
+            "héllo 世界 🦀"
+        );
+    }
+
+    #[test]
+    fn utf16le_replaces_invalid_data() {
+        let mut odd = utf16le("ab");
+        odd.push(b'c');
+        assert_eq!(utf16le_to_string(&odd), "ab\u{FFFD}");
+        assert_eq!(utf16le_to_string(&[0x41]), "\u{FFFD}");
+
+        // Lone high and low surrogates
+        assert_eq!(utf16le_to_string(&[0x3d, 0xd8, 0x41, 0x00]), "\u{FFFD}A");
+        assert_eq!(utf16le_to_string(&[0x80, 0xdc]), "\u{FFFD}");
+    }
+
+    #[test]
+    fn utf16le_handles_unaligned_input() {
+        let bytes = [0u8, 0x41, 0x00, 0x42, 0x00];
+        assert_eq!(utf16le_to_string(&bytes[1..]), "AB");
+    }
+
+    #[test]
+    fn percent_decode_borrows_unescaped_input() {
+        assert!(matches!(percent_decode(b""), Cow::Borrowed(b"")));
+        assert!(matches!(
+            percent_decode(b"/home/user/file.txt"),
+            Cow::Borrowed(b"/home/user/file.txt")
+        ));
+    }
+
+    #[test]
+    fn percent_decode_decodes_escapes() {
+        assert_eq!(&*percent_decode(b"a%20b"), b"a b");
+        assert_eq!(&*percent_decode(b"%2f%2F"), b"//");
+        assert_eq!(&*percent_decode(b"%E4%B8%96"), "世".as_bytes());
+        assert_eq!(&*percent_decode(b"%00%ff"), &[0x00, 0xff]);
+        assert_eq!(&*percent_decode(b"%25"), b"%");
+        assert_eq!(&*percent_decode(b"%2525"), b"%25");
+    }
+
+    #[test]
+    fn percent_decode_keeps_invalid_escapes() {
+        assert_eq!(&*percent_decode(b"%"), b"%");
+        assert_eq!(&*percent_decode(b"100%"), b"100%");
+        assert_eq!(&*percent_decode(b"%4"), b"%4");
+        assert_eq!(&*percent_decode(b"%zz"), b"%zz");
+        assert_eq!(&*percent_decode(b"%+1"), b"%+1");
+        // The escape right after an invalid one still counts
+        assert_eq!(&*percent_decode(b"%4%41"), b"%4A");
+        assert_eq!(&*percent_decode(b"%%41"), b"%A");
+        // Bytes that aren't UTF-8 after '%'
+        assert_eq!(&*percent_decode(&[b'%', 0xff, 0xfe]), &[b'%', 0xff, 0xfe]);
+    }
+
+    #[test]
+    fn percent_decode_decodes_every_byte() {
+        for byte in 0..=u8::MAX {
+            for escape in [format!("%{byte:02x}"), format!("%{byte:02X}")] {
+                assert_eq!(&*percent_decode(escape.as_bytes()), &[byte]);
+            }
+        }
+    }
+
+    #[test]
+    fn percent_encode_round_trips() {
+        let all_bytes = (0..=u8::MAX).collect::<Vec<_>>();
+        let encoded = percent_encode(&all_bytes);
+        assert!(encoded.is_ascii());
+        assert_eq!(&*percent_decode(&encoded), all_bytes.as_slice());
+
+        assert_eq!(percent_encode(b"my file.txt"), b"my%20file.txt");
+        assert_eq!(percent_encode("ü".as_bytes()), b"%C3%BC");
+    }
+}
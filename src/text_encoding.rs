@@ -0,0 +1,161 @@
+use encoding_rs::{
+    EUC_JP, EUC_KR, Encoding, GBK, ISO_8859_2, ISO_8859_3, ISO_8859_4, ISO_8859_5, ISO_8859_6,
+    ISO_8859_7, ISO_8859_8, ISO_8859_15, UTF_16LE, WINDOWS_1252, WINDOWS_1254,
+};
+use log::debug;
+
+const ESC: u8 = 0x1b;
+
+/// Converts a plaintext target that isn't UTF-8 into UTF-8, None if the target is UTF-8 already
+pub fn legacy_text_to_utf8(mime: &str, data: &[u8]) -> Option<String> {
+    let mime = mime.to_ascii_lowercase();
+    match mime.as_str() {
+        "utf8_string" | "text/plain;charset=utf-8" => None,
+        // ICCCM defines STRING as ISO Latin-1
+        "string" => Some(latin1_to_string(data)),
+        "compound_text" => Some(compound_text_to_string(data)),
+        // Mozilla and Qt mean UTF-16 by "unicode"
+        "text/plain;charset=unicode" => Some(UTF_16LE.decode(data).0.into_owned()),
+        _ => {
+            if let Some(charset) = mime.strip_prefix("text/plain;charset=")
+                && let Some(encoding) = Encoding::for_label(charset.as_bytes())
+                && encoding != encoding_rs::UTF_8
+            {
+                return Some(encoding.decode(data).0.into_owned());
+            }
+
+            // TEXT and the unlabeled targets can be anything, but are UTF-8 for almost every
+            // client, so only invalid UTF-8 is taken as Latin-1
+            str::from_utf8(data)
+                .is_err()
+                .then(|| latin1_to_string(data))
+        }
+    }
+}
+
+fn latin1_to_string(data: &[u8]) -> String {
+    data.iter().map(|&b| b as char).collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Charset {
+    Ascii,
+    // Bytes are fed to the encoding with their high bit set
+    Encoded(&'static Encoding),
+    Unsupported,
+}
+
+/// Decodes the ISO 2022 based Compound Text encoding of X11 for the charsets Xlib emits: ASCII,
+/// the ISO 8859 parts, the CJK double-byte sets and UTF-8 extended segments
+fn compound_text_to_string(data: &[u8]) -> String {
+    let mut text = String::new();
+    let mut gl = Charset::Ascii;
+    let mut gr = Charset::Encoded(WINDOWS_1252);
+    let mut run = vec![];
+    let mut run_charset = Charset::Ascii;
+
+    let flush = |text: &mut String, run: &mut Vec<u8>, charset: Charset| {
+        if run.is_empty() {
+            return;
+        }
+        match charset {
+            Charset::Ascii => text.push_str(&String::from_utf8_lossy(run)),
+            Charset::Encoded(encoding) => {
+                text.push_str(&encoding.decode_without_bom_handling(run).0)
+            }
+            Charset::Unsupported => text.push(char::REPLACEMENT_CHARACTER),
+        }
+        run.clear();
+    };
+
+    let mut i = 0;
+    while i < data.len() {
+        let byte = data[i];
+        if byte == ESC {
+            let end = data[i + 1..]
+                .iter()
+                .position(|b| !(0x20..=0x2f).contains(b))
+                .map(|p| i + 1 + p);
+            let Some(end) = end.filter(|&end| end < data.len()) else {
+                break;
+            };
+            let sequence = &data[i + 1..=end];
+            i = end + 1;
+
+            match sequence {
+                b"(B" | b"(J" => gl = Charset::Ascii,
+                [b'-', f] => gr = iso_8859_part(*f),
+                [b'$', b'(', f] | [b'$', f @ (b'A' | b'B' | b'C')] => gl = double_byte_set(*f),
+                [b'$', b')', f] => gr = double_byte_set(*f),
+                b"%G" => {
+                    flush(&mut text, &mut run, run_charset);
+                    let segment_end = data[i..]
+                        .windows(3)
+                        .position(|w| w == b"\x1b%@")
+                        .map_or(data.len(), |p| i + p);
+                    text.push_str(&String::from_utf8_lossy(&data[i..segment_end]));
+                    i = (segment_end + 3).min(data.len());
+                }
+                // Extended segments with a length, used for non-standard charsets
+                [b'%', b'/', _] if i + 1 < data.len() => {
+                    let length = (data[i] as usize & 0x7f) * 128 + (data[i + 1] as usize & 0x7f);
+                    flush(&mut text, &mut run, run_charset);
+                    text.push(char::REPLACEMENT_CHARACTER);
+                    i = (i + 2 + length).min(data.len());
+                }
+                _ => debug!("unsupported compound text escape sequence {sequence:?}"),
+            }
+            continue;
+        }
+        i += 1;
+
+        // C1 controls have no meaning in text
+        if (0x80..0xa0).contains(&byte) {
+            continue;
+        }
+        let charset = if byte < 0x20 || byte == 0x7f {
+            Charset::Ascii
+        } else if byte < 0x80 {
+            gl
+        } else {
+            gr
+        };
+
+        if charset != run_charset {
+            flush(&mut text, &mut run, run_charset);
+            run_charset = charset;
+        }
+        run.push(match charset {
+            Charset::Encoded(_) => byte | 0x80,
+            _ => byte,
+        });
+    }
+    flush(&mut text, &mut run, run_charset);
+
+    text
+}
+
+fn iso_8859_part(final_byte: u8) -> Charset {
+    Charset::Encoded(match final_byte {
+        b'A' => WINDOWS_1252,
+        b'B' => ISO_8859_2,
+        b'C' => ISO_8859_3,
+        b'D' => ISO_8859_4,
+        b'L' => ISO_8859_5,
+        b'G' => ISO_8859_6,
+        b'F' => ISO_8859_7,
+        b'H' => ISO_8859_8,
+        b'M' => WINDOWS_1254,
+        b'b' => ISO_8859_15,
+        _ => return Charset::Unsupported,
+    })
+}
+
+fn double_byte_set(final_byte: u8) -> Charset {
+    Charset::Encoded(match final_byte {
+        b'A' => GBK,
+        b'B' => EUC_JP,
+        b'C' => EUC_KR,
+        _ => return Charset::Unsupported,
+    })
+}
@@ -0,0 +1,290 @@
+use std::{
+    collections::HashMap,
+    mem,
+    time::{Duration, Instant},
+};
+
+use egui::{Color32, ColorImage, Rect, TextureHandle, Vec2, load::SizedTexture, pos2, vec2};
+use image::{RgbaImage, imageops};
+use log::{debug, trace};
+
+use crate::config::Config;
+
+// Room for 256 previews of up to 62x62 in a single 1 MiB texture
+const ATLAS_SIZE: usize = 1024;
+const SLOT_SIZE: usize = 64;
+const SLOTS_PER_ROW: usize = ATLAS_SIZE / SLOT_SIZE;
+// Each preview is surrounded by a copy of its edge pixels, so linear filtering doesn't bleed in
+// its neighbors
+const SLOT_BORDER: usize = 1;
+// Previews seen more recently are kept over the memory limit, so visible ones aren't unloaded
+// and loaded again on every frame
+const UNLOAD_UNSEEN_AFTER: Duration = Duration::from_secs(10);
+// Previews loaded past the memory limit are halved in size down to this
+const MIN_DOWNSCALED_SIDE: u32 = 8;
+
+/// A preview to paint, either a texture of its own or a part of the shared atlas
+#[derive(Clone)]
+pub struct PreviewTexture {
+    // Keeps the texture alive
+    handle: TextureHandle,
+    pub uv: Rect,
+    pub size: Vec2,
+}
+
+impl PreviewTexture {
+    pub fn sized_texture(&self) -> SizedTexture {
+        SizedTexture::new(self.handle.id(), self.size)
+    }
+}
+
+enum Placement {
+    Own(TextureHandle),
+    Atlas(usize),
+}
+
+struct CachedTexture {
+    // Kept to tell if a rebuilt preview changed
+    source: RgbaImage,
+    // Possibly downscaled, kept to upload it again into a new context
+    image: ColorImage,
+    placement: Placement,
+    last_seen: Instant,
+}
+
+/// Preview textures of items, so rebuilding a widget only uploads its preview when it changed.
+/// Small previews, like file icons and image thumbnails, share one atlas texture instead of
+/// taking one each. Textures live until their item is removed, or until they get unloaded to
+/// stay within `preview_memory_limit_mb`
+#[derive(Default)]
+pub struct TextureCache {
+    textures: HashMap<u64, CachedTexture>,
+    atlas: Option<TextureHandle>,
+    free_slots: Vec<usize>,
+    next_slot: usize,
+    max_side: Option<u32>,
+    memory_limit: usize,
+    memory_used: usize,
+    unloaded: Vec<u64>,
+}
+
+impl TextureCache {
+    pub fn new(config: &Config) -> Self {
+        TextureCache {
+            textures: HashMap::new(),
+            atlas: None,
+            free_slots: vec![],
+            next_slot: 0,
+            max_side: (config.preview_max_texture_side > 0)
+                .then_some(config.preview_max_texture_side),
+            memory_limit: config.preview_memory_limit_mb * 1024 * 1024,
+            memory_used: 0,
+            unloaded: vec![],
+        }
+    }
+
+    pub fn load(&mut self, ctx: &egui::Context, id: u64, img: &RgbaImage) -> PreviewTexture {
+        match self.textures.get_mut(&id) {
+            Some(cached) if cached.source == *img => {
+                trace!("reusing preview texture of item {id}");
+                cached.last_seen = Instant::now();
+            }
+            Some(_) => {
+                trace!("updating preview texture of item {id}");
+                self.remove(id);
+                self.insert(ctx, id, img.clone());
+            }
+            None => {
+                trace!("uploading preview texture of item {id}");
+                self.insert(ctx, id, img.clone());
+            }
+        }
+        self.preview_texture(id)
+    }
+
+    pub fn remove(&mut self, id: u64) {
+        if let Some(cached) = self.textures.remove(&id) {
+            self.memory_used -= cached.image.pixels.len() * 4;
+            if let Placement::Atlas(slot) = cached.placement {
+                self.free_slots.push(slot);
+            }
+        }
+    }
+
+    /// Marks the preview of an item as shown, which keeps it from being unloaded for a while
+    pub fn mark_seen(&mut self, id: u64) {
+        if let Some(cached) = self.textures.get_mut(&id) {
+            cached.last_seen = Instant::now();
+        }
+    }
+
+    /// Items whose previews got unloaded since the last call, their widgets should drop the
+    /// textures and load them again once shown
+    pub fn take_unloaded(&mut self) -> Vec<u64> {
+        mem::take(&mut self.unloaded)
+    }
+
+    /// Uploads the textures again into a recreated context, which doesn't know the old ones
+    pub fn reload(&mut self, ctx: &egui::Context) {
+        debug!("reloading {} preview textures", self.textures.len());
+        self.atlas = None;
+        let mut atlas = self.atlas(ctx).clone();
+        for (id, cached) in &mut self.textures {
+            match &mut cached.placement {
+                Placement::Own(handle) => {
+                    *handle =
+                        ctx.load_texture(id.to_string(), cached.image.clone(), Default::default());
+                }
+                Placement::Atlas(slot) => {
+                    upload_to_slot(&mut atlas, *slot, &cached.image);
+                }
+            }
+        }
+    }
+
+    /// Unloads the least recently seen previews until `needed` more bytes fit in the limit,
+    /// returning false if the recently seen ones alone take too much
+    fn make_room(&mut self, needed: usize) -> bool {
+        if self.memory_used + needed <= self.memory_limit {
+            return true;
+        }
+
+        let mut unseen = self
+            .textures
+            .iter()
+            .filter(|(_, cached)| cached.last_seen.elapsed() >= UNLOAD_UNSEEN_AFTER)
+            .map(|(&id, cached)| (cached.last_seen, id))
+            .collect::<Vec<_>>();
+        unseen.sort_unstable();
+        for (_, id) in unseen {
+            if self.memory_used + needed <= self.memory_limit {
+                break;
+            }
+            debug!("unloading preview texture of item {id} to stay within memory limit");
+            self.remove(id);
+            self.unloaded.push(id);
+        }
+        self.memory_used + needed <= self.memory_limit
+    }
+
+    fn insert(&mut self, ctx: &egui::Context, id: u64, source: RgbaImage) {
+        let mut img = match self.max_side {
+            Some(max_side) => downscale(&source, max_side),
+            None => source.clone(),
+        };
+        while !self.make_room(img.width() as usize * img.height() as usize * 4)
+            && img.width().max(img.height()) > MIN_DOWNSCALED_SIDE
+        {
+            let side = (img.width().max(img.height()) / 2).max(MIN_DOWNSCALED_SIDE);
+            debug!("preview memory limit exceeded, loading preview of item {id} at {side}px");
+            img = downscale(&img, side);
+        }
+        let size = [img.width() as usize, img.height() as usize];
+        let image = ColorImage::from_rgba_unmultiplied(size, img.as_flat_samples().as_slice());
+        self.memory_used += image.pixels.len() * 4;
+
+        let fits_slot = image
+            .size
+            .iter()
+            .all(|&side| side + SLOT_BORDER * 2 <= SLOT_SIZE);
+        let slot = fits_slot
+            .then(|| {
+                self.free_slots.pop().or_else(|| {
+                    (self.next_slot < SLOTS_PER_ROW * SLOTS_PER_ROW).then(|| {
+                        self.next_slot += 1;
+                        self.next_slot - 1
+                    })
+                })
+            })
+            .flatten();
+
+        let placement = if let Some(slot) = slot {
+            upload_to_slot(self.atlas(ctx), slot, &image);
+            Placement::Atlas(slot)
+        } else {
+            // Too big for a slot, or the atlas is full
+            Placement::Own(ctx.load_texture(id.to_string(), image.clone(), Default::default()))
+        };
+        self.textures.insert(
+            id,
+            CachedTexture {
+                source,
+                image,
+                placement,
+                last_seen: Instant::now(),
+            },
+        );
+    }
+
+    fn atlas(&mut self, ctx: &egui::Context) -> &mut TextureHandle {
+        self.atlas.get_or_insert_with(|| {
+            debug!("creating preview texture atlas");
+            ctx.load_texture(
+                "preview_atlas",
+                ColorImage::filled([ATLAS_SIZE, ATLAS_SIZE], Color32::TRANSPARENT),
+                Default::default(),
+            )
+        })
+    }
+
+    fn preview_texture(&self, id: u64) -> PreviewTexture {
+        let cached = &self.textures[&id];
+        let [width, height] = cached.image.size;
+        let size = vec2(width as f32, height as f32);
+        match &cached.placement {
+            Placement::Own(handle) => PreviewTexture {
+                handle: handle.clone(),
+                uv: Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)),
+                size,
+            },
+            Placement::Atlas(slot) => {
+                let [x, y] =
+                    slot_origin(*slot).map(|v| (v + SLOT_BORDER) as f32 / ATLAS_SIZE as f32);
+                PreviewTexture {
+                    // The atlas exists as long as anything is placed in it
+                    handle: self.atlas.clone().unwrap(),
+                    uv: Rect::from_min_size(pos2(x, y), size / ATLAS_SIZE as f32),
+                    size,
+                }
+            }
+        }
+    }
+}
+
+// Keeps the aspect ratio, unlike imageops::thumbnail on its own
+fn downscale(img: &RgbaImage, max_side: u32) -> RgbaImage {
+    let (width, height) = img.dimensions();
+    if width <= max_side && height <= max_side {
+        return img.clone();
+    }
+    let scale = max_side as f32 / width.max(height) as f32;
+    imageops::thumbnail(
+        img,
+        ((width as f32 * scale).round() as u32).max(1),
+        ((height as f32 * scale).round() as u32).max(1),
+    )
+}
+
+fn slot_origin(slot: usize) -> [usize; 2] {
+    [
+        slot % SLOTS_PER_ROW * SLOT_SIZE,
+        slot / SLOTS_PER_ROW * SLOT_SIZE,
+    ]
+}
+
+fn upload_to_slot(atlas: &mut TextureHandle, slot: usize, image: &ColorImage) {
+    let [width, height] = image.size;
+    if width == 0 || height == 0 {
+        return;
+    }
+    let bordered_size = [width + SLOT_BORDER * 2, height + SLOT_BORDER * 2];
+    let mut bordered = ColorImage::filled(bordered_size, Color32::TRANSPARENT);
+    for y in 0..bordered_size[1] {
+        for x in 0..bordered_size[0] {
+            let src_x = x.saturating_sub(SLOT_BORDER).min(width - 1);
+            let src_y = y.saturating_sub(SLOT_BORDER).min(height - 1);
+            bordered[(x, y)] = image[(src_x, src_y)];
+        }
+    }
+    atlas.set_partial(slot_origin(slot), bordered, Default::default());
+}
@@ -0,0 +1,58 @@
+#[cfg(feature = "pdf-thumbnails")]
+use std::process::{Command, Stdio};
+use std::{fs::File, io::Read as _, path::Path};
+
+use anyhow::Result;
+#[cfg(feature = "pdf-thumbnails")]
+use anyhow::{Context as _, bail};
+#[cfg(feature = "pdf-thumbnails")]
+use image::RgbaImage;
+
+const TEXT_HEAD_BYTES: u64 = 4096;
+const TEXT_HEAD_LINES: usize = 2;
+const TEXT_HEAD_LINE_CHARS: usize = 200;
+
+/// Renders the first page of a PDF using poppler's pdftoppm, scaled to fit `max_side`
+#[cfg(feature = "pdf-thumbnails")]
+pub fn render_pdf_page(path: &Path, max_side: u32) -> Result<RgbaImage> {
+    let output = Command::new("pdftoppm")
+        .args(["-png", "-singlefile", "-f", "1", "-l", "1", "-scale-to"])
+        .arg(max_side.to_string())
+        .arg(path)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .context("failed to run pdftoppm")?;
+    if !output.status.success() {
+        bail!("pdftoppm exited with {}", output.status);
+    }
+
+    Ok(image::load_from_memory(&output.stdout)?.to_rgba8())
+}
+
+/// First non-empty lines of a text file, None if the file doesn't look like text
+pub fn read_text_head(path: &Path) -> Result<Option<Vec<String>>> {
+    let mut data = vec![];
+    File::open(path)?
+        .take(TEXT_HEAD_BYTES)
+        .read_to_end(&mut data)?;
+    if data.contains(&0) {
+        return Ok(None);
+    }
+
+    // The read may stop in the middle of a multi-byte character
+    let text = match str::from_utf8(&data) {
+        Ok(text) => text,
+        Err(err) if err.error_len().is_none() => str::from_utf8(&data[..err.valid_up_to()])?,
+        Err(_) => return Ok(None),
+    };
+
+    Ok(Some(
+        text.lines()
+            .map(str::trim_end)
+            .filter(|line| !line.trim().is_empty())
+            .take(TEXT_HEAD_LINES)
+            .map(|line| line.chars().take(TEXT_HEAD_LINE_CHARS).collect())
+            .collect(),
+    ))
+}
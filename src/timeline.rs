@@ -0,0 +1,245 @@
+//! The timeline view, where the list is grouped under a header per day the items were copied on,
+//! e.g., "Today", "Yesterday" or "Nov 12"
+
+use std::{collections::HashMap, ops::Range};
+
+use log::debug;
+
+use crate::{format::DateFormat, providers::timestamp::civil_from_days};
+
+const SECS_PER_DAY: i64 = 24 * 60 * 60;
+// Time zones only change their offset on a quarter hour
+const OFFSET_SPAN_SECS: u64 = 15 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GroupKey {
+    Pinned,
+    // Days since the Unix epoch in local time
+    Day(i64),
+    // Items copied before copy times were recorded
+    Earlier,
+}
+
+/// Consecutive items of the list under one header
+#[derive(Debug, PartialEq, Eq)]
+pub struct DayGroup {
+    pub label: String,
+    pub items: Range<usize>,
+}
+
+/// Groups the items, given when each was copied in Unix seconds in list order. Pinned items get a
+/// group of their own, and items without a copy time at the top, e.g., virtual items, go with
+/// today's. `utc_offset` gives the local offset from UTC in seconds at a time
+pub fn group_by_day(
+    copied_at: impl IntoIterator<Item = Option<u64>>,
+    pinned_count: usize,
+    now: u64,
+    mut utc_offset: impl FnMut(u64) -> i64,
+    date_format: &DateFormat,
+) -> Vec<DayGroup> {
+    let today = local_day(now, utc_offset(now));
+    let mut groups: Vec<(GroupKey, Range<usize>)> = vec![];
+    let mut dated_seen = false;
+    for (i, copied_at) in copied_at.into_iter().enumerate() {
+        let key = match copied_at {
+            _ if i < pinned_count => GroupKey::Pinned,
+            Some(copied_at) => {
+                dated_seen = true;
+                GroupKey::Day(local_day(copied_at, utc_offset(copied_at)))
+            }
+            None if !dated_seen => GroupKey::Day(today),
+            None => GroupKey::Earlier,
+        };
+        match groups.last_mut() {
+            Some((last_key, range)) if *last_key == key => range.end = i + 1,
+            _ => groups.push((key, i..i + 1)),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(key, items)| DayGroup {
+            label: match key {
+                GroupKey::Pinned => "Pinned".to_string(),
+                GroupKey::Day(day) => day_label(day, today, date_format),
+                GroupKey::Earlier => "Earlier".to_string(),
+            },
+            items,
+        })
+        .collect()
+}
+
+/// Offsets of the local time zone from UTC, which differ between times with daylight saving
+/// time. Kept per quarter hour, so the list doesn't ask libc again for each item on every frame
+pub struct LocalTime {
+    offsets: HashMap<u64, i64>,
+}
+
+impl LocalTime {
+    /// Reads the time zone again, so a changed TZ or /etc/localtime is picked up
+    pub fn new() -> Self {
+        unsafe { tzset() };
+        LocalTime {
+            offsets: HashMap::new(),
+        }
+    }
+
+    pub fn utc_offset(&mut self, secs: u64) -> i64 {
+        *self
+            .offsets
+            .entry(secs / OFFSET_SPAN_SECS)
+            .or_insert_with(|| local_utc_offset(secs))
+    }
+}
+
+impl Default for LocalTime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Not bound by the libc crate
+unsafe extern "C" {
+    fn tzset();
+}
+
+// UTC if it can't be found
+fn local_utc_offset(secs: u64) -> i64 {
+    let time = secs as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        debug!("failed to find the local time of {secs}, using UTC");
+        return 0;
+    }
+    tm.tm_gmtoff
+}
+
+fn local_day(secs: u64, utc_offset: i64) -> i64 {
+    (secs as i64 + utc_offset).div_euclid(SECS_PER_DAY)
+}
+
+fn day_label(day: i64, today: i64, date_format: &DateFormat) -> String {
+    match today - day {
+        0 => "Today".to_string(),
+        1 => "Yesterday".to_string(),
+        _ => {
+            let (year, month, day) = civil_from_days(day);
+            if civil_from_days(today).0 == year {
+                date_format.day(month, day)
+            } else {
+                date_format.day_with_year(year, month, day)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2025-03-10T12:00:00Z
+    const NOW: u64 = 1741608000;
+    const DAY: u64 = SECS_PER_DAY as u64;
+
+    fn utc(_: u64) -> i64 {
+        0
+    }
+
+    fn english() -> DateFormat {
+        DateFormat::from_locale("en_US")
+    }
+
+    fn labels(groups: &[DayGroup]) -> Vec<(&str, Range<usize>)> {
+        groups
+            .iter()
+            .map(|group| (group.label.as_str(), group.items.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn groups_consecutive_items_by_day() {
+        let groups = group_by_day(
+            [
+                Some(NOW - 3 * DAY),
+                None,
+                Some(NOW),
+                Some(NOW - 60),
+                Some(NOW - DAY),
+                Some(NOW - 4 * DAY),
+                Some(NOW - 400 * DAY),
+                None,
+            ],
+            2,
+            NOW,
+            utc,
+            &english(),
+        );
+        assert_eq!(
+            labels(&groups),
+            [
+                ("Pinned", 0..2),
+                ("Today", 2..4),
+                ("Yesterday", 4..5),
+                ("Mar 6", 5..6),
+                ("Feb 4, 2024", 6..7),
+                ("Earlier", 7..8),
+            ]
+        );
+    }
+
+    #[test]
+    fn items_without_copy_time_at_the_top_are_today() {
+        let groups = group_by_day([None, None, Some(NOW - DAY)], 0, NOW, utc, &english());
+        assert_eq!(labels(&groups), [("Today", 0..2), ("Yesterday", 2..3)]);
+    }
+
+    #[test]
+    fn days_follow_the_local_time_zone() {
+        // 23:00 in UTC is past midnight two hours east of it
+        let late = NOW + 11 * 60 * 60;
+        assert_eq!(
+            labels(&group_by_day([Some(NOW)], 0, late, utc, &english())),
+            [("Today", 0..1)]
+        );
+        assert_eq!(
+            labels(&group_by_day(
+                [Some(NOW)],
+                0,
+                late,
+                |_| 2 * 60 * 60,
+                &english()
+            )),
+            [("Yesterday", 0..1)]
+        );
+    }
+
+    #[test]
+    fn offsets_are_found_for_each_time() {
+        // Two hours east of UTC from NOW on and one before, like across a daylight saving time
+        // change, so 22:30 in UTC the day before NOW is still that day locally
+        let offset = |secs| if secs < NOW { 60 * 60 } else { 2 * 60 * 60 };
+        let late = NOW + 11 * 60 * 60;
+        assert_eq!(
+            labels(&group_by_day(
+                [Some(late - 60), Some(NOW - 27 * 60 * 60 / 2)],
+                0,
+                late,
+                offset,
+                &english()
+            )),
+            [("Today", 0..1), ("Mar 9", 1..2)]
+        );
+    }
+
+    #[test]
+    fn months_follow_the_locale() {
+        let groups = group_by_day(
+            [Some(NOW - 4 * DAY), Some(NOW - 400 * DAY)],
+            0,
+            NOW,
+            utc,
+            &DateFormat::from_locale("de_DE.UTF-8"),
+        );
+        assert_eq!(labels(&groups), [("6. Mär", 0..1), ("4. Feb 2024", 1..2)]);
+    }
+}
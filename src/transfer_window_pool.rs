@@ -1,9 +1,14 @@
-use std::collections::VecDeque;
+use std::{
+    collections::{HashMap, VecDeque},
+    mem,
+    time::{Duration, Instant},
+};
 
 use anyhow::Result;
-use log::{trace, warn};
+use log::{debug, trace, warn};
 use x11rb::{
     connection::Connection,
+    errors::ReplyError,
     protocol::xproto::{
         Atom, ConnectionExt, CreateWindowAux, EventMask, PropMode, Window, WindowClass,
     },
@@ -15,6 +20,11 @@ use crate::selection::SelectionType;
 
 const WIN_TITLE_PREFIX: &str = "Memoni transfer window";
 const ATOM_PREFIX: &str = "TRANSFER_SELECTION_DATA";
+const BASELINE_WINDOW_COUNT: usize = 4;
+const LIVENESS_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+const SHRINK_DELAY: Duration = Duration::from_secs(10);
+/// How often [`TransferWindowPool::maintain`] should run
+pub const MAINTENANCE_INTERVAL: Duration = SHRINK_DELAY;
 
 x11rb::atom_manager! {
     pub Atoms: AtomsCookie {
@@ -36,8 +46,12 @@ pub struct TransferWindowPool<'a> {
     atoms: Atoms,
     windows: VecDeque<TransferWindow>,
     counter: u8,
+    atom_counters: HashMap<Atom, u8>,
+    spare_counters: Vec<u8>,
     get_count: usize,
     release_count: usize,
+    last_get: Instant,
+    last_liveness_check: Instant,
 }
 
 impl<'a> TransferWindowPool<'a> {
@@ -54,17 +68,18 @@ impl<'a> TransferWindowPool<'a> {
             atoms,
             windows: VecDeque::new(),
             counter: 0,
+            atom_counters: HashMap::new(),
+            spare_counters: Vec::new(),
             get_count: 0,
             release_count: 0,
+            last_get: Instant::now(),
+            last_liveness_check: Instant::now(),
         };
 
-        let initial_windows = vec![
-            window_pool.create_window()?,
-            window_pool.create_window()?,
-            window_pool.create_window()?,
-            window_pool.create_window()?,
-        ];
-        window_pool.windows.extend(initial_windows);
+        for _ in 0..BASELINE_WINDOW_COUNT {
+            let window = window_pool.create_window()?;
+            window_pool.windows.push_back(window);
+        }
 
         Ok(window_pool)
     }
@@ -76,6 +91,7 @@ impl<'a> TransferWindowPool<'a> {
         }
 
         self.get_count += 1;
+        self.last_get = Instant::now();
         let window = match self.windows.pop_front() {
             Some(w) => Ok(w),
             None => self.create_window(),
@@ -91,8 +107,90 @@ impl<'a> TransferWindowPool<'a> {
         self.windows.push_back(window);
     }
 
+    // Idle windows can be destroyed behind our back (e.g. by `xkill` or a misbehaving WM), after
+    // which every conversion using them silently goes nowhere
+    pub fn maintain(&mut self) -> Result<()> {
+        let now = Instant::now();
+        if now.duration_since(self.last_liveness_check) >= LIVENESS_CHECK_INTERVAL {
+            self.last_liveness_check = now;
+            self.recreate_dead_windows()?;
+        }
+
+        if now.duration_since(self.last_get) >= SHRINK_DELAY {
+            self.shrink()?;
+        }
+
+        Ok(())
+    }
+
+    fn recreate_dead_windows(&mut self) -> Result<()> {
+        trace!(
+            "checking liveness of {} idle transfer windows",
+            self.windows.len()
+        );
+
+        let conn = self.conn;
+        let cookies = self
+            .windows
+            .iter()
+            .map(|w| conn.get_window_attributes(w.id))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut alive_windows = VecDeque::with_capacity(self.windows.len());
+        for (window, cookie) in mem::take(&mut self.windows).into_iter().zip(cookies) {
+            match cookie.reply() {
+                Ok(_) => alive_windows.push_back(window),
+                Err(ReplyError::X11Error(err)) => {
+                    warn!(
+                        "transfer window {window:?} is gone ({:?}), recreating it",
+                        err.error_kind
+                    );
+                    self.retire_window(window);
+                    alive_windows.push_back(self.create_window()?);
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        self.windows = alive_windows;
+
+        Ok(())
+    }
+
+    fn shrink(&mut self) -> Result<()> {
+        if self.windows.len() <= BASELINE_WINDOW_COUNT {
+            return Ok(());
+        }
+
+        debug!(
+            "shrinking transfer window pool from {} to {BASELINE_WINDOW_COUNT} idle windows",
+            self.windows.len()
+        );
+        while self.windows.len() > BASELINE_WINDOW_COUNT
+            && let Some(window) = self.windows.pop_back()
+        {
+            self.conn.destroy_window(window.id)?;
+            self.retire_window(window);
+        }
+
+        Ok(())
+    }
+
+    fn retire_window(&mut self, window: TransferWindow) {
+        if let Some(counter) = self.atom_counters.remove(&window.atom) {
+            self.spare_counters.push(counter);
+        }
+    }
+
     fn create_window(&mut self) -> Result<TransferWindow> {
-        let counter_str = self.counter.to_string();
+        let counter = match self.spare_counters.pop() {
+            Some(counter) => counter,
+            None => {
+                let counter = self.counter;
+                self.counter += 1;
+                counter
+            }
+        };
+        let counter_str = counter.to_string();
 
         let transfer_window = self.create_util_window(
             &format!("{WIN_TITLE_PREFIX} {counter_str} - {}", self.selection_type).into_bytes(),
@@ -107,8 +205,7 @@ impl<'a> TransferWindowPool<'a> {
             )?
             .reply()?
             .atom;
-
-        self.counter += 1;
+        self.atom_counters.insert(atom, counter);
 
         Ok(TransferWindow {
             id: transfer_window,
@@ -0,0 +1,371 @@
+use std::{collections::HashSet, mem};
+
+use anyhow::{Result, anyhow, bail};
+use unicode_segmentation::UnicodeSegmentation as _;
+
+use crate::{
+    config::Config, selection::SelectionItem, text::percent_decode, utils::plaintext_mime_score,
+};
+
+/// Text transforms, applied to a text item either as a new item or only for the next paste
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+    DecodeBase64,
+    UrlDecode,
+    PrettyJson,
+    Unescape,
+    UpperCase,
+    LowerCase,
+    TitleCase,
+    CamelCase,
+    SnakeCase,
+    SortLines,
+    UniqueLines,
+    JoinLines,
+    NumberLines,
+}
+
+impl Transform {
+    pub fn name(self) -> &'static str {
+        match self {
+            Transform::DecodeBase64 => "Decode Base64",
+            Transform::UrlDecode => "URL-decode",
+            Transform::PrettyJson => "Pretty-print JSON",
+            Transform::Unescape => "Unescape string literal",
+            Transform::UpperCase => "UPPER CASE",
+            Transform::LowerCase => "lower case",
+            Transform::TitleCase => "Title Case",
+            Transform::CamelCase => "camelCase",
+            Transform::SnakeCase => "snake_case",
+            Transform::SortLines => "Sort lines",
+            Transform::UniqueLines => "Remove duplicate lines",
+            Transform::JoinLines => "Join lines",
+            Transform::NumberLines => "Number lines",
+        }
+    }
+
+    /// Transforms the text of an item, None if the item has no text
+    pub fn apply_to_item(self, item: &SelectionItem, config: &Config) -> Result<Option<String>> {
+        let Some((_, data)) = item
+            .data
+            .iter()
+            .filter_map(|(mime, data)| plaintext_mime_score(mime).map(|s| (s, data)))
+            .max_by_key(|(score, _)| *score)
+        else {
+            return Ok(None);
+        };
+
+        let text = str::from_utf8(data).map_err(|_| anyhow!("item text isn't valid UTF-8"))?;
+        let transformed = self.apply(text, config)?;
+        if transformed == text {
+            bail!("nothing to change");
+        }
+        Ok(Some(transformed))
+    }
+
+    pub fn apply(self, text: &str, config: &Config) -> Result<String> {
+        match self {
+            Transform::DecodeBase64 => {
+                let data = decode_base64(text)?;
+                String::from_utf8(data).map_err(|_| anyhow!("decoded data isn't text"))
+            }
+            Transform::UrlDecode => {
+                String::from_utf8(percent_decode(text.trim().as_bytes()).into_owned())
+                    .map_err(|_| anyhow!("decoded data isn't valid UTF-8"))
+            }
+            Transform::PrettyJson => {
+                let value = serde_json::from_str::<serde_json::Value>(text)?;
+                Ok(serde_json::to_string_pretty(&value)?)
+            }
+            Transform::Unescape => unescape(text),
+            Transform::UpperCase => Ok(text.to_uppercase()),
+            Transform::LowerCase => Ok(text.to_lowercase()),
+            Transform::TitleCase => Ok(title_case(text)),
+            Transform::CamelCase => Ok(map_lines(text, |words| {
+                words
+                    .iter()
+                    .enumerate()
+                    .map(|(i, word)| match i {
+                        0 => word.to_lowercase(),
+                        _ => capitalize(word),
+                    })
+                    .collect()
+            })),
+            Transform::SnakeCase => Ok(map_lines(text, |words| {
+                words
+                    .iter()
+                    .map(|word| word.to_lowercase())
+                    .collect::<Vec<_>>()
+                    .join("_")
+            })),
+            Transform::SortLines => Ok(map_line_list(text, |mut lines| {
+                lines.sort_unstable();
+                lines
+            })),
+            Transform::UniqueLines => Ok(map_line_list(text, |lines| {
+                let mut seen = HashSet::new();
+                lines
+                    .into_iter()
+                    .filter(|line| seen.insert(*line))
+                    .collect()
+            })),
+            Transform::JoinLines => Ok(text
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .collect::<Vec<_>>()
+                .join(&config.join_delimiter)),
+            Transform::NumberLines => {
+                let width = text.lines().count().to_string().len();
+                Ok(map_line_list(text, |lines| {
+                    lines
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, line)| format!("{:>width$}. {line}", i + 1))
+                        .collect()
+                }))
+            }
+        }
+    }
+}
+
+// Accepts both the standard and URL-safe alphabets, with or without padding, and ignores line
+// breaks of wrapped data
+fn decode_base64(text: &str) -> Result<Vec<u8>> {
+    let mut data = Vec::with_capacity(text.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    let mut padding = 0;
+
+    for b in text.bytes().filter(|b| !b.is_ascii_whitespace()) {
+        let value = match b {
+            b'A'..=b'Z' => b - b'A',
+            b'a'..=b'z' => b - b'a' + 26,
+            b'0'..=b'9' => b - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => {
+                padding += 1;
+                continue;
+            }
+            _ => bail!("invalid Base64 character '{}'", b as char),
+        };
+        if padding > 0 {
+            bail!("Base64 data continues after padding");
+        }
+
+        buffer = buffer << 6 | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            data.push((buffer >> bits) as u8);
+        }
+    }
+
+    // A single leftover character can't encode a whole byte
+    if bits >= 6 || padding > 2 {
+        bail!("truncated Base64 data");
+    }
+    Ok(data)
+}
+
+// Escapes shared by most languages: C-like single character escapes, \xHH, JSON's \uXXXX with
+// surrogate pairs and Rust's \u{...}. Unknown escapes are kept as they are
+fn unescape(text: &str) -> Result<String> {
+    let text = text.trim();
+    let text = ['"', '\'', '`']
+        .iter()
+        .find_map(|&quote| text.strip_prefix(quote).and_then(|t| t.strip_suffix(quote)))
+        .unwrap_or(text);
+
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+
+        let Some(escape) = chars.next() else {
+            out.push('\\');
+            break;
+        };
+        match escape {
+            'n' => out.push('\n'),
+            't' => out.push('\t'),
+            'r' => out.push('\r'),
+            '0' => out.push('\0'),
+            'b' => out.push('\u{8}'),
+            'f' => out.push('\u{c}'),
+            'v' => out.push('\u{b}'),
+            'a' => out.push('\u{7}'),
+            'e' => out.push('\u{1b}'),
+            '\\' | '"' | '\'' | '`' | '/' => out.push(escape),
+            // A run of them spells the bytes of one UTF-8 string, e.g., \xc3\xa9 for é
+            'x' => {
+                let mut bytes = vec![hex_byte(&mut chars)?];
+                loop {
+                    let mut rest = chars.clone();
+                    if rest.next() != Some('\\') || rest.next() != Some('x') {
+                        break;
+                    }
+                    chars = rest;
+                    bytes.push(hex_byte(&mut chars)?);
+                }
+                let decoded = String::from_utf8(bytes)
+                    .map_err(|e| anyhow!("\\x escapes aren't valid UTF-8: {e}"))?;
+                out.push_str(&decoded);
+            }
+            'u' if chars.peek() == Some(&'{') => {
+                chars.next();
+                let hex = chars.by_ref().take_while(|&c| c != '}').collect::<String>();
+                out.push(code_point(&hex)?);
+            }
+            'u' => {
+                let hex = chars.by_ref().take(4).collect::<String>();
+                let unit = u16::from_str_radix(&hex, 16)
+                    .map_err(|_| anyhow!("invalid escape \\u{hex}"))?;
+                let mut units = vec![unit];
+                // The low surrogate of a pair comes as its own escape
+                if (0xd800..0xdc00).contains(&unit) && chars.next_if_eq(&'\\').is_some() {
+                    if chars.next_if_eq(&'u').is_none() {
+                        bail!("unpaired surrogate \\u{hex}");
+                    }
+                    let hex = chars.by_ref().take(4).collect::<String>();
+                    units.push(
+                        u16::from_str_radix(&hex, 16)
+                            .map_err(|_| anyhow!("invalid escape \\u{hex}"))?,
+                    );
+                }
+                out.push_str(
+                    &String::from_utf16(&units).map_err(|_| anyhow!("unpaired surrogate"))?,
+                );
+            }
+            _ => {
+                out.push('\\');
+                out.push(escape);
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+fn hex_byte(chars: &mut impl Iterator<Item = char>) -> Result<u8> {
+    let hex = chars.take(2).collect::<String>();
+    u8::from_str_radix(&hex, 16).map_err(|_| anyhow!("invalid escape \\x{hex}"))
+}
+
+fn code_point(hex: &str) -> Result<char> {
+    u32::from_str_radix(hex, 16)
+        .ok()
+        .and_then(char::from_u32)
+        .ok_or_else(|| anyhow!("invalid escape \\u{{{hex}}}"))
+}
+
+fn title_case(text: &str) -> String {
+    text.split_word_bounds()
+        .map(|segment| {
+            if segment.chars().any(char::is_alphanumeric) {
+                capitalize(segment)
+            } else {
+                segment.to_string()
+            }
+        })
+        .collect()
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.flat_map(char::to_lowercase))
+            .collect(),
+        None => String::new(),
+    }
+}
+
+// Identifiers are converted line by line, keeping the indentation and line breaks around them
+fn map_lines(text: &str, join: impl Fn(&[String]) -> String) -> String {
+    text.split_inclusive('\n')
+        .map(|line| {
+            let content = line.trim();
+            if content.is_empty() {
+                return line.to_string();
+            }
+
+            let start = line.find(content).unwrap_or(0);
+            let end = start + content.len();
+            format!(
+                "{}{}{}",
+                &line[..start],
+                join(&split_identifier(content)),
+                &line[end..]
+            )
+        })
+        .collect()
+}
+
+// Splits on anything that isn't a letter or digit, and on case changes: "parseHTTPResponse2"
+// becomes "parse", "HTTP", "Response2"
+fn split_identifier(text: &str) -> Vec<String> {
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut words = vec![];
+    let mut current = String::new();
+
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if !current.is_empty() {
+                words.push(mem::take(&mut current));
+            }
+            continue;
+        }
+
+        // Only set once a previous character was pushed
+        if !current.is_empty() {
+            let prev = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+            let is_boundary = ((prev.is_lowercase() || prev.is_numeric()) && c.is_uppercase())
+                || (prev.is_uppercase() && c.is_uppercase() && next_is_lower);
+            if is_boundary {
+                words.push(mem::take(&mut current));
+            }
+        }
+        current.push(c);
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+
+    words
+}
+
+// Rearranges whole lines, keeping the line ending style and the final line break if any
+fn map_line_list<'a, T: AsRef<str>>(
+    text: &'a str,
+    map: impl FnOnce(Vec<&'a str>) -> Vec<T>,
+) -> String {
+    let line_break = if text.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut out = map(text.lines().collect())
+        .iter()
+        .map(AsRef::as_ref)
+        .collect::<Vec<_>>()
+        .join(line_break);
+    if text.ends_with('\n') {
+        out.push_str(line_break);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unescape_decodes_hex_escapes_as_utf8() {
+        assert_eq!(unescape(r"caf\xc3\xa9 \x41").unwrap(), "café A");
+        assert!(unescape(r"\xe9").is_err());
+        assert!(unescape(r"\xc3").is_err());
+    }
+}
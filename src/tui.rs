@@ -0,0 +1,261 @@
+//! `memoni tui`, a terminal client browsing the history of a running server, for sessions where
+//! its window can't show (e.g., over SSH). The chosen item is handed to the terminal in an OSC 52
+//! sequence, which most terminals put in the local clipboard
+
+use std::{
+    io::{self, Read as _, Write},
+    net::Shutdown,
+    os::{fd::AsFd as _, unix::net::UnixStream},
+    path::Path,
+};
+
+use anyhow::{Result, anyhow};
+use log::{debug, info};
+use rustix::termios::{self, OptionalActions, Termios};
+use serde::Deserialize;
+use unicode_segmentation::UnicodeSegmentation as _;
+use unicode_width::UnicodeWidthStr as _;
+
+use crate::{
+    cli_output::{self, DEFAULT_OSC52_LIMIT, Multiplexer},
+    ipc::{ClientCommand, parse_item_id},
+};
+
+const ENTER_ALT_SCREEN: &str = "\x1b[?1049h\x1b[?25l";
+const LEAVE_ALT_SCREEN: &str = "\x1b[?25h\x1b[?1049l";
+const CLEAR_SCREEN: &str = "\x1b[H\x1b[2J";
+const REVERSE_VIDEO: &str = "\x1b[7m";
+const RESET_STYLE: &str = "\x1b[0m";
+const HELP_LINE: &str = "j/k: move  Enter: copy  d: delete  r: reload  q: quit";
+
+#[derive(Deserialize)]
+struct ListedItem {
+    id: String,
+    kind: String,
+    preview: String,
+    pinned: bool,
+}
+
+enum Key {
+    Up,
+    Down,
+    Copy,
+    Delete,
+    Reload,
+    Quit,
+    Other,
+}
+
+// Restores the terminal however the client exits
+struct RawMode {
+    saved: Termios,
+}
+
+impl RawMode {
+    fn enable() -> Result<Self> {
+        let stdin = io::stdin();
+        let saved = termios::tcgetattr(stdin.as_fd())
+            .map_err(|e| anyhow!("stdin isn't a terminal: {e}"))?;
+        let mut raw = saved.clone();
+        raw.make_raw();
+        termios::tcsetattr(stdin.as_fd(), OptionalActions::Now, &raw)?;
+
+        let mut stdout = io::stdout();
+        stdout.write_all(ENTER_ALT_SCREEN.as_bytes())?;
+        stdout.flush()?;
+        Ok(RawMode { saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(LEAVE_ALT_SCREEN.as_bytes());
+        let _ = stdout.flush();
+        let _ = termios::tcsetattr(io::stdin().as_fd(), OptionalActions::Now, &self.saved);
+    }
+}
+
+pub fn run(socket_path: &Path) -> Result<()> {
+    let mut items = list_items(socket_path)?;
+    let mut active = 0;
+    let mut top = 0;
+    let mut status = String::new();
+
+    let raw_mode = RawMode::enable()?;
+    let mut stdin = io::stdin().lock();
+    let copied_sequence = loop {
+        let (rows, cols) = terminal_size();
+        // The help and status lines take a row each
+        let visible_rows = rows.saturating_sub(2).max(1);
+        if active < top {
+            top = active;
+        } else if active >= top + visible_rows {
+            top = active + 1 - visible_rows;
+        }
+        draw(&items, active, top, visible_rows, cols, &status)?;
+        status.clear();
+
+        match read_key(&mut stdin)? {
+            Key::Up => active = active.saturating_sub(1),
+            Key::Down => active = (active + 1).min(items.len().saturating_sub(1)),
+            Key::Copy => {
+                let Some(item) = items.get(active) else {
+                    continue;
+                };
+                let text = request(socket_path, ClientCommand::Text(item_id(item)?))?;
+                if text.is_empty() {
+                    status = format!("{} items can't be copied from the terminal", item.kind);
+                    continue;
+                }
+                match cli_output::osc52(&text, Multiplexer::from_env(), DEFAULT_OSC52_LIMIT) {
+                    Ok(sequence) => break Some(sequence),
+                    Err(e) => status = e.to_string(),
+                }
+            }
+            Key::Delete => {
+                let Some(item) = items.get(active) else {
+                    continue;
+                };
+                info!("deleting item {}", item.id);
+                request(socket_path, ClientCommand::Delete(item_id(item)?))?;
+                items = list_items(socket_path)?;
+                active = active.min(items.len().saturating_sub(1));
+            }
+            Key::Reload => {
+                items = list_items(socket_path)?;
+                active = active.min(items.len().saturating_sub(1));
+            }
+            Key::Quit => break None,
+            Key::Other => {}
+        }
+    };
+    drop(raw_mode);
+
+    if let Some(sequence) = copied_sequence {
+        let mut stdout = io::stdout();
+        stdout.write_all(&sequence)?;
+        stdout.flush()?;
+        eprintln!("Copied to the terminal's clipboard");
+    }
+    Ok(())
+}
+
+fn request(socket_path: &Path, command: ClientCommand) -> Result<Vec<u8>> {
+    debug!("sending {command:?} to server");
+    let mut stream = UnixStream::connect(socket_path)?;
+    stream.write_all(&command.to_bytes())?;
+    stream.shutdown(Shutdown::Write)?;
+    let mut reply = vec![];
+    stream.read_to_end(&mut reply)?;
+    Ok(reply)
+}
+
+fn list_items(socket_path: &Path) -> Result<Vec<ListedItem>> {
+    let reply = request(socket_path, ClientCommand::List)?;
+    String::from_utf8_lossy(&reply)
+        .lines()
+        .map(|line| Ok(serde_json::from_str(line)?))
+        .collect()
+}
+
+fn item_id(item: &ListedItem) -> Result<u64> {
+    parse_item_id(item.id.as_bytes()).ok_or_else(|| anyhow!("invalid item id {:?}", item.id))
+}
+
+fn read_key(stdin: &mut impl io::Read) -> Result<Key> {
+    let mut buf = [0u8; 8];
+    let n = stdin.read(&mut buf)?;
+    Ok(match &buf[..n] {
+        // Ctrl-P and Ctrl-N as in the window
+        b"k" | b"\x1b[A" | b"\x10" => Key::Up,
+        b"j" | b"\x1b[B" | b"\x0e" => Key::Down,
+        b"\r" | b"\n" => Key::Copy,
+        b"d" => Key::Delete,
+        b"r" => Key::Reload,
+        // Escape, Ctrl-C and end of input
+        b"q" | b"\x1b" | b"\x03" | b"" => Key::Quit,
+        _ => Key::Other,
+    })
+}
+
+fn terminal_size() -> (usize, usize) {
+    match termios::tcgetwinsize(io::stdout().as_fd()) {
+        Ok(size) if size.ws_row > 0 && size.ws_col > 0 => {
+            (size.ws_row as usize, size.ws_col as usize)
+        }
+        _ => (24, 80),
+    }
+}
+
+fn draw(
+    items: &[ListedItem],
+    active: usize,
+    top: usize,
+    visible_rows: usize,
+    cols: usize,
+    status: &str,
+) -> Result<()> {
+    let mut out = String::from(CLEAR_SCREEN);
+    out.push_str(&fit(HELP_LINE, cols));
+    out.push_str("\r\n");
+
+    if items.is_empty() {
+        out.push_str("Your clipboard history will appear here.\r\n");
+    }
+    for (idx, item) in items.iter().enumerate().skip(top).take(visible_rows) {
+        let marker = if item.pinned { "* " } else { "  " };
+        let line = fit(&format!("{marker}{}", item.preview), cols);
+        if idx == active {
+            out.push_str(REVERSE_VIDEO);
+            out.push_str(&line);
+            out.push_str(RESET_STYLE);
+        } else {
+            out.push_str(&line);
+        }
+        out.push_str("\r\n");
+    }
+
+    out.push_str(&format!("\x1b[{};1H", visible_rows + 2));
+    out.push_str(&fit(status, cols));
+
+    let mut stdout = io::stdout();
+    stdout.write_all(out.as_bytes())?;
+    stdout.flush()?;
+    Ok(())
+}
+
+// Cuts to the terminal width in columns, as wide characters like CJK take two, padded so the
+// active line is highlighted across it
+fn fit(text: &str, cols: usize) -> String {
+    let mut line = String::new();
+    let mut width = 0;
+    for grapheme in text.graphemes(true) {
+        if grapheme.chars().any(char::is_control) {
+            continue;
+        }
+        let grapheme_width = grapheme.width();
+        if width + grapheme_width > cols {
+            break;
+        }
+        line.push_str(grapheme);
+        width += grapheme_width;
+    }
+    line.extend(std::iter::repeat_n(' ', cols - width));
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fit_counts_columns() {
+        assert_eq!(fit("abc", 5), "abc  ");
+        assert_eq!(fit("abcdef", 4), "abcd");
+        // Each of these takes two columns, and one can't be split
+        assert_eq!(fit("日本語", 5), "日本 ");
+        assert_eq!(fit("e\u{301}x", 2), "e\u{301}x");
+        assert_eq!(fit("a\tb\n", 3), "ab ");
+    }
+}
@@ -1,10 +1,14 @@
 use std::{
-    collections::HashMap,
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet, hash_map::Entry},
     ffi::CString,
-    fs, mem,
+    fs,
+    ops::Range,
     path::{Path, PathBuf},
+    rc::Rc,
     str::FromStr as _,
     sync::{Arc, LazyLock},
+    time::Duration,
 };
 
 use anyhow::{Result, anyhow};
@@ -14,20 +18,48 @@ use egui::{
     TextureHandle, TextureOptions, Vec2, WidgetText, epaint, scroll_area::ScrollAreaOutput,
 };
 use fontconfig::Fontconfig;
-use image::{GenericImageView, RgbaImage};
+use image::RgbaImage;
 use log::{debug, error, info, log_enabled, trace, warn};
 use xdg_mime::SharedMimeInfo;
 
 use crate::{
-    ScrollAreaStateExt,
-    color::parse_color,
-    config::{Config, Dimensions, LayoutConfig, ThemeConfig},
+    AppMode, ScrollAreaStateExt,
+    classification::{ItemKind, Link, classify, file_path},
+    color::{ensure_contrast, parse_color},
+    config::{Config, Dimensions, LabelOverflow, LayoutConfig, LayoutMode, TextEllipsis},
+    desktop_entry::DesktopEntries,
+    display_text::build_display_text,
+    expiry,
+    favicon::FaviconFetcher,
+    file_stats::{FileStats, FileStatsScanner},
+    format::{DateFormat, NumberFormat},
     freedesktop_cache::get_cached_thumbnail,
-    keymap_action::{KeyChord, ScrollAction},
+    icon_theme::IconTheme,
+    image_decode::decode_image,
+    keymap_action::{
+        Action, KeyAction, KeyChord, LabelScrollAction, ScrollAction, ZoomAction, keys_for,
+    },
     ordered_hash_map::OrderedHashMap,
-    selection::{SelectionItem, SelectionMetadata},
-    utils::{is_image_mime, is_plaintext_mime, percent_decode, utf16le_to_string},
-    widgets::{clipboard_button::ClipboardButton, help_modal::HelpModal},
+    renderer::RenderTimings,
+    script::{Script, scripts_in},
+    selection::SelectionItem,
+    text_details::{self, TextDetails},
+    texture_cache::TextureCache,
+    timeline::{self, DayGroup, LocalTime},
+    utils::{image_mime_score, is_image_mime, is_url},
+    widgets::{
+        clipboard_button::{ClipboardButton, LABEL_SCROLL_ID},
+        command_palette::CommandPalette,
+        confirm_modal::ConfirmModal,
+        context_menu::ContextMenu,
+        details_viewer::DetailsViewer,
+        help_modal::HelpModal,
+        image_viewer::ImageViewer,
+        onboarding::Onboarding,
+        profile_overlay::ProfileOverlay,
+        toasts::{ToastKind, Toasts},
+        transform_picker::TransformPicker,
+    },
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -36,6 +68,42 @@ pub enum UiFlow {
     BottomToTop,
 }
 
+#[derive(Debug, Clone, Copy)]
+pub enum UiAction {
+    RetryKeyboardGrab,
+    Close,
+    ItemAction(u64, KeyAction),
+    Confirm(bool),
+    // A command of the palette clicked, run like pressing Enter on it
+    RunCommand,
+    ShowHelp,
+}
+
+/// What the main loop has for a frame to show, besides the input
+#[derive(Clone, Copy)]
+pub struct FrameState<'a> {
+    pub selection_items: &'a OrderedHashMap<u64, &'a SelectionItem>,
+    pub pinned_count: usize,
+    pub expiries: &'a BTreeMap<u64, u64>,
+    pub copied_at: &'a BTreeMap<u64, u64>,
+    pub search_query: Option<&'a str>,
+    pub flow: UiFlow,
+    pub scroll_actions: &'a [ScrollAction],
+    pub pending_keys: &'a [KeyChord],
+    pub show_help: bool,
+    pub confirm_message: Option<&'a str>,
+    pub keyboard_unavailable: bool,
+}
+
+#[derive(Default)]
+struct Favicons {
+    // By domain, None for those that failed to load. Kept across context resets, unlike textures
+    images: HashMap<String, Option<RgbaImage>>,
+    textures: HashMap<String, TextureHandle>,
+    // Items whose buttons wait on the favicon of a domain being fetched
+    pending: HashMap<String, Vec<u64>>,
+}
+
 struct ImageInfo {
     r#type: String,
     thumbnail: RgbaImage,
@@ -73,12 +141,40 @@ const NOTO_EMOJI: &[u8] = include_bytes!(concat!(
     "/assets/fonts/Noto_Emoji/NotoEmoji-Regular.ttf"
 ));
 
+// Of paddings around buttons with accessibility.large_hit_targets
+const LARGE_HIT_TARGET_SCALE: f32 = 1.5;
+
 #[derive(Debug)]
 struct ScrollAreaInfo {
     id: egui::Id,
     rect: Rect,
     content_rects: HashMap<u64, Rect>,
     offset: f32,
+    // Of a day header and the spacing after it, 0 outside the timeline
+    day_header_height: f32,
+}
+
+impl ScrollAreaInfo {
+    // Where items are fully in view, inside the padding and below the sticky day header
+    fn view_rect(&self, padding: f32) -> Rect {
+        let mut rect = self.rect.shrink2(egui::vec2(0.0, padding));
+        rect.min.y += self.day_header_height;
+        rect
+    }
+}
+
+// What's drawn top to bottom, items or the header of their day in the timeline
+enum ListRow<'a> {
+    DayHeader(&'a DayGroup),
+    // Index into the item rows
+    Items(usize),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ScrollAnimation {
+    from: f32,
+    to: f32,
+    started_at: f64,
 }
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
@@ -91,18 +187,58 @@ enum ActiveSource {
 pub struct Ui<'a> {
     pub egui_ctx: egui::Context,
     config: &'a Config,
+    // The config with another theme, while one is previewed
+    theme_preview: Option<Rc<Config>>,
     fonts: FontDefinitions,
+    // Fallback fonts of scripts no item has been seen in yet, by family name
+    pending_font_fallbacks: Vec<(Script, String)>,
     prev_active_id: u64,
     prev_active_idx: usize,
     item_widget_ids: HashMap<u64, egui::Id>,
     active_source: Option<ActiveSource>,
     scroll_area_info: Option<ScrollAreaInfo>,
     is_initial_run: bool,
+    restored_scroll_offset: Option<f32>,
+    scroll_animation: Option<ScrollAnimation>,
+    // Sideways offset of the active item's label
+    label_scroll: Option<(u64, f32)>,
     hides_scroll_bar: bool,
+    // The last item was on screen in the last frame, for loading the saved ones after it
+    list_end_visible: bool,
     button_widgets: HashMap<u64, ClipboardButton>,
     fallback: Fallback,
+    icon_theme: IconTheme,
+    // Read on the first source icon, then kept as installed applications rarely change
+    desktop_entries: Option<DesktopEntries>,
+    // By WM_CLASS class name, so the items of an application share one texture
+    source_icons: HashMap<String, Option<TextureHandle>>,
     help_modal: HelpModal,
+    context_menu: ContextMenu,
+    toasts: Toasts,
+    onboarding: Option<Onboarding>,
+    image_viewer: ImageViewer,
+    details_viewer: DetailsViewer,
+    transform_picker: TransformPicker,
+    command_palette: CommandPalette,
+    file_stats_scanner: Option<FileStatsScanner>,
+    file_stats: HashMap<u64, FileStats>,
+    favicon_fetcher: Option<FaviconFetcher>,
+    favicons: Favicons,
+    // Computed on first view, items never change once captured
+    text_details: HashMap<u64, Rc<TextDetails>>,
+    number_format: NumberFormat,
+    date_format: DateFormat,
     color_preview_background_texture: TextureHandle,
+    preview_textures: TextureCache,
+    // Widgets that dropped their preview to stay within the memory limit, rebuilt once shown
+    unloaded_previews: HashSet<u64>,
+    profile_overlay: Option<ProfileOverlay>,
+    // Offset of the local time zone from UTC while the list is grouped by day
+    timeline: Option<LocalTime>,
+    // Rows moved without items being added or removed, e.g., for day headers
+    rows_changed: bool,
+    // Bytes the history takes on disk, for the header
+    disk_usage: u64,
 }
 
 impl<'a> Ui<'a> {
@@ -156,53 +292,132 @@ impl<'a> Ui<'a> {
         font_family_names.push("NotoEmoji-Regular".to_owned());
         font_family_names.push("NotoSansMath-Regular".to_owned());
 
+        // No monospace font is bundled, so the proportional ones stand in for missing glyphs
+        let mut monospace_family_names = font_family_names.clone();
+        if !font.monospace_family.is_empty() {
+            if let Some(font_path) = Self::find_font(&font.monospace_family)? {
+                debug!(
+                    "found monospace font family '{}' file: {font_path:?}",
+                    font.monospace_family
+                );
+                fonts.font_data.insert(
+                    font.monospace_family.clone(),
+                    Arc::new(FontData::from_owned(fs::read(font_path)?)),
+                );
+                monospace_family_names.insert(0, font.monospace_family.clone());
+            } else {
+                warn!(
+                    "monospace font family '{}' not found",
+                    font.monospace_family
+                );
+            }
+        }
+
         fonts
             .families
             .insert(FontFamily::Proportional, font_family_names);
+        fonts
+            .families
+            .insert(FontFamily::Monospace, monospace_family_names);
         egui_ctx.set_fonts(fonts.clone());
 
+        let pending_font_fallbacks = [
+            (Script::Cjk, &font.cjk_fallback),
+            (Script::Arabic, &font.arabic_fallback),
+            (Script::Devanagari, &font.devanagari_fallback),
+        ]
+        .into_iter()
+        .filter(|(_, family)| !family.is_empty())
+        .map(|(script, family)| (script, family.clone()))
+        .collect();
+
         debug!("loading fallback images");
         let fallback_img = image::load_from_memory(FALLBACK_IMG_BYTES)?.to_rgba8();
         let fallback_file = image::load_from_memory(FALLBACK_FILE_BYTES)?.to_rgba8();
         let fallback_dir = image::load_from_memory(FALLBACK_DIR_BYTES)?.to_rgba8();
 
-        let color_preview_background_image = make_alpha_checkerboard_image(6);
-        let color_preview_background_texture = egui_ctx.load_texture(
-            "color_preview_background",
-            color_preview_background_image,
-            TextureOptions::NEAREST,
-        );
+        let color_preview_background_texture = load_color_preview_background_texture(&egui_ctx);
 
         Ok(Ui {
             egui_ctx,
             config,
+            theme_preview: None,
             fonts,
+            pending_font_fallbacks,
             prev_active_id: 0,
             prev_active_idx: 0,
             item_widget_ids: HashMap::new(),
             active_source: None,
             scroll_area_info: None,
             is_initial_run: true,
+            restored_scroll_offset: None,
+            scroll_animation: None,
+            label_scroll: None,
             hides_scroll_bar: config.scroll_bar_auto_hide,
+            list_end_visible: false,
             button_widgets: HashMap::new(),
             fallback: Fallback {
                 image: fallback_img,
                 file: fallback_file,
                 directory: fallback_dir,
             },
+            icon_theme: IconTheme::new(&config.icon_theme),
+            desktop_entries: None,
+            source_icons: HashMap::new(),
             help_modal: HelpModal::new(),
+            context_menu: ContextMenu::new(),
+            toasts: Toasts::new(),
+            onboarding: None,
+            image_viewer: ImageViewer::new(),
+            details_viewer: DetailsViewer::new(),
+            transform_picker: TransformPicker::new(),
+            command_palette: CommandPalette::new(config),
+            file_stats_scanner: None,
+            file_stats: HashMap::new(),
+            favicon_fetcher: None,
+            favicons: Favicons::default(),
+            text_details: HashMap::new(),
+            number_format: NumberFormat::from_locale(&config.locale),
+            date_format: DateFormat::from_locale(&config.locale),
             color_preview_background_texture,
+            preview_textures: TextureCache::new(config),
+            unloaded_previews: HashSet::new(),
+            profile_overlay: None,
+            timeline: None,
+            rows_changed: false,
+            disk_usage: 0,
         })
     }
 
     pub fn reset_context(&mut self) {
         info!("recreating egui context");
-        let egui_ctx = Self::create_egui_context(self.config);
+        let egui_ctx =
+            Self::create_egui_context(self.theme_preview.as_deref().unwrap_or(self.config));
         egui_ctx.set_fonts(self.fonts.clone());
+        self.color_preview_background_texture = load_color_preview_background_texture(&egui_ctx);
+        self.preview_textures.reload(&egui_ctx);
+        self.source_icons.clear();
+        self.favicons.textures.clear();
+
+        // Goes through serialization, so only what egui persists is kept, e.g., scroll offsets and
+        // collapsed sections, and none of the temporary state tied to the old context
+        let data = self.egui_ctx.data(serde_json::to_vec);
+        match data.and_then(|data| serde_json::from_slice(&data)) {
+            Ok(data) => egui_ctx.data_mut(|d| *d = data),
+            Err(e) => warn!("failed to carry egui memory over to the new context: {e:?}"),
+        }
         self.egui_ctx = egui_ctx;
 
         debug!("clearing button widgets");
         self.button_widgets.clear();
+        self.unloaded_previews.clear();
+    }
+
+    /// Draws with the theme of `config` instead of the loaded one until called with None. Button
+    /// widgets are cleared and need to be built again
+    pub fn preview_theme(&mut self, config: Option<Config>) {
+        self.theme_preview = config.map(Rc::new);
+        self.reset_context();
     }
 
     fn create_egui_context(config: &Config) -> egui::Context {
@@ -210,11 +425,46 @@ impl<'a> Ui<'a> {
         let layout = &config.layout;
         let font = &config.font;
         let theme = &config.theme;
+        let accessibility = &config.accessibility;
+
+        // Text is laid over buttons, active buttons and the window itself
+        let foreground = if accessibility.min_contrast > 0.0 {
+            let backgrounds: [Color32; 3] = [
+                theme.background.into(),
+                theme.button_background.into(),
+                theme.button_active_background.into(),
+            ];
+            let foreground = ensure_contrast(
+                theme.foreground.into(),
+                &backgrounds,
+                accessibility.min_contrast,
+            );
+            if foreground != Color32::from(theme.foreground) {
+                info!(
+                    "foreground adjusted to {foreground:?} for a contrast ratio of at least {}",
+                    accessibility.min_contrast
+                );
+            }
+            foreground
+        } else {
+            theme.foreground.into()
+        };
+
+        #[cfg(feature = "accessibility")]
+        egui_ctx.enable_accesskit();
 
         info!("setting global egui style");
         egui_ctx.style_mut(|style| {
             // style.debug.debug_on_hover = true;
             style.spacing.button_padding = layout.button_padding.into();
+            if accessibility.large_hit_targets {
+                style.spacing.button_padding *= LARGE_HIT_TARGET_SCALE;
+                style.spacing.interact_size *= LARGE_HIT_TARGET_SCALE;
+            }
+            if accessibility.reduced_motion {
+                style.animation_time = 0.0;
+                style.scroll_animation = egui::style::ScrollAnimation::none();
+            }
             style.spacing.item_spacing = egui::vec2(0.0, layout.button_spacing);
             style.interaction.selectable_labels = false;
 
@@ -223,13 +473,13 @@ impl<'a> Ui<'a> {
             style.visuals.widgets.noninteractive.bg_stroke.color = theme.muted_foreground.into();
             style.visuals.code_bg_color = theme.button_background.into();
 
-            style.visuals.override_text_color = Some(theme.foreground.into());
+            style.visuals.override_text_color = Some(foreground);
             for widget in [
                 &mut style.visuals.widgets.inactive,
                 &mut style.visuals.widgets.hovered,
                 &mut style.visuals.widgets.active,
             ] {
-                widget.fg_stroke.color = theme.foreground.into();
+                widget.fg_stroke.color = foreground;
                 widget.weak_bg_fill = theme.button_background.into();
                 widget.corner_radius = CornerRadius::same(layout.button_corner_radius);
                 widget.bg_stroke = Stroke::NONE;
@@ -242,7 +492,16 @@ impl<'a> Ui<'a> {
                     *font_id = egui::FontId::proportional(font.size);
                 }
             }
+            if let Some(font_id) = style.text_styles.get_mut(&egui::TextStyle::Monospace) {
+                *font_id = egui::FontId::monospace(font.size);
+            }
+            if font.gamma > 0.0 {
+                style.visuals.text_alpha_from_coverage =
+                    egui::epaint::AlphaFromCoverage::Gamma(font.gamma);
+            }
         });
+        // A press held for long still clicks the item, as the hold gesture of [pointer] is one
+        egui_ctx.options_mut(|options| options.input_options.max_click_duration = f64::INFINITY);
 
         egui_ctx
     }
@@ -271,16 +530,26 @@ impl<'a> Ui<'a> {
         &mut self,
         egui_input: RawInput,
         active_id: &mut u64,
-        selection_items: &OrderedHashMap<u64, SelectionItem>,
-        selection_metadata: &SelectionMetadata,
-        flow: UiFlow,
-        scroll_actions: &[ScrollAction],
-        pending_keys: &[KeyChord],
-        show_help: bool,
-    ) -> Result<(FullOutput, Option<u64>)> {
+        frame_state: &FrameState,
+    ) -> Result<(FullOutput, Option<u64>, Option<UiAction>)> {
+        let &FrameState {
+            selection_items,
+            pinned_count,
+            expiries,
+            copied_at,
+            search_query,
+            flow,
+            scroll_actions,
+            pending_keys,
+            show_help,
+            confirm_message,
+            keyboard_unavailable,
+        } = frame_state;
         trace!("painting ui with flow {flow:?}");
         let mut run_error = None;
         let layout = &self.config.layout;
+        let theme_preview = self.theme_preview.clone();
+        let themed_config = theme_preview.as_deref().unwrap_or(self.config);
         let active_idx = selection_items
             .iter()
             .position(|(id, _)| *id == *active_id)
@@ -298,6 +567,28 @@ impl<'a> Ui<'a> {
             .as_ref()
             .and_then(|info| info.content_rects.get(&self.prev_active_id).cloned());
 
+        // Search results go by how well they match, which days would only break up
+        let day_groups = self
+            .timeline
+            .as_mut()
+            .filter(|_| search_query.is_none_or(str::is_empty))
+            .map(|local_time| {
+                timeline::group_by_day(
+                    selection_items
+                        .iter()
+                        .map(|(id, _)| copied_at.get(id).copied()),
+                    pinned_count,
+                    expiry::now(),
+                    |secs| local_time.utc_offset(secs),
+                    &self.date_format,
+                )
+            });
+        let item_rows = self.item_rows(selection_items, day_groups.as_deref());
+        // Computed ahead as the UI closure can't borrow all of self
+        let grid_rows: Vec<bool> = item_rows
+            .iter()
+            .map(|row| self.is_grid_item(selection_items, row.start))
+            .collect();
         for action in scroll_actions {
             let action = if flow == UiFlow::TopToBottom {
                 *action
@@ -305,11 +596,35 @@ impl<'a> Ui<'a> {
                 action.flipped()
             };
 
-            if let Some(scroll_info) = &self.scroll_area_info {
+            if let Some(scroll_info) = &self.scroll_area_info
+                && !item_rows.is_empty()
+            {
+                // Repeated actions, e.g., from a count, go on from where the previous one got to
+                let active_idx = selection_items
+                    .iter()
+                    .position(|(id, _)| *id == *active_id)
+                    .unwrap_or(0);
                 let id_from_idx = |idx| *selection_items.get_by_index(idx).unwrap().0;
+                let active_row = item_rows
+                    .iter()
+                    .position(|row| row.contains(&active_idx))
+                    .unwrap_or(0);
+                let active_column = active_idx - item_rows[active_row].start;
+                // Keeps the column when moving between rows, as far as the other row goes
+                let idx_in_row = |row: usize| {
+                    let row = &item_rows[row % item_rows.len()];
+                    row.start + active_column.min(row.len() - 1)
+                };
                 let next_id = match action {
-                    ScrollAction::ItemUp => id_from_idx((active_idx + items_size - 1) % items_size),
-                    ScrollAction::ItemDown => id_from_idx((active_idx + 1) % items_size),
+                    ScrollAction::ItemUp => {
+                        id_from_idx(idx_in_row(active_row + item_rows.len() - 1))
+                    }
+                    ScrollAction::ItemDown => id_from_idx(idx_in_row(active_row + 1)),
+                    ScrollAction::ItemLeft if active_column > 0 => id_from_idx(active_idx - 1),
+                    ScrollAction::ItemRight if active_idx + 1 < item_rows[active_row].end => {
+                        id_from_idx(active_idx + 1)
+                    }
+                    ScrollAction::ItemLeft | ScrollAction::ItemRight => *active_id,
 
                     ScrollAction::HalfUp if active_idx == 0 => id_from_idx(items_size - 1),
                     ScrollAction::HalfUp => find_item_at_distance_from(
@@ -362,6 +677,11 @@ impl<'a> Ui<'a> {
                 self.active_source = Some(ActiveSource::Hovering);
             }
 
+            // Scrolling by hand takes over from an animation on the way
+            if matches!(ev, egui::Event::MouseWheel { .. }) {
+                self.scroll_animation = None;
+            }
+
             // With scroll_bar_auto_hide = true, on window shown, the scroll bar may still be
             // briefly visible, so we hide it before showing the window. This shows the scroll
             // bar back when the pointer starts to move.
@@ -390,10 +710,21 @@ impl<'a> Ui<'a> {
             self.egui_ctx.request_discard(
                 "Recalculate scroll area's content rects when active item got moved",
             );
+        } else if self.rows_changed {
+            self.egui_ctx
+                .request_discard("Recalculate scroll area's content rects when rows got moved");
+        }
+
+        if !selection_items.is_empty() && self.onboarding.take().is_some() {
+            info!("first item captured, onboarding panel disabled");
         }
 
         let mut clicked_item = None;
-        let full_output = self.egui_ctx.run(egui_input, |ctx| {
+        let mut ui_action = None;
+        let mut shown_unloaded_previews = vec![];
+        // A handle of its own, so the UI closure can borrow self mutably
+        let egui_ctx = self.egui_ctx.clone();
+        let full_output = egui_ctx.run(egui_input, |ctx| {
             // Pick new active item if the current one got removed
             if !ctx.will_discard() && active_item_removed {
                 let nearest_item_id = if let Some(scroll_info) = &self.scroll_area_info
@@ -437,13 +768,12 @@ impl<'a> Ui<'a> {
             // Active item is scrolled out of view, pick a new one
             if !ctx.will_discard()
                 && !self.is_initial_run
+                && self.scroll_animation.is_none()
                 && self.prev_active_id == *active_id
                 && self.prev_active_idx == active_idx
                 && let Some(scroll_info) = &self.scroll_area_info
                 && let Some(active_rect) = scroll_info.content_rects.get(active_id)
-                && let scroll_rect = scroll_info
-                    .rect
-                    .shrink2(egui::vec2(0.0, layout.window_padding.y as f32))
+                && let scroll_rect = scroll_info.view_rect(layout.window_padding.y as f32)
                 && !scroll_rect.contains_rect(*active_rect)
             {
                 let active_rect_above_view = active_rect.min.y < scroll_rect.min.y;
@@ -489,14 +819,18 @@ impl<'a> Ui<'a> {
                 .as_ref()
                 .map(|s| {
                     let mut size = 0.0;
-                    for (item_id, _) in selection_items {
-                        size += s
-                            .content_rects
-                            .get(item_id)
+                    for row in &item_rows {
+                        size += row
+                            .clone()
+                            .filter_map(|i| {
+                                s.content_rects
+                                    .get(selection_items.get_by_index(i).unwrap().0)
+                            })
                             .map(|r| r.height())
-                            .unwrap_or(0.0);
+                            .fold(0.0, f32::max);
                         size += self.config.layout.button_spacing;
                     }
+                    size += day_groups.as_ref().map_or(0, Vec::len) as f32 * s.day_header_height;
                     size -= self.config.layout.button_spacing;
                     size += (self.config.layout.window_padding.y as f32) * 2.0;
                     size
@@ -523,7 +857,16 @@ impl<'a> Ui<'a> {
                     let scroll_offset = scroll_area.offset;
 
                     if sets_default_scroll_offset {
-                        if flow == UiFlow::TopToBottom {
+                        if let Some(offset) = self.restored_scroll_offset
+                            && self.is_initial_run
+                            && content_overflowed
+                        {
+                            Some(
+                                offset
+                                    .min(scroll_content_size - scroll_rect.height())
+                                    .max(0.0),
+                            )
+                        } else if flow == UiFlow::TopToBottom {
                             Some(0.0)
                         } else {
                             Some(scroll_content_size - scroll_rect.height())
@@ -537,10 +880,15 @@ impl<'a> Ui<'a> {
                         Some(scroll_content_size - scroll_rect.height())
                     } else if let Some(&active_rect) = scroll_area.content_rects.get(active_id)
                         && let unpadded_scroll_rect = scroll_rect.shrink2(egui::vec2(0.0, padding))
-                        && !unpadded_scroll_rect.contains_rect(active_rect)
+                        && !scroll_area.view_rect(padding).contains_rect(active_rect)
                     {
-                        if active_rect.top() < unpadded_scroll_rect.top() {
-                            Some(scroll_offset + active_rect.top() - padding)
+                        if active_rect.top() < scroll_area.view_rect(padding).top() {
+                            // Clear of the sticky day header
+                            Some(
+                                scroll_offset + active_rect.top()
+                                    - padding
+                                    - scroll_area.day_header_height,
+                            )
                         } else {
                             Some(
                                 scroll_offset + active_rect.bottom()
@@ -555,6 +903,13 @@ impl<'a> Ui<'a> {
                     None
                 };
 
+            let next_scroll_offset =
+                if self.config.smooth_scroll && !self.config.accessibility.reduced_motion {
+                    self.animate_scroll_offset(ctx, next_scroll_offset)
+                } else {
+                    next_scroll_offset
+                };
+
             self.item_widget_ids.clear();
 
             if next_scroll_offset.is_some()
@@ -564,48 +919,147 @@ impl<'a> Ui<'a> {
                 debug!("failed to reset main scroll area velocity: {e}");
             }
 
+            // Without the header, the label still gets a strip of its own
+            let header = if self.config.show_header {
+                Some(Self::header_text(
+                    &self.number_format,
+                    &self.config.selection_label,
+                    selection_items,
+                    pinned_count,
+                    *active_id,
+                    self.disk_usage,
+                ))
+            } else {
+                (!self.config.selection_label.is_empty())
+                    .then(|| self.config.selection_label.clone())
+            };
+
             let mut content_sizes = HashMap::new();
+            let mut help_requested = false;
+            let mut list_end_visible = false;
+            let mut day_header_height = 0.0;
             let container_result = Self::container(
                 ctx,
-                self.config,
+                themed_config,
+                flow,
+                header.as_deref(),
+                search_query,
                 next_scroll_offset,
                 self.hides_scroll_bar,
                 |ui| {
                     if selection_items.is_empty() {
-                        ui.centered_and_justified(|ui| {
-                            ui.add(egui::Label::new("Your clipboard history will appear here."))
-                        });
+                        if search_query.is_some_and(|q| !q.is_empty()) {
+                            ui.centered_and_justified(|ui| {
+                                ui.add(egui::Label::new("No items match your search."))
+                            });
+                        } else if let Some(onboarding) = &self.onboarding {
+                            help_requested = onboarding.ui(ui);
+                        } else {
+                            ui.centered_and_justified(|ui| {
+                                ui.add(egui::Label::new("Your clipboard history will appear here."))
+                            });
+                        }
                         return Ok(());
                     }
 
+                    // Rows are reversed with the flow, items within a row stay left to right
                     let layout_reversed = flow == UiFlow::BottomToTop;
-                    let item_it: Box<dyn Iterator<Item = _>> = if layout_reversed {
-                        Box::new(selection_items.iter().enumerate().rev())
-                    } else {
-                        Box::new(selection_items.iter().enumerate())
+                    let list_rows = list_rows(&item_rows, day_groups.as_deref(), layout_reversed);
+                    let label_offset = match self.label_scroll {
+                        Some((id, offset)) if id == *active_id => offset,
+                        _ => 0.0,
                     };
+                    let columns = self.config.layout.grid_columns.max(1);
+                    let cell_spacing = self.config.layout.button_spacing;
+                    let now = expiry::now();
+
+                    let mut day_headers = vec![];
+                    for list_row in list_rows {
+                        let row_idx = match list_row {
+                            ListRow::DayHeader(group) => {
+                                let header = ui.add(
+                                    egui::Label::new(
+                                        RichText::new(&group.label)
+                                            .size(themed_config.font.secondary_size)
+                                            .color(themed_config.theme.header_foreground),
+                                    )
+                                    .selectable(false),
+                                );
+                                day_headers.push((header.rect, group.label.as_str()));
+                                continue;
+                            }
+                            ListRow::Items(row_idx) => row_idx,
+                        };
+                        let row = &item_rows[row_idx];
+                        let is_grid_row = grid_rows[row_idx];
+                        let mut add_item = |ui: &mut egui::Ui, i: usize| -> Result<()> {
+                            let (&id, item) = selection_items.get_by_index(i).unwrap();
+                            let is_active = id == *active_id;
+                            let is_pinned = i < pinned_count;
+
+                            let mut btn_widget = self
+                                .button_widgets
+                                .get(&item.id)
+                                .ok_or_else(|| {
+                                    anyhow!("missing button widget for item {}", item.id)
+                                })?
+                                .clone()
+                                .is_active(is_active)
+                                .is_pinned(is_pinned)
+                                .tile(is_grid_row);
+                            if is_active {
+                                btn_widget = btn_widget.label_scroll(label_offset);
+                            }
+                            if self.config.show_quick_paste_hint && i < 10 {
+                                btn_widget = btn_widget.keyboard_hint(((i + 1) % 10).to_string());
+                            }
+                            if let Some(&expires_at) = expiries.get(&id) {
+                                btn_widget =
+                                    btn_widget.expiry_badge(expiry::countdown(expires_at, now));
+                            }
 
-                    for (i, (&id, item)) in item_it {
-                        let is_active = id == *active_id;
-                        let is_pinned = i < selection_metadata.pinned_count;
+                            let btn = ui.add(btn_widget);
+                            if ui.is_rect_visible(btn.rect) {
+                                self.preview_textures.mark_seen(id);
+                                if self.unloaded_previews.contains(&id) {
+                                    shown_unloaded_previews.push(id);
+                                }
+                                if i == selection_items.len() - 1 {
+                                    list_end_visible = true;
+                                }
+                            }
+                            if btn.secondary_clicked() {
+                                let pos = btn.interact_pointer_pos().unwrap_or(btn.rect.center());
+                                self.context_menu.open(id, pos);
+                            }
 
-                        let mut btn_widget = self
-                            .button_widgets
-                            .get(&item.id)
-                            .ok_or_else(|| anyhow!("missing button widget for item {}", item.id))?
-                            .clone()
-                            .is_active(is_active)
-                            .is_pinned(is_pinned);
-                        if self.config.show_quick_paste_hint && i < 10 {
-                            btn_widget = btn_widget.keyboard_hint(((i + 1) % 10).to_string());
-                        }
+                            self.item_widget_ids.insert(id, btn.id);
+                            content_sizes.insert(item.id, btn.rect);
+                            Ok(())
+                        };
 
-                        let btn = ui.add(btn_widget);
+                        if !is_grid_row {
+                            add_item(ui, row.start)?;
+                            continue;
+                        }
 
-                        self.item_widget_ids.insert(id, btn.id);
-                        content_sizes.insert(item.id, btn.rect);
+                        let cell_width = (ui.available_width()
+                            - cell_spacing * (columns - 1) as f32)
+                            / columns as f32;
+                        ui.horizontal_top(|ui| {
+                            ui.spacing_mut().item_spacing.x = cell_spacing;
+                            row.clone().try_for_each(|i| {
+                                ui.allocate_ui(egui::vec2(cell_width, 0.0), |ui| add_item(ui, i))
+                                    .inner
+                            })
+                        })
+                        .inner?;
                     }
 
+                    Self::draw_sticky_day_header(ui, themed_config, &day_headers);
+                    day_header_height = day_headers
+                        .first()
+                        .map_or(0.0, |(rect, _)| rect.height() + cell_spacing);
                     Ok(())
                 },
             );
@@ -619,6 +1073,11 @@ impl<'a> Ui<'a> {
                 })
             });
 
+            self.list_end_visible = list_end_visible;
+            if help_requested {
+                ui_action = Some(UiAction::ShowHelp);
+            }
+
             if show_help {
                 self.help_modal
                     .show(ctx, self.config.layout.window_dimensions.into());
@@ -626,8 +1085,48 @@ impl<'a> Ui<'a> {
                 self.help_modal.hide();
             }
 
+            self.image_viewer
+                .show(ctx, self.config.layout.window_dimensions.into());
+            self.details_viewer
+                .show(ctx, themed_config, &self.number_format);
+
+            if let Some(action) = self
+                .transform_picker
+                .show(ctx, self.config.layout.window_dimensions.into())
+            {
+                ui_action = Some(UiAction::ItemAction(*active_id, action));
+            }
+
+            if self
+                .command_palette
+                .show(ctx, self.config.layout.window_dimensions.into())
+            {
+                ui_action = Some(UiAction::RunCommand);
+            }
+
+            if let Some(message) = confirm_message
+                && let Some(confirmed) =
+                    ConfirmModal::show(ctx, self.config.layout.window_dimensions.into(), message)
+            {
+                ui_action = Some(UiAction::Confirm(confirmed));
+            }
+
             if !pending_keys.is_empty() {
-                Self::draw_pending_keys_overlay(ctx, pending_keys, self.config);
+                Self::draw_pending_keys_overlay(ctx, pending_keys, themed_config);
+            }
+
+            self.toasts.show(ctx, themed_config);
+
+            if let Some(profile_overlay) = &self.profile_overlay {
+                profile_overlay.show(ctx, themed_config);
+            }
+
+            if keyboard_unavailable {
+                ui_action = Self::draw_keyboard_unavailable_banner(ctx, themed_config);
+            }
+
+            if let Some((item_id, action)) = self.context_menu.show(ctx) {
+                ui_action = Some(UiAction::ItemAction(item_id, action));
             }
 
             match container_result {
@@ -637,13 +1136,23 @@ impl<'a> Ui<'a> {
                         rect: scroll_area_output.inner_rect,
                         content_rects: content_sizes,
                         offset: scroll_area_output.state.offset[1],
+                        day_header_height,
                     });
                 }
                 Err(err) => run_error = Some(err),
             }
         });
 
+        // Drawn without a preview for this frame, the next one has it loaded again
+        for id in shown_unloaded_previews {
+            if let Some(item) = selection_items.get(&id) {
+                self.build_button_widget(item)?;
+            }
+        }
+
         self.is_initial_run = false;
+        self.rows_changed = false;
+        self.restored_scroll_offset = None;
         self.prev_active_id = *active_id;
         self.prev_active_idx = selection_items
             .iter()
@@ -651,14 +1160,18 @@ impl<'a> Ui<'a> {
             .unwrap_or(0);
 
         match run_error {
-            None => Ok((full_output, clicked_item)),
+            None => Ok((full_output, clicked_item, ui_action)),
             Some(err) => Err(err),
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn container(
         ctx: &egui::Context,
         config: &Config,
+        flow: UiFlow,
+        header: Option<&str>,
+        search_query: Option<&str>,
         scroll_offset: Option<f32>,
         hides_scroll_bar: bool,
         add_contents: impl FnOnce(&mut egui::Ui) -> Result<()>,
@@ -672,6 +1185,57 @@ impl<'a> Ui<'a> {
         let mut scroll_area_output = None;
         let mut err: Option<anyhow::Error> = None;
 
+        // Sits on the side the list starts from, next to where the pointer usually is
+        if let Some(header) = header {
+            let panel = if flow == UiFlow::TopToBottom {
+                egui::TopBottomPanel::top("header")
+            } else {
+                egui::TopBottomPanel::bottom("header")
+            };
+            panel
+                .frame(
+                    egui::Frame::new()
+                        .fill(theme.header_background.into())
+                        .inner_margin(egui::Margin::symmetric(padding.x, padding.y / 2)),
+                )
+                .show_separator_line(false)
+                .show(ctx, |ui| {
+                    ui.label(
+                        RichText::new(header)
+                            .size(config.font.secondary_size)
+                            .color(theme.header_foreground),
+                    );
+                });
+        }
+
+        // Between the header and the list, with a block cursor as the query can't be clicked into
+        if let Some(query) = search_query {
+            let panel = if flow == UiFlow::TopToBottom {
+                egui::TopBottomPanel::top("search")
+            } else {
+                egui::TopBottomPanel::bottom("search")
+            };
+            panel
+                .frame(
+                    egui::Frame::new()
+                        .inner_margin(egui::Margin::symmetric(padding.x, padding.y / 2)),
+                )
+                .show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.spacing_mut().item_spacing.x = 0.0;
+                        ui.label(RichText::new("/ ").color(theme.muted_foreground));
+                        if query.is_empty() {
+                            ui.label(
+                                RichText::new("▏Type to search").color(theme.muted_foreground),
+                            );
+                        } else {
+                            ui.label(query);
+                            ui.label("▏");
+                        }
+                    });
+                });
+        }
+
         egui::CentralPanel::default()
             .frame(egui::Frame::new())
             .show(ctx, |ui| {
@@ -732,6 +1296,66 @@ impl<'a> Ui<'a> {
         }
     }
 
+    fn header_text(
+        number_format: &NumberFormat,
+        selection_label: &str,
+        selection_items: &OrderedHashMap<u64, &SelectionItem>,
+        pinned_count: usize,
+        active_id: u64,
+        disk_usage: u64,
+    ) -> String {
+        let total = number_format.count(selection_items.len());
+        let mut text = if selection_label.is_empty() {
+            String::new()
+        } else {
+            format!("{selection_label} · ")
+        };
+        text.push_str(
+            &match selection_items.iter().position(|(&id, _)| id == active_id) {
+                Some(idx) => format!("item {} / {total}", number_format.count(idx + 1)),
+                None => format!("{total} items"),
+            },
+        );
+        if pinned_count > 0 {
+            text.push_str(&format!(" · {} pinned", number_format.count(pinned_count)));
+        }
+        if disk_usage > 0 {
+            text.push_str(&format!(" · {}", number_format.size(disk_usage)));
+        }
+        text
+    }
+
+    // The header of the day at the top of the view stays there until the next one pushes it up
+    fn draw_sticky_day_header(ui: &egui::Ui, config: &Config, headers: &[(Rect, &str)]) {
+        let view = ui.clip_rect();
+        let Some(idx) = headers
+            .iter()
+            .rposition(|(rect, _)| rect.top() < view.top())
+        else {
+            return;
+        };
+        let (rect, label) = headers[idx];
+        let spacing = config.layout.button_spacing;
+        let top = match headers.get(idx + 1) {
+            Some((next_rect, _)) => view.top().min(next_rect.top() - rect.height() - spacing),
+            None => view.top(),
+        };
+
+        let painter = ui.painter();
+        painter.rect_filled(
+            Rect::from_x_y_ranges(view.x_range(), top..=top + rect.height() + spacing),
+            0.0,
+            config.theme.background,
+        );
+        painter.text(
+            egui::pos2(rect.left(), top),
+            egui::Align2::LEFT_TOP,
+            label,
+            FontId::proportional(config.font.secondary_size),
+            config.theme.header_foreground.into(),
+        );
+    }
+
     fn draw_ribbon(painter: &Painter, container_rect: &Rect, size: f32, color: impl Into<Color32>) {
         let mut points = [
             egui::pos2(-size, 0.0),
@@ -788,12 +1412,327 @@ impl<'a> Ui<'a> {
             });
     }
 
+    // Without the keyboard, the pointer is the only way to recover or to leave the window
+    fn draw_keyboard_unavailable_banner(ctx: &egui::Context, config: &Config) -> Option<UiAction> {
+        let fg_color: Color32 = config.theme.pending_keys_foreground.into();
+        let bg_color: Color32 = config.theme.pending_keys_background.into();
+        let padding: Vec2 = config.layout.pending_keys_padding.into();
+        let margin: Vec2 = config.layout.pending_keys_margin.into();
+        let text_size = config.font.pending_keys_text_size;
+
+        let rect = ctx.input(|i| i.content_rect());
+        let mut action = None;
+        Area::new("keyboard_unavailable_banner".into())
+            .fixed_pos(rect.min + margin)
+            .order(Order::Foreground)
+            .fade_in(false)
+            .show(ctx, |ui| {
+                egui::Frame::new()
+                    .fill(bg_color)
+                    .corner_radius(config.layout.pending_keys_corner_radius)
+                    .inner_margin(egui::Margin::symmetric(padding.x as i8, padding.y as i8))
+                    .show(ui, |ui| {
+                        ui.set_width(rect.width() - margin.x * 2.0 - padding.x * 2.0);
+                        ui.horizontal(|ui| {
+                            let retry = ui.add(
+                                egui::Button::new(
+                                    RichText::new("keyboard unavailable — click to retry")
+                                        .size(text_size)
+                                        .color(fg_color),
+                                )
+                                .frame(false),
+                            );
+                            if retry.clicked() {
+                                action = Some(UiAction::RetryKeyboardGrab);
+                            }
+
+                            ui.with_layout(
+                                egui::Layout::right_to_left(egui::Align::Center),
+                                |ui| {
+                                    let close = ui.add(
+                                        egui::Button::new(
+                                            RichText::new("✕").size(text_size).color(fg_color),
+                                        )
+                                        .frame(false),
+                                    );
+                                    if close.clicked() {
+                                        action = Some(UiAction::Close);
+                                    }
+                                },
+                            );
+                        });
+                    });
+            });
+
+        action
+    }
+
+    // Eases towards offsets that key navigation scrolls to, others are jumped to right away
+    fn animate_scroll_offset(
+        &mut self,
+        ctx: &egui::Context,
+        target_offset: Option<f32>,
+    ) -> Option<f32> {
+        let now = ctx.input(|i| i.time);
+        let current_offset = self.scroll_area_info.as_ref().map(|info| info.offset);
+
+        if let Some(to) = target_offset {
+            if self.is_initial_run || self.active_source != Some(ActiveSource::ScrollAction) {
+                self.scroll_animation = None;
+                return target_offset;
+            }
+            // A new target on the way starts from where the list is now
+            let from = current_offset.unwrap_or(to);
+            if self.scroll_animation.is_none_or(|a| a.to != to) {
+                self.scroll_animation = Some(ScrollAnimation {
+                    from,
+                    to,
+                    started_at: now,
+                });
+            }
+        }
+
+        let animation = self.scroll_animation?;
+        let duration = self.config.smooth_scroll_duration_ms as f64 / 1000.0;
+        let progress = if duration > 0.0 {
+            ((now - animation.started_at) / duration).clamp(0.0, 1.0) as f32
+        } else {
+            1.0
+        };
+        if progress >= 1.0 {
+            self.scroll_animation = None;
+            return Some(animation.to);
+        }
+
+        ctx.request_repaint();
+        Some(egui::lerp(
+            animation.from..=animation.to,
+            egui::emath::easing::cubic_out(progress),
+        ))
+    }
+
+    /// Scrolls the label of an item sideways by half its width, to read the rest of a line too
+    /// long for the window
+    pub fn scroll_label(&mut self, item_id: u64, action: LabelScrollAction) {
+        let Some(&widget_id) = self.item_widget_ids.get(&item_id) else {
+            return;
+        };
+        let Some((max_offset, visible_width)) = self
+            .egui_ctx
+            .data(|d| d.get_temp::<(f32, f32)>(widget_id.with(LABEL_SCROLL_ID)))
+        else {
+            return;
+        };
+
+        let offset = match self.label_scroll {
+            Some((id, offset)) if id == item_id => offset,
+            _ => 0.0,
+        };
+        let step = visible_width / 2.0;
+        let offset = match action {
+            LabelScrollAction::Left => offset - step,
+            LabelScrollAction::Right => offset + step,
+        };
+        self.label_scroll = Some((item_id, offset.clamp(0.0, max_offset)));
+    }
+
+    /// Switches between the plain list and the one grouped by day, returning true for the latter
+    pub fn toggle_timeline(&mut self) -> bool {
+        self.timeline = match self.timeline {
+            Some(_) => None,
+            None => Some(LocalTime::new()),
+        };
+        self.rows_changed = true;
+        self.timeline.is_some()
+    }
+
     pub fn reset(&mut self) {
         info!("resetting ui states");
         self.active_source = None;
+        self.scroll_animation = None;
+        self.label_scroll = None;
         self.is_initial_run = true;
         self.hides_scroll_bar = self.config.scroll_bar_auto_hide;
         self.help_modal.hide();
+        self.context_menu.close();
+        self.image_viewer.close();
+        self.details_viewer.close();
+        self.transform_picker.close();
+        self.command_palette.close();
+    }
+
+    /// Resets like `reset`, but opens scrolled to `scroll_offset` instead of the start of the list
+    pub fn reset_to(&mut self, scroll_offset: f32) {
+        self.reset();
+        self.restored_scroll_offset = Some(scroll_offset);
+    }
+
+    pub fn scroll_offset(&self) -> Option<f32> {
+        self.scroll_area_info.as_ref().map(|info| info.offset)
+    }
+
+    pub fn is_context_menu_open(&self) -> bool {
+        self.context_menu.is_open()
+    }
+
+    pub fn close_context_menu(&mut self) {
+        self.context_menu.close();
+    }
+
+    pub fn enable_profile_overlay(&mut self) {
+        self.profile_overlay = Some(ProfileOverlay::new());
+    }
+
+    pub fn record_frame(&mut self, ui: Duration, render: RenderTimings) {
+        if let Some(profile_overlay) = &mut self.profile_overlay {
+            profile_overlay.record_frame(ui, render);
+        }
+    }
+
+    pub fn record_event_loop(&mut self, duration: Duration) {
+        if let Some(profile_overlay) = &mut self.profile_overlay {
+            profile_overlay.record_event_loop(duration);
+        }
+    }
+
+    /// Buttons of copied files get their stats filled in once the scanner reports back
+    pub fn set_file_stats_scanner(&mut self, scanner: FileStatsScanner) {
+        self.file_stats_scanner = Some(scanner);
+    }
+
+    /// Collects finished file scans, returning the items whose buttons need rebuilding
+    pub fn take_file_stats(&mut self) -> Vec<u64> {
+        let Some(scanner) = &self.file_stats_scanner else {
+            return vec![];
+        };
+
+        scanner
+            .take_results()
+            .into_iter()
+            // The item may be gone by the time its scan finishes
+            .filter(|(item_id, _)| self.button_widgets.contains_key(item_id))
+            .map(|(item_id, stats)| {
+                self.file_stats.insert(item_id, stats);
+                item_id
+            })
+            .collect()
+    }
+
+    /// Link buttons get the favicon of their domain once the fetcher reports back
+    pub fn set_favicon_fetcher(&mut self, fetcher: FaviconFetcher) {
+        self.favicon_fetcher = Some(fetcher);
+    }
+
+    /// Collects fetched favicons, returning the items whose buttons need rebuilding
+    pub fn take_favicons(&mut self) -> Vec<u64> {
+        let Some(fetcher) = &self.favicon_fetcher else {
+            return vec![];
+        };
+
+        let mut item_ids = vec![];
+        for (domain, icon) in fetcher.take_results() {
+            item_ids.extend(self.favicons.pending.remove(&domain).unwrap_or_default());
+            self.favicons.images.insert(domain, icon);
+        }
+        // The item may be gone by the time its favicon arrives
+        item_ids.retain(|item_id| self.button_widgets.contains_key(item_id));
+        item_ids
+    }
+
+    /// Whether the end of the list was on screen in the last frame
+    pub fn list_end_visible(&self) -> bool {
+        self.list_end_visible
+    }
+
+    /// Returns false if the item has no image to view
+    pub fn open_image_viewer(&mut self, item: &SelectionItem) -> Result<bool> {
+        let Some((mime, data)) = item
+            .data
+            .iter()
+            .filter(|(mime, _)| is_image_mime(mime))
+            .max_by_key(|(mime, _)| image_mime_score(mime))
+        else {
+            return Ok(false);
+        };
+
+        let max_side = self.egui_ctx.input(|i| i.max_texture_side) as u32;
+        let mut img = if mime.eq_ignore_ascii_case("image/svg+xml") {
+            let dimensions: Vec2 = self.config.layout.window_dimensions.into();
+            load_svg(data, dimensions * 2.0)?.0
+        } else {
+            decode_image(data)?
+        };
+        if img.width() > max_side || img.height() > max_side {
+            debug!(
+                "image of item {} exceeds max texture side, downscaling",
+                item.id
+            );
+            let scale = max_side as f32 / img.width().max(img.height()) as f32;
+            img = image::imageops::thumbnail(
+                &img,
+                ((img.width() as f32 * scale).round() as u32).max(1),
+                ((img.height() as f32 * scale).round() as u32).max(1),
+            );
+        }
+
+        let size = [img.width() as usize, img.height() as usize];
+        let image = ColorImage::from_rgba_unmultiplied(size, img.as_flat_samples().as_slice());
+        self.image_viewer.open(&self.egui_ctx, item.id, image);
+        Ok(true)
+    }
+
+    pub fn zoom_image_viewer(&mut self, action: ZoomAction) {
+        self.image_viewer.zoom(action);
+    }
+
+    /// Returns false if the item has no text to show details of
+    pub fn open_details_viewer(&mut self, item: &SelectionItem) -> bool {
+        let details = match self.text_details.entry(item.id) {
+            Entry::Occupied(entry) => entry.get().clone(),
+            Entry::Vacant(entry) => {
+                let kind = classify(item);
+                let Some(text) = kind.text() else {
+                    return false;
+                };
+                debug!("computing text details of item {}", item.id);
+                entry.insert(Rc::new(text_details::analyze(&text))).clone()
+            }
+        };
+        self.details_viewer.open(details);
+        true
+    }
+
+    /// Closes whichever of the image viewer and details viewer is open
+    pub fn close_preview(&mut self) {
+        self.image_viewer.close();
+        self.details_viewer.close();
+    }
+
+    pub fn open_transform_picker(&mut self) {
+        self.transform_picker.open();
+    }
+
+    pub fn close_transform_picker(&mut self) {
+        self.transform_picker.close();
+    }
+
+    pub fn command_palette(&mut self) -> &mut CommandPalette {
+        &mut self.command_palette
+    }
+
+    /// Shows a first-run panel while history is empty, until the first item gets captured
+    pub fn enable_onboarding(&mut self, show_command: String) {
+        self.onboarding = Some(Onboarding::new(show_command));
+    }
+
+    /// Size of the history on disk, shown in the header
+    pub fn set_disk_usage(&mut self, bytes: u64) {
+        self.disk_usage = bytes;
+    }
+
+    /// Queues a transient message, shown the next time the window is painted
+    pub fn toast(&mut self, kind: ToastKind, message: impl Into<String>) {
+        self.toasts.push(kind, message.into());
     }
 
     pub fn build_button_widget(&mut self, item: &SelectionItem) -> Result<()> {
@@ -801,28 +1740,182 @@ impl<'a> Ui<'a> {
         let Ui {
             egui_ctx: ctx,
             config,
+            theme_preview,
             fallback,
+            icon_theme,
+            desktop_entries,
+            source_icons,
+            number_format,
+            file_stats_scanner,
+            file_stats,
+            favicon_fetcher,
+            favicons,
+            preview_textures,
             ..
         } = self;
+        let config = theme_preview.as_deref().unwrap_or(*config);
+
+        let kind = classify(item);
+
+        if !self.pending_font_fallbacks.is_empty()
+            && let Some(text) = kind.text()
+        {
+            load_font_fallbacks(
+                ctx,
+                &mut self.fonts,
+                &mut self.pending_font_fallbacks,
+                &text,
+            )?;
+        }
+
+        let mut btn = ClipboardButton::default()
+            .secondary_foreground(config.theme.muted_foreground)
+            .underline_offset(config.font.underline_offset)
+            .pixel_snap(config.font.pixel_snap)
+            .with_preview_padding(config.layout.button_with_preview_padding)
+            .pin_size(config.layout.pin_size)
+            .pin_color(config.theme.pin_color)
+            .color_preview_size(config.layout.color_preview_size)
+            .color_preview_corner_radius(config.layout.color_preview_corner_radius)
+            .color_preview_background(self.color_preview_background_texture.clone());
+
+        if config.show_source_icon
+            && let Some(wm_class) = &item.source
+            && let Some(icon) = load_source_icon(
+                ctx,
+                wm_class,
+                config.layout.source_icon_size,
+                icon_theme,
+                desktop_entries,
+                source_icons,
+            )
+        {
+            btn = btn
+                .source_icon(icon)
+                .source_icon_size(config.layout.source_icon_size);
+        }
+
+        match kind {
+            ItemKind::CaptureFailed { owner } => {
+                let remove_keys = keys_for(AppMode::Normal, |action| {
+                    matches!(action, Action::Key(KeyAction::Remove))
+                });
+                let hint = remove_keys
+                    .map(|keys| format!("PRESS {} TO DISMISS", keys.to_uppercase()))
+                    .unwrap_or_default();
+                btn = btn
+                    .label(vec![
+                        RichText::new(format!("[capture failed from {owner}]"))
+                            .color(config.theme.muted_foreground),
+                    ])
+                    .sublabel(RichText::new(hint).size(config.font.secondary_size));
+            }
+            ItemKind::Virtual { source, text } => {
+                // Not in the history yet, so it's drawn fainter than the items around it
+                btn = btn
+                    .label(vec![
+                        RichText::new(text.to_string())
+                            .italics()
+                            .color(config.theme.muted_foreground),
+                    ])
+                    .sublabel(
+                        RichText::new(format!("{} | PASTE TO KEEP", source.to_uppercase()))
+                            .size(config.font.secondary_size),
+                    );
+            }
+            ItemKind::Files {
+                action,
+                uris: file_uris,
+            } => {
+                let file_paths = file_uris
+                    .iter()
+                    .map(|&uri| file_path(uri))
+                    .collect::<Vec<_>>();
+                let mut path_iter = file_paths.iter();
+                if let Some(path) = path_iter.next() {
+                    btn = btn.append_label(vec![format_path_str(path).into()]);
+                }
+                if let Some(path) = path_iter.next() {
+                    btn = btn.append_label(vec![format_path_str(path).into()]);
+                }
+                let more_count = path_iter.count();
+                btn = btn
+                    .middle_ellipsis(config.text_ellipsis == TextEllipsis::Middle)
+                    .wrap_labels(config.layout.path_overflow == LabelOverflow::Wrap)
+                    .max_label_rows(config.layout.preview_lines.clamp(1, 5));
+
+                // A single copied folder or text file shows its first entries or lines below its path
+                let stats = file_stats.get(&item.id);
+                let detail_lines = stats.and_then(|s| {
+                    s.listing
+                        .as_ref()
+                        .map(|l| &l.entries)
+                        .or(s.text_head.as_ref())
+                });
+                for line in detail_lines.into_iter().flatten() {
+                    btn = btn.append_label(vec![
+                        RichText::new(format!("  {line}")).color(config.theme.muted_foreground),
+                    ]);
+                }
+
+                let mut sublabel_text = "".to_owned();
+                if let Some(action) = action {
+                    sublabel_text.push_str(&action.to_uppercase());
+                }
 
-        let mut text_content = None;
-        let mut img_info = None;
-        let mut img_metadata = None;
-        let mut files = None;
-        for (mime, data) in &item.data {
-            if is_plaintext_mime(mime) {
-                text_content = Some(str::from_utf8(data)?);
-            } else if is_image_mime(mime) {
+                if more_count > 0 {
+                    if !sublabel_text.is_empty() {
+                        sublabel_text.push_str(" | ");
+                    }
+                    sublabel_text
+                        .push_str(&format!("+{} MORE...", number_format.count(more_count)));
+                }
+
+                if let Some(stats) = file_stats.get(&item.id) {
+                    if !sublabel_text.is_empty() {
+                        sublabel_text.push_str(" | ");
+                    }
+                    sublabel_text.push_str(&format_file_stats(stats, number_format));
+                } else if let Some(scanner) = file_stats_scanner {
+                    scanner.request(item.id, file_paths.iter().map(PathBuf::from).collect());
+                }
+
+                if !sublabel_text.is_empty() {
+                    btn =
+                        btn.sublabel(RichText::new(sublabel_text).size(config.font.secondary_size))
+                }
+
+                let thumbnail = create_files_thumbnail(
+                    &file_paths,
+                    stats.map(|s| s.thumbnails.as_slice()).unwrap_or_default(),
+                    config.layout.preview_size,
+                    &fallback.file,
+                    &fallback.directory,
+                    icon_theme,
+                );
+                let texture = preview_textures.load(ctx, item.id, &thumbnail);
+                btn = btn.preview(texture, config.layout.preview_size);
+            }
+            ItemKind::Image {
+                mime,
+                data,
+                metadata: img_metadata,
+            } => {
                 let img_type = mime.split(['/', '+']).nth(1).unwrap_or(mime).to_uppercase();
                 let img = if img_type == "SVG" {
                     load_svg(data, config.layout.preview_size.into())
                 } else {
-                    image::load_from_memory(data)
-                        .map(|i| (i.to_rgba8(), i.dimensions()))
-                        .map_err(anyhow::Error::from)
+                    decode_image(data).map(|i| {
+                        let dimensions = i.dimensions();
+                        (i, dimensions)
+                    })
                 };
 
-                img_info = Some(match img {
+                let ImageInfo {
+                    r#type,
+                    size,
+                    thumbnail,
+                } = match img {
                     Ok((img, size)) => {
                         let thumbnail = create_thumbnail(&img, config.layout.preview_size.into());
                         ImageInfo {
@@ -842,123 +1935,165 @@ impl<'a> Ui<'a> {
                             thumbnail: fallback.image.clone(),
                         }
                     }
-                });
-            } else if mime == "text/x-moz-url" {
-                // Firefox encodes data with UTF-16
-                // https://stackoverflow.com/a/51581772
-                let data = utf16le_to_string(data);
-                img_metadata = Some(
-                    data.split_once('\n')
-                        .map(|(s, a)| (s.to_string(), a.to_string()))
-                        .unwrap_or((data, "".to_string())),
-                );
-            } else if mime == "text/uri-list" && files.is_none() {
-                let uris = str::from_utf8(data)?
-                    .lines()
-                    // text/uri-list can contain comment (based on RFC 2483)
-                    .filter(|l| !l.is_empty() && !l.starts_with("#"))
-                    .collect::<Vec<_>>();
-                if uris.len() == uris.iter().filter(|u| u.starts_with("file://")).count() {
-                    files = Some((None, uris));
-                }
-            } else if mime == "x-special/gnome-copied-files" {
-                let mut file_iter = str::from_utf8(data)?.lines();
-                let action = file_iter.next();
-                files = Some((action, file_iter.collect()));
-            }
-        }
-
-        let mut btn = ClipboardButton::default()
-            .secondary_foreground(config.theme.muted_foreground)
-            .underline_offset(config.font.underline_offset)
-            .with_preview_padding(config.layout.button_with_preview_padding)
-            .pin_size(config.layout.pin_size)
-            .pin_color(config.theme.pin_color)
-            .color_preview_size(config.layout.color_preview_size)
-            .color_preview_corner_radius(config.layout.color_preview_corner_radius)
-            .color_preview_background(self.color_preview_background_texture.clone());
+                };
+                let texture = preview_textures.load(ctx, item.id, &thumbnail);
+                let sublabel_text = if let Some(size) = size {
+                    format!("{} [{}x{}]", r#type, size.0, size.1)
+                } else {
+                    format!("{} [?x?]", r#type)
+                };
 
-        if let Some((action, file_uris)) = files {
-            let file_paths = file_uris
-                .iter()
-                .map(|u| {
-                    str::from_utf8(&percent_decode(&u.as_bytes()["file://".len()..]))
-                        .unwrap()
-                        .to_owned()
-                })
-                .collect::<Vec<_>>();
-            let mut path_iter = file_paths.iter();
-            if let Some(path) = path_iter.next() {
-                btn = btn.append_label(vec![format_path_str(path).into()]);
-            }
-            if let Some(path) = path_iter.next() {
-                btn = btn.append_label(vec![format_path_str(path).into()]);
+                btn = btn
+                    .preview(texture, config.layout.preview_size)
+                    .sublabel(RichText::new(sublabel_text).size(config.font.secondary_size))
+                    .preview_background(config.theme.preview_background);
+
+                if let Some((src, alt)) = img_metadata {
+                    if !alt.is_empty() {
+                        btn = btn.label(build_display_text(
+                            &alt,
+                            &config.theme,
+                            false,
+                            config.show_whitespace,
+                        ));
+                    }
+                    btn = btn.preview_source(&src);
+                }
             }
-            let more_count = path_iter.count();
-
-            let mut sublabel_text = "".to_owned();
-            if let Some(action) = action {
-                sublabel_text.push_str(action);
+            ItemKind::Link(Link { url, title, domain }) => {
+                // Titled by the page, pasting still gives the URL
+                btn = btn
+                    .label(build_display_text(
+                        title.as_deref().unwrap_or(&url),
+                        &config.theme,
+                        false,
+                        config.show_whitespace,
+                    ))
+                    .middle_ellipsis(
+                        title.is_none() && config.text_ellipsis == TextEllipsis::Middle,
+                    );
+                if let Some(domain) = domain {
+                    if config.fetch_favicons
+                        && let Some(favicon) = load_favicon(
+                            ctx,
+                            item.id,
+                            &domain,
+                            config.layout.color_preview_size,
+                            favicon_fetcher,
+                            favicons,
+                        )
+                    {
+                        btn = btn.leading_icon(favicon);
+                    }
+                    btn = btn.sublabel(RichText::new(domain).size(config.font.secondary_size));
+                }
             }
-
-            if more_count > 0 {
-                if !sublabel_text.is_empty() {
-                    sublabel_text.push_str(" | ");
+            ItemKind::Text(text) => {
+                let preview_lines = config.layout.preview_lines.clamp(1, 5);
+                let monospace = config.font.monospace_code && text_details::looks_like_code(&text);
+                let display_text = |text: &str, multi_line: bool| {
+                    let label =
+                        build_display_text(text, &config.theme, multi_line, config.show_whitespace);
+                    if monospace {
+                        label.into_iter().map(RichText::monospace).collect()
+                    } else {
+                        label
+                    }
+                };
+                let overflow = if is_url(&text) {
+                    config.layout.url_overflow
+                } else {
+                    config.layout.text_overflow
+                };
+                if overflow == LabelOverflow::Truncate && preview_lines > 1 {
+                    // Each line gets a row of its own, cut at the window edge
+                    let line_count = text.lines().count();
+                    for (i, line) in text.lines().take(preview_lines).enumerate() {
+                        let mut label = display_text(line, false);
+                        if i == preview_lines - 1 && line_count > preview_lines {
+                            label.push(RichText::new(" …").color(config.theme.muted_foreground));
+                        }
+                        btn = btn.append_label(label);
+                    }
+                } else {
+                    btn = btn
+                        .label(display_text(&text, preview_lines > 1))
+                        .wrap_labels(overflow == LabelOverflow::Wrap)
+                        .max_label_rows(preview_lines);
+                }
+                btn = btn.middle_ellipsis(
+                    config.text_ellipsis == TextEllipsis::Middle && !text.trim().contains('\n'),
+                );
+                if let Cow::Owned(_) = text {
+                    // Replacement characters were substituted, the pasted bytes stay as captured
+                    btn = btn.sublabel(
+                        RichText::new("INVALID ENCODING")
+                            .size(config.font.secondary_size)
+                            .color(config.theme.toast_error_foreground),
+                    );
+                } else if let Some(color) = parse_color(&text) {
+                    btn = btn.color_preview(color);
                 }
-                sublabel_text.push_str(&format!("+{more_count} MORE..."));
             }
-
-            if !sublabel_text.is_empty() {
-                btn = btn.sublabel(
-                    RichText::new(sublabel_text.to_uppercase()).size(config.font.secondary_size),
-                )
+            ItemKind::Unknown => {
+                btn = btn.label(vec![
+                    RichText::new("[unknown]").color(config.theme.muted_foreground),
+                ]);
             }
+        }
 
-            let thumbnail = create_files_thumbnail(
-                &file_paths,
-                config.layout.preview_size,
-                &fallback.file,
-                &fallback.directory,
-            );
-            let texture = load_texture(ctx, item.id, &thumbnail);
-            btn = btn.preview(texture, config.layout.preview_size);
-        } else if let Some(ImageInfo {
-            r#type,
-            size,
-            thumbnail,
-        }) = img_info
-        {
-            let texture = load_texture(ctx, item.id, &thumbnail);
-            let sublabel_text = if let Some(size) = size {
-                format!("{} [{}x{}]", r#type, size.0, size.1)
-            } else {
-                format!("{} [?x?]", r#type)
-            };
+        self.button_widgets.insert(item.id, btn);
+        self.unloaded_previews.remove(&item.id);
 
-            btn = btn
-                .preview(texture, config.layout.preview_size)
-                .sublabel(RichText::new(sublabel_text).size(config.font.secondary_size))
-                .preview_background(config.theme.preview_background);
+        for id in self.preview_textures.take_unloaded() {
+            if let Some(btn) = self.button_widgets.get_mut(&id) {
+                btn.unload_preview();
+                self.unloaded_previews.insert(id);
+            }
+        }
+        Ok(())
+    }
 
-            if let Some((src, alt)) = img_metadata {
-                if !alt.is_empty() {
-                    btn = btn.label(build_display_text(&alt, &config.theme));
+    /// Indices of the items on each row, in the grid mode consecutive items with previews share
+    /// rows of up to `grid_columns`, within their day in the timeline
+    fn item_rows(
+        &self,
+        selection_items: &OrderedHashMap<u64, &SelectionItem>,
+        day_groups: Option<&[DayGroup]>,
+    ) -> Vec<Range<usize>> {
+        let columns = self.config.layout.grid_columns.max(1);
+        let day_starts = day_groups
+            .unwrap_or_default()
+            .iter()
+            .map(|group| group.items.start)
+            .collect::<HashSet<_>>();
+        let mut rows: Vec<Range<usize>> = vec![];
+        for i in 0..selection_items.len() {
+            match rows.last_mut() {
+                Some(row)
+                    if row.len() < columns
+                        && !day_starts.contains(&i)
+                        && self.is_grid_item(selection_items, row.start)
+                        && self.is_grid_item(selection_items, i) =>
+                {
+                    row.end = i + 1;
                 }
-                btn = btn.preview_source(&src);
-            }
-        } else if let Some(text) = text_content {
-            btn = btn.label(build_display_text(text, &config.theme));
-            if let Some(color) = parse_color(text) {
-                btn = btn.color_preview(color);
+                _ => rows.push(i..i + 1),
             }
-        } else {
-            btn = btn.label(vec![
-                RichText::new("[unknown]").color(config.theme.muted_foreground),
-            ]);
         }
+        rows
+    }
 
-        self.button_widgets.insert(item.id, btn);
-        Ok(())
+    fn is_grid_item(
+        &self,
+        selection_items: &OrderedHashMap<u64, &SelectionItem>,
+        idx: usize,
+    ) -> bool {
+        self.config.layout.mode == LayoutMode::Grid
+            && selection_items
+                .get_by_index(idx)
+                .and_then(|(id, _)| self.button_widgets.get(id))
+                .is_some_and(|btn| btn.has_preview())
     }
 
     pub fn remove_button_widgets<I: IntoIterator<Item = SelectionItem>>(
@@ -968,14 +2103,72 @@ impl<'a> Ui<'a> {
         for item in removed_items {
             trace!("removing button widget for item {}", item.id);
             self.button_widgets.remove(&item.id);
+            self.file_stats.remove(&item.id);
+            self.text_details.remove(&item.id);
+            self.preview_textures.remove(item.id);
+            self.unloaded_previews.remove(&item.id);
+        }
+    }
+}
+
+// e.g., "3 images, 2 PDFs · 14 MB"
+fn format_file_stats(stats: &FileStats, number_format: &NumberFormat) -> String {
+    if let Some(listing) = &stats.listing {
+        let plus = if listing.truncated { "+" } else { "" };
+        let noun = if listing.count == 1 { "item" } else { "items" };
+        return format!("{}{plus} {noun}", number_format.count(listing.count));
+    }
+
+    let counts = stats
+        .counts
+        .iter()
+        .map(|(kind, count)| format!("{} {}", number_format.count(*count), kind.noun(*count)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    if stats.total_size == 0 {
+        counts
+    } else {
+        format!("{counts} · {}", number_format.size(stats.total_size))
+    }
+}
+
+// Day headers go above their items with either flow
+fn list_rows<'r>(
+    item_rows: &[Range<usize>],
+    day_groups: Option<&'r [DayGroup]>,
+    reversed: bool,
+) -> Vec<ListRow<'r>> {
+    let mut rows = vec![];
+    match day_groups {
+        None => rows.extend((0..item_rows.len()).map(ListRow::Items)),
+        Some(day_groups) => {
+            for group in day_groups {
+                let group_rows = item_rows
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, row)| group.items.contains(&row.start))
+                    .map(|(i, _)| ListRow::Items(i));
+                // Reversed below, which puts the header back above the items
+                if reversed {
+                    rows.extend(group_rows);
+                    rows.push(ListRow::DayHeader(group));
+                } else {
+                    rows.push(ListRow::DayHeader(group));
+                    rows.extend(group_rows);
+                }
+            }
         }
     }
+    if reversed {
+        rows.reverse();
+    }
+    rows
 }
 
 fn find_item_at_distance_from(
     from_idx: usize,
     distance: f32,
-    items: &OrderedHashMap<u64, SelectionItem>,
+    items: &OrderedHashMap<u64, &SelectionItem>,
     item_rects: &HashMap<u64, Rect>,
 ) -> u64 {
     let items_size = items.len();
@@ -1044,9 +2237,11 @@ fn find_item_at_distance_from(
 
 fn create_files_thumbnail(
     files: &[String],
+    generated_thumbnails: &[(PathBuf, RgbaImage)],
     size: Dimensions,
     fallback_file: &RgbaImage,
     fallback_dir: &RgbaImage,
+    icon_theme: &mut IconTheme,
 ) -> RgbaImage {
     let mut thumbnail = RgbaImage::from_pixel(
         size.width.into(),
@@ -1089,12 +2284,20 @@ fn create_files_thumbnail(
         ];
         let size = Vec2::new((coord[2] - coord[0]).into(), (coord[3] - coord[1]).into());
 
-        let file_thumb = get_file_thumbnail(file, size, is_dir).unwrap_or_else(|e| {
-            error!("failed to get file thumbnail for {file}: {e}");
+        let generated_thumb = generated_thumbnails
+            .iter()
+            .find(|(path, _)| path == Path::new(file))
+            .map(|(_, thumb)| thumb);
+        let file_thumb = if generated_thumb.is_some() {
             None
-        });
+        } else {
+            get_file_thumbnail(file, size, is_dir, icon_theme).unwrap_or_else(|e| {
+                error!("failed to get file thumbnail for {file}: {e}");
+                None
+            })
+        };
         let fallback = if is_dir { fallback_dir } else { fallback_file };
-        let file_thumb = file_thumb.as_ref().unwrap_or(fallback);
+        let file_thumb = generated_thumb.or(file_thumb.as_ref()).unwrap_or(fallback);
         let scaled_file_thumb = create_thumbnail(file_thumb, size);
         image::imageops::overlay(
             &mut thumbnail,
@@ -1111,45 +2314,35 @@ fn get_file_thumbnail<P: AsRef<Path>>(
     file: P,
     size_hint: Vec2,
     is_dir: bool,
+    icon_theme: &mut IconTheme,
 ) -> Result<Option<RgbaImage>> {
-    let thumb_path = if is_dir {
-        freedesktop_icon::get_icon("folder")
-    } else {
-        get_cached_thumbnail(&file)
-            .unwrap_or_else(|e| {
-                warn!(
-                    "failed to get cached thumbnail for {:?}: {e}",
-                    file.as_ref()
-                );
-                None
-            })
-            .or_else(|| {
-                get_file_icon_path(&file).unwrap_or_else(|e| {
-                    warn!("failed to get icon for {:?}: {e}", file.as_ref());
-                    None
-                })
-            })
-    };
-    let Some(path) = thumb_path else {
-        return Ok(None);
-    };
+    let icon_size = size_hint.max_elem().round() as u32;
+    if is_dir {
+        return icon_theme.load_icon("folder", icon_size);
+    }
 
-    if let Some(ext) = path.extension()
-        && (ext == "png" || ext == "svg")
-    {
-        let data = fs::read(&path)?;
-        if ext == "png" {
-            Ok(Some(image::load_from_memory(&data)?.to_rgba8()))
-        } else {
-            Ok(Some(load_svg(&data, size_hint)?.0))
+    let cached_thumbnail = get_cached_thumbnail(&file).unwrap_or_else(|e| {
+        warn!(
+            "failed to get cached thumbnail for {:?}: {e}",
+            file.as_ref()
+        );
+        None
+    });
+    if let Some(path) = cached_thumbnail {
+        return Ok(Some(image::load_from_memory(&fs::read(&path)?)?.to_rgba8()));
+    }
+
+    for icon_name in get_file_icon_names(&file)? {
+        if let Some(icon) = icon_theme.load_icon(&icon_name, icon_size)? {
+            return Ok(Some(icon));
         }
-    } else {
-        warn!("unsupported thumbnail file type, expected png or svg: {path:?}");
-        Ok(None)
     }
+
+    debug!("icon for {:?} not found", file.as_ref());
+    Ok(None)
 }
 
-fn get_file_icon_path<P: AsRef<Path>>(file: P) -> Result<Option<PathBuf>> {
+fn get_file_icon_names<P: AsRef<Path>>(file: P) -> Result<Vec<String>> {
     static SMI: LazyLock<SharedMimeInfo> = LazyLock::new(SharedMimeInfo::new);
 
     let file_data = fs::read(&file)
@@ -1185,18 +2378,97 @@ fn get_file_icon_path<P: AsRef<Path>>(file: P) -> Result<Option<PathBuf>> {
         mime::Mime::from_str("application/x-generic")?
     };
 
-    for icon_name in SMI.lookup_icon_names(&mime) {
-        if let Some(icon) = freedesktop_icon::get_icon(&icon_name) {
-            return Ok(Some(icon));
+    Ok(SMI.lookup_icon_names(&mime))
+}
+
+fn load_source_icon(
+    ctx: &egui::Context,
+    wm_class: &str,
+    size: f32,
+    icon_theme: &mut IconTheme,
+    desktop_entries: &mut Option<DesktopEntries>,
+    source_icons: &mut HashMap<String, Option<TextureHandle>>,
+) -> Option<TextureHandle> {
+    if let Some(texture) = source_icons.get(wm_class) {
+        return texture.clone();
+    }
+
+    let icon_name = desktop_entries
+        .get_or_insert_with(DesktopEntries::load)
+        .icon(wm_class);
+    let pixel_size = (size * ctx.pixels_per_point()).round().max(1.0);
+    let texture = match icon_theme.load_icon(&icon_name, pixel_size as u32) {
+        Ok(Some(icon)) => {
+            let icon = create_thumbnail(&icon, Vec2::splat(pixel_size));
+            let image = ColorImage::from_rgba_unmultiplied(
+                [icon.width() as usize, icon.height() as usize],
+                icon.as_flat_samples().as_slice(),
+            );
+            Some(ctx.load_texture(
+                format!("source_icon_{wm_class}"),
+                image,
+                TextureOptions::LINEAR,
+            ))
+        }
+        Ok(None) => {
+            debug!("no icon '{icon_name}' found for application {wm_class}");
+            None
         }
+        Err(err) => {
+            warn!("failed to load icon '{icon_name}' of application {wm_class}: {err}");
+            None
+        }
+    };
+    source_icons.insert(wm_class.to_string(), texture.clone());
+    texture
+}
+
+// Requests the favicon on first use, the button is rebuilt with it once fetched
+fn load_favicon(
+    ctx: &egui::Context,
+    item_id: u64,
+    domain: &str,
+    size: f32,
+    fetcher: &Option<FaviconFetcher>,
+    favicons: &mut Favicons,
+) -> Option<TextureHandle> {
+    if let Some(texture) = favicons.textures.get(domain) {
+        return Some(texture.clone());
     }
 
-    debug!(
-        "icon for {:?} with mime '{}' not found",
-        file.as_ref(),
-        mime
-    );
-    Ok(None)
+    match favicons.images.get(domain) {
+        Some(Some(icon)) => {
+            let pixel_size = (size * ctx.pixels_per_point()).round().max(1.0);
+            let icon = create_thumbnail(icon, Vec2::splat(pixel_size));
+            let image = ColorImage::from_rgba_unmultiplied(
+                [icon.width() as usize, icon.height() as usize],
+                icon.as_flat_samples().as_slice(),
+            );
+            let texture =
+                ctx.load_texture(format!("favicon_{domain}"), image, TextureOptions::LINEAR);
+            favicons
+                .textures
+                .insert(domain.to_string(), texture.clone());
+            Some(texture)
+        }
+        Some(None) => None,
+        None => {
+            // Only host names and IP literals go into the request URL
+            let fetcher = fetcher.as_ref().filter(|_| {
+                domain
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b"-.:[]".contains(&b))
+            })?;
+            let pending = favicons.pending.entry(domain.to_string()).or_default();
+            if pending.is_empty() {
+                fetcher.request(domain.to_string());
+            }
+            if !pending.contains(&item_id) {
+                pending.push(item_id);
+            }
+            None
+        }
+    }
 }
 
 fn create_thumbnail(image: &RgbaImage, size: Vec2) -> RgbaImage {
@@ -1223,12 +2495,52 @@ fn create_thumbnail(image: &RgbaImage, size: Vec2) -> RgbaImage {
     thumbnail
 }
 
-fn load_texture(ctx: &egui::Context, id: u64, img: &RgbaImage) -> TextureHandle {
-    let thumb_size = [img.width() as usize, img.height() as usize];
+// Appends the fallback fonts of the scripts `text` is the first to have to the font families
+fn load_font_fallbacks(
+    ctx: &egui::Context,
+    fonts: &mut FontDefinitions,
+    pending: &mut Vec<(Script, String)>,
+    text: &str,
+) -> Result<()> {
+    let scripts = pending
+        .iter()
+        .map(|(script, _)| *script)
+        .collect::<Vec<_>>();
+    let found = scripts_in(text, &scripts);
+    if found.is_empty() {
+        return Ok(());
+    }
+
+    for (script, family) in pending.extract_if(.., |(script, _)| found.contains(script)) {
+        // Not looked for again either way, as items with the script keep coming
+        let Some(font_path) = Ui::find_font(&family)? else {
+            warn!("fallback font family '{family}' for {script:?} not found");
+            continue;
+        };
+        info!("loading fallback font family '{family}' for {script:?} from {font_path:?}");
+        let font_data = match fs::read(&font_path) {
+            Ok(font_data) => font_data,
+            Err(e) => {
+                warn!("failed to read font file {font_path:?}: {e}");
+                continue;
+            }
+        };
+        fonts
+            .font_data
+            .insert(family.clone(), Arc::new(FontData::from_owned(font_data)));
+        for names in fonts.families.values_mut() {
+            names.push(family.clone());
+        }
+    }
+    ctx.set_fonts(fonts.clone());
+    Ok(())
+}
+
+fn load_color_preview_background_texture(ctx: &egui::Context) -> TextureHandle {
     ctx.load_texture(
-        id.to_string(),
-        egui::ColorImage::from_rgba_unmultiplied(thumb_size, img.as_flat_samples().as_slice()),
-        Default::default(),
+        "color_preview_background",
+        make_alpha_checkerboard_image(6),
+        TextureOptions::NEAREST,
     )
 }
 
@@ -1294,77 +2606,6 @@ fn make_alpha_checkerboard_image(cell_count_per_line: usize) -> ColorImage {
     ColorImage::new([size, size], pixels)
 }
 
-fn build_display_text(s: &str, theme: &ThemeConfig) -> Vec<RichText> {
-    let mut text = vec![];
-    let mut chars = s.chars();
-
-    let mut last_non_whitespace = None;
-    let mut trailing_whitespace_str = String::new();
-    let mut trailing_count = 0;
-    while let Some(c) = chars.next_back() {
-        trailing_count += 1;
-        if c == ' ' {
-            trailing_whitespace_str.push('·');
-        } else {
-            last_non_whitespace = Some(c);
-            break;
-        }
-    }
-
-    let mut str = String::with_capacity(s.len());
-    let mut chars = chars.enumerate().peekable();
-    let mut is_leading_whitespace = true;
-    let mut i_c = chars.next();
-    while let Some((i, c)) = i_c {
-        // Very very long string causes egui to choke on first render, even when we only display it
-        // on a single line
-        if i == (10_000 - trailing_count) && chars.peek().is_some() {
-            if is_leading_whitespace {
-                text.push(RichText::new(str).color(theme.muted_foreground));
-            }
-            text.push("…".into());
-            return text;
-        }
-
-        if c != ' ' && is_leading_whitespace {
-            is_leading_whitespace = false;
-            let prev_str = mem::take(&mut str);
-            text.push(RichText::new(prev_str).color(theme.muted_foreground));
-        }
-
-        match c {
-            '\r' => {}
-            '\n' => {
-                let prev_str = mem::take(&mut str);
-                text.push(prev_str.into());
-                text.push(RichText::new('↵').color(theme.muted_foreground));
-            }
-            '\t' => {
-                let prev_str = mem::take(&mut str);
-                text.push(prev_str.into());
-                text.push(RichText::new(" ⇥ ").color(theme.muted_foreground));
-            }
-            ' ' if is_leading_whitespace => {
-                str.push('·');
-            }
-            _ => str.push(c),
-        }
-
-        i_c = chars.next();
-        if i_c.is_none()
-            && let Some(last_c) = last_non_whitespace
-        {
-            i_c = Some((i + 1, last_c));
-            last_non_whitespace = None;
-        }
-    }
-
-    text.push(str.into());
-    text.push(RichText::new(trailing_whitespace_str).color(theme.muted_foreground));
-
-    text
-}
-
 fn format_path_str(path: &str) -> String {
     let home = dirs::home_dir();
     let mut s = String::with_capacity(path.len());
@@ -121,6 +121,7 @@ pub fn plaintext_mime_score(mime: &str) -> Option<usize> {
     // low to high
     const TEXT_MIMES_ORDER: &[&str] = &[
         "",
+        "compound_text",
         "text/plain;charset=us-ascii",
         "text/plain;charset=unicode",
         "text",
@@ -140,12 +141,23 @@ pub fn is_plaintext_mime(mime: &str) -> bool {
 }
 
 pub fn image_mime_score(mime: &str) -> usize {
-    // low to high
-    const IMAGE_MIMES_ORDER: &[&str] = &["image/jpeg", "image/png", "image/gif", "image/svg+xml"];
+    // low to high, formats that can hold more than 8 bits per channel are only preferred over
+    // PNG when built with their decoder, so the item still gets a preview
+    const IMAGE_MIMES_ORDER: &[(&str, bool)] = &[
+        ("image/jpeg", true),
+        ("image/png", true),
+        ("image/heic", cfg!(feature = "heif")),
+        ("image/heif", cfg!(feature = "heif")),
+        ("image/avif", cfg!(feature = "avif")),
+        ("image/tiff", cfg!(feature = "tiff")),
+        ("image/gif", true),
+        ("image/svg+xml", true),
+    ];
 
     IMAGE_MIMES_ORDER
         .iter()
-        .position(|b| mime.eq_ignore_ascii_case(b))
+        .filter(|(_, decodable)| *decodable)
+        .position(|(b, _)| mime.eq_ignore_ascii_case(b))
         .map(|pos| pos + 1)
         .unwrap_or(0)
 }
@@ -154,71 +166,11 @@ pub fn is_image_mime(mime: &str) -> bool {
     mime.starts_with("image/")
 }
 
-pub fn utf16le_to_string(bytes: &[u8]) -> String {
-    assert!(bytes.len() % 2 == 0);
-    let u16_slice: &[u16] =
-        unsafe { std::slice::from_raw_parts(bytes.as_ptr() as *const u16, bytes.len() / 2) };
-    String::from_utf16_lossy(u16_slice)
-}
-
-pub fn percent_decode(input: &[u8]) -> Vec<u8> {
-    let mut out = Vec::with_capacity(input.len());
-    let mut bytes = input.iter();
-    while let Some(&b) = bytes.next() {
-        if b == b'%' {
-            let x = bytes.next();
-            let y = bytes.next();
-            if let (Some(&x), Some(&y)) = (x, y)
-                && let Ok(v) = u8::from_str_radix(str::from_utf8(&[x, y]).unwrap(), 16)
-            {
-                out.push(v);
-                continue;
-            }
-
-            out.push(b'%');
-            if let Some(&x) = x {
-                out.push(x);
-            }
-            if let Some(&y) = y {
-                out.push(y);
-            }
-        } else {
-            out.push(b);
-        }
-    }
-    out
-}
-
-pub fn percent_encode(input: &[u8]) -> Vec<u8> {
-    let mut out = Vec::with_capacity(input.len());
-    for b in input {
-        match b {
-            b'A'..=b'Z'
-            | b'a'..=b'z'
-            | b'0'..=b'9'
-            | b'!'
-            | b'$'
-            | b'&'
-            | b'\''
-            | b'('
-            | b')'
-            | b'*'
-            | b'+'
-            | b','
-            | b'-'
-            | b'.'
-            | b':'
-            | b'='
-            | b'@'
-            | b'_'
-            | b'~' => out.push(*b),
-            _ => {
-                out.push(b'%');
-                out.append(&mut format!("{:02X}", b).into_bytes());
-            }
-        }
-    }
-    out
+/// Whether the text, trimmed, is a single http(s) URL
+pub fn is_url(text: &str) -> bool {
+    let text = text.trim();
+    (text.starts_with("http://") || text.starts_with("https://"))
+        && !text.contains(char::is_whitespace)
 }
 
 pub fn to_hex_string(bytes: &[u8]) -> String {
@@ -0,0 +1,132 @@
+use std::{collections::HashSet, mem};
+
+use anyhow::Result;
+use log::debug;
+
+use crate::{
+    classification::{ItemKind, classify},
+    config::Config,
+    ordered_hash_map::OrderedHashMap,
+    providers::{self, Provider, ProviderContext},
+    selection::{SelectionItem, SelectionMetadata},
+};
+
+/// Items offered by providers, shown in the list without being part of the history. They sit
+/// right below the pinned items and are only kept once pasted
+pub struct VirtualItems {
+    providers: Vec<Box<dyn Provider>>,
+    items: OrderedHashMap<u64, SelectionItem>,
+}
+
+impl VirtualItems {
+    pub fn new(config: &Config) -> Self {
+        VirtualItems {
+            providers: providers::from_config(config),
+            items: OrderedHashMap::new(),
+        }
+    }
+
+    /// Asks the providers again, based on the search query and the newest history item. Returns
+    /// the previous items if they changed, so their widgets can be replaced
+    pub fn refresh(
+        &mut self,
+        selection_items: &OrderedHashMap<u64, SelectionItem>,
+        selection_metadata: &SelectionMetadata,
+        query: &str,
+    ) -> Result<Option<Vec<SelectionItem>>> {
+        let newest_kind = selection_items
+            .get_by_index(selection_metadata.pinned_count)
+            .map(|(_, newest)| classify(newest));
+        let newest_text = newest_kind.as_ref().and_then(ItemKind::text);
+        let context = ProviderContext {
+            query,
+            text: newest_text.as_deref(),
+        };
+
+        let mut items = OrderedHashMap::new();
+        for provider in &mut self.providers {
+            for provided in provider.provide(&context) {
+                let description = match provided.detail {
+                    Some(detail) => format!("{} · {detail}", provider.name()),
+                    None => provider.name().to_string(),
+                };
+                let item = SelectionItem::new_virtual(&description, provided.text)?;
+                items.push_back(item.id, item);
+            }
+        }
+
+        let unchanged = items.len() == self.items.len()
+            && items.iter().all(|(id, _)| self.items.contains_key(id));
+        if unchanged {
+            return Ok(None);
+        }
+
+        debug!("virtual items changed, {} items", items.len());
+        let previous = mem::replace(&mut self.items, items);
+        Ok(Some(previous.into_iter().map(|(_, item)| item).collect()))
+    }
+
+    pub fn get(&self, item_id: u64) -> Option<&SelectionItem> {
+        self.items.get(&item_id)
+    }
+
+    pub fn contains(&self, item_id: u64) -> bool {
+        self.items.contains_key(&item_id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &SelectionItem> {
+        self.items.iter().map(|(_, item)| item)
+    }
+
+    /// The list as shown: pinned items, then the virtual items, then the rest of the history.
+    /// With search matches, only those are shown in their order, pinned ones still first. Returns
+    /// the list and how many pinned items it starts with
+    pub fn layered<'a>(
+        &'a self,
+        selection_items: &'a OrderedHashMap<u64, SelectionItem>,
+        selection_metadata: &SelectionMetadata,
+        matches: Option<&[u64]>,
+    ) -> (OrderedHashMap<u64, &'a SelectionItem>, usize) {
+        let pinned_count = selection_metadata.pinned_count;
+        let mut layered = OrderedHashMap::new();
+
+        let Some(matches) = matches else {
+            for (&id, item) in selection_items
+                .iter()
+                .take(pinned_count)
+                .chain(&self.items)
+                .chain(selection_items.iter().skip(pinned_count))
+            {
+                layered.push_back(id, item);
+            }
+            return (layered, pinned_count);
+        };
+
+        let pinned_ids = selection_items
+            .iter()
+            .take(pinned_count)
+            .map(|(&id, _)| id)
+            .collect::<HashSet<_>>();
+        let (pinned_matches, unpinned_matches): (Vec<_>, Vec<_>) = matches
+            .iter()
+            .copied()
+            .partition(|id| pinned_ids.contains(id));
+        let shown_pinned_count = pinned_matches.len();
+
+        for (id, item) in history_items(selection_items, pinned_matches)
+            .chain(self.items.iter().map(|(&id, item)| (id, item)))
+            .chain(history_items(selection_items, unpinned_matches))
+        {
+            layered.push_back(id, item);
+        }
+        (layered, shown_pinned_count)
+    }
+}
+
+fn history_items(
+    selection_items: &OrderedHashMap<u64, SelectionItem>,
+    ids: Vec<u64>,
+) -> impl Iterator<Item = (u64, &SelectionItem)> {
+    ids.into_iter()
+        .filter_map(move |id| selection_items.get(&id).map(|item| (id, item)))
+}
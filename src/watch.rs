@@ -0,0 +1,239 @@
+use std::{io::Write as _, os::unix::net::UnixStream};
+
+use anyhow::Result;
+use log::{debug, info, warn};
+use serde::Serialize;
+
+use crate::{
+    archive::ArchivedItem,
+    classification::{self, classify, file_path},
+    ipc::format_item_id,
+    ordered_hash_map::OrderedHashMap,
+    selection::{CAPTURE_FAILED_MIME, SelectionItem},
+    text_details,
+};
+
+const MAX_PREVIEW_CHARS: usize = 100;
+const REDACTED_PREVIEW: &str = "••••••••";
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum EventKind {
+    /// The newest item at the time the watcher connected
+    Latest,
+    NewItem,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum ItemKind {
+    Text,
+    Image,
+    Files,
+    Other,
+}
+
+#[derive(Serialize)]
+struct ItemEvent {
+    event: EventKind,
+    id: String,
+    kind: ItemKind,
+    preview: String,
+    redacted: bool,
+}
+
+/// An item as `ClientCommand::List` replies with it
+#[derive(Serialize)]
+struct ListedItem {
+    id: String,
+    kind: ItemKind,
+    preview: String,
+    redacted: bool,
+    pinned: bool,
+}
+
+/// An item as `ClientCommand::ListArchived` replies with it
+#[derive(Serialize)]
+struct ListedArchivedItem {
+    id: String,
+    kind: ItemKind,
+    preview: String,
+    redacted: bool,
+    /// Seconds since the Unix epoch
+    archived_at: u64,
+}
+
+/// Clients of `memoni watch`, sent a JSON line whenever another item becomes the newest one, for
+/// status bars to show
+#[derive(Default)]
+pub struct Watchers {
+    streams: Vec<UnixStream>,
+    latest_id: Option<u64>,
+}
+
+impl Watchers {
+    pub fn new() -> Self {
+        Watchers {
+            streams: vec![],
+            latest_id: None,
+        }
+    }
+
+    pub fn add(&mut self, stream: UnixStream, latest_item: Option<&SelectionItem>) -> Result<()> {
+        // A watcher that stops reading must not block the main loop
+        stream.set_nonblocking(true)?;
+        self.streams.push(stream);
+        info!("watcher added, {} watching", self.streams.len());
+
+        if let Some(item) = latest_item {
+            let line = event_line(EventKind::Latest, item)?;
+            let index = self.streams.len() - 1;
+            if let Err(e) = self.streams[index].write_all(line.as_bytes()) {
+                warn!("failed to send latest item to watcher: {e}");
+                self.streams.remove(index);
+            }
+        }
+        Ok(())
+    }
+
+    /// Notifies the watchers if the newest item isn't the one they were last told about
+    pub fn update(&mut self, latest_item: Option<&SelectionItem>) -> Result<()> {
+        let latest_id = latest_item.map(|item| item.id);
+        if latest_id == self.latest_id {
+            return Ok(());
+        }
+        self.latest_id = latest_id;
+
+        let Some(item) = latest_item else {
+            return Ok(());
+        };
+        if self.streams.is_empty() || item.data.contains_key(CAPTURE_FAILED_MIME) {
+            return Ok(());
+        }
+
+        let line = event_line(EventKind::NewItem, item)?;
+        self.streams
+            .retain_mut(|stream| match stream.write_all(line.as_bytes()) {
+                Ok(()) => true,
+                Err(e) => {
+                    // Also the case for watchers too far behind to take a line, they can reconnect
+                    debug!("dropping watcher: {e}");
+                    false
+                }
+            });
+        debug!("item {} sent to {} watchers", item.id, self.streams.len());
+        Ok(())
+    }
+}
+
+/// JSON lines of the items in history order, for clients browsing the history
+pub fn item_list(
+    items: &OrderedHashMap<u64, SelectionItem>,
+    pinned_count: usize,
+) -> Result<String> {
+    let mut lines = String::new();
+    for (idx, (_, item)) in items.iter().enumerate() {
+        if item.data.contains_key(CAPTURE_FAILED_MIME) {
+            continue;
+        }
+        let (kind, preview, redacted) = describe(item);
+        lines.push_str(&serde_json::to_string(&ListedItem {
+            id: format_item_id(item.id),
+            kind,
+            preview,
+            redacted,
+            pinned: idx < pinned_count,
+        })?);
+        lines.push('\n');
+    }
+    Ok(lines)
+}
+
+/// JSON lines of archived items, in the order given
+pub fn archived_item_list(items: &[ArchivedItem]) -> Result<String> {
+    let mut lines = String::new();
+    for record in items {
+        let (kind, preview, redacted) = describe(&record.item);
+        lines.push_str(&serde_json::to_string(&ListedArchivedItem {
+            id: format_item_id(record.item.id),
+            kind,
+            preview,
+            redacted,
+            archived_at: record.archived_at,
+        })?);
+        lines.push('\n');
+    }
+    Ok(lines)
+}
+
+fn event_line(event: EventKind, item: &SelectionItem) -> Result<String> {
+    let (kind, preview, redacted) = describe(item);
+    let mut line = serde_json::to_string(&ItemEvent {
+        event,
+        id: format_item_id(item.id),
+        kind,
+        preview,
+        redacted,
+    })?;
+    line.push('\n');
+    Ok(line)
+}
+
+fn describe(item: &SelectionItem) -> (ItemKind, String, bool) {
+    let kind = classify(item);
+    match &kind {
+        classification::ItemKind::Files { uris, .. } => {
+            let files = uris
+                .iter()
+                .map(|&uri| {
+                    let path = file_path(uri);
+                    path.rsplit('/').next().unwrap_or_default().to_string()
+                })
+                .collect::<Vec<_>>();
+            let preview = match files.as_slice() {
+                [] => String::new(),
+                [file] => file.clone(),
+                [file, rest @ ..] => format!("{file} (+{} more)", rest.len()),
+            };
+            (ItemKind::Files, preview, false)
+        }
+        classification::ItemKind::Image { mime, .. } => {
+            let format = mime.trim_start_matches("image/").to_uppercase();
+            (ItemKind::Image, format!("Image ({format})"), false)
+        }
+        // Links paste as their URL, so they're text to clients
+        _ => match kind.text() {
+            Some(text) if text_details::is_likely_secret(&text) => {
+                (ItemKind::Text, REDACTED_PREVIEW.to_string(), true)
+            }
+            Some(text) => (ItemKind::Text, single_line_preview(&text), false),
+            None => {
+                let mime = item.data.keys().next().cloned().unwrap_or_default();
+                (ItemKind::Other, mime, false)
+            }
+        },
+    }
+}
+
+// Status bars show a single line, so whitespace runs are collapsed and long texts cut short
+fn single_line_preview(text: &str) -> String {
+    let mut preview = String::new();
+    let mut chars = 0;
+    for word in text.split_whitespace() {
+        if chars > 0 {
+            preview.push(' ');
+            chars += 1;
+        }
+        preview.push_str(word);
+        chars += word.chars().count();
+        if chars > MAX_PREVIEW_CHARS {
+            break;
+        }
+    }
+
+    if let Some((end, _)) = preview.char_indices().nth(MAX_PREVIEW_CHARS) {
+        preview.truncate(end);
+        preview.push('…');
+    }
+    preview
+}
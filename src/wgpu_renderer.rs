@@ -0,0 +1,286 @@
+use std::{
+    num::NonZero,
+    ptr::NonNull,
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use egui::Color32;
+use egui_wgpu::{RendererOptions, ScreenDescriptor};
+use log::{info, trace, warn};
+use raw_window_handle::{RawDisplayHandle, RawWindowHandle, XcbDisplayHandle, XcbWindowHandle};
+
+use crate::{
+    config::Config,
+    damage::{Damage, DamageTracker},
+    renderer::{RenderTimings, Renderer},
+    x11_window::X11Window,
+};
+
+// Pacing of frames that are skipped, as there's no present to wait for vsync on
+const FRAME_INTERVAL: Duration = Duration::from_micros(16_667);
+
+/// Draws through wgpu on Vulkan, or on GLES where there's no usable Vulkan driver. egui's wgpu
+/// shader blends in gamma space and converts colors itself, so the surface uses a non-sRGB format
+pub struct WgpuRenderer<'a> {
+    pub dimensions: [u32; 2],
+    window: &'a X11Window<'a>,
+    instance: wgpu::Instance,
+    adapter: wgpu::Adapter,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    surface: wgpu::Surface<'static>,
+    surface_config: wgpu::SurfaceConfiguration,
+    painter: egui_wgpu::Renderer,
+    background: wgpu::Color,
+    damage_tracker: DamageTracker,
+    last_presented_at: Option<Instant>,
+}
+
+impl<'a> WgpuRenderer<'a> {
+    pub fn new(window: &'a X11Window, config: &Config) -> Result<Self> {
+        info!("creating wgpu instance");
+        let dimensions = [
+            config.layout.window_dimensions.width as u32,
+            config.layout.window_dimensions.height as u32,
+        ];
+
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::VULKAN | wgpu::Backends::GL,
+            ..Default::default()
+        });
+        let surface = Self::create_surface(&instance, window)?;
+
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::LowPower,
+            force_fallback_adapter: false,
+            compatible_surface: Some(&surface),
+        }))?;
+        info!(
+            "using wgpu adapter: {}",
+            egui_wgpu::adapter_info_summary(&adapter.get_info())
+        );
+
+        let (device, queue) =
+            pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+                label: Some("memoni"),
+                required_limits:
+                    wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits()),
+                ..Default::default()
+            }))?;
+
+        let capabilities = surface.get_capabilities(&adapter);
+        let format = egui_wgpu::preferred_framebuffer_format(&capabilities.formats)?;
+        // egui's colors are premultiplied, so transparent backgrounds blend right with it
+        let alpha_mode = [
+            wgpu::CompositeAlphaMode::PreMultiplied,
+            wgpu::CompositeAlphaMode::Inherit,
+        ]
+        .into_iter()
+        .find(|mode| capabilities.alpha_modes.contains(mode))
+        .unwrap_or(capabilities.alpha_modes[0]);
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: dimensions[0],
+            height: dimensions[1],
+            present_mode: wgpu::PresentMode::AutoVsync,
+            desired_maximum_frame_latency: 2,
+            alpha_mode,
+            view_formats: vec![],
+        };
+        surface.configure(&device, &surface_config);
+
+        info!("creating egui_wgpu renderer");
+        let painter = egui_wgpu::Renderer::new(&device, format, RendererOptions::default());
+
+        Ok(WgpuRenderer {
+            dimensions,
+            window,
+            instance,
+            adapter,
+            device,
+            queue,
+            surface,
+            surface_config,
+            painter,
+            background: wgpu_color(config.theme.background.into()),
+            damage_tracker: DamageTracker::new(),
+            last_presented_at: None,
+        })
+    }
+
+    fn create_surface(
+        instance: &wgpu::Instance,
+        window: &X11Window,
+    ) -> Result<wgpu::Surface<'static>> {
+        let raw_display_handle = RawDisplayHandle::Xcb(XcbDisplayHandle::new(
+            NonNull::new(window.conn.get_raw_xcb_connection()),
+            window.screen_num as _,
+        ));
+        let raw_window_handle = RawWindowHandle::Xcb(XcbWindowHandle::new(
+            NonZero::new(window.win_id.get()).context("main window has no id")?,
+        ));
+
+        // The connection outlives the renderer, and the surface is replaced along with the window
+        Ok(unsafe {
+            instance.create_surface_unsafe(wgpu::SurfaceTargetUnsafe::RawHandle {
+                raw_display_handle,
+                raw_window_handle,
+            })?
+        })
+    }
+
+    /// Reconfigures the surface after it got lost or outdated, e.g. by the window being remapped
+    fn reconfigure(&mut self) {
+        self.surface.configure(&self.device, &self.surface_config);
+        self.damage_tracker.reset();
+    }
+}
+
+impl Renderer for WgpuRenderer<'_> {
+    fn recreate(&mut self) -> Result<()> {
+        info!("recreating wgpu surface");
+
+        let surface = Self::create_surface(&self.instance, self.window)?;
+        if !self.adapter.is_surface_supported(&surface) {
+            warn!("wgpu adapter can't present to the recreated window");
+        }
+        self.surface = surface;
+        self.reconfigure();
+
+        Ok(())
+    }
+
+    fn render(
+        &mut self,
+        egui_ctx: &egui::Context,
+        full_output: egui::FullOutput,
+    ) -> Result<RenderTimings> {
+        trace!("rendering frame");
+        let egui::FullOutput {
+            platform_output: _,
+            textures_delta,
+            shapes,
+            pixels_per_point,
+            viewport_output: _,
+        } = full_output;
+
+        let started_at = Instant::now();
+        let clipped_primitives = egui_ctx.tessellate(shapes, pixels_per_point);
+        let tessellated_at = Instant::now();
+
+        // Presented frames may land in any of the swapchain's images, so anything that changed
+        // is painted in full
+        let textures_changed = !textures_delta.set.is_empty() || !textures_delta.free.is_empty();
+        let damage = self
+            .damage_tracker
+            .frame_damage(&clipped_primitives, 0, textures_changed);
+        if damage == Damage::None {
+            trace!("frame unchanged, skipping render");
+            if let Some(last_presented_at) = self.last_presented_at {
+                thread::sleep(FRAME_INTERVAL.saturating_sub(last_presented_at.elapsed()));
+            }
+            self.last_presented_at = Some(Instant::now());
+            return Ok(RenderTimings {
+                tessellate: tessellated_at - started_at,
+                paint: Duration::ZERO,
+                swap: Duration::ZERO,
+            });
+        }
+
+        let surface_texture = match self.surface.get_current_texture() {
+            Ok(surface_texture) => surface_texture,
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                trace!("surface lost or outdated, reconfiguring");
+                self.reconfigure();
+                self.surface.get_current_texture()?
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let view = surface_texture
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        for (id, image_delta) in &textures_delta.set {
+            self.painter
+                .update_texture(&self.device, &self.queue, *id, image_delta);
+        }
+
+        let screen_descriptor = ScreenDescriptor {
+            size_in_pixels: self.dimensions,
+            pixels_per_point,
+        };
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("memoni frame"),
+            });
+        let callback_buffers = self.painter.update_buffers(
+            &self.device,
+            &self.queue,
+            &mut encoder,
+            &clipped_primitives,
+            &screen_descriptor,
+        );
+        {
+            let mut render_pass = encoder
+                .begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("memoni frame"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &view,
+                        depth_slice: None,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(self.background),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    ..Default::default()
+                })
+                .forget_lifetime();
+            self.painter
+                .render(&mut render_pass, &clipped_primitives, &screen_descriptor);
+        }
+        self.queue
+            .submit(callback_buffers.into_iter().chain([encoder.finish()]));
+
+        for id in &textures_delta.free {
+            self.painter.free_texture(id);
+        }
+        let painted_at = Instant::now();
+
+        surface_texture.present();
+        self.last_presented_at = Some(Instant::now());
+        trace!("frame rendered");
+
+        Ok(RenderTimings {
+            tessellate: tessellated_at - started_at,
+            paint: painted_at - tessellated_at,
+            swap: painted_at.elapsed(),
+        })
+    }
+
+    fn invalidate(&mut self) {
+        self.damage_tracker.reset();
+    }
+
+    fn set_background(&mut self, color: Color32) {
+        self.background = wgpu_color(color);
+        self.damage_tracker.reset();
+    }
+
+    fn destroy(&mut self) {
+        info!("destroying wgpu renderer");
+        if let Err(err) = self.device.poll(wgpu::PollType::wait_indefinitely()) {
+            warn!("failed to wait for the GPU to finish: {err}");
+        }
+    }
+}
+
+// The surface isn't sRGB, so the clear color is passed through as is, like egui's own colors
+fn wgpu_color(color: Color32) -> wgpu::Color {
+    let [r, g, b, a] = color.to_array().map(|c| c as f64 / 255.0);
+    wgpu::Color { r, g, b, a }
+}
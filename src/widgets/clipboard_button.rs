@@ -2,16 +2,26 @@ use std::sync::Arc;
 
 use egui::{
     Color32, CornerRadius, FontSelection, Galley, Image, Pos2, Rect, Response, RichText, Sense,
-    Stroke, StrokeKind, TextStyle, TextWrapMode, TextureHandle, Ui, Vec2, Widget,
-    WidgetText, text::LayoutJob,
+    Stroke, StrokeKind, TextStyle, TextWrapMode, TextureHandle, Ui, Vec2, Widget, WidgetText,
+    emath::GuiRounding as _,
+    text::{LayoutJob, LayoutSection, TextWrapping},
 };
+use unicode_segmentation::UnicodeSegmentation as _;
+
+use crate::texture_cache::PreviewTexture;
+
+const ELLIPSIS: &str = "…";
+/// Where a button keeps how far its label can scroll sideways and how wide it shows, as
+/// `(max_offset, visible_width)` in the temporary data of its widget id
+pub const LABEL_SCROLL_ID: &str = "label_scroll";
 
 #[derive(Default, Clone)]
 pub struct ClipboardButton {
     labels: Vec<Vec<RichText>>,
     sublabel: Option<WidgetText>,
     secondary_foreground: Option<Color32>,
-    preview: Option<(TextureHandle, Vec2)>,
+    // Keeps its size after the texture got unloaded, so the layout stays the same
+    preview: Option<(Option<PreviewTexture>, Vec2)>,
     preview_source: Option<String>,
     preview_background: Color32,
     is_active: bool,
@@ -21,10 +31,20 @@ pub struct ClipboardButton {
     pin_size: f32,
     pin_color: Color32,
     keyboard_hint: Option<String>,
+    expiry_badge: Option<String>,
     color_preview: Option<Color32>,
     color_preview_size: f32,
     color_preview_corner_radius: u8,
     color_preview_background: Option<TextureHandle>,
+    source_icon: Option<TextureHandle>,
+    source_icon_size: f32,
+    leading_icon: Option<TextureHandle>,
+    middle_ellipsis: bool,
+    wrap_labels: bool,
+    max_label_rows: usize,
+    tile: bool,
+    label_scroll: Option<f32>,
+    pixel_snap: bool,
 }
 
 impl ClipboardButton {
@@ -53,11 +73,22 @@ impl ClipboardButton {
     }
 
     #[inline]
-    pub fn preview(mut self, texture: TextureHandle, size: impl Into<Vec2>) -> Self {
-        self.preview = Some((texture, size.into()));
+    pub fn preview(mut self, texture: PreviewTexture, size: impl Into<Vec2>) -> Self {
+        self.preview = Some((Some(texture), size.into()));
         self
     }
 
+    pub fn has_preview(&self) -> bool {
+        self.preview.is_some()
+    }
+
+    /// Drops the preview texture, leaving an empty space of the same size
+    pub fn unload_preview(&mut self) {
+        if let Some((texture, _)) = &mut self.preview {
+            *texture = None;
+        }
+    }
+
     #[inline]
     pub fn preview_source(mut self, preview_source: &str) -> Self {
         self.preview_source = Some(preview_source.to_string());
@@ -112,6 +143,13 @@ impl ClipboardButton {
         self
     }
 
+    /// Time left until the item expires, drawn before the keyboard hint
+    #[inline]
+    pub fn expiry_badge(mut self, expiry_badge: impl Into<String>) -> Self {
+        self.expiry_badge = Some(expiry_badge.into());
+        self
+    }
+
     #[inline]
     pub fn color_preview(mut self, color_preview: impl Into<Color32>) -> Self {
         self.color_preview = Some(color_preview.into());
@@ -135,14 +173,266 @@ impl ClipboardButton {
         self.color_preview_background = Some(color_preview_background);
         self
     }
+
+    /// Icon of the application the item was copied from, drawn at the right end
+    #[inline]
+    pub fn source_icon(mut self, source_icon: TextureHandle) -> Self {
+        self.source_icon = Some(source_icon);
+        self
+    }
+
+    #[inline]
+    pub fn source_icon_size(mut self, source_icon_size: f32) -> Self {
+        self.source_icon_size = source_icon_size;
+        self
+    }
+
+    /// Icon before the labels, e.g., the favicon of a link, sized like the color preview
+    #[inline]
+    pub fn leading_icon(mut self, leading_icon: TextureHandle) -> Self {
+        self.leading_icon = Some(leading_icon);
+        self
+    }
+
+    #[inline]
+    pub fn middle_ellipsis(mut self, middle_ellipsis: bool) -> Self {
+        self.middle_ellipsis = middle_ellipsis;
+        self
+    }
+
+    /// Wraps labels too long for one row instead of truncating them
+    #[inline]
+    pub fn wrap_labels(mut self, wrap_labels: bool) -> Self {
+        self.wrap_labels = wrap_labels;
+        self
+    }
+
+    /// Rows each wrapped label may take, the button grows to fit them
+    #[inline]
+    pub fn max_label_rows(mut self, max_label_rows: usize) -> Self {
+        self.max_label_rows = max_label_rows;
+        self
+    }
+
+    /// Shifts single-row labels left by `label_scroll`, showing their ends instead of truncating
+    /// them. Only the labels of one button at a time are laid out in full for this
+    #[inline]
+    pub fn label_scroll(mut self, label_scroll: f32) -> Self {
+        self.label_scroll = Some(label_scroll);
+        self
+    }
+
+    /// Draws the preview over the first label across the whole width, as a cell of the grid
+    /// layout
+    #[inline]
+    pub fn tile(mut self, tile: bool) -> Self {
+        self.tile = tile;
+        self
+    }
+
+    /// Snaps the button to whole pixels, so its edges and text don't get blurred across two
+    #[inline]
+    pub fn pixel_snap(mut self, pixel_snap: bool) -> Self {
+        self.pixel_snap = pixel_snap;
+        self
+    }
+
+    // What screen readers announce for the button
+    #[cfg(feature = "accessibility")]
+    fn accessible_name(&self) -> String {
+        self.labels
+            .iter()
+            .map(|label| label.iter().map(RichText::text).collect::<String>())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn tile_ui(self, ui: &mut Ui) -> Response {
+        let keyboard_hint_size = 11.0;
+        #[cfg(feature = "accessibility")]
+        let accessible_name = self.accessible_name();
+
+        let padding = ui.style().spacing.button_padding;
+        let width = ui.available_width();
+        let mut text_width = width - padding.x * 2.0;
+        if self.source_icon.is_some() {
+            text_width -= self.source_icon_size + padding.x;
+        }
+        let preview_height = match self.preview {
+            Some((_, size)) if size.x > 0.0 => width * size.y / size.x,
+            _ => 0.0,
+        };
+
+        let label_galley = self.labels.into_iter().next().map(|l| {
+            rich_texts_to_galley(
+                l,
+                ui,
+                1,
+                text_width,
+                TextStyle::Button,
+                self.middle_ellipsis,
+            )
+        });
+        let label_height = label_galley
+            .as_ref()
+            .map(|g| g.size().y + padding.y * 2.0)
+            .unwrap_or(0.0);
+        let keyboard_hint_galley = self.keyboard_hint.map(|sh| {
+            WidgetText::RichText(Arc::new(
+                RichText::new(sh)
+                    .size(keyboard_hint_size)
+                    .color(self.secondary_foreground.unwrap_or(Color32::PLACEHOLDER)),
+            ))
+            .into_galley(ui, Some(TextWrapMode::Truncate), width, TextStyle::Button)
+        });
+        let expiry_badge_galley = self.expiry_badge.map(|badge| {
+            WidgetText::RichText(Arc::new(
+                RichText::new(badge)
+                    .size(keyboard_hint_size)
+                    .color(self.secondary_foreground.unwrap_or(Color32::PLACEHOLDER)),
+            ))
+            .into_galley(ui, Some(TextWrapMode::Truncate), width, TextStyle::Button)
+        });
+
+        let (rect, response) = ui.allocate_exact_size(
+            Vec2::new(width, preview_height + label_height),
+            Sense::CLICK,
+        );
+        let rect = snap_to_pixels(ui, rect, self.pixel_snap);
+        #[cfg(feature = "accessibility")]
+        response.widget_info(|| {
+            egui::WidgetInfo::selected(
+                egui::WidgetType::Button,
+                true,
+                self.is_active,
+                &accessible_name,
+            )
+        });
+
+        if ui.is_rect_visible(rect) {
+            let visuals = &ui.style().visuals.widgets.inactive;
+            let bg_fill = if self.is_active {
+                ui.style().visuals.widgets.active.weak_bg_fill
+            } else {
+                visuals.weak_bg_fill
+            };
+
+            ui.painter().rect(
+                rect,
+                visuals.corner_radius,
+                bg_fill,
+                Stroke::NONE,
+                StrokeKind::Inside,
+            );
+
+            let preview_rect = Rect::from_min_size(rect.min, Vec2::new(width, preview_height));
+            if let Some((texture, _)) = self.preview {
+                let corner_radius = CornerRadius {
+                    nw: visuals.corner_radius.nw,
+                    ne: visuals.corner_radius.ne,
+                    ..Default::default()
+                };
+                if let Some(texture) = texture {
+                    Image::from_texture(texture.sized_texture())
+                        .uv(texture.uv)
+                        .maintain_aspect_ratio(true)
+                        .bg_fill(self.preview_background)
+                        .corner_radius(corner_radius)
+                        .paint_at(ui, preview_rect);
+                } else {
+                    ui.painter()
+                        .rect_filled(preview_rect, corner_radius, self.preview_background);
+                }
+            }
+
+            if let Some(galley) = label_galley {
+                let text_pos = Pos2::new(rect.min.x + padding.x, preview_rect.max.y + padding.y);
+                ui.painter().galley(text_pos, galley, visuals.text_color());
+            }
+
+            if let Some(source_icon) = &self.source_icon {
+                // Beside the label, below the preview
+                let icon_rect = Rect::from_center_size(
+                    Pos2::new(
+                        rect.max.x - padding.x - self.source_icon_size / 2.0,
+                        (preview_rect.max.y + rect.max.y) / 2.0,
+                    ),
+                    Vec2::splat(self.source_icon_size),
+                );
+                Image::from_texture(source_icon)
+                    .maintain_aspect_ratio(true)
+                    .paint_at(ui, icon_rect);
+            }
+
+            // On a background of its own in the corner, as it sits over the preview
+            if let Some(galley) = keyboard_hint_galley {
+                let hint_rect = Rect::from_min_size(
+                    Pos2::new(
+                        preview_rect.max.x - galley.size().x - padding.x,
+                        preview_rect.min.y,
+                    ),
+                    galley.size() + padding,
+                );
+                ui.painter().rect_filled(
+                    hint_rect,
+                    CornerRadius {
+                        ne: visuals.corner_radius.ne,
+                        sw: visuals.corner_radius.sw,
+                        ..Default::default()
+                    },
+                    bg_fill,
+                );
+                ui.painter().galley(
+                    hint_rect.min + padding / 2.0,
+                    galley,
+                    self.secondary_foreground.unwrap_or(visuals.text_color()),
+                );
+            }
+
+            // In the corner below the keyboard hint, at the bottom of the preview
+            if let Some(galley) = expiry_badge_galley {
+                let badge_rect = Rect::from_min_size(
+                    preview_rect.max - galley.size() - padding,
+                    galley.size() + padding,
+                );
+                ui.painter().rect_filled(
+                    badge_rect,
+                    CornerRadius {
+                        nw: visuals.corner_radius.nw,
+                        ..Default::default()
+                    },
+                    bg_fill,
+                );
+                ui.painter().galley(
+                    badge_rect.min + padding / 2.0,
+                    galley,
+                    self.secondary_foreground.unwrap_or(visuals.text_color()),
+                );
+            }
+
+            if self.is_pinned {
+                let pin_center = rect.min + Vec2::splat(self.pin_size / 2.0);
+                ui.painter()
+                    .circle_filled(pin_center, self.pin_size, self.pin_color);
+            }
+        }
+
+        response
+    }
 }
 
 impl Widget for ClipboardButton {
     fn ui(self, ui: &mut Ui) -> Response {
+        if self.tile {
+            return self.tile_ui(ui);
+        }
+
         // TODO: make these configurable?
         let sublabel_gap = 3.0;
         let keyboard_hint_gap = 10.0;
         let keyboard_hint_size = 11.0;
+        #[cfg(feature = "accessibility")]
+        let accessible_name = self.accessible_name();
 
         let padding = if self.preview.is_some()
             && let Some(with_preview_padding) = self.with_preview_padding
@@ -159,7 +449,7 @@ impl Widget for ClipboardButton {
         if let Some((_, img_size)) = self.preview {
             text_width -= img_size.x;
         }
-        if self.color_preview.is_some() {
+        if self.color_preview.is_some() || self.leading_icon.is_some() {
             text_width -= self.color_preview_size + padding.x;
         }
 
@@ -180,7 +470,48 @@ impl Widget for ClipboardButton {
             .as_ref()
             .map(|g| g.size().x + keyboard_hint_gap)
             .unwrap_or(0.0);
+        let expiry_badge_galley = self.expiry_badge.map(|badge| {
+            WidgetText::RichText(Arc::new(
+                RichText::new(badge)
+                    .size(keyboard_hint_size)
+                    .color(self.secondary_foreground.unwrap_or(Color32::PLACEHOLDER)),
+            ))
+            .into_galley(
+                ui,
+                Some(TextWrapMode::Truncate),
+                desired_width * 0.2,
+                TextStyle::Button,
+            )
+        });
+        text_width -= expiry_badge_galley
+            .as_ref()
+            .map(|g| g.size().x + keyboard_hint_gap)
+            .unwrap_or(0.0);
+        if self.source_icon.is_some() {
+            text_width -= self.source_icon_size + keyboard_hint_gap;
+        }
 
+        let middle_ellipsis = self.middle_ellipsis;
+        let max_label_rows = if self.wrap_labels {
+            self.max_label_rows.max(1)
+        } else {
+            1
+        };
+        let label_scroll = self.label_scroll.filter(|_| max_label_rows == 1);
+        let full_label_width = label_scroll.map(|_| {
+            self.labels
+                .iter()
+                .map(|l| {
+                    rich_texts_to_galley(l.clone(), ui, 1, f32::INFINITY, TextStyle::Button, false)
+                        .size()
+                        .x
+                })
+                .fold(0.0, f32::max)
+        });
+        let max_label_offset = full_label_width
+            .map(|width| (width - text_width).max(0.0))
+            .unwrap_or(0.0);
+        let label_offset = label_scroll.unwrap_or(0.0).min(max_label_offset);
         let galleys = self
             .labels
             .into_iter()
@@ -188,9 +519,14 @@ impl Widget for ClipboardButton {
                 rich_texts_to_galley(
                     l,
                     ui,
-                    Some(TextWrapMode::Truncate),
-                    text_width,
+                    max_label_rows,
+                    if label_offset > 0.0 {
+                        f32::INFINITY
+                    } else {
+                        text_width
+                    },
                     TextStyle::Button,
+                    middle_ellipsis && label_offset == 0.0,
                 )
             })
             .collect::<Vec<_>>();
@@ -223,17 +559,35 @@ impl Widget for ClipboardButton {
                 .unwrap_or(0.0)
             + img_src_galley.as_ref().map(|g| g.size().y).unwrap_or(0.0);
         let preview_height = self.preview.as_ref().map(|i| i.1.y).unwrap_or(0.0);
-        let color_preview_height = self
-            .color_preview
-            .as_ref()
-            .map(|_| self.color_preview_size + padding.y * 2.0)
-            .unwrap_or(0.0);
+        let color_preview_height = if self.color_preview.is_some() || self.leading_icon.is_some() {
+            self.color_preview_size + padding.y * 2.0
+        } else {
+            0.0
+        };
         let desired_height = preview_height
             .max(color_preview_height)
             .max(text_height + padding.y * 2.0);
 
         let (rect, response) =
             ui.allocate_at_least(Vec2::new(desired_width, desired_height), Sense::CLICK);
+        let rect = snap_to_pixels(ui, rect, self.pixel_snap);
+        #[cfg(feature = "accessibility")]
+        response.widget_info(|| {
+            egui::WidgetInfo::selected(
+                egui::WidgetType::Button,
+                true,
+                self.is_active,
+                &accessible_name,
+            )
+        });
+        if label_scroll.is_some() {
+            ui.data_mut(|d| {
+                d.insert_temp(
+                    response.id.with(LABEL_SCROLL_ID),
+                    (max_label_offset, text_width),
+                )
+            });
+        }
 
         if ui.is_rect_visible(rect) {
             let visuals = &ui.style().visuals.widgets.inactive;
@@ -255,16 +609,23 @@ impl Widget for ClipboardButton {
             if let Some((ref texture, size)) = self.preview {
                 let preview_rect =
                     Rect::from_min_size(rect.min, egui::vec2(size.x, desired_height));
-                let preview = Image::from_texture(texture)
-                    .maintain_aspect_ratio(true)
-                    .bg_fill(self.preview_background)
-                    .corner_radius(CornerRadius {
-                        nw: visuals.corner_radius.nw,
-                        sw: visuals.corner_radius.sw,
-                        ..Default::default()
-                    });
+                let corner_radius = CornerRadius {
+                    nw: visuals.corner_radius.nw,
+                    sw: visuals.corner_radius.sw,
+                    ..Default::default()
+                };
+                if let Some(texture) = texture {
+                    let preview = Image::from_texture(texture.sized_texture())
+                        .uv(texture.uv)
+                        .maintain_aspect_ratio(true)
+                        .bg_fill(self.preview_background)
+                        .corner_radius(corner_radius);
 
-                preview.paint_at(ui, preview_rect);
+                    preview.paint_at(ui, preview_rect);
+                } else {
+                    ui.painter()
+                        .rect_filled(preview_rect, corner_radius, self.preview_background);
+                }
 
                 assert!(preview_rect.width() == size.x);
                 cursor_x += preview_rect.width();
@@ -299,14 +660,34 @@ impl Widget for ClipboardButton {
                 );
 
                 cursor_x += preview_rect.width();
+            } else if let Some(leading_icon) = &self.leading_icon {
+                cursor_x += padding.x;
+
+                let icon_rect = Rect::from_center_size(
+                    egui::pos2(cursor_x + self.color_preview_size / 2.0, rect.center().y),
+                    Vec2::splat(self.color_preview_size),
+                );
+                Image::from_texture(leading_icon)
+                    .maintain_aspect_ratio(true)
+                    .paint_at(ui, icon_rect);
+
+                cursor_x += icon_rect.width();
             }
 
             cursor_x += padding.x;
             let mut cursor_y = rect.min.y + padding.y;
+            let label_painter = if label_offset > 0.0 {
+                ui.painter().with_clip_rect(Rect::from_x_y_ranges(
+                    cursor_x..=cursor_x + text_width,
+                    rect.y_range(),
+                ))
+            } else {
+                ui.painter().clone()
+            };
             for galley in galleys {
-                let text_pos = Pos2::new(cursor_x, cursor_y);
+                let text_pos = Pos2::new(cursor_x - label_offset, cursor_y);
                 cursor_y += galley.size().y;
-                ui.painter().galley(text_pos, galley, visuals.text_color());
+                label_painter.galley(text_pos, galley, visuals.text_color());
             }
 
             if let Some(galley) = img_src_galley {
@@ -339,8 +720,20 @@ impl Widget for ClipboardButton {
                 );
             }
 
+            cursor_x += text_width;
+            if let Some(galley) = expiry_badge_galley {
+                cursor_x += keyboard_hint_gap;
+                let text_pos = Pos2::new(cursor_x, rect.center().y - galley.rect.height() / 2.0);
+                cursor_x += galley.size().x;
+                ui.painter().galley(
+                    text_pos,
+                    galley,
+                    self.secondary_foreground.unwrap_or(visuals.text_color()),
+                );
+            }
+
             if let Some(galley) = keyboard_hint_galley {
-                cursor_x += text_width + keyboard_hint_gap;
+                cursor_x += keyboard_hint_gap;
                 let text_pos = Pos2::new(cursor_x, rect.center().y - galley.rect.height() / 2.0);
                 ui.painter().galley(
                     text_pos,
@@ -349,6 +742,19 @@ impl Widget for ClipboardButton {
                 );
             }
 
+            if let Some(source_icon) = &self.source_icon {
+                let icon_rect = Rect::from_center_size(
+                    Pos2::new(
+                        rect.max.x - right_padding - self.source_icon_size / 2.0,
+                        rect.center().y,
+                    ),
+                    Vec2::splat(self.source_icon_size),
+                );
+                Image::from_texture(source_icon)
+                    .maintain_aspect_ratio(true)
+                    .paint_at(ui, icon_rect);
+            }
+
             if self.is_pinned {
                 let pin_center = rect.min + Vec2::splat(self.pin_size / 2.0);
                 ui.painter()
@@ -363,9 +769,10 @@ impl Widget for ClipboardButton {
 fn rich_texts_to_galley(
     rich_texts: Vec<RichText>,
     ui: &Ui,
-    wrap_mode: Option<TextWrapMode>,
+    max_rows: usize,
     available_width: f32,
     fallback_font: impl Into<FontSelection>,
+    middle_ellipsis: bool,
 ) -> Arc<Galley> {
     let valign = ui.text_valign();
     let style = ui.style();
@@ -376,5 +783,129 @@ fn rich_texts_to_galley(
         text.append_to(&mut layout_job, style, fallback_font.clone(), valign);
     }
 
-    WidgetText::from(layout_job).into_galley(ui, wrap_mode, available_width, fallback_font)
+    if max_rows > 1 {
+        let wrapping = TextWrapping {
+            max_rows,
+            ..TextWrapping::wrap_at_width(available_width)
+        };
+        return WidgetText::from(layout_job).into_galley_impl(
+            ui.ctx(),
+            style,
+            wrapping,
+            fallback_font,
+            valign,
+        );
+    }
+
+    let wrap_mode = Some(TextWrapMode::Truncate);
+    let galley = WidgetText::from(layout_job.clone()).into_galley(
+        ui,
+        wrap_mode,
+        available_width,
+        fallback_font.clone(),
+    );
+    if !middle_ellipsis || !galley.elided {
+        return galley;
+    }
+
+    // Binary search the most graphemes that fit, kept evenly from both ends. egui caches laid out
+    // galleys, so repeating the search every frame is cheap
+    let grapheme_starts = layout_job
+        .text
+        .grapheme_indices(true)
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    let elide = |kept: usize| {
+        let text_len = layout_job.text.len();
+        let head_end = grapheme_starts
+            .get(kept.div_ceil(2))
+            .copied()
+            .unwrap_or(text_len);
+        let tail_start = match kept / 2 {
+            0 => text_len,
+            tail => grapheme_starts[grapheme_starts.len() - tail],
+        };
+        elide_middle(&layout_job, head_end, tail_start)
+    };
+    let (mut low, mut high) = (0, grapheme_starts.len() - 1);
+    while low < high {
+        let mid = (low + high).div_ceil(2);
+        let width = WidgetText::from(elide(mid))
+            .into_galley(
+                ui,
+                Some(TextWrapMode::Extend),
+                available_width,
+                fallback_font.clone(),
+            )
+            .size()
+            .x;
+        if width <= available_width {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+
+    WidgetText::from(elide(low)).into_galley(ui, wrap_mode, available_width, fallback_font)
+}
+
+fn elide_middle(job: &LayoutJob, head_end: usize, tail_start: usize) -> LayoutJob {
+    let mut sections = job
+        .sections
+        .iter()
+        .filter(|s| s.byte_range.start < head_end)
+        .map(|s| LayoutSection {
+            byte_range: s.byte_range.start..s.byte_range.end.min(head_end),
+            ..s.clone()
+        })
+        .collect::<Vec<_>>();
+
+    let ellipsis_format = sections
+        .last()
+        .or(job.sections.first())
+        .map(|s| s.format.clone())
+        .unwrap_or_default();
+    sections.push(LayoutSection {
+        leading_space: 0.0,
+        byte_range: head_end..head_end + ELLIPSIS.len(),
+        format: ellipsis_format,
+    });
+
+    let tail_offset = head_end + ELLIPSIS.len();
+    sections.extend(
+        job.sections
+            .iter()
+            .filter(|s| s.byte_range.end > tail_start)
+            .map(|s| {
+                let start = s.byte_range.start.max(tail_start);
+                LayoutSection {
+                    leading_space: if start == s.byte_range.start {
+                        s.leading_space
+                    } else {
+                        0.0
+                    },
+                    byte_range: start - tail_start + tail_offset
+                        ..s.byte_range.end - tail_start + tail_offset,
+                    format: s.format.clone(),
+                }
+            }),
+    );
+
+    LayoutJob {
+        text: format!(
+            "{}{ELLIPSIS}{}",
+            &job.text[..head_end],
+            &job.text[tail_start..]
+        ),
+        sections,
+        ..job.clone()
+    }
+}
+
+fn snap_to_pixels(ui: &Ui, rect: Rect, pixel_snap: bool) -> Rect {
+    if pixel_snap {
+        rect.round_to_pixels(ui.pixels_per_point())
+    } else {
+        rect
+    }
 }
@@ -0,0 +1,244 @@
+use egui::{Color32, Context, Frame, Id, Modal, RichText, ScrollArea, Separator, Vec2, Widget};
+
+use crate::{
+    AppMode,
+    config::Config,
+    keymap_action::{ACTION_KEYMAPS, Action, KeyAction, KeyChord, macro_key_action},
+    search::fuzzy_score,
+};
+
+struct Command {
+    name: String,
+    keys: String,
+    actions: Vec<KeyAction>,
+}
+
+/// Lists the actions of Normal mode, the transforms and the configured macros, filtered by a
+/// fuzzy query, so rarely used ones don't need their keys remembered
+pub struct CommandPalette {
+    commands: Vec<Command>,
+    is_open: bool,
+    query: String,
+    // Indexes into commands, best match first
+    matches: Vec<usize>,
+    selected: usize,
+    scrolls_to_selected: bool,
+}
+
+impl CommandPalette {
+    pub fn new(config: &Config) -> Self {
+        let mut palette = CommandPalette {
+            commands: commands(config),
+            is_open: false,
+            query: String::new(),
+            matches: vec![],
+            selected: 0,
+            scrolls_to_selected: false,
+        };
+        palette.update_matches();
+        palette
+    }
+
+    pub fn open(&mut self) {
+        self.is_open = true;
+        self.query.clear();
+        self.update_matches();
+    }
+
+    pub fn close(&mut self) {
+        self.is_open = false;
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.is_open
+    }
+
+    pub fn push_str(&mut self, text: &str) {
+        self.query.push_str(text);
+        self.update_matches();
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.update_matches();
+    }
+
+    pub fn clear_query(&mut self) {
+        self.query.clear();
+        self.update_matches();
+    }
+
+    /// Moves the selection down, or up with a negative `offset`, wrapping around
+    pub fn move_selection(&mut self, offset: isize) {
+        if self.matches.is_empty() {
+            return;
+        }
+        self.selected =
+            (self.selected as isize + offset).rem_euclid(self.matches.len() as isize) as usize;
+        self.scrolls_to_selected = true;
+    }
+
+    /// Closes the palette, returning the actions of the selected command
+    pub fn pick(&mut self) -> Vec<KeyAction> {
+        self.close();
+        self.matches
+            .get(self.selected)
+            .map(|&idx| self.commands[idx].actions.clone())
+            .unwrap_or_default()
+    }
+
+    fn update_matches(&mut self) {
+        let mut scored = self
+            .commands
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, command)| Some((idx, fuzzy_score(&self.query, &command.name)?)))
+            .collect::<Vec<_>>();
+        // Stable, so equally good matches keep the order of the help
+        scored.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+        self.matches = scored.into_iter().map(|(idx, _)| idx).collect();
+        self.selected = 0;
+        self.scrolls_to_selected = true;
+    }
+
+    /// Returns true when a command gets clicked, which is then selected for `pick`
+    pub fn show(&mut self, ctx: &Context, dimension: Vec2) -> bool {
+        if !self.is_open {
+            return false;
+        }
+        let margin = 24.0;
+        let spacing = 10.0;
+        let mut clicked = false;
+
+        Modal::new(Id::new("command_palette"))
+            .backdrop_color(Color32::from_black_alpha(180))
+            .frame(Frame::popup(&ctx.style()).inner_margin(spacing))
+            .show(ctx, |ui| {
+                ui.set_width(dimension.x - margin * 2.0 - spacing * 2.0);
+
+                // A block cursor, as the query can't be clicked into
+                ui.label(RichText::new(format!(":{}\u{2588}", self.query)).monospace());
+                Separator::default().spacing(spacing).ui(ui);
+
+                ScrollArea::vertical()
+                    .max_height(dimension.y / 2.0)
+                    .show(ui, |ui| {
+                        if self.matches.is_empty() {
+                            ui.label(RichText::new("No matching commands").weak());
+                        }
+                        for (i, &idx) in self.matches.iter().enumerate() {
+                            let command = &self.commands[idx];
+                            let button = egui::Button::new(&command.name)
+                                .shortcut_text(&command.keys)
+                                .frame(false)
+                                .selected(i == self.selected)
+                                .min_size(egui::vec2(ui.available_width(), 0.0));
+                            let response = ui.add(button);
+                            if i == self.selected && self.scrolls_to_selected {
+                                response.scroll_to_me(None);
+                            }
+                            if response.clicked() {
+                                self.selected = i;
+                                clicked = true;
+                            }
+                        }
+                    });
+                self.scrolls_to_selected = false;
+
+                Separator::default().spacing(spacing).ui(ui);
+                ui.vertical_centered(|ui| {
+                    ui.label(RichText::new("Enter to run, Escape to cancel").weak());
+                });
+            });
+
+        clicked
+    }
+}
+
+fn keys_text(keys: &[KeyChord]) -> String {
+    keys.iter()
+        .map(|k| k.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn commands(config: &Config) -> Vec<Command> {
+    let mut commands: Vec<Command> = vec![];
+    let groups = ACTION_KEYMAPS.iter();
+    let normal_entries = groups
+        .clone()
+        .filter(|group| group.mode == AppMode::Normal)
+        .flat_map(|group| &group.entries);
+    let transform_keys = normal_entries
+        .clone()
+        .find(|entry| matches!(entry.action, Action::Key(KeyAction::OpenTransforms)))
+        .map(|entry| keys_text(&entry.keys));
+
+    for entry in normal_entries {
+        let Action::Key(action) = entry.action else {
+            continue;
+        };
+        // Marks need a letter typed after them, and the palette is open already
+        if entry.hidden
+            || matches!(
+                action,
+                KeyAction::SetMark(_) | KeyAction::JumpToMark(_) | KeyAction::OpenPalette
+            )
+        {
+            continue;
+        }
+        // Listed once, under the first of the keys doing it
+        if commands
+            .iter()
+            .any(|command| command.name == entry.description)
+        {
+            continue;
+        }
+        commands.push(Command {
+            name: entry.description.to_string(),
+            keys: keys_text(&entry.keys),
+            actions: vec![action],
+        });
+    }
+
+    let transform_entries = groups
+        .filter(|group| group.mode == AppMode::Transform)
+        .flat_map(|group| &group.entries);
+    for entry in transform_entries {
+        if let Action::Key(action @ KeyAction::PasteTransformed(_)) = entry.action {
+            let keys = keys_text(&entry.keys);
+            commands.push(Command {
+                name: entry.description.to_string(),
+                keys: match &transform_keys {
+                    Some(transform_keys) => format!("{transform_keys} {keys}"),
+                    None => keys,
+                },
+                actions: vec![action],
+            });
+        }
+    }
+
+    let mut macros = config.macros.iter().collect::<Vec<_>>();
+    macros.sort_by_key(|&(keys, _)| keys);
+    for (keys, actions) in macros {
+        let names = actions
+            .iter()
+            .filter_map(|action| {
+                serde_json::to_value(action)
+                    .ok()?
+                    .as_str()
+                    .map(str::to_string)
+            })
+            .collect::<Vec<_>>();
+        commands.push(Command {
+            name: format!("Macro: {}", names.join(", ")),
+            keys: keys.clone(),
+            actions: actions
+                .iter()
+                .map(|&action| macro_key_action(action))
+                .collect(),
+        });
+    }
+
+    commands
+}
@@ -0,0 +1,45 @@
+use egui::{Color32, Context, Frame, Id, Modal, RichText, Separator, Vec2, Widget};
+
+pub struct ConfirmModal;
+
+impl ConfirmModal {
+    /// Returns Some(true) if confirmed, Some(false) if cancelled with the pointer
+    pub fn show(ctx: &Context, dimension: Vec2, message: &str) -> Option<bool> {
+        let margin = 24.0;
+        let spacing = 10.0;
+        let mut answer = None;
+
+        Modal::new(Id::new("confirm_modal"))
+            .backdrop_color(Color32::from_black_alpha(180))
+            .frame(Frame::popup(&ctx.style()).inner_margin(spacing))
+            .show(ctx, |ui| {
+                ui.set_width(dimension.x - margin * 2.0 - spacing * 2.0);
+
+                ui.vertical_centered(|ui| {
+                    ui.heading("Are you sure?");
+                    Separator::default().spacing(spacing).ui(ui);
+                    ui.label(message);
+                    Separator::default().spacing(spacing).ui(ui);
+
+                    ui.horizontal(|ui| {
+                        let button_width = (ui.available_width() - spacing) / 2.0;
+                        if ui
+                            .add(egui::Button::new("Yes").min_size(egui::vec2(button_width, 0.0)))
+                            .clicked()
+                        {
+                            answer = Some(true);
+                        }
+                        if ui
+                            .add(egui::Button::new("No").min_size(egui::vec2(button_width, 0.0)))
+                            .clicked()
+                        {
+                            answer = Some(false);
+                        }
+                    });
+                    ui.label(RichText::new("Press y to confirm, n or Escape to cancel").weak());
+                });
+            });
+
+        answer
+    }
+}
@@ -0,0 +1,111 @@
+use egui::{Area, Context, Frame, Id, Order, Pos2};
+
+use crate::{
+    keymap_action::{KeyAction, PasteModifier},
+    transform::Transform,
+};
+
+const ENTRIES: &[(&str, KeyAction)] = &[
+    (
+        "Paste",
+        KeyAction::Paste(PasteModifier {
+            trim: false,
+            and_enter: false,
+            keep_open: false,
+            plain_text: false,
+        }),
+    ),
+    (
+        "Paste as plain text",
+        KeyAction::Paste(PasteModifier {
+            trim: false,
+            and_enter: false,
+            keep_open: false,
+            plain_text: true,
+        }),
+    ),
+    ("Toggle pin", KeyAction::Pin),
+    ("Toggle expiry", KeyAction::ToggleExpiry),
+    ("Edit", KeyAction::Edit),
+    ("Delete", KeyAction::Remove),
+    ("Save to file", KeyAction::SaveToFile),
+    ("Open", KeyAction::Open),
+    ("Transform…", KeyAction::OpenTransforms),
+];
+
+const TRANSFORMS: &[Transform] = &[
+    Transform::DecodeBase64,
+    Transform::UrlDecode,
+    Transform::PrettyJson,
+    Transform::Unescape,
+];
+
+#[derive(Default)]
+pub struct ContextMenu {
+    target: Option<(u64, Pos2)>,
+    // The click that opens the menu lands outside of it, so don't let it close the menu right away
+    just_opened: bool,
+}
+
+impl ContextMenu {
+    pub fn new() -> Self {
+        ContextMenu {
+            target: None,
+            just_opened: false,
+        }
+    }
+
+    pub fn open(&mut self, item_id: u64, pos: Pos2) {
+        self.target = Some((item_id, pos));
+        self.just_opened = true;
+    }
+
+    pub fn close(&mut self) {
+        self.target = None;
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.target.is_some()
+    }
+
+    /// Returns the item and the action picked from the menu, if any
+    pub fn show(&mut self, ctx: &Context) -> Option<(u64, KeyAction)> {
+        let (item_id, pos) = self.target?;
+
+        let mut picked = None;
+        let area = Area::new(Id::new("item_context_menu"))
+            .fixed_pos(pos)
+            .order(Order::Foreground)
+            .constrain(true)
+            .fade_in(false)
+            .show(ctx, |ui| {
+                Frame::menu(ui.style()).show(ui, |ui| {
+                    ui.set_min_width(140.0);
+                    let entries = ENTRIES.iter().copied().chain(
+                        TRANSFORMS
+                            .iter()
+                            .map(|&transform| (transform.name(), KeyAction::Transform(transform))),
+                    );
+                    for (i, (label, action)) in entries.enumerate() {
+                        // Transforms add new items, so keep them apart from the item's own actions
+                        if i == ENTRIES.len() {
+                            ui.separator();
+                        }
+                        let button = egui::Button::new(label)
+                            .frame(false)
+                            .min_size(egui::vec2(ui.available_width(), 0.0));
+                        if ui.add(button).clicked() {
+                            picked = Some((item_id, action));
+                        }
+                    }
+                });
+            });
+
+        if picked.is_some() || (!self.just_opened && area.response.clicked_elsewhere()) {
+            self.close();
+        }
+        self.just_opened = false;
+
+        picked
+    }
+}
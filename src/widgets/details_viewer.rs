@@ -0,0 +1,105 @@
+use std::rc::Rc;
+
+use egui::{Color32, Context, Frame, Grid, Id, Modal, RichText, Separator, Vec2, Widget};
+
+use crate::{
+    config::Config,
+    format::NumberFormat,
+    text_details::{TextDetails, TextKind},
+};
+
+/// Metadata of a text item: counts, a language guess, recognized formats, and a warning for
+/// tokens that look like secrets
+#[derive(Default)]
+pub struct DetailsViewer {
+    details: Option<Rc<TextDetails>>,
+}
+
+impl DetailsViewer {
+    pub fn new() -> Self {
+        DetailsViewer { details: None }
+    }
+
+    pub fn open(&mut self, details: Rc<TextDetails>) {
+        self.details = Some(details);
+    }
+
+    pub fn close(&mut self) {
+        self.details = None;
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.details.is_some()
+    }
+
+    pub fn show(&self, ctx: &Context, config: &Config, number_format: &NumberFormat) {
+        let Some(details) = &self.details else {
+            return;
+        };
+        let dimension: Vec2 = config.layout.window_dimensions.into();
+        let margin = 24.0;
+        let spacing = 10.0;
+
+        Modal::new(Id::new("details_viewer"))
+            .backdrop_color(Color32::from_black_alpha(180))
+            .frame(Frame::popup(&ctx.style()).inner_margin(spacing))
+            .show(ctx, |ui| {
+                ui.set_width(dimension.x - margin * 2.0 - spacing * 2.0);
+
+                ui.vertical_centered(|ui| {
+                    ui.heading("Details");
+                });
+                Separator::default().spacing(spacing).ui(ui);
+
+                Grid::new("details_viewer_grid")
+                    .num_columns(2)
+                    .spacing([spacing * 2.0, spacing / 2.0])
+                    .show(ui, |ui| {
+                        let mut row = |name: &str, value: String| {
+                            ui.label(RichText::new(name).weak());
+                            ui.label(value);
+                            ui.end_row();
+                        };
+
+                        row("Characters", number_format.count(details.chars));
+                        row("Words", number_format.count(details.words));
+                        row("Lines", number_format.count(details.lines));
+                        row("Size", number_format.size(details.bytes as u64));
+                        row(
+                            "Language",
+                            details.language.unwrap_or("Unknown").to_string(),
+                        );
+                        if let Some(kind) = details.kind {
+                            row("Format", format_kind(kind, number_format));
+                        }
+                        row("Entropy", format!("{:.2} bits/char", details.entropy));
+                    });
+
+                if details.looks_like_secret {
+                    Separator::default().spacing(spacing).ui(ui);
+                    ui.label(
+                        RichText::new("⚠ This looks like a secret, such as a password or token")
+                            .color(config.theme.toast_error_foreground),
+                    );
+                }
+
+                Separator::default().spacing(spacing).ui(ui);
+                ui.vertical_centered(|ui| {
+                    ui.label(RichText::new("Press i or Escape to close").weak());
+                });
+            });
+    }
+}
+
+fn format_kind(kind: TextKind, number_format: &NumberFormat) -> String {
+    match kind {
+        TextKind::Json => "JSON".to_string(),
+        TextKind::Base64 { decoded_len } => {
+            format!("Base64, {} decoded", number_format.size(decoded_len as u64))
+        }
+        TextKind::Uuid {
+            version: Some(version),
+        } => format!("UUID version {version}"),
+        TextKind::Uuid { version: None } => "UUID".to_string(),
+    }
+}
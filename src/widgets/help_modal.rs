@@ -95,7 +95,7 @@ impl HelpModal {
                             );
                         });
 
-                        for entry in &group.entries {
+                        for entry in group.entries.iter().filter(|entry| !entry.hidden) {
                             let key_str = entry
                                 .keys
                                 .iter()
@@ -113,8 +113,9 @@ impl HelpModal {
                                                 .fill(ui.visuals().code_bg_color)
                                                 .corner_radius(4.0)
                                                 .inner_margin(key_block_padding)
-                                                // TODO: use monospace font
-                                                .show(ui, |ui| ui.label(key_str))
+                                                .show(ui, |ui| {
+                                                    ui.label(RichText::new(key_str).monospace())
+                                                })
                                         },
                                     )
                                 });
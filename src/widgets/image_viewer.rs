@@ -0,0 +1,159 @@
+use egui::{
+    Color32, ColorImage, Context, Frame, Id, Modal, Pos2, Rect, RichText, Sense, TextureFilter,
+    TextureHandle, TextureOptions, Vec2, pos2, vec2,
+};
+
+use crate::keymap_action::ZoomAction;
+
+const MIN_ZOOM: f32 = 0.05;
+const MAX_ZOOM: f32 = 32.0;
+const ZOOM_STEP: f32 = 1.25;
+const SCROLL_ZOOM_SPEED: f32 = 0.0015;
+
+struct ViewerState {
+    texture: TextureHandle,
+    image_size: Vec2,
+    // None means fit to the viewport; otherwise screen pixels per image pixel
+    zoom: Option<f32>,
+    // Zoom of the fit mode as of the last show, the base when zooming in or out from it
+    fit_zoom: f32,
+    // Image pixel shown at the center of the viewport
+    center: Pos2,
+}
+
+/// Full-window image preview with zooming and panning, drawn by cropping the texture's UV rect
+/// to the visible part of the image
+#[derive(Default)]
+pub struct ImageViewer {
+    state: Option<ViewerState>,
+}
+
+impl ImageViewer {
+    pub fn new() -> Self {
+        ImageViewer { state: None }
+    }
+
+    pub fn open(&mut self, ctx: &Context, item_id: u64, image: ColorImage) {
+        let image_size = vec2(image.size[0] as f32, image.size[1] as f32);
+        let texture = ctx.load_texture(
+            format!("image_viewer_{item_id}"),
+            image,
+            TextureOptions {
+                // Zoomed in pixels stay sharp so screenshots can be checked pixel by pixel
+                magnification: TextureFilter::Nearest,
+                ..TextureOptions::LINEAR
+            },
+        );
+        self.state = Some(ViewerState {
+            texture,
+            image_size,
+            zoom: None,
+            fit_zoom: 1.0,
+            center: (image_size / 2.0).to_pos2(),
+        });
+    }
+
+    pub fn close(&mut self) {
+        self.state = None;
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.state.is_some()
+    }
+
+    pub fn zoom(&mut self, action: ZoomAction) {
+        let Some(state) = &mut self.state else {
+            return;
+        };
+
+        let zoom = state.zoom.unwrap_or(state.fit_zoom);
+        state.zoom = match action {
+            ZoomAction::Fit => None,
+            ZoomAction::ActualSize => Some(1.0),
+            ZoomAction::In => Some((zoom * ZOOM_STEP).min(MAX_ZOOM)),
+            ZoomAction::Out => Some((zoom / ZOOM_STEP).max(MIN_ZOOM)),
+        };
+    }
+
+    pub fn show(&mut self, ctx: &Context, dimension: Vec2) {
+        let Some(state) = &mut self.state else {
+            return;
+        };
+        let margin = 24.0;
+        let spacing = 10.0;
+
+        Modal::new(Id::new("image_viewer"))
+            .backdrop_color(Color32::from_black_alpha(180))
+            .frame(Frame::popup(&ctx.style()).inner_margin(spacing))
+            .show(ctx, |ui| {
+                let total_spacing = margin * 2.0 + spacing * 2.0;
+                let footer_height = ui.text_style_height(&egui::TextStyle::Body) + spacing;
+                let viewport_size = vec2(
+                    dimension.x - total_spacing,
+                    dimension.y - total_spacing - footer_height,
+                );
+
+                let (viewport, response) = ui.allocate_exact_size(viewport_size, Sense::drag());
+                let fit_zoom = (viewport.width() / state.image_size.x)
+                    .min(viewport.height() / state.image_size.y)
+                    .min(1.0);
+                state.fit_zoom = fit_zoom;
+                let mut zoom = state.zoom.unwrap_or(fit_zoom);
+
+                if let Some(pointer) = response.hover_pos() {
+                    let scroll = ui.input(|i| i.smooth_scroll_delta.y);
+                    if scroll != 0.0 {
+                        let new_zoom =
+                            (zoom * (1.0 + scroll * SCROLL_ZOOM_SPEED)).clamp(MIN_ZOOM, MAX_ZOOM);
+                        // Keep the image pixel under the pointer in place
+                        let offset = pointer - viewport.center();
+                        state.center += offset / zoom - offset / new_zoom;
+                        zoom = new_zoom;
+                        state.zoom = Some(zoom);
+                    }
+                }
+                if response.dragged() {
+                    state.center -= response.drag_delta() / zoom;
+                    if state.zoom.is_none() {
+                        state.zoom = Some(zoom);
+                    }
+                }
+
+                // Keep the visible region within the image
+                let visible_size = (viewport.size() / zoom).min(state.image_size);
+                let half = visible_size / 2.0;
+                state.center = pos2(
+                    state
+                        .center
+                        .x
+                        .clamp(half.x, state.image_size.x - half.x),
+                    state
+                        .center
+                        .y
+                        .clamp(half.y, state.image_size.y - half.y),
+                );
+
+                let visible = Rect::from_center_size(state.center, visible_size);
+                let uv = Rect::from_min_max(
+                    (visible.min.to_vec2() / state.image_size).to_pos2(),
+                    (visible.max.to_vec2() / state.image_size).to_pos2(),
+                );
+                let draw_rect = Rect::from_center_size(viewport.center(), visible_size * zoom);
+                ui.painter()
+                    .image(state.texture.id(), draw_rect, uv, Color32::WHITE);
+
+                ui.add_space(spacing / 2.0);
+                ui.vertical_centered(|ui| {
+                    ui.label(
+                        RichText::new(format!(
+                            "{}x{} · {:.0}% · scroll to zoom, drag to pan, 1 for actual size, 0 to fit",
+                            state.image_size.x,
+                            state.image_size.y,
+                            zoom * 100.0
+                        ))
+                        .weak(),
+                    );
+                });
+            });
+    }
+}
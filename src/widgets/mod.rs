@@ -1,2 +1,11 @@
 pub mod clipboard_button;
+pub mod command_palette;
+pub mod confirm_modal;
+pub mod context_menu;
+pub mod details_viewer;
 pub mod help_modal;
+pub mod image_viewer;
+pub mod onboarding;
+pub mod profile_overlay;
+pub mod toasts;
+pub mod transform_picker;
@@ -0,0 +1,88 @@
+use egui::{Align, Frame, Grid, Layout, RichText, Ui};
+
+use crate::{AppMode, keymap_action::ACTION_KEYMAPS};
+
+// Descriptions of the Normal mode keymaps worth knowing first, shown with their first keys
+const HIGHLIGHTED_KEYMAPS: &[&str] = &[
+    "Move to next item",
+    "Move to previous item",
+    "Paste item",
+    "Toggle pin",
+    "Remove item",
+    "Close window",
+];
+
+/// First-run panel shown in place of the item list until the first item gets captured
+pub struct Onboarding {
+    show_command: String,
+}
+
+impl Onboarding {
+    pub fn new(show_command: String) -> Self {
+        Onboarding { show_command }
+    }
+
+    /// Returns true if the help button is clicked
+    pub fn ui(&self, ui: &mut Ui) -> bool {
+        let mut help_clicked = false;
+
+        ui.with_layout(Layout::top_down(Align::Center), |ui| {
+            ui.add_space(ui.available_height() / 6.0);
+            ui.heading("Welcome to memoni");
+            ui.add_space(4.0);
+            ui.label("Copy something and it will appear here.");
+            ui.add_space(12.0);
+
+            ui.label("Bind this command to a hotkey to open the window:");
+            Frame::NONE
+                .fill(ui.visuals().code_bg_color)
+                .corner_radius(4.0)
+                .inner_margin(egui::vec2(8.0, 4.0))
+                .show(ui, |ui| {
+                    ui.label(RichText::new(&self.show_command).monospace())
+                });
+            ui.add_space(12.0);
+
+            let normal_entries = ACTION_KEYMAPS
+                .iter()
+                .filter(|group| group.mode == AppMode::Normal)
+                .flat_map(|group| &group.entries);
+            Grid::new("onboarding_keys")
+                .num_columns(2)
+                .spacing(egui::vec2(12.0, 6.0))
+                .show(ui, |ui| {
+                    for &description in HIGHLIGHTED_KEYMAPS {
+                        let Some(entry) = normal_entries
+                            .clone()
+                            .find(|entry| entry.description == description)
+                        else {
+                            continue;
+                        };
+                        let key_str = entry
+                            .keys
+                            .iter()
+                            .map(|k| k.to_string())
+                            .collect::<Vec<_>>()
+                            .join(" ");
+
+                        ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                            Frame::NONE
+                                .fill(ui.visuals().code_bg_color)
+                                .corner_radius(4.0)
+                                .inner_margin(egui::vec2(8.0, 4.0))
+                                .show(ui, |ui| ui.label(key_str));
+                        });
+                        ui.label(description);
+                        ui.end_row();
+                    }
+                });
+            ui.add_space(12.0);
+
+            if ui.button("Show all keyboard shortcuts").clicked() {
+                help_clicked = true;
+            }
+        });
+
+        help_clicked
+    }
+}
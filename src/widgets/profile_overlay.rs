@@ -0,0 +1,155 @@
+use std::{collections::VecDeque, time::Duration};
+
+use egui::{
+    Align2, Area, Color32, Context, FontId, Order, Pos2, Rect, Sense, Stroke, StrokeKind, Vec2,
+};
+
+use crate::{config::Config, renderer::RenderTimings};
+
+const MAX_SAMPLES: usize = 120;
+const GRAPH_SIZE: Vec2 = Vec2::new(240.0, 60.0);
+// Graph height stands for two frames at 60 Hz, longer ones are clipped
+const GRAPH_MAX_MS: f32 = 2.0 * FRAME_BUDGET_MS;
+const FRAME_BUDGET_MS: f32 = 1000.0 / 60.0;
+
+const UI_COLOR: Color32 = Color32::from_rgb(0x4e, 0x9a, 0xf0);
+const TESSELLATE_COLOR: Color32 = Color32::from_rgb(0xe0, 0xb0, 0x40);
+const PAINT_COLOR: Color32 = Color32::from_rgb(0x5c, 0xc0, 0x6a);
+const SWAP_COLOR: Color32 = Color32::from_rgb(0xb0, 0x7c, 0xe0);
+const STALL_COLOR: Color32 = Color32::from_rgb(0xf0, 0x50, 0x50);
+
+struct FrameSample {
+    ui: Duration,
+    render: RenderTimings,
+}
+
+/// Graph of recent frame times, broken down into building the ui, tessellation, painting and
+/// buffer swap, along with the longest main loop iteration. Enabled with --profile-overlay
+#[derive(Default)]
+pub struct ProfileOverlay {
+    frames: VecDeque<FrameSample>,
+    event_loop: VecDeque<Duration>,
+}
+
+impl ProfileOverlay {
+    pub fn new() -> Self {
+        ProfileOverlay {
+            frames: VecDeque::with_capacity(MAX_SAMPLES),
+            event_loop: VecDeque::with_capacity(MAX_SAMPLES),
+        }
+    }
+
+    pub fn record_frame(&mut self, ui: Duration, render: RenderTimings) {
+        if self.frames.len() == MAX_SAMPLES {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(FrameSample { ui, render });
+    }
+
+    pub fn record_event_loop(&mut self, duration: Duration) {
+        if self.event_loop.len() == MAX_SAMPLES {
+            self.event_loop.pop_front();
+        }
+        self.event_loop.push_back(duration);
+    }
+
+    pub fn show(&self, ctx: &Context, config: &Config) {
+        let margin: Vec2 = config.layout.pending_keys_margin.into();
+        let text_color: Color32 = config.theme.toast_foreground.into();
+        let bg_color: Color32 = config.theme.toast_background.into();
+        let font = FontId::monospace(10.0);
+
+        Area::new("profile_overlay".into())
+            .anchor(Align2::RIGHT_BOTTOM, -margin)
+            .order(Order::Tooltip)
+            .fade_in(false)
+            .interactable(false)
+            .show(ctx, |ui| {
+                let (graph_rect, _) = ui.allocate_exact_size(GRAPH_SIZE, Sense::hover());
+                let painter = ui.painter();
+                painter.rect_filled(graph_rect, 2.0, bg_color);
+
+                let bar_width = graph_rect.width() / MAX_SAMPLES as f32;
+                let y_for = |ms: f32| {
+                    graph_rect.bottom() - (ms / GRAPH_MAX_MS).min(1.0) * graph_rect.height()
+                };
+                let budget_y = y_for(FRAME_BUDGET_MS);
+                painter.hline(
+                    graph_rect.x_range(),
+                    budget_y,
+                    Stroke::new(1.0, text_color.gamma_multiply(0.4)),
+                );
+
+                let start = MAX_SAMPLES - self.frames.len();
+                for (i, frame) in self.frames.iter().enumerate() {
+                    let x = graph_rect.left() + (start + i) as f32 * bar_width;
+                    let mut bottom_ms = 0.0;
+                    for (duration, color) in [
+                        (frame.ui, UI_COLOR),
+                        (frame.render.tessellate, TESSELLATE_COLOR),
+                        (frame.render.paint, PAINT_COLOR),
+                        (frame.render.swap, SWAP_COLOR),
+                    ] {
+                        let top_ms = bottom_ms + duration.as_secs_f32() * 1000.0;
+                        painter.rect_filled(
+                            Rect::from_min_max(
+                                Pos2::new(x, y_for(top_ms)),
+                                Pos2::new(x + bar_width, y_for(bottom_ms)),
+                            ),
+                            0.0,
+                            color,
+                        );
+                        bottom_ms = top_ms;
+                    }
+                }
+
+                // Iterations over budget are stalls, whether or not they rendered a frame
+                let start = MAX_SAMPLES - self.event_loop.len();
+                for (i, duration) in self.event_loop.iter().enumerate() {
+                    let ms = duration.as_secs_f32() * 1000.0;
+                    if ms > FRAME_BUDGET_MS {
+                        let x = graph_rect.left() + (start + i) as f32 * bar_width;
+                        painter.vline(
+                            x + bar_width / 2.0,
+                            y_for(ms)..=budget_y,
+                            Stroke::new(1.0, STALL_COLOR),
+                        );
+                    }
+                }
+                painter.rect_stroke(
+                    graph_rect,
+                    2.0,
+                    Stroke::new(1.0, text_color.gamma_multiply(0.2)),
+                    StrokeKind::Inside,
+                );
+
+                let average_ms = |durations: &mut dyn Iterator<Item = Duration>| {
+                    let (sum, count) =
+                        durations.fold((Duration::ZERO, 0), |(sum, count), d| (sum + d, count + 1));
+                    if count == 0 {
+                        0.0
+                    } else {
+                        sum.as_secs_f32() * 1000.0 / count as f32
+                    }
+                };
+                let summary = format!(
+                    "ui {:.1} tess {:.1} paint {:.1} swap {:.1} ms\nloop max {:.1} ms",
+                    average_ms(&mut self.frames.iter().map(|f| f.ui)),
+                    average_ms(&mut self.frames.iter().map(|f| f.render.tessellate)),
+                    average_ms(&mut self.frames.iter().map(|f| f.render.paint)),
+                    average_ms(&mut self.frames.iter().map(|f| f.render.swap)),
+                    self.event_loop
+                        .iter()
+                        .max()
+                        .map_or(0.0, |d| d.as_secs_f32() * 1000.0),
+                );
+                painter.text(
+                    graph_rect.left_top() + Vec2::splat(3.0),
+                    Align2::LEFT_TOP,
+                    summary,
+                    font,
+                    text_color,
+                );
+            });
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::VecDeque;
+
+use egui::{Area, Color32, Context, FontId, Frame, Margin, Order, RichText, Vec2};
+
+use crate::config::Config;
+
+const TOAST_DURATION_SECS: f64 = 3.0;
+const TOAST_FADE_SECS: f64 = 0.5;
+const MAX_VISIBLE_TOASTS: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToastKind {
+    Info,
+    Error,
+}
+
+struct Toast {
+    kind: ToastKind,
+    message: String,
+    // Toasts can be pushed while the window is hidden, so their time only starts once they're seen
+    shown_at: Option<f64>,
+}
+
+#[derive(Default)]
+pub struct Toasts {
+    queue: VecDeque<Toast>,
+}
+
+impl Toasts {
+    pub fn new() -> Self {
+        Toasts {
+            queue: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, kind: ToastKind, message: String) {
+        self.queue.push_back(Toast {
+            kind,
+            message,
+            shown_at: None,
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub fn show(&mut self, ctx: &Context, config: &Config) {
+        let now = ctx.input(|i| i.time);
+        self.queue.retain(|toast| {
+            toast
+                .shown_at
+                .is_none_or(|shown_at| now - shown_at < TOAST_DURATION_SECS)
+        });
+        if self.queue.is_empty() {
+            return;
+        }
+
+        let padding: Vec2 = config.layout.pending_keys_padding.into();
+        let margin: Vec2 = config.layout.pending_keys_margin.into();
+        let rect = ctx.input(|i| i.content_rect());
+
+        Area::new("toasts_overlay".into())
+            .fixed_pos(rect.min + margin)
+            .order(Order::Tooltip)
+            .fade_in(false)
+            .interactable(false)
+            .show(ctx, |ui| {
+                ui.set_width(rect.width() - margin.x * 2.0);
+                for toast in self.queue.iter_mut().take(MAX_VISIBLE_TOASTS) {
+                    let shown_at = *toast.shown_at.get_or_insert(now);
+                    let remaining = TOAST_DURATION_SECS - (now - shown_at);
+                    let opacity = if config.accessibility.reduced_motion {
+                        1.0
+                    } else {
+                        (remaining / TOAST_FADE_SECS).clamp(0.0, 1.0) as f32
+                    };
+
+                    let fg_color: Color32 = match toast.kind {
+                        ToastKind::Info => config.theme.toast_foreground.into(),
+                        ToastKind::Error => config.theme.toast_error_foreground.into(),
+                    };
+                    let bg_color: Color32 = config.theme.toast_background.into();
+
+                    Frame::new()
+                        .fill(bg_color.gamma_multiply(opacity))
+                        .corner_radius(config.layout.pending_keys_corner_radius)
+                        .inner_margin(Margin::symmetric(padding.x as i8, padding.y as i8))
+                        .show(ui, |ui| {
+                            ui.set_width(ui.available_width());
+                            ui.label(
+                                RichText::new(&toast.message)
+                                    .font(FontId::proportional(config.font.pending_keys_text_size))
+                                    .color(fg_color.gamma_multiply(opacity)),
+                            );
+                        });
+                    ui.add_space(margin.y / 2.0);
+                }
+            });
+    }
+}
@@ -0,0 +1,92 @@
+use egui::{Color32, Context, Frame, Id, Modal, RichText, Separator, Vec2, Widget};
+
+use crate::{
+    AppMode,
+    keymap_action::{ACTION_KEYMAPS, Action, KeyAction},
+};
+
+/// Lists the transforms of Transform mode along with their keys. Picking one pastes the
+/// transformed text, or adds it as a new item with Shift held
+#[derive(Default)]
+pub struct TransformPicker {
+    is_open: bool,
+}
+
+impl TransformPicker {
+    pub fn new() -> Self {
+        TransformPicker { is_open: false }
+    }
+
+    pub fn open(&mut self) {
+        self.is_open = true;
+    }
+
+    pub fn close(&mut self) {
+        self.is_open = false;
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.is_open
+    }
+
+    /// Returns the action of the transform clicked, if any
+    pub fn show(&self, ctx: &Context, dimension: Vec2) -> Option<KeyAction> {
+        if !self.is_open {
+            return None;
+        }
+        let margin = 24.0;
+        let spacing = 10.0;
+        let mut picked = None;
+
+        let entries = ACTION_KEYMAPS
+            .iter()
+            .filter(|group| group.mode == AppMode::Transform)
+            .flat_map(|group| &group.entries)
+            .filter_map(|entry| match entry.action {
+                Action::Key(action @ KeyAction::PasteTransformed(transform)) => {
+                    Some((&entry.keys, action, transform))
+                }
+                _ => None,
+            });
+
+        Modal::new(Id::new("transform_picker"))
+            .backdrop_color(Color32::from_black_alpha(180))
+            .frame(Frame::popup(&ctx.style()).inner_margin(spacing))
+            .show(ctx, |ui| {
+                ui.set_width(dimension.x - margin * 2.0 - spacing * 2.0);
+
+                ui.vertical_centered(|ui| {
+                    ui.heading("Transform");
+                });
+                Separator::default().spacing(spacing).ui(ui);
+
+                for (keys, action, transform) in entries {
+                    let key_str = keys
+                        .iter()
+                        .map(|k| k.to_string())
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    let button = egui::Button::new(transform.name())
+                        .shortcut_text(key_str)
+                        .frame(false)
+                        .min_size(egui::vec2(ui.available_width(), 0.0));
+                    if ui.add(button).clicked() {
+                        picked = if ui.input(|i| i.modifiers.shift) {
+                            Some(KeyAction::Transform(transform))
+                        } else {
+                            Some(action)
+                        };
+                    }
+                }
+
+                Separator::default().spacing(spacing).ui(ui);
+                ui.vertical_centered(|ui| {
+                    ui.label(
+                        RichText::new("Hold Shift to add as a new item, Escape to cancel").weak(),
+                    );
+                });
+            });
+
+        picked
+    }
+}
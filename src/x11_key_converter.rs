@@ -4,7 +4,7 @@ use anyhow::Result;
 use log::debug;
 use x11rb::{
     connection::Connection as _,
-    protocol::xproto::{ConnectionExt as _, GetKeyboardMappingReply},
+    protocol::xproto::{ConnectionExt as _, GetKeyboardMappingReply, GetModifierMappingReply},
     xcb_ffi::XCBConnection,
 };
 use xkeysym::{KeyCode, Keysym, keysym as xkeysym_keycode_to_keysym};
@@ -14,6 +14,7 @@ pub struct X11KeyConverter<'a> {
     min_keycode: RefCell<u8>,
     max_keycode: RefCell<u8>,
     mapping: RefCell<GetKeyboardMappingReply>,
+    modifier_mapping: RefCell<GetModifierMappingReply>,
 }
 
 impl<'a> X11KeyConverter<'a> {
@@ -24,12 +25,14 @@ impl<'a> X11KeyConverter<'a> {
         let mapping_reply = conn
             .get_keyboard_mapping(min_keycode, max_keycode - min_keycode + 1)?
             .reply()?;
+        let modifier_mapping_reply = conn.get_modifier_mapping()?.reply()?;
 
         Ok(Self {
             conn,
             min_keycode: RefCell::new(min_keycode),
             max_keycode: RefCell::new(max_keycode),
             mapping: RefCell::new(mapping_reply),
+            modifier_mapping: RefCell::new(modifier_mapping_reply),
         })
     }
 
@@ -56,6 +59,13 @@ impl<'a> X11KeyConverter<'a> {
             *self_mapping = mapping_reply;
         }
 
+        let modifier_mapping_reply = self.conn.get_modifier_mapping()?.reply()?;
+        let mut self_modifier_mapping = self.modifier_mapping.borrow_mut();
+        if self_modifier_mapping.keycodes != modifier_mapping_reply.keycodes {
+            debug!("modifier mapping changed; using new mapping");
+            *self_modifier_mapping = modifier_mapping_reply;
+        }
+
         Ok(())
     }
 
@@ -72,6 +82,22 @@ impl<'a> X11KeyConverter<'a> {
         )
     }
 
+    /// Character typed by the key, None for keys that don't type one
+    pub fn keycode_to_char(&self, keycode: KeyCode, shift: bool) -> Option<char> {
+        let min_keycode = *self.min_keycode.borrow();
+        let mapping = self.mapping.borrow();
+
+        xkeysym_keycode_to_keysym(
+            keycode,
+            shift.into(),
+            min_keycode.into(),
+            mapping.keysyms_per_keycode,
+            &mapping.keysyms,
+        )
+        .and_then(|keysym| keysym.key_char())
+        .filter(|c| !c.is_control())
+    }
+
     pub fn keysym_to_keycode(&self, keysym: Keysym) -> Option<KeyCode> {
         let min_keycode = *self.min_keycode.borrow();
         let mapping = self.mapping.borrow();
@@ -82,7 +108,7 @@ impl<'a> X11KeyConverter<'a> {
             .enumerate()
         {
             for &ks in keysyms {
-                if ks == keysym.into() {
+                if ks == u32::from(keysym) {
                     let keycode = min_keycode + i as u8;
                     return Some(keycode.into());
                 }
@@ -91,4 +117,19 @@ impl<'a> X11KeyConverter<'a> {
 
         None
     }
+
+    /// Returns the modifier mask bound to the keycode, or 0 if it isn't a modifier key
+    pub fn keycode_to_modifier_mask(&self, keycode: KeyCode) -> u16 {
+        let modifier_mapping = self.modifier_mapping.borrow();
+        let keycodes_per_modifier = modifier_mapping.keycodes_per_modifier() as usize;
+        if keycodes_per_modifier == 0 {
+            return 0;
+        }
+
+        modifier_mapping
+            .keycodes
+            .chunks(keycodes_per_modifier)
+            .position(|keycodes| keycodes.contains(&(keycode.raw() as u8)))
+            .map_or(0, |i| 1 << i)
+    }
 }
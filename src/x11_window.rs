@@ -1,11 +1,14 @@
 extern crate x11rb;
 
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
 use std::os::unix::ffi::OsStrExt as _;
+use std::time::{Duration, Instant};
 
-use anyhow::Result;
+use anyhow::{Result, bail};
 use log::{debug, info, trace, warn};
 use x11rb::connection::Connection;
+use x11rb::properties::{WmHints, WmHintsState, WmSizeHints, WmSizeHintsSpecification};
 use x11rb::protocol::randr::ConnectionExt as _;
 use x11rb::protocol::xfixes::ConnectionExt as _;
 use x11rb::protocol::xproto::{ConnectionExt as _, *};
@@ -19,6 +22,7 @@ use crate::timerfd_source::TimerfdSource;
 x11rb::atom_manager! {
     pub Atoms: AtomsCookie {
         WM_PROTOCOLS,
+        WM_DELETE_WINDOW,
         WM_CLIENT_MACHINE,
         UTF8_STRING,
         _NET_CURRENT_DESKTOP,
@@ -27,6 +31,12 @@ x11rb::atom_manager! {
         _NET_WM_PID,
         _NET_WM_STATE,
         _NET_WM_STATE_ABOVE,
+        _NET_WM_STATE_SKIP_TASKBAR,
+        _NET_WM_STATE_SKIP_PAGER,
+        _NET_WM_WINDOW_TYPE,
+        _NET_WM_WINDOW_TYPE_DIALOG,
+        _NET_WM_WINDOW_TYPE_UTILITY,
+        _NET_ACTIVE_WINDOW,
     }
 }
 
@@ -37,6 +47,11 @@ struct Viewport {
 }
 
 const MAX_GRAB_RETRIES: u8 = 255;
+const KEYBOARD_GRAB_SLOW_RETRY_MS: u64 = 1000;
+// A window manager destroying the window as soon as it's recreated would otherwise keep memoni
+// busy recreating it forever
+const MAX_RECREATIONS: usize = 10;
+const RECREATION_PERIOD: Duration = Duration::from_secs(60);
 
 pub struct X11Window<'a> {
     pub conn: XCBConnection,
@@ -47,13 +62,20 @@ pub struct X11Window<'a> {
     pub selection_type: SelectionType,
     pub dimensions: Dimensions,
     pub win_opened_pointer_pos: Cell<(i16, i16)>,
+    pub win_opened_pointer_anchor: Cell<Option<(Window, (i16, i16))>>,
+    win_opened_focus: Cell<Window>,
     config: &'a Config,
     shown_win_event_mask: EventMask,
     hidden_win_event_mask: EventMask,
     win_pos: Cell<(i16, i16)>,
+    last_win_pos: Cell<Option<(i16, i16)>>,
     win_placed_above_pointer: Cell<bool>,
     keyboard_grab_retry_count: Cell<u8>,
+    keyboard_grab_failed: Cell<bool>,
     pointer_grab_retry_count: Cell<u8>,
+    // Shared by every main window, as they all use the same visual
+    colormap: Cell<Option<Colormap>>,
+    recreated_at: RefCell<VecDeque<Instant>>,
 }
 
 impl<'a> X11Window<'a> {
@@ -71,6 +93,12 @@ impl<'a> X11Window<'a> {
             | EventMask::BUTTON_RELEASE
             | EventMask::POINTER_MOTION;
         let hidden_win_event_mask = EventMask::STRUCTURE_NOTIFY;
+        // Without a pointer grab, losing focus is the only sign of the user clicking elsewhere
+        let shown_win_event_mask = if config.grab_pointer && !config.managed_window {
+            shown_win_event_mask
+        } else {
+            shown_win_event_mask | EventMask::FOCUS_CHANGE
+        };
 
         let x11_window = X11Window {
             conn,
@@ -84,10 +112,16 @@ impl<'a> X11Window<'a> {
             shown_win_event_mask,
             hidden_win_event_mask,
             win_pos: Cell::new((0, 0)),
+            last_win_pos: Cell::new(None),
             win_opened_pointer_pos: Cell::new((0, 0)),
+            win_opened_pointer_anchor: Cell::new(None),
+            win_opened_focus: Cell::new(x11rb::NONE),
             win_placed_above_pointer: Cell::new(false),
             keyboard_grab_retry_count: Cell::new(0),
+            keyboard_grab_failed: Cell::new(false),
             pointer_grab_retry_count: Cell::new(0),
+            colormap: Cell::new(None),
+            recreated_at: RefCell::new(VecDeque::new()),
         };
 
         info!("creating main window with id {win_id}");
@@ -96,9 +130,30 @@ impl<'a> X11Window<'a> {
         Ok(x11_window)
     }
 
+    /// Fails when the window got destroyed too many times in a short while
     pub fn recreate_main_window(&self) -> Result<()> {
+        let now = Instant::now();
+        let mut recreated_at = self.recreated_at.borrow_mut();
+        while recreated_at
+            .front()
+            .is_some_and(|&at| now.duration_since(at) >= RECREATION_PERIOD)
+        {
+            recreated_at.pop_front();
+        }
+        if recreated_at.len() >= MAX_RECREATIONS {
+            bail!(
+                "main window got destroyed {MAX_RECREATIONS} times within {}s, giving up",
+                RECREATION_PERIOD.as_secs()
+            );
+        }
+        recreated_at.push_back(now);
+
         let win_id = self.conn.generate_id()?;
-        info!("recreating main window with id {win_id}");
+        info!(
+            "recreating main window with id {win_id}, {} time(s) in the last {}s",
+            recreated_at.len(),
+            RECREATION_PERIOD.as_secs()
+        );
 
         self.win_id.set(win_id);
         self.create_main_window()?;
@@ -131,22 +186,27 @@ impl<'a> X11Window<'a> {
             })
             .map(|v| v.visual_id);
 
-        let colormap = if let Some(visual_id) = target_visual_id {
-            let colormap_id = conn.generate_id()?;
-            conn.create_colormap(ColormapAlloc::NONE, colormap_id, screen.root, visual_id)?
-                .check()?;
-            Some(colormap_id)
-        } else {
-            None
+        let colormap = match (self.colormap.get(), target_visual_id) {
+            (Some(colormap_id), _) => Some(colormap_id),
+            (None, Some(visual_id)) => {
+                let colormap_id = conn.generate_id()?;
+                conn.create_colormap(ColormapAlloc::NONE, colormap_id, screen.root, visual_id)?
+                    .check()?;
+                self.colormap.set(Some(colormap_id));
+                Some(colormap_id)
+            }
+            (None, None) => None,
         };
 
         let win_aux = CreateWindowAux::new()
             .event_mask(*hidden_win_event_mask)
-            .background_pixel(*config.theme.background)
+            // No background, or the server clears the window with it on every map before the first
+            // frame is swapped in, flashing it
+            .background_pixmap(x11rb::NONE)
             .win_gravity(Gravity::NORTH_WEST)
             .colormap(colormap)
             .border_pixel(0)
-            .override_redirect(1);
+            .override_redirect(if config.managed_window { 0 } else { 1 });
         conn.create_window(
             target_visual_id
                 .map(|_| target_depth)
@@ -194,14 +254,54 @@ impl<'a> X11Window<'a> {
         )?
         .check()?;
 
-        conn.change_property32(
-            PropMode::REPLACE,
-            win_id,
-            atoms._NET_WM_STATE,
-            AtomEnum::ATOM,
-            &[atoms._NET_WM_STATE_ABOVE],
-        )?
-        .check()?;
+        if config.managed_window {
+            conn.change_property32(
+                PropMode::REPLACE,
+                win_id,
+                atoms._NET_WM_STATE,
+                AtomEnum::ATOM,
+                &[
+                    atoms._NET_WM_STATE_ABOVE,
+                    atoms._NET_WM_STATE_SKIP_TASKBAR,
+                    atoms._NET_WM_STATE_SKIP_PAGER,
+                ],
+            )?
+            .check()?;
+            conn.change_property32(
+                PropMode::REPLACE,
+                win_id,
+                atoms._NET_WM_WINDOW_TYPE,
+                AtomEnum::ATOM,
+                &[
+                    atoms._NET_WM_WINDOW_TYPE_DIALOG,
+                    atoms._NET_WM_WINDOW_TYPE_UTILITY,
+                ],
+            )?
+            .check()?;
+            conn.change_property32(
+                PropMode::REPLACE,
+                win_id,
+                atoms.WM_PROTOCOLS,
+                AtomEnum::ATOM,
+                &[atoms.WM_DELETE_WINDOW],
+            )?
+            .check()?;
+
+            let mut wm_hints = WmHints::new();
+            wm_hints.input = Some(true);
+            wm_hints.initial_state = Some(WmHintsState::Normal);
+            wm_hints.set(conn, win_id)?.check()?;
+            self.set_size_hints(None)?;
+        } else {
+            conn.change_property32(
+                PropMode::REPLACE,
+                win_id,
+                atoms._NET_WM_STATE,
+                AtomEnum::ATOM,
+                &[atoms._NET_WM_STATE_ABOVE],
+            )?
+            .check()?;
+        }
         conn.change_property32(
             PropMode::REPLACE,
             win_id,
@@ -225,10 +325,18 @@ impl<'a> X11Window<'a> {
 
     pub fn update_window_pos(&self) -> Result<()> {
         let pointer = self.conn.query_pointer(self.screen.root)?.reply()?;
+        let focus = self.conn.get_input_focus()?.reply()?.focus;
         self.win_opened_pointer_pos
             .set((pointer.root_x, pointer.root_y));
+        self.win_opened_pointer_anchor
+            .set(self.get_pointer_anchor(&pointer, focus)?);
+        self.win_opened_focus.set(focus);
 
         let (x, y, placed_above_pointer) = self.calculate_window_pos()?;
+        if self.config.managed_window {
+            // WMs read the position from the size hints when the window gets mapped
+            self.set_size_hints(Some((x, y)))?;
+        }
         self.conn.configure_window(
             self.win_id.get(),
             &ConfigureWindowAux::new().x(x as i32).y(y as i32),
@@ -247,6 +355,58 @@ impl<'a> X11Window<'a> {
         Ok(())
     }
 
+    fn set_size_hints(&self, pos: Option<(i16, i16)>) -> Result<()> {
+        let Dimensions { width, height } = self.dimensions;
+        let size = (width as i32, height as i32);
+
+        let mut size_hints = WmSizeHints::new();
+        size_hints.position = pos.map(|(x, y)| {
+            (
+                WmSizeHintsSpecification::ProgramSpecified,
+                x as i32,
+                y as i32,
+            )
+        });
+        size_hints.size = Some((WmSizeHintsSpecification::ProgramSpecified, size.0, size.1));
+        size_hints.min_size = Some(size);
+        size_hints.max_size = Some(size);
+        size_hints
+            .set_normal_hints(&self.conn, self.win_id.get())?
+            .check()?;
+
+        Ok(())
+    }
+
+    // Root coordinates go stale when the target window moves or the monitor pans before pasting,
+    // so also remember the pointer position relative to the focused window
+    fn get_pointer_anchor(
+        &self,
+        pointer: &QueryPointerReply,
+        focus: Window,
+    ) -> Result<Option<(Window, (i16, i16))>> {
+        let anchor = if focus != x11rb::NONE
+            && focus != u32::from(InputFocus::POINTER_ROOT)
+            && focus != self.screen.root
+        {
+            focus
+        } else if pointer.child != x11rb::NONE {
+            pointer.child
+        } else {
+            return Ok(None);
+        };
+
+        let translated = self
+            .conn
+            .translate_coordinates(self.screen.root, anchor, pointer.root_x, pointer.root_y)?
+            .reply()?;
+        trace!(
+            "pointer anchored to window {anchor} at ({}, {})",
+            translated.dst_x, translated.dst_y
+        );
+
+        Ok(Some((anchor, (translated.dst_x, translated.dst_y))))
+    }
+
     pub fn show_window(&self) -> Result<()> {
         debug!("mapping window");
         self.conn.configure_window(
@@ -284,19 +444,37 @@ impl<'a> X11Window<'a> {
         if grab_keyboard.reply()?.status == GrabStatus::SUCCESS {
             debug!("keyboard grabbed successfully");
             self.keyboard_grab_retry_count.set(0);
+            self.keyboard_grab_failed.set(false);
         } else {
             let new_count = self.keyboard_grab_retry_count.get() + 1;
             if new_count < MAX_GRAB_RETRIES {
                 self.keyboard_grab_retry_count.set(new_count);
                 timer.set_timer(10)?;
             } else {
-                warn!("failed to grab keyboard after {MAX_GRAB_RETRIES} retries");
-                self.keyboard_grab_retry_count.set(0);
+                // Keep trying in the background since the window is keyboard-dead until then
+                if !self.keyboard_grab_failed.get() {
+                    warn!(
+                        "failed to grab keyboard after {MAX_GRAB_RETRIES} retries, retrying every {KEYBOARD_GRAB_SLOW_RETRY_MS}ms"
+                    );
+                    self.keyboard_grab_failed.set(true);
+                }
+                timer.set_timer(KEYBOARD_GRAB_SLOW_RETRY_MS)?;
             }
         }
         Ok(())
     }
 
+    pub fn retry_grab_keyboard(&self, timer: &TimerfdSource) -> Result<()> {
+        debug!("restarting keyboard grab retries");
+        timer.disarm()?;
+        self.keyboard_grab_retry_count.set(0);
+        self.grab_keyboard(timer)
+    }
+
+    pub fn is_keyboard_grab_failed(&self) -> bool {
+        self.keyboard_grab_failed.get()
+    }
+
     // Same reason to retry as above, just applied to pointer keymaps
     pub fn grab_pointer(&self, timer: &TimerfdSource) -> Result<()> {
         let retry_count = self.pointer_grab_retry_count.get();
@@ -333,6 +511,61 @@ impl<'a> X11Window<'a> {
         Ok(())
     }
 
+    pub fn focus_window(&self) -> Result<()> {
+        debug!("focusing window");
+        if self.config.managed_window {
+            // The window may not be mapped by the WM yet, so ask it to activate the window instead
+            let event = ClientMessageEvent::new(
+                32,
+                self.win_id.get(),
+                self.atoms._NET_ACTIVE_WINDOW,
+                [1, x11rb::CURRENT_TIME, 0, 0, 0],
+            );
+            self.conn.send_event(
+                false,
+                self.screen.root,
+                EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY,
+                event,
+            )?;
+        } else {
+            self.conn
+                .set_input_focus(InputFocus::PARENT, self.win_id.get(), x11rb::CURRENT_TIME)?
+                .check()?;
+        }
+        Ok(())
+    }
+
+    pub fn closes_on_focus_out(&self) -> bool {
+        !self.config.grab_pointer || self.config.managed_window
+    }
+
+    pub fn is_delete_request(&self, ev: &ClientMessageEvent) -> bool {
+        ev.window == self.win_id.get()
+            && ev.type_ == self.atoms.WM_PROTOCOLS
+            && ev.data.as_data32()[0] == self.atoms.WM_DELETE_WINDOW
+    }
+
+    // Hand focus back to the window focused before showing, unless the user already moved it
+    // somewhere else
+    pub fn restore_focus(&self) -> Result<()> {
+        let prev_focus = self.win_opened_focus.get();
+        if prev_focus == x11rb::NONE
+            || self.conn.get_input_focus()?.reply()?.focus != self.win_id.get()
+        {
+            return Ok(());
+        }
+
+        debug!("restoring focus to {prev_focus}");
+        if let Err(err) = self
+            .conn
+            .set_input_focus(InputFocus::PARENT, prev_focus, x11rb::CURRENT_TIME)?
+            .check()
+        {
+            warn!("failed to restore focus to {prev_focus}: {err:?}");
+        }
+        Ok(())
+    }
+
     pub fn cancel_grab_retries(
         &self,
         keyboard_timer: &TimerfdSource,
@@ -342,6 +575,7 @@ impl<'a> X11Window<'a> {
 
         keyboard_timer.disarm()?;
         self.keyboard_grab_retry_count.set(0);
+        self.keyboard_grab_failed.set(false);
         pointer_timer.disarm()?;
         self.pointer_grab_retry_count.set(0);
 
@@ -380,6 +614,28 @@ impl<'a> X11Window<'a> {
         Ok(())
     }
 
+    pub fn move_window(&self, x: i16, y: i16) -> Result<()> {
+        trace!("moving window to ({x}, {y})");
+        self.conn.configure_window(
+            self.win_id.get(),
+            &ConfigureWindowAux::new().x(x as i32).y(y as i32),
+        )?;
+        self.win_pos.set((x, y));
+        Ok(())
+    }
+
+    /// The window has no title bar, so the top padding strip and the ribbon act as one
+    pub fn is_in_drag_area(&self, (x, y): (f32, f32)) -> bool {
+        let layout = &self.config.layout;
+        let width = self.dimensions.width as f32;
+        if x < 0.0 || x >= width || y < 0.0 {
+            return false;
+        }
+
+        y < layout.window_padding.y as f32
+            || (self.config.show_ribbon && (width - x) + y <= layout.ribbon_size)
+    }
+
     pub fn get_current_win_pos(&self) -> (i16, i16) {
         self.win_pos.get()
     }
@@ -388,6 +644,32 @@ impl<'a> X11Window<'a> {
         self.win_opened_pointer_pos.get()
     }
 
+    pub fn set_last_win_pos(&self, pos: Option<(i16, i16)>) {
+        self.last_win_pos.set(pos);
+    }
+
+    /// Records where the window currently is, returning the position if it changed
+    pub fn update_last_win_pos(&self) -> Result<Option<(i16, i16)>> {
+        // Managed windows may have been moved or reparented by the WM
+        let pos = if self.config.managed_window {
+            let translated = self
+                .conn
+                .translate_coordinates(self.win_id.get(), self.screen.root, 0, 0)?
+                .reply()?;
+            (translated.dst_x, translated.dst_y)
+        } else {
+            self.win_pos.get()
+        };
+
+        if self.last_win_pos.get() == Some(pos) {
+            return Ok(None);
+        }
+
+        debug!("last window position updated: {pos:?}");
+        self.last_win_pos.set(Some(pos));
+        Ok(Some(pos))
+    }
+
     pub fn is_win_placed_above_pointer(&self) -> bool {
         self.win_placed_above_pointer.get()
     }
@@ -466,6 +748,18 @@ impl<'a> X11Window<'a> {
                     ))
                 }
             }
+            WindowPositionMode::Last => {
+                if let Some((x, y)) = self.last_win_pos.get() {
+                    Ok((x, y, false))
+                } else {
+                    Ok(Self::position_by_monitor(focused_monitor, width, height))
+                }
+            }
+            WindowPositionMode::Fixed { x, y } => Ok((
+                x.clamp(i16::MIN as i32, i16::MAX as i32) as i16,
+                y.clamp(i16::MIN as i32, i16::MAX as i32) as i16,
+                false,
+            )),
         }
     }
 
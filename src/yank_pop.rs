@@ -0,0 +1,162 @@
+//! Cycling through the history by pasting again, like yank-pop of Emacs: pressing a paste hotkey
+//! again shortly after undoes what it pasted and pastes the next older item instead
+
+use std::time::{Duration, Instant};
+
+use crate::config::{YankPopConfig, YankPopUndo};
+
+/// How the previous paste is taken back before pasting over it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasteUndo {
+    /// Ctrl-Z in the focused window
+    Undo,
+    /// Backspace once per character of the pasted text
+    Backspace(usize),
+}
+
+struct LastYank {
+    start: usize,
+    offset: usize,
+    // None until pasted, and for items Backspace can't take back
+    undo: Option<PasteUndo>,
+    at: Instant,
+}
+
+pub struct YankPop {
+    enabled: bool,
+    window: Duration,
+    undo: YankPopUndo,
+    last: Option<LastYank>,
+}
+
+impl YankPop {
+    pub fn new(config: &YankPopConfig) -> Self {
+        YankPop {
+            enabled: config.enabled,
+            window: Duration::from_millis(config.window_ms),
+            undo: config.undo,
+            last: None,
+        }
+    }
+
+    /// Index of the item a paste starting at the history index `start` pastes, and how to undo
+    /// the previous paste first when it's a repeated one. Pasting moves the item to the top, so the
+    /// next older item is always one further from `start`, wrapping around past the oldest
+    pub fn next(
+        &mut self,
+        start: usize,
+        item_count: usize,
+        now: Instant,
+    ) -> (usize, Option<PasteUndo>) {
+        if !self.enabled {
+            return (start, None);
+        }
+
+        let repeated = self
+            .last
+            .take()
+            .filter(|last| last.start == start && now.duration_since(last.at) < self.window);
+        let (offset, undo) = match repeated {
+            Some(LastYank {
+                offset,
+                undo: Some(undo),
+                ..
+            }) if start + offset + 1 < item_count => (offset + 1, Some(undo)),
+            Some(LastYank {
+                undo: Some(undo), ..
+            }) => (0, Some(undo)),
+            _ => (0, None),
+        };
+        self.last = Some(LastYank {
+            start,
+            offset,
+            undo: None,
+            at: now,
+        });
+        (start + offset, undo)
+    }
+
+    /// Records what the paste [`next`](Self::next) chose put in, None for items without text
+    pub fn pasted(&mut self, text: Option<&str>) {
+        let Some(last) = &mut self.last else {
+            return;
+        };
+        last.undo = match self.undo {
+            YankPopUndo::Undo => Some(PasteUndo::Undo),
+            YankPopUndo::Backspace => text.map(|text| PasteUndo::Backspace(text.chars().count())),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yank_pop(undo: YankPopUndo) -> YankPop {
+        YankPop::new(&YankPopConfig {
+            enabled: true,
+            window_ms: 1000,
+            undo,
+        })
+    }
+
+    #[test]
+    fn repeated_paste_cycles_older_items() {
+        let mut yank_pop = yank_pop(YankPopUndo::Undo);
+        let now = Instant::now();
+        assert_eq!(yank_pop.next(1, 4, now), (1, None));
+        yank_pop.pasted(Some("b"));
+        assert_eq!(yank_pop.next(1, 4, now), (2, Some(PasteUndo::Undo)));
+        yank_pop.pasted(Some("c"));
+        assert_eq!(yank_pop.next(1, 4, now), (3, Some(PasteUndo::Undo)));
+        yank_pop.pasted(Some("d"));
+        // Back to the first one past the oldest item
+        assert_eq!(yank_pop.next(1, 4, now), (1, Some(PasteUndo::Undo)));
+    }
+
+    #[test]
+    fn paste_after_window_starts_over() {
+        let mut yank_pop = yank_pop(YankPopUndo::Undo);
+        let now = Instant::now();
+        yank_pop.next(0, 4, now);
+        yank_pop.pasted(Some("a"));
+        assert_eq!(
+            yank_pop.next(0, 4, now + Duration::from_millis(1000)),
+            (0, None)
+        );
+    }
+
+    #[test]
+    fn other_hotkey_starts_over() {
+        let mut yank_pop = yank_pop(YankPopUndo::Undo);
+        let now = Instant::now();
+        yank_pop.next(0, 4, now);
+        yank_pop.pasted(Some("a"));
+        assert_eq!(yank_pop.next(1, 4, now), (1, None));
+    }
+
+    #[test]
+    fn backspace_counts_characters() {
+        let mut yank_pop = yank_pop(YankPopUndo::Backspace);
+        let now = Instant::now();
+        yank_pop.next(0, 4, now);
+        yank_pop.pasted(Some("héllo"));
+        assert_eq!(yank_pop.next(0, 4, now), (1, Some(PasteUndo::Backspace(5))));
+        // Images can't be backspaced over
+        yank_pop.pasted(None);
+        assert_eq!(yank_pop.next(0, 4, now), (0, None));
+    }
+
+    #[test]
+    fn disabled_never_cycles() {
+        let mut yank_pop = YankPop::new(&YankPopConfig {
+            enabled: false,
+            window_ms: 1000,
+            undo: YankPopUndo::Undo,
+        });
+        let now = Instant::now();
+        yank_pop.next(0, 4, now);
+        yank_pop.pasted(Some("a"));
+        assert_eq!(yank_pop.next(0, 4, now), (0, None));
+    }
+}
@@ -0,0 +1,553 @@
+//! Harness for end-to-end tests: a private Xvfb display with a memoni server on it, and a fake
+//! clipboard peer that copies and pastes like other X clients do
+
+// Compiled into each test binary, which only uses a part of it
+#![allow(dead_code)]
+
+use std::{
+    env, fs,
+    io::{BufRead as _, BufReader},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context as _, Result, anyhow, bail};
+use serde_json::Value;
+use x11rb::{
+    connection::Connection,
+    protocol::{
+        Event,
+        xproto::{
+            Atom, AtomEnum, ChangeWindowAttributesAux, ConnectionExt as _, CreateWindowAux,
+            EventMask, KEY_PRESS_EVENT, KEY_RELEASE_EVENT, PropMode, Property,
+            SELECTION_NOTIFY_EVENT, SelectionNotifyEvent, SelectionRequestEvent, Window,
+            WindowClass,
+        },
+        xtest::ConnectionExt as _,
+    },
+    rust_connection::RustConnection,
+    wrapper::ConnectionExt as _,
+};
+
+const MEMONI: &str = env!("CARGO_BIN_EXE_memoni");
+const FIRST_DISPLAY: u32 = 90;
+const DISPLAY_ATTEMPTS: u32 = 50;
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+pub const EVENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Payloads above this are sent with INCR, in chunks of this size
+pub const INCR_THRESHOLD: usize = 256 * 1024;
+
+const CONFIG: &str = r#"
+renderer = "software"
+window_position_mode = { x = 0, y = 0 }
+grab_pointer = false
+"#;
+
+/// Xvfb and a memoni server for CLIPBOARD on it, both killed on drop. The tests using it are
+/// ignored unless asked for, as they need Xvfb installed, and fail without it
+pub struct TestEnv {
+    pub display: String,
+    xvfb: Child,
+    server: Child,
+    home: PathBuf,
+    socket_path: PathBuf,
+}
+
+impl TestEnv {
+    pub fn start() -> Result<Self> {
+        let Some((display, xvfb)) = start_xvfb()? else {
+            bail!("Xvfb not found, it's needed for the end-to-end tests");
+        };
+
+        let home = env::temp_dir().join(format!("memoni-test-{}-{display}", std::process::id()));
+        let config_dir = home.join("config").join("memoni");
+        fs::create_dir_all(&config_dir)?;
+        fs::write(config_dir.join("config.toml"), CONFIG)?;
+
+        let server = Command::new(MEMONI)
+            .args(["server", "--log-level", "debug"])
+            .envs(xdg_env(&display, &home))
+            .stdout(Stdio::null())
+            .spawn()?;
+        let socket_path = Path::new("/tmp/memoni").join(format!(
+            "CLIPBOARD_{}.sock",
+            display.trim_start_matches(':')
+        ));
+        let mut test_env = TestEnv {
+            display,
+            xvfb,
+            server,
+            home,
+            socket_path,
+        };
+        test_env.wait_for_server()?;
+        Ok(test_env)
+    }
+
+    /// Waits for the server to listen for clients, e.g., after it started over
+    pub fn wait_for_server(&mut self) -> Result<()> {
+        wait_for(|| {
+            if let Some(status) = self.server.try_wait()? {
+                bail!("memoni server exited with {status}");
+            }
+            Ok(self.socket_path.exists())
+        })
+    }
+
+    /// Runs `memoni watch`, its events can be read as they come
+    pub fn watch(&self) -> Result<Watch> {
+        let mut child = Command::new(MEMONI)
+            .arg("watch")
+            .envs(xdg_env(&self.display, &self.home))
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdout = child.stdout.take().unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Watch { child, lines: rx })
+    }
+
+    /// Runs the `memoni` client, which shows the window
+    pub fn show_window(&self) -> Result<()> {
+        let status = Command::new(MEMONI)
+            .envs(xdg_env(&self.display, &self.home))
+            .status()?;
+        if !status.success() {
+            bail!("memoni client exited with {status}");
+        }
+        Ok(())
+    }
+
+    pub fn peer(&self) -> Result<FakePeer> {
+        FakePeer::connect(&self.display)
+    }
+
+    pub fn server_exited(&mut self) -> Result<bool> {
+        Ok(self.server.try_wait()?.is_some())
+    }
+}
+
+impl Drop for TestEnv {
+    fn drop(&mut self) {
+        let _ = self.server.kill();
+        let _ = self.server.wait();
+        let _ = self.xvfb.kill();
+        let _ = self.xvfb.wait();
+        let _ = fs::remove_dir_all(&self.home);
+    }
+}
+
+pub struct Watch {
+    child: Child,
+    lines: Receiver<String>,
+}
+
+impl Watch {
+    /// Waits for the next event of `memoni watch`
+    pub fn next_event(&self) -> Result<Value> {
+        let line = self
+            .lines
+            .recv_timeout(EVENT_TIMEOUT)
+            .context("no watch event received")?;
+        Ok(serde_json::from_str(&line)?)
+    }
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// An X client owning and requesting CLIPBOARD, standing in for the applications memoni copies
+/// from and pastes into
+pub struct FakePeer {
+    display: String,
+    conn: RustConnection,
+    window: Window,
+    clipboard: Atom,
+    targets: Atom,
+    incr: Atom,
+    property: Atom,
+}
+
+impl FakePeer {
+    fn connect(display: &str) -> Result<Self> {
+        let (conn, screen_num) = x11rb::connect(Some(display))?;
+        let root = conn.setup().roots[screen_num].root;
+        let window = conn.generate_id()?;
+        conn.create_window(
+            0,
+            window,
+            root,
+            0,
+            0,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_ONLY,
+            0,
+            &CreateWindowAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+        )?;
+        let clipboard = intern(&conn, "CLIPBOARD")?;
+        let targets = intern(&conn, "TARGETS")?;
+        let incr = intern(&conn, "INCR")?;
+        let property = intern(&conn, "MEMONI_TEST")?;
+        conn.flush()?;
+        Ok(FakePeer {
+            display: display.to_string(),
+            conn,
+            window,
+            clipboard,
+            targets,
+            incr,
+            property,
+        })
+    }
+
+    /// Takes CLIPBOARD ownership and serves the given targets from a background thread until
+    /// another client takes it over
+    pub fn copy(self, data: Vec<(&str, Vec<u8>)>) -> Result<()> {
+        let data = data
+            .into_iter()
+            .map(|(target, bytes)| Ok((intern(&self.conn, target)?, bytes)))
+            .collect::<Result<Vec<_>>>()?;
+        self.conn
+            .set_selection_owner(self.window, self.clipboard, x11rb::CURRENT_TIME)?;
+        let owner = self
+            .conn
+            .get_selection_owner(self.clipboard)?
+            .reply()?
+            .owner;
+        if owner != self.window {
+            bail!("failed to own CLIPBOARD");
+        }
+
+        thread::spawn(move || {
+            if let Err(e) = self.serve(&data) {
+                eprintln!("fake peer stopped serving: {e:?}");
+            }
+        });
+        Ok(())
+    }
+
+    fn serve(&self, data: &[(Atom, Vec<u8>)]) -> Result<()> {
+        loop {
+            match self.conn.wait_for_event()? {
+                Event::SelectionRequest(request) => self.answer(&request, data)?,
+                Event::SelectionClear(_) => return Ok(()),
+                _ => {}
+            }
+        }
+    }
+
+    fn answer(&self, request: &SelectionRequestEvent, data: &[(Atom, Vec<u8>)]) -> Result<()> {
+        let property = if request.target == self.targets {
+            let mut targets = vec![self.targets];
+            targets.extend(data.iter().map(|(target, _)| *target));
+            self.conn.change_property32(
+                PropMode::REPLACE,
+                request.requestor,
+                request.property,
+                AtomEnum::ATOM,
+                &targets,
+            )?;
+            request.property
+        } else if let Some((target, bytes)) = data.iter().find(|(t, _)| *t == request.target) {
+            if bytes.len() > INCR_THRESHOLD {
+                self.conn.change_window_attributes(
+                    request.requestor,
+                    &ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+                )?;
+                self.conn.change_property32(
+                    PropMode::REPLACE,
+                    request.requestor,
+                    request.property,
+                    self.incr,
+                    &[bytes.len() as u32],
+                )?;
+                self.notify(request, request.property)?;
+                return self.send_incr(request, *target, bytes);
+            }
+            self.conn.change_property8(
+                PropMode::REPLACE,
+                request.requestor,
+                request.property,
+                *target,
+                bytes,
+            )?;
+            request.property
+        } else {
+            AtomEnum::NONE.into()
+        };
+        self.notify(request, property)
+    }
+
+    fn notify(&self, request: &SelectionRequestEvent, property: Atom) -> Result<()> {
+        let event = SelectionNotifyEvent {
+            response_type: SELECTION_NOTIFY_EVENT,
+            sequence: 0,
+            time: request.time,
+            requestor: request.requestor,
+            selection: request.selection,
+            target: request.target,
+            property,
+        };
+        self.conn
+            .send_event(false, request.requestor, EventMask::NO_EVENT, event)?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    // Each chunk is written once the requestor deleted the previous one, ending with an empty one
+    fn send_incr(&self, request: &SelectionRequestEvent, target: Atom, bytes: &[u8]) -> Result<()> {
+        let mut chunks = bytes.chunks(INCR_THRESHOLD).chain([&[][..]]);
+        loop {
+            match self.conn.wait_for_event()? {
+                Event::PropertyNotify(event)
+                    if event.window == request.requestor
+                        && event.atom == request.property
+                        && event.state == Property::DELETE =>
+                {
+                    let Some(chunk) = chunks.next() else {
+                        return Ok(());
+                    };
+                    self.conn.change_property8(
+                        PropMode::REPLACE,
+                        request.requestor,
+                        request.property,
+                        target,
+                        chunk,
+                    )?;
+                    self.conn.flush()?;
+                    if chunk.is_empty() {
+                        return Ok(());
+                    }
+                }
+                Event::SelectionClear(_) => bail!("lost CLIPBOARD during INCR transfer"),
+                _ => {}
+            }
+        }
+    }
+
+    /// Requests CLIPBOARD as `target` from whoever owns it, like pasting does
+    pub fn paste(&self, target: &str) -> Result<Vec<u8>> {
+        let target = intern(&self.conn, target)?;
+        self.conn.convert_selection(
+            self.window,
+            self.clipboard,
+            target,
+            self.property,
+            x11rb::CURRENT_TIME,
+        )?;
+        self.conn.flush()?;
+
+        let notify = self.wait_event(|event| match event {
+            Event::SelectionNotify(notify) => Some(notify),
+            _ => None,
+        })?;
+        if notify.property == u32::from(AtomEnum::NONE) {
+            bail!("CLIPBOARD owner refused target");
+        }
+
+        let reply = self.take_property()?;
+        if reply.0 != self.incr {
+            return Ok(reply.1);
+        }
+        let mut bytes = vec![];
+        loop {
+            self.wait_event(|event| match event {
+                Event::PropertyNotify(event)
+                    if event.atom == self.property && event.state == Property::NEW_VALUE =>
+                {
+                    Some(())
+                }
+                _ => None,
+            })?;
+            let (_, chunk) = self.take_property()?;
+            if chunk.is_empty() {
+                return Ok(bytes);
+            }
+            bytes.extend(chunk);
+        }
+    }
+
+    fn take_property(&self) -> Result<(Atom, Vec<u8>)> {
+        let reply = self
+            .conn
+            .get_property(
+                true,
+                self.window,
+                self.property,
+                AtomEnum::ANY,
+                0,
+                u32::MAX / 4,
+            )?
+            .reply()?;
+        self.conn.flush()?;
+        Ok((reply.type_, reply.value))
+    }
+
+    fn wait_event<T>(&self, mut matches: impl FnMut(Event) -> Option<T>) -> Result<T> {
+        let deadline = Instant::now() + EVENT_TIMEOUT;
+        while Instant::now() < deadline {
+            match self.conn.poll_for_event()? {
+                Some(event) => {
+                    if let Some(value) = matches(event) {
+                        return Ok(value);
+                    }
+                }
+                None => thread::sleep(Duration::from_millis(10)),
+            }
+        }
+        Err(anyhow!("timed out waiting for X event on {}", self.display))
+    }
+
+    /// Main windows of the memoni server, found by their WM_CLASS
+    pub fn memoni_windows(&self) -> Result<Vec<Window>> {
+        let root = self.conn.setup().roots[0].root;
+        let mut windows = vec![];
+        for window in self.conn.query_tree(root)?.reply()?.children {
+            // Windows can go away between listing and asking
+            let Ok(class) = self
+                .conn
+                .get_property(false, window, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 64)?
+                .reply()
+            else {
+                continue;
+            };
+            if class.value.starts_with(b"memoni-clipboard\0") {
+                windows.push(window);
+            }
+        }
+        Ok(windows)
+    }
+
+    /// Kills the client owning a window with XKillClient, like xkill or a window manager giving
+    /// up on an unresponsive window does, and waits for the window to go with it
+    pub fn kill_client(&self, window: Window) -> Result<()> {
+        self.conn.change_window_attributes(
+            window,
+            &ChangeWindowAttributesAux::new().event_mask(EventMask::STRUCTURE_NOTIFY),
+        )?;
+        self.conn.kill_client(window)?;
+        self.conn.flush()?;
+        self.wait_event(|event| match event {
+            Event::DestroyNotify(event) if event.window == window => Some(()),
+            _ => None,
+        })
+    }
+
+    /// Presses and releases each key in turn through XTest
+    pub fn press_keys(&self, keysyms: &[xkeysym::Keysym]) -> Result<()> {
+        let setup = self.conn.setup();
+        let (min_keycode, max_keycode) = (setup.min_keycode, setup.max_keycode);
+        let mapping = self
+            .conn
+            .get_keyboard_mapping(min_keycode, max_keycode - min_keycode + 1)?
+            .reply()?;
+        let per_keycode = mapping.keysyms_per_keycode as usize;
+        let root = setup.roots[0].root;
+
+        for keysym in keysyms {
+            let index = mapping
+                .keysyms
+                .iter()
+                .position(|&k| k == keysym.raw())
+                .with_context(|| format!("no keycode for {keysym:?}"))?;
+            let keycode = min_keycode + (index / per_keycode) as u8;
+            for event_type in [KEY_PRESS_EVENT, KEY_RELEASE_EVENT] {
+                self.conn.xtest_fake_input(
+                    event_type,
+                    keycode,
+                    x11rb::CURRENT_TIME,
+                    root,
+                    0,
+                    0,
+                    0,
+                )?;
+            }
+            self.conn.flush()?;
+            thread::sleep(Duration::from_millis(50));
+        }
+        Ok(())
+    }
+}
+
+fn intern(conn: &RustConnection, name: &str) -> Result<Atom> {
+    Ok(conn.intern_atom(false, name.as_bytes())?.reply()?.atom)
+}
+
+fn xdg_env(display: &str, home: &Path) -> Vec<(&'static str, String)> {
+    vec![
+        ("DISPLAY", display.to_string()),
+        ("XDG_CONFIG_HOME", home.join("config").display().to_string()),
+        ("XDG_DATA_HOME", home.join("data").display().to_string()),
+        ("XDG_CACHE_HOME", home.join("cache").display().to_string()),
+    ]
+}
+
+// Xvfb refuses displays that are taken, so the first free one from FIRST_DISPLAY is used
+fn start_xvfb() -> Result<Option<(String, Child)>> {
+    for number in FIRST_DISPLAY..FIRST_DISPLAY + DISPLAY_ATTEMPTS {
+        if Path::new(&format!("/tmp/.X{number}-lock")).exists() {
+            continue;
+        }
+        let display = format!(":{number}");
+        let mut xvfb = match Command::new("Xvfb")
+            .args([
+                display.as_str(),
+                "-screen",
+                "0",
+                "1280x800x24",
+                "-nolisten",
+                "tcp",
+            ])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(xvfb) => xvfb,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let socket = PathBuf::from(format!("/tmp/.X11-unix/X{number}"));
+        let started = wait_for(|| {
+            if let Some(status) = xvfb.try_wait()? {
+                bail!("Xvfb exited with {status}");
+            }
+            Ok(socket.exists())
+        });
+        if started.is_ok() {
+            return Ok(Some((display, xvfb)));
+        }
+        let _ = xvfb.kill();
+        let _ = xvfb.wait();
+    }
+    bail!("no free X display found")
+}
+
+pub fn wait_for(mut ready: impl FnMut() -> Result<bool>) -> Result<()> {
+    let deadline = Instant::now() + STARTUP_TIMEOUT;
+    while Instant::now() < deadline {
+        if ready()? {
+            return Ok(());
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    bail!("timed out")
+}
@@ -0,0 +1,115 @@
+//! End-to-end tests of capturing and pasting CLIPBOARD, run against a memoni server on Xvfb
+
+mod common;
+
+use std::{io::Cursor, thread, time::Duration};
+
+use anyhow::Result;
+use image::{ImageFormat, Rgba, RgbaImage};
+use xkeysym::Keysym;
+
+use common::{INCR_THRESHOLD, TestEnv};
+
+#[test]
+#[ignore = "needs Xvfb"]
+fn captures_text() -> Result<()> {
+    let env = TestEnv::start()?;
+    let watch = env.watch()?;
+
+    env.peer()?
+        .copy(vec![("UTF8_STRING", b"hello from the fake peer".to_vec())])?;
+
+    let event = watch.next_event()?;
+    assert_eq!(event["event"], "new_item");
+    assert_eq!(event["kind"], "text");
+    assert_eq!(event["preview"], "hello from the fake peer");
+    Ok(())
+}
+
+#[test]
+#[ignore = "needs Xvfb"]
+fn captures_image() -> Result<()> {
+    let env = TestEnv::start()?;
+    let watch = env.watch()?;
+
+    let mut png = vec![];
+    RgbaImage::from_pixel(4, 4, Rgba([0xff, 0x00, 0x00, 0xff]))
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+    env.peer()?.copy(vec![("image/png", png)])?;
+
+    let event = watch.next_event()?;
+    assert_eq!(event["kind"], "image");
+    assert_eq!(event["preview"], "Image (PNG)");
+    Ok(())
+}
+
+#[test]
+#[ignore = "needs Xvfb"]
+fn captures_incr_payload() -> Result<()> {
+    let env = TestEnv::start()?;
+    let watch = env.watch()?;
+
+    let text = "incremental ".repeat(INCR_THRESHOLD * 4 / 12);
+    env.peer()?.copy(vec![("UTF8_STRING", text.into_bytes())])?;
+
+    let event = watch.next_event()?;
+    assert_eq!(event["kind"], "text");
+    assert!(
+        event["preview"]
+            .as_str()
+            .is_some_and(|preview| preview.starts_with("incremental incremental")),
+        "unexpected preview: {}",
+        event["preview"]
+    );
+    Ok(())
+}
+
+#[test]
+#[ignore = "needs Xvfb"]
+fn keeps_newest_item_on_top() -> Result<()> {
+    let env = TestEnv::start()?;
+    let watch = env.watch()?;
+
+    env.peer()?.copy(vec![("UTF8_STRING", b"first".to_vec())])?;
+    assert_eq!(watch.next_event()?["preview"], "first");
+    env.peer()?
+        .copy(vec![("UTF8_STRING", b"second".to_vec())])?;
+    assert_eq!(watch.next_event()?["preview"], "second");
+
+    // A second watcher is told about the newest item right away
+    let event = env.watch()?.next_event()?;
+    assert_eq!(event["event"], "latest");
+    assert_eq!(event["preview"], "second");
+    Ok(())
+}
+
+#[test]
+#[ignore = "needs Xvfb"]
+fn pastes_selected_item() -> Result<()> {
+    let env = TestEnv::start()?;
+    let watch = env.watch()?;
+
+    env.peer()?.copy(vec![("UTF8_STRING", b"first".to_vec())])?;
+    watch.next_event()?;
+    env.peer()?
+        .copy(vec![("UTF8_STRING", b"second".to_vec())])?;
+    watch.next_event()?;
+
+    env.show_window()?;
+    // Give the window time to map and grab the keyboard
+    thread::sleep(Duration::from_millis(500));
+    let peer = env.peer()?;
+    peer.press_keys(&[Keysym::Tab, Keysym::Return])?;
+
+    // memoni takes CLIPBOARD over to serve the selected item
+    let mut pasted = vec![];
+    for _ in 0..50 {
+        pasted = peer.paste("UTF8_STRING")?;
+        if pasted == b"first" {
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    assert_eq!(String::from_utf8_lossy(&pasted), "first");
+    Ok(())
+}
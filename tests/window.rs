@@ -0,0 +1,51 @@
+//! End-to-end tests of the main window, run against a memoni server on Xvfb
+
+mod common;
+
+use anyhow::{Result, bail};
+
+use common::{FakePeer, TestEnv, wait_for};
+
+// Kills the X client of the server through its only main window, and waits for the server to start
+// over on a new connection
+fn kill_server_client(env: &mut TestEnv, peer: &FakePeer) -> Result<()> {
+    let windows = peer.memoni_windows()?;
+    let [window] = windows[..] else {
+        bail!("expected one main window, found {windows:?}");
+    };
+    peer.kill_client(window)?;
+    wait_for(|| Ok(!peer.memoni_windows()?.is_empty()))?;
+    env.wait_for_server()
+}
+
+#[test]
+#[ignore = "needs Xvfb"]
+fn starts_over_when_killed() -> Result<()> {
+    let mut env = TestEnv::start()?;
+    let peer = env.peer()?;
+
+    for _ in 0..3 {
+        kill_server_client(&mut env, &peer)?;
+    }
+    assert!(!env.server_exited()?);
+    env.show_window()?;
+    assert_eq!(peer.memoni_windows()?.len(), 1);
+    Ok(())
+}
+
+#[test]
+#[ignore = "needs Xvfb"]
+fn gives_up_on_kill_storm() -> Result<()> {
+    let mut env = TestEnv::start()?;
+    let peer = env.peer()?;
+
+    // The limit is 10 restarts a minute
+    for _ in 0..10 {
+        kill_server_client(&mut env, &peer)?;
+    }
+    assert!(!env.server_exited()?);
+    let windows = peer.memoni_windows()?;
+    peer.kill_client(windows[0])?;
+    wait_for(|| env.server_exited())?;
+    Ok(())
+}